@@ -1,6 +1,7 @@
 use std::{
     fs::{self, File},
-    path::Path,
+    io::BufReader,
+    path::{Path, PathBuf},
     process::Command as ProcessCommand,
 };
 
@@ -11,14 +12,22 @@ use repo_reaper_core::{
     config::Config as ReaperConfig,
     evaluation::{
         Evaluation, EvaluationCorpus, EvaluationData, EvaluationReport, RawEvaluationData, TestSet,
-        dataset::Relevance,
-        metrics::{GroundednessResult, TestQuery, TokenEfficiencyEvaluation},
+        dataset::{LabelledQueries, Relevance},
+        metrics::{GroundednessResult, TestQuery, TokenEfficiencyEvaluation, average_evaluations},
+        tuning::{FoldTuning, Metric, SearchStrategy, Tuner, parameter_space, project_config},
+    },
+    index::{GitHistory, InvertedIndex},
+    query::{
+        QueryExpansionConfig,
+        intent::{IntentClassifier, cross_validate_intents},
     },
-    index::InvertedIndex,
-    query::QueryExpansionConfig,
     ranking::{
         RankingAlgo,
-        features::{JsonlFeatureSink, export_evaluation_features_to_sink},
+        features::{
+            FeatureExportRecord, FeatureRecordSource, JsonlFeatureSink, JsonlFeatureSource,
+            export_evaluation_features_to_sink,
+        },
+        learned::{FoldEvaluation, TrainingOptions, cross_validate, train},
     },
     tokenizer::n_gram_transform,
 };
@@ -94,6 +103,79 @@ struct TokenEfficiencyComparison {
 }
 
 pub(crate) fn evaluate_training(args: &crate::Args, config: &ReaperConfig) -> Result<()> {
+    let (inverted_index, evaluation_data, queries) = load_evaluation(args, config)?;
+
+    let evaluation_report = TestSet {
+        ranking_algorithm: args.ranking_algorithm.clone(),
+        queries,
+        feedback_expansion: args.feedback_expansion,
+        diversity: args.diversify.then(|| args.ranking.diversity.clone()),
+    }
+    .evaluate_report(&inverted_index, args.top_n);
+
+    if let Some(path) = &args.export_features {
+        write_feature_export(
+            path,
+            &inverted_index,
+            &args.ranking_algorithm,
+            &evaluation_data,
+            args.top_n,
+        )?;
+    }
+
+    if let Some(baseline_path) = &args.eval_compare {
+        let baseline = read_evaluation_baseline(baseline_path)?;
+        let mut comparison = compare_evaluation_reports(&baseline, &evaluation_report);
+        comparison.file_retrieval.regressions =
+            collect_regressions(&comparison, args.eval_regression_threshold);
+
+        match args.eval_format {
+            EvalOutputFormat::Pretty => print_evaluation_comparison(&comparison),
+            EvalOutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&comparison)
+                    .context("failed to serialize evaluation comparison")?;
+                println!("{json}");
+            }
+        }
+
+        if !comparison.file_retrieval.regressions.is_empty() {
+            bail!(
+                "evaluation regressed beyond threshold {}: {}",
+                args.eval_regression_threshold,
+                comparison
+                    .file_retrieval
+                    .regressions
+                    .iter()
+                    .map(|regression| format!(
+                        "{} {} ({:+.4})",
+                        regression.scope, regression.metric, regression.delta
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        return Ok(());
+    }
+
+    match args.eval_format {
+        EvalOutputFormat::Pretty => print_pretty_evaluation(&evaluation_report),
+        EvalOutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&evaluation_report)
+                .context("failed to serialize evaluation report")?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads `--eval-data`, checks out its corpus and indexes it for
+/// `--ranking-algorithm`.
+fn load_evaluation(
+    args: &crate::Args,
+    config: &ReaperConfig,
+) -> Result<(InvertedIndex, EvaluationData, Vec<TestQuery>)> {
     let file_content = fs::read_to_string(&args.eval_data).with_context(|| {
         format!(
             "failed to read evaluation data from {}",
@@ -143,7 +225,7 @@ pub(crate) fn evaluate_training(args: &crate::Args, config: &ReaperConfig) -> Re
         }
     };
 
-    let inverted_index = if args.ranking_algorithm.needs_fielded_index() {
+    let mut inverted_index = if args.ranking_algorithm.needs_fielded_index() {
         InvertedIndex::new_fielded(index_root.as_path(), config, Some(index_root.as_path()))
     } else {
         InvertedIndex::new(
@@ -151,9 +233,19 @@ pub(crate) fn evaluate_training(args: &crate::Args, config: &ReaperConfig) -> Re
             |content: &str| n_gram_transform(content, config),
             Some(index_root.as_path()),
         )
-    };
+    }
+    .with_quality_priors(args.ranking.quality_priors.clone());
+    if args.history {
+        match GitHistory::read(&index_root) {
+            Ok(history) => inverted_index = inverted_index.with_history(history, &index_root),
+            Err(error) => eprintln!("history unavailable, evaluating without it: {error}"),
+        }
+    }
+    if args.ranking_algorithm.needs_dense_index() {
+        inverted_index.build_dense(&args.dense);
+    }
 
-    let queries = evaluation_data
+    let queries: Vec<TestQuery> = evaluation_data
         .examples
         .par_iter()
         .map(|example| {
@@ -194,76 +286,254 @@ pub(crate) fn evaluate_training(args: &crate::Args, config: &ReaperConfig) -> Re
         })
         .collect();
 
-    let evaluation_report = TestSet {
-        ranking_algorithm: args.ranking_algorithm.clone(),
-        queries,
-        feedback_expansion: args.feedback_expansion,
-    }
-    .evaluate_report(&inverted_index, args.top_n);
+    Ok((inverted_index, evaluation_data, queries))
+}
 
-    if let Some(path) = &args.export_features {
-        write_feature_export(
-            path,
-            &inverted_index,
-            &args.ranking_algorithm,
-            &evaluation_data,
-            args.top_n,
-        )?;
+/// Fits a learned reranker to exported feature rows, reporting
+/// cross-validated metrics first when `folds` is at least two.
+pub(crate) fn train_ranker(
+    args: &crate::Args,
+    features: &Path,
+    output: &Path,
+    folds: usize,
+) -> Result<()> {
+    if matches!(args.ranking_algorithm, RankingAlgo::Learned(_)) {
+        bail!("a learned model cannot be the first stage of another");
     }
 
-    if let Some(baseline_path) = &args.eval_compare {
-        let baseline = read_evaluation_baseline(baseline_path)?;
-        let mut comparison = compare_evaluation_reports(&baseline, &evaluation_report);
-        comparison.file_retrieval.regressions =
-            collect_regressions(&comparison, args.eval_regression_threshold);
+    let file = File::open(features)
+        .with_context(|| format!("failed to open feature export {}", features.display()))?;
+    let rows = JsonlFeatureSource::new(BufReader::new(file))
+        .read_records()
+        .context("failed to read feature export")?
+        .into_iter()
+        .filter_map(|record| match record {
+            FeatureExportRecord::Feature(row) => Some(row),
+            FeatureExportRecord::PairwisePreference(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let first_stage = args.ranking_algorithm.name();
+    let options = TrainingOptions {
+        k: args.top_n,
+        ..Default::default()
+    };
 
+    if folds >= 2 {
+        let evaluations = cross_validate(&rows, first_stage, folds, &options)?;
         match args.eval_format {
-            EvalOutputFormat::Pretty => print_evaluation_comparison(&comparison),
+            EvalOutputFormat::Pretty => print_cross_validation(&evaluations),
             EvalOutputFormat::Json => {
-                let json = serde_json::to_string_pretty(&comparison)
-                    .context("failed to serialize evaluation comparison")?;
+                let json = serde_json::to_string_pretty(&evaluations)
+                    .context("failed to serialize cross-validation report")?;
                 println!("{json}");
             }
         }
+    }
 
-        if !comparison.file_retrieval.regressions.is_empty() {
-            bail!(
-                "evaluation regressed beyond threshold {}: {}",
-                args.eval_regression_threshold,
-                comparison
-                    .file_retrieval
-                    .regressions
-                    .iter()
-                    .map(|regression| format!(
-                        "{} {} ({:+.4})",
-                        regression.scope, regression.metric, regression.delta
-                    ))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            );
+    let model = train(&rows, first_stage, &options)?;
+    model.save(output)?;
+    eprintln!(
+        "saved a {first_stage} reranker over the top {} results to {}; search with -r learned:{}",
+        model.candidates,
+        output.display(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Fits the query intent classifier to the labelled queries in `data`,
+/// reporting a cross-validated confusion matrix first when `folds` is at
+/// least two.
+pub(crate) fn train_intent_classifier(
+    args: &crate::Args,
+    data: &[PathBuf],
+    output: &Path,
+    folds: usize,
+) -> Result<()> {
+    let mut examples = Vec::new();
+    for path in data {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open labelled queries {}", path.display()))?;
+        let labelled: LabelledQueries = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("failed to parse labelled queries {}", path.display()))?;
+        examples.extend(labelled.examples.into_iter().filter_map(|example| {
+            example
+                .query_shape
+                .intent()
+                .map(|intent| (example.query, intent))
+        }));
+    }
+
+    if folds >= 2 {
+        let confusion = cross_validate_intents(&examples, folds)?;
+        match args.eval_format {
+            EvalOutputFormat::Pretty => println!("{confusion}"),
+            EvalOutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&confusion)
+                    .context("failed to serialize intent confusion matrix")?;
+                println!("{json}");
+            }
         }
+    }
 
-        return Ok(());
+    let classifier = IntentClassifier::train(
+        examples
+            .iter()
+            .map(|(query, intent)| (query.as_str(), *intent)),
+    )?;
+    classifier.save(output)?;
+    eprintln!(
+        "saved an intent classifier trained on {} queries to {}",
+        examples.len(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn print_cross_validation(evaluations: &[FoldEvaluation]) {
+    for fold in evaluations {
+        println!(
+            "fold {fold} (n={queries}): MAP@{k}: {map_before:.4} -> {map:.4}, MRR@{k}: {mrr_before:.4} -> {mrr:.4}, NDCG@{k}: {ndcg_before:.4} -> {ndcg:.4}",
+            fold = fold.fold,
+            queries = fold.queries,
+            k = fold.learned.k,
+            map_before = fold.first_stage.mean_average_precision,
+            map = fold.learned.mean_average_precision,
+            mrr_before = fold.first_stage.mean_reciprocal_rank,
+            mrr = fold.learned.mean_reciprocal_rank,
+            ndcg_before = fold.first_stage.normalized_discounted_cumulative_gain,
+            ndcg = fold.learned.normalized_discounted_cumulative_gain,
+        );
     }
 
-    match args.eval_format {
-        EvalOutputFormat::Pretty => print_pretty_evaluation(&evaluation_report),
-        EvalOutputFormat::Json => {
-            let json = serde_json::to_string_pretty(&evaluation_report)
-                .context("failed to serialize evaluation report")?;
-            println!("{json}");
+    let average = |metrics: fn(&FoldEvaluation) -> &Evaluation| {
+        average_evaluations(&evaluations.iter().map(metrics).cloned().collect::<Vec<_>>())
+    };
+    println!(
+        "
+first stage:
+{}",
+        average(|fold| &fold.first_stage)
+    );
+    println!(
+        "
+learned:
+{}",
+        average(|fold| &fold.learned)
+    );
+}
+
+/// Searches `--ranking-algorithm`'s parameters for the best `metric` on
+/// the evaluation set and writes the winner to `output` as a project
+/// config, reporting cross-validated scores first when `folds` is at
+/// least two.
+pub(crate) fn tune_ranker(
+    args: &crate::Args,
+    config: &ReaperConfig,
+    metric: Metric,
+    strategy: SearchStrategy,
+    folds: usize,
+    output: &Path,
+) -> Result<()> {
+    let (inverted_index, _, queries) = load_evaluation(args, config)?;
+    let mut intents = queries
+        .iter()
+        .map(|query| query.query.intent())
+        .collect::<Vec<_>>();
+    intents.sort();
+    intents.dedup();
+
+    let algo = &args.ranking_algorithm;
+    let tuner = Tuner::new(
+        &inverted_index,
+        algo.clone(),
+        metric,
+        parameter_space(algo, &intents),
+        strategy,
+    )?;
+
+    if folds >= 2 {
+        let tunings = tuner.cross_validate(&queries, folds)?;
+        match args.eval_format {
+            EvalOutputFormat::Pretty => print_tuning_cross_validation(&tunings, metric),
+            EvalOutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&tunings)
+                    .context("failed to serialize cross-validation report")?;
+                println!("{json}");
+            }
+        }
+    }
+
+    let baseline = tuner.baseline();
+    let best = tuner.search(&queries.iter().collect::<Vec<_>>())?;
+    if matches!(args.eval_format, EvalOutputFormat::Pretty) {
+        let baseline_score = tuner.score(&baseline, &queries.iter().collect::<Vec<_>>())?;
+        println!("{metric}: {baseline_score:.4} -> {:.4}", best.score);
+        for (key, value) in &best.assignment {
+            if baseline.get(key) != Some(value) {
+                println!("  {key}: {} -> {value}", baseline[key]);
+            }
         }
     }
 
+    fs::write(
+        output,
+        project_config(algo, tuner.parameters(), &best.assignment).to_string(),
+    )
+    .with_context(|| format!("failed to write tuned config to {}", output.display()))?;
+    eprintln!(
+        "saved tuned {} parameters to {}; search with --config {}",
+        algo.name(),
+        output.display(),
+        output.display()
+    );
+
     Ok(())
 }
 
+fn print_tuning_cross_validation(tunings: &[FoldTuning], metric: Metric) {
+    for fold in tunings {
+        println!(
+            "fold {fold} (n={queries}): {metric} held out: {baseline:.4} -> {tuned:.4} (training {training:.4})",
+            fold = fold.fold,
+            queries = fold.queries,
+            baseline = fold.baseline_held_out,
+            tuned = fold.held_out,
+            training = fold.training.score,
+        );
+    }
+
+    let folds = tunings.len().max(1) as f64;
+    println!(
+        "\nmean held-out {metric}: {:.4} -> {:.4}\n",
+        tunings
+            .iter()
+            .map(|fold| fold.baseline_held_out)
+            .sum::<f64>()
+            / folds,
+        tunings.iter().map(|fold| fold.held_out).sum::<f64>() / folds,
+    );
+}
+
 fn print_pretty_evaluation(evaluation: &repo_reaper_core::evaluation::metrics::EvaluationReport) {
     println!("{}", evaluation.file_retrieval.aggregate);
+    println!(
+        "Redundancy@{}: {:.4}",
+        evaluation.file_retrieval.aggregate.k, evaluation.file_retrieval.redundancy_at_k
+    );
     println!("\ngroundedness:");
     println!("{}", evaluation.evidence.groundedness);
     println!("\ntoken efficiency:");
     println!("{}", evaluation.evidence.token_efficiency);
+    println!("\ncontext pack:");
+    println!("{}", evaluation.evidence.context_pack);
+
+    if evaluation.intents.total() > 0 {
+        println!("\nquery intents:");
+        println!("{}", evaluation.intents);
+    }
 
     if evaluation.file_retrieval.slices.is_empty() {
         return;
@@ -300,6 +570,7 @@ fn read_evaluation_baseline(path: &Path) -> Result<EvaluationReport> {
             aggregate,
             slices: Vec::new(),
             queries: Vec::new(),
+            redundancy_at_k: 0.0,
         },
         evidence: repo_reaper_core::evaluation::metrics::EvidenceReport {
             status: repo_reaper_core::evaluation::metrics::EvidenceReportStatus::NotScored,
@@ -307,7 +578,9 @@ fn read_evaluation_baseline(path: &Path) -> Result<EvaluationReport> {
             total_evidence_spans: 0,
             groundedness: repo_reaper_core::evaluation::metrics::GroundednessEvaluation::default(),
             token_efficiency: TokenEfficiencyEvaluation::default(),
+            context_pack: repo_reaper_core::evaluation::metrics::ContextPackEvaluation::default(),
         },
+        intents: Default::default(),
     })
 }
 
@@ -1,24 +1,36 @@
 use std::{
-    collections::HashSet,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use eval::{EvalOutputFormat, evaluate_training};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use clap::{
+    ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, parser::ValueSource,
+};
+use eval::{
+    EvalOutputFormat, evaluate_training, train_intent_classifier, train_ranker, tune_ranker,
+};
+use live_search::PackFormat;
+use output::OutputFormat;
 use repo_reaper_core::{
-    config::Config as ReaperConfig,
-    index::{CorpusStats, InvertedIndex},
+    config::{AnalyzerSettings, Config as ReaperConfig, ProjectConfig, RankingSettings},
+    evaluation::tuning::{Metric, SearchStrategy},
+    index::{CorpusStats, DenseConfig, InvertedIndex, SearchFilter},
+    pack::DEFAULT_BUDGET_TOKENS,
     ranking::RankingAlgo,
     regex_search::{RegexSearchEngine, RegexSearchMatch},
-    tokenizer::n_gram_transform,
+    tokenizer::{FileType, Language, n_gram_transform},
 };
-use rust_stemmers::{Algorithm, Stemmer};
+use server::ListenAddr;
 
 mod eval;
 mod live_search;
+mod lsp;
+mod mcp;
+mod output;
+mod repl;
+mod server;
+mod tui;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -30,7 +42,13 @@ struct Args {
     #[clap(short, long, default_value = "1")]
     n_grams: usize,
     /// Ranking algorithm
-    #[clap(short, long, default_value = "bm25-proximity")]
+    #[clap(
+        short,
+        long,
+        visible_alias = "algo",
+        default_value = "bm25-proximity",
+        global = true
+    )]
     ranking_algorithm: RankingAlgo,
     /// Number of results to return
     #[clap(short, long, default_value = "10")]
@@ -60,11 +78,21 @@ struct Args {
     #[clap(long, default_value = "false")]
     stats: bool,
     /// Enable controlled abbreviation query expansion
-    #[clap(long, default_value = "false")]
+    #[clap(long, default_value = "false", overrides_with = "no_query_expansion")]
     query_expansion: bool,
+    /// Disable abbreviation query expansion even when the project config enables it
+    #[clap(long, overrides_with = "query_expansion")]
+    no_query_expansion: bool,
     /// Enable experimental pseudo-relevance feedback expansion
-    #[clap(long, default_value = "false")]
+    #[clap(
+        long,
+        default_value = "false",
+        overrides_with = "no_feedback_expansion"
+    )]
     feedback_expansion: bool,
+    /// Disable pseudo-relevance feedback expansion even when the project config enables it
+    #[clap(long, overrides_with = "feedback_expansion")]
+    no_feedback_expansion: bool,
     /// Write ranking feature export JSONL while evaluating
     #[clap(long)]
     export_features: Option<PathBuf>,
@@ -77,9 +105,71 @@ struct Args {
     /// Respect Git ignore rules when indexing inside a Git repository
     #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
     respect_gitignore: bool,
+    /// Add recency, churn and co-change signals from the Git history
+    #[clap(long, default_value = "false", overrides_with = "no_history")]
+    history: bool,
+    /// Disable Git history signals even when the project config enables it
+    #[clap(long, overrides_with = "history")]
+    no_history: bool,
+    /// Diversify results and collapse near-duplicates into a "+N similar" group
+    #[clap(long, default_value = "false", overrides_with = "no_diversify")]
+    diversify: bool,
+    /// Disable result diversification even when the project config enables it
+    #[clap(long, overrides_with = "diversify")]
+    no_diversify: bool,
+    /// Rank functions, types and Markdown sections as `path#symbol` instead of whole files.
+    /// Symbols are not diversified or feedback-expanded
+    #[clap(long, default_value = "false", overrides_with = "no_symbols")]
+    symbols: bool,
+    /// Disable symbol-level results even when the project config enables it
+    #[clap(long, overrides_with = "symbols")]
+    no_symbols: bool,
+    /// Default language for stemming and stop words when detection is inconclusive
+    #[clap(long, default_value = "english")]
+    language: Language,
+    /// JSON file declaring token-filter chains per field and file type
+    #[clap(long, value_name = "PATH")]
+    analyzer_config: Option<PathBuf>,
+    /// Only search files of this type (rust, python, js, ts, go, md, toml, yaml, json, sh)
+    #[clap(long = "type", value_name = "TYPE")]
+    file_types: Vec<FileType>,
+    /// Only search paths matching this glob; prefix with ! to exclude
+    #[clap(long = "glob", value_name = "GLOB")]
+    globs: Vec<String>,
+    /// Skip test files
+    #[clap(long, default_value = "false")]
+    exclude_tests: bool,
+    /// Skip generated files
+    #[clap(long, default_value = "false")]
+    exclude_generated: bool,
+    /// Skip vendored files
+    #[clap(long, default_value = "false")]
+    exclude_vendor: bool,
+    /// Skip files larger than this many bytes
+    #[clap(long, value_name = "BYTES")]
+    max_size: Option<u64>,
+    /// Output format for a one-shot ranked search
+    #[clap(long, value_enum, default_value = "human")]
+    format: OutputFormat,
+    /// Break down why each one-shot result scored what it did
+    #[clap(long, default_value = "false", overrides_with = "no_explain")]
+    explain: bool,
+    /// Disable score breakdowns even when the project config enables it
+    #[clap(long, overrides_with = "explain")]
+    no_explain: bool,
+    /// Project config file; defaults to the nearest reaper.toml between the
+    /// directory and the repository root, then the user config directory
+    #[clap(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
     /// Ranked search query to run once, then exit
     #[arg(value_name = "QUERY")]
     query: Option<String>,
+    /// Ranking parameters from the project config
+    #[arg(skip)]
+    ranking: RankingSettings,
+    /// Latent semantic model settings from the project config
+    #[arg(skip)]
+    dense: DenseConfig,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -91,21 +181,197 @@ enum Commands {
         /// Regex pattern to search for
         pattern: String,
     },
+    /// Bundle the best passages for a query into a token-budgeted context pack
+    Pack {
+        /// Ranked search query to pack context for
+        query: String,
+        /// Maximum estimated tokens in the pack
+        #[clap(long, default_value_t = DEFAULT_BUDGET_TOKENS)]
+        budget: usize,
+        /// Pack output format
+        #[clap(long, value_enum, default_value = "markdown")]
+        format: PackFormat,
+    },
+    /// Keep the index warm and answer JSON-RPC requests over HTTP
+    Serve {
+        /// Address to listen on: HOST:PORT or unix:PATH
+        #[clap(long, default_value = "127.0.0.1:7878")]
+        listen: ListenAddr,
+    },
+    /// Serve search tools to coding agents over the Model Context Protocol on stdio
+    Mcp,
+    /// Answer workspace symbol and search requests as a language server on stdio
+    Lsp,
+    /// Full-screen search as you type, with a results list and file preview
+    Tui,
+    /// Fit a reranking model to rows written by --export-features; the
+    /// --ranking-algorithm the rows were exported with is its first stage
+    Train {
+        /// Feature export JSONL to train on
+        features: PathBuf,
+        /// Where to write the model JSON
+        #[clap(short, long, default_value = "model.json")]
+        output: PathBuf,
+        /// Cross-validation folds reported before training on every query;
+        /// below two skips cross-validation
+        #[clap(long, default_value = "5")]
+        folds: usize,
+    },
+    /// Search the --ranking-algorithm's parameters for the best score on
+    /// --eval-data and write them out as a project config
+    Tune {
+        /// Metric to maximise: p, r, map, mrr or ndcg, optionally @k
+        #[clap(long, default_value = "ndcg@10")]
+        metric: Metric,
+        /// How to explore the parameter space
+        #[clap(long, value_enum, default_value = "coordinate")]
+        search: TuneSearch,
+        /// Combinations drawn by random search
+        #[clap(long, default_value = "200")]
+        trials: usize,
+        /// Seed for random search
+        #[clap(long, default_value = "0")]
+        seed: u64,
+        /// Most passes coordinate ascent makes over the parameters
+        #[clap(long, default_value = "5")]
+        passes: usize,
+        /// Cross-validation folds reported before tuning on every query;
+        /// below two skips cross-validation
+        #[clap(long, default_value = "5")]
+        folds: usize,
+        /// Where to write the tuned project config
+        #[clap(short, long, default_value = "reaper.tuned.toml")]
+        output: PathBuf,
+    },
+    /// Fit the query intent classifier to the query_shape labels of
+    /// evaluation data or labelled query files
+    TrainIntent {
+        /// JSON files whose examples carry a query and its query_shape
+        #[clap(required = true)]
+        data: Vec<PathBuf>,
+        /// Where to write the model JSON
+        #[clap(short, long, default_value = "intent_model.json")]
+        output: PathBuf,
+        /// Cross-validation folds reported before training on every query;
+        /// below two skips cross-validation
+        #[clap(long, default_value = "5")]
+        folds: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum TuneSearch {
+    Grid,
+    Random,
+    Coordinate,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum CliMode<'a> {
-    Regex { pattern: &'a str },
+    Regex {
+        pattern: &'a str,
+    },
+    Pack {
+        query: &'a str,
+        budget: usize,
+        format: PackFormat,
+    },
+    Serve {
+        listen: &'a ListenAddr,
+    },
+    Mcp,
+    Lsp,
+    Tui,
+    Train {
+        features: &'a Path,
+        output: &'a Path,
+        folds: usize,
+    },
+    Tune {
+        metric: Metric,
+        strategy: SearchStrategy,
+        folds: usize,
+        output: &'a Path,
+    },
+    TrainIntent {
+        data: &'a [PathBuf],
+        output: &'a Path,
+        folds: usize,
+    },
     Evaluate,
     Stats,
-    RankedOneShot { query: &'a str },
+    RankedOneShot {
+        query: &'a str,
+    },
     Live,
 }
 
 impl Args {
     fn mode(&self) -> CliMode<'_> {
-        if let Some(Commands::Regex { pattern }) = &self.command {
-            return CliMode::Regex { pattern };
+        match &self.command {
+            Some(Commands::Regex { pattern }) => return CliMode::Regex { pattern },
+            Some(Commands::Pack {
+                query,
+                budget,
+                format,
+            }) => {
+                return CliMode::Pack {
+                    query,
+                    budget: *budget,
+                    format: *format,
+                };
+            }
+            Some(Commands::Serve { listen }) => return CliMode::Serve { listen },
+            Some(Commands::Mcp) => return CliMode::Mcp,
+            Some(Commands::Lsp) => return CliMode::Lsp,
+            Some(Commands::Tui) => return CliMode::Tui,
+            Some(Commands::Train {
+                features,
+                output,
+                folds,
+            }) => {
+                return CliMode::Train {
+                    features,
+                    output,
+                    folds: *folds,
+                };
+            }
+            Some(Commands::Tune {
+                metric,
+                search,
+                trials,
+                seed,
+                passes,
+                folds,
+                output,
+            }) => {
+                let strategy = match search {
+                    TuneSearch::Grid => SearchStrategy::Grid,
+                    TuneSearch::Random => SearchStrategy::Random {
+                        trials: *trials,
+                        seed: *seed,
+                    },
+                    TuneSearch::Coordinate => SearchStrategy::CoordinateAscent { passes: *passes },
+                };
+                return CliMode::Tune {
+                    metric: *metric,
+                    strategy,
+                    folds: *folds,
+                    output,
+                };
+            }
+            Some(Commands::TrainIntent {
+                data,
+                output,
+                folds,
+            }) => {
+                return CliMode::TrainIntent {
+                    data,
+                    output,
+                    folds: *folds,
+                };
+            }
+            None => {}
         }
 
         if self.evaluate {
@@ -122,24 +388,165 @@ impl Args {
 
         CliMode::Live
     }
+
+    /// Takes every setting not given on the command line from `project`,
+    /// then applies its ranking parameters to the chosen algorithm.
+    fn apply_project_config(
+        &mut self,
+        project: &ProjectConfig,
+        matches: &ArgMatches,
+    ) -> Result<()> {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        let analyzer = &project.analyzer;
+        let ranking = &project.ranking;
+        let index = &project.index;
+        let output = &project.output;
+
+        if unset("n_grams")
+            && let Some(n_grams) = analyzer.n_grams
+        {
+            self.n_grams = n_grams;
+        }
+        if unset("language")
+            && let Some(language) = analyzer.language
+        {
+            self.language = language;
+        }
+        if unset("analyzer_config") && analyzer.analyzer_config.is_some() {
+            self.analyzer_config = analyzer.analyzer_config.clone();
+        }
+        if unset("ranking_algorithm")
+            && let Some(name) = &ranking.algorithm
+        {
+            self.ranking_algorithm = name.parse().map_err(anyhow::Error::msg)?;
+        }
+        if unset("top_n")
+            && let Some(top_n) = ranking.top_n
+        {
+            self.top_n = top_n;
+        }
+        let toggle = |flag: &mut bool, id: &str, disabled: bool, configured: Option<bool>| {
+            if unset(id)
+                && !disabled
+                && let Some(configured) = configured
+            {
+                *flag = configured;
+            }
+        };
+
+        toggle(
+            &mut self.query_expansion,
+            "query_expansion",
+            self.no_query_expansion,
+            ranking.query_expansion,
+        );
+        toggle(
+            &mut self.feedback_expansion,
+            "feedback_expansion",
+            self.no_feedback_expansion,
+            ranking.feedback_expansion,
+        );
+        toggle(
+            &mut self.diversify,
+            "diversify",
+            self.no_diversify,
+            ranking.diversify,
+        );
+        if unset("index_dir") && index.dir.is_some() {
+            self.index_dir = index.dir.clone();
+        }
+        if unset("respect_gitignore")
+            && let Some(respect_gitignore) = index.respect_gitignore
+        {
+            self.respect_gitignore = respect_gitignore;
+        }
+        toggle(&mut self.history, "history", self.no_history, index.history);
+        toggle(&mut self.symbols, "symbols", self.no_symbols, index.symbols);
+        if unset("globs") {
+            self.globs = index.globs();
+        }
+        if unset("format")
+            && let Some(format) = &output.format
+        {
+            self.format = OutputFormat::from_str(format, true).map_err(anyhow::Error::msg)?;
+        }
+        toggle(
+            &mut self.explain,
+            "explain",
+            self.no_explain,
+            output.explain,
+        );
+
+        self.ranking_algorithm = ranking.apply(self.ranking_algorithm.clone());
+        self.ranking = ranking.clone();
+        self.dense = index.dense.clone();
+        Ok(())
+    }
 }
 
 pub fn run_cli() -> Result<()> {
-    let args = Args::parse();
-
-    let config = Arc::new(ReaperConfig {
-        n_grams: args.n_grams,
-        stemmer: Stemmer::create(Algorithm::English),
-        stop_words: stop_words::get(stop_words::LANGUAGE::English)
-            .par_iter()
-            .map(|word| word.to_string())
-            .collect::<HashSet<String>>(),
-    });
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    let (args, config) = configure(args, &matches)?;
 
     match args.mode() {
         CliMode::Regex { pattern } => {
             run_regex_search(&args.directory, pattern, args.respect_gitignore)
         }
+        CliMode::Pack {
+            query,
+            budget,
+            format,
+        } => live_search::run_pack(
+            args.directory.clone(),
+            config,
+            args.ranking_algorithm.clone(),
+            live_search_options(&args)?,
+            query,
+            budget,
+            format,
+        ),
+        CliMode::Serve { listen } => server::run(
+            args.directory.clone(),
+            config,
+            args.ranking_algorithm.clone(),
+            live_search_options(&args)?,
+            listen,
+        ),
+        CliMode::Mcp => mcp::run(
+            args.directory.clone(),
+            config,
+            args.ranking_algorithm.clone(),
+            live_search_options(&args)?,
+        ),
+        CliMode::Lsp => lsp::run(
+            args.directory.clone(),
+            config,
+            args.ranking_algorithm.clone(),
+            live_search_options(&args)?,
+        ),
+        CliMode::Tui => tui::run(
+            args.directory.clone(),
+            config,
+            args.ranking_algorithm.clone(),
+            live_search_options(&args)?,
+        ),
+        CliMode::Train {
+            features,
+            output,
+            folds,
+        } => train_ranker(&args, features, output, folds),
+        CliMode::Tune {
+            metric,
+            strategy,
+            folds,
+            output,
+        } => tune_ranker(&args, &config, metric, strategy, folds, output),
+        CliMode::TrainIntent {
+            data,
+            output,
+            folds,
+        } => train_intent_classifier(&args, data, output, folds),
         CliMode::Evaluate => evaluate_training(&args, &config),
         CliMode::Stats => {
             print_directory_stats(&args.directory, &config, args.respect_gitignore);
@@ -149,27 +556,70 @@ pub fn run_cli() -> Result<()> {
             args.directory.clone(),
             config,
             args.ranking_algorithm.clone(),
-            live_search_options(&args),
+            live_search_options(&args)?,
             query,
+            args.format,
+            args.explain,
         ),
         CliMode::Live => live_search::run(
             args.directory.clone(),
             config,
             args.ranking_algorithm.clone(),
-            live_search_options(&args),
+            live_search_options(&args)?,
         ),
     }
 }
 
-fn live_search_options(args: &Args) -> live_search::LiveSearchOptions {
-    live_search::LiveSearchOptions {
+/// Applies the project config found for `args` and builds the analyzer
+/// config the command runs with.
+fn configure(mut args: Args, matches: &ArgMatches) -> Result<(Args, Arc<ReaperConfig>)> {
+    let project = match &args.config {
+        Some(path) => ProjectConfig::load(path)?,
+        None => ProjectConfig::discover(&args.directory)?
+            .map(|(_, project)| project)
+            .unwrap_or_default(),
+    };
+    args.apply_project_config(&project, matches)?;
+
+    let config = Arc::new(ReaperConfig::from_settings(&AnalyzerSettings {
+        language: Some(args.language),
+        n_grams: Some(args.n_grams),
+        analyzer_config: args.analyzer_config.clone(),
+        ..project.analyzer
+    })?);
+    Ok((args, config))
+}
+
+fn live_search_options(args: &Args) -> Result<live_search::LiveSearchOptions> {
+    Ok(live_search::LiveSearchOptions {
         top_n: args.top_n,
         query_expansion: args.query_expansion,
         feedback_expansion: args.feedback_expansion,
         index_dir: args.index_dir.clone(),
         reindex: args.reindex,
         respect_gitignore: args.respect_gitignore,
-    }
+        history: args.history,
+        diversify: args.diversify,
+        symbols: args.symbols,
+        filter: search_filter(args)?,
+        ranking: args.ranking.clone(),
+        dense: args.dense.clone(),
+    })
+}
+
+fn search_filter(args: &Args) -> Result<SearchFilter> {
+    let filter = SearchFilter::new()
+        .with_root(&args.directory)
+        .with_file_types(args.file_types.iter().copied())
+        .with_globs(&args.globs)?
+        .with_exclude_tests(args.exclude_tests)
+        .with_exclude_generated(args.exclude_generated)
+        .with_exclude_vendor(args.exclude_vendor);
+
+    Ok(match args.max_size {
+        Some(max_size) => filter.with_max_size_bytes(max_size),
+        None => filter,
+    })
 }
 
 fn print_directory_stats(directory: &Path, config: &ReaperConfig, respect_gitignore: bool) {
@@ -183,7 +633,7 @@ fn print_directory_stats(directory: &Path, config: &ReaperConfig, respect_gitign
     print_corpus_stats(&index.corpus_stats(10));
 }
 
-fn print_corpus_stats(stats: &CorpusStats) {
+pub(crate) fn print_corpus_stats(stats: &CorpusStats) {
     println!("documents: {}", stats.document_count);
     println!("total tokens: {}", stats.total_token_count);
     println!("vocabulary size: {}", stats.vocabulary_size);
@@ -239,9 +689,233 @@ fn print_regex_match(match_: &RegexSearchMatch) {
 
 #[cfg(test)]
 mod tests {
-    use clap::Parser;
+    use std::{path::Path, sync::Arc};
+
+    use clap::{CommandFactory, FromArgMatches, Parser};
+    use repo_reaper_core::{
+        config::{AnalyzerSettings, IndexSettings, OutputSettings, ProjectConfig, RankingSettings},
+        query::AnalyzedQuery,
+        ranking::{BM25HyperParams, RankingAlgo},
+        tokenizer::Language,
+    };
+
+    use super::{
+        Args, CliMode, ListenAddr, OutputFormat, PackFormat, SearchStrategy, configure,
+        live_search, live_search_options, search_filter,
+    };
+
+    fn write_temp_file(dir: &Path, name: &str, content: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    /// A project under the working directory, indexed through a `./`
+    /// prefixed path the way `-d .` indexes the current directory.
+    fn relative_project() -> tempfile::TempDir {
+        let dir = tempfile::Builder::new()
+            .prefix("rr-project-")
+            .tempdir_in(".")
+            .unwrap();
+        write_temp_file(dir.path(), "src/pool.rs", "fn connect_pool() {}");
+        write_temp_file(dir.path(), "tests/pool.rs", "fn connect_pool_test() {}");
+        write_temp_file(dir.path(), "docs/pool.md", "connect pool");
+        dir
+    }
+
+    /// Prepares a one-shot search the way `rr` does.
+    fn prepare_search(
+        argv: &[&str],
+    ) -> (
+        Args,
+        live_search::LiveSearchOptions,
+        live_search::PreparedRankedSearch,
+        AnalyzedQuery,
+    ) {
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let (args, config) = configure(args, &matches).unwrap();
+        let options = live_search_options(&args).unwrap();
+        let algo = &args.ranking_algorithm;
+        let prepared = live_search::prepare_ranked_search(
+            &args.directory,
+            Arc::clone(&config),
+            algo,
+            &options,
+            false,
+        )
+        .unwrap();
+        let query =
+            live_search::analyze_query(&config, algo, args.query.as_deref().unwrap(), false, false);
+        (args, options, prepared, query)
+    }
+
+    /// Runs a one-shot search the way `rr` does and returns the result
+    /// paths relative to the searched directory.
+    fn search_paths(argv: &[&str]) -> Vec<String> {
+        let (args, options, prepared, query) = prepare_search(argv);
+        let mut paths = live_search::search_ranked(
+            &prepared.engine,
+            &args.ranking_algorithm,
+            &query,
+            options.top_n,
+            options.search_modes(),
+            &options.filter,
+        )
+        .unwrap()
+        .map_or_else(Vec::new, |ranking| ranking.0)
+        .into_iter()
+        .map(|score| {
+            score
+                .doc_path
+                .strip_prefix(&args.directory)
+                .unwrap()
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn project_config_include_and_exclude_filter_results() {
+        let project = relative_project();
+        write_temp_file(
+            project.path(),
+            "reaper.toml",
+            "[index]\ninclude = [\"src/**\", \"tests/**\"]\nexclude = [\"tests/**\"]\n",
+        );
+        let directory = format!(
+            "./{}",
+            project.path().file_name().unwrap().to_str().unwrap()
+        );
+
+        assert_eq!(
+            search_paths(&["rr", "-d", &directory, "connect pool"]),
+            vec!["src/pool.rs"]
+        );
+    }
+
+    #[test]
+    fn no_flags_turn_off_toggles_the_project_config_enables() {
+        let project = relative_project();
+        write_temp_file(
+            project.path(),
+            "reaper.toml",
+            "[ranking]\ndiversify = true\n\n[output]\nexplain = true\n",
+        );
+        let directory = project.path().to_str().unwrap();
+        let configured = |argv: &[&str]| {
+            let matches = Args::command().try_get_matches_from(argv).unwrap();
+            let args = Args::from_arg_matches(&matches).unwrap();
+            let (args, _) = configure(args, &matches).unwrap();
+            (args.diversify, args.explain)
+        };
+
+        assert_eq!(configured(&["rr", "-d", directory]), (true, true));
+        assert_eq!(
+            configured(&["rr", "-d", directory, "--no-diversify"]),
+            (false, true)
+        );
+        assert_eq!(
+            configured(&["rr", "-d", directory, "--explain", "--no-explain"]),
+            (true, false)
+        );
+        assert_eq!(
+            configured(&["rr", "-d", directory, "--no-explain", "--explain"]),
+            (true, true)
+        );
+    }
+
+    #[test]
+    fn history_outside_a_repository_ranks_without_it() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", "fn connect_pool() {}");
+        let directory = dir.path().to_str().unwrap();
+
+        let (_, _, prepared, _) = prepare_search(&["rr", "-d", directory, "--history", "pool"]);
+
+        assert!(
+            prepared
+                .engine
+                .with_read(|index| index.history().is_none() && index.num_docs() == 1)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn symbol_results_are_explained_and_packed_by_their_files() {
+        let project = relative_project();
+        let directory = format!(
+            "./{}",
+            project.path().file_name().unwrap().to_str().unwrap()
+        );
+        let (args, options, prepared, query) =
+            prepare_search(&["rr", "-d", &directory, "--symbols", "connect pool"]);
+        let algo = &args.ranking_algorithm;
+
+        let symbols = live_search::search_ranked(
+            &prepared.engine,
+            algo,
+            &query,
+            options.top_n,
+            options.search_modes(),
+            &options.filter,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(!symbols.0.is_empty());
+        assert!(
+            symbols
+                .0
+                .iter()
+                .all(|score| score.doc_path.to_string_lossy().contains('#'))
+        );
+        let explanations = live_search::explain_results(
+            &prepared.engine,
+            algo,
+            &query,
+            &symbols.0,
+            &options.filter,
+        )
+        .unwrap();
+        assert!(explanations.iter().all(Option::is_some));
+
+        let files = live_search::search_pack_files(
+            &prepared.engine,
+            algo,
+            &query,
+            options.top_n,
+            options.search_modes(),
+            &options.filter,
+        )
+        .unwrap();
+        assert!(!files.0.is_empty());
+        for file in &files.0 {
+            assert!(file.doc_path.is_file(), "{}", file.doc_path.display());
+            assert!(!file.passages.is_empty());
+        }
+    }
 
-    use super::{Args, CliMode};
+    #[test]
+    fn globs_match_paths_relative_to_the_searched_directory() {
+        let project = relative_project();
+        let directory = format!(
+            "./{}",
+            project.path().file_name().unwrap().to_str().unwrap()
+        );
+        let directory = directory.as_str();
+
+        assert_eq!(
+            search_paths(&["rr", "-d", directory, "--glob", "src/**", "connect pool"]),
+            vec!["src/pool.rs"]
+        );
+        assert_eq!(
+            search_paths(&["rr", "-d", directory, "--glob", "!tests/**", "connect pool"]),
+            vec!["docs/pool.md", "src/pool.rs"]
+        );
+    }
 
     #[test]
     fn parse_accepts_quoted_positional_query_as_one_search_string() {
@@ -288,6 +962,141 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_pack_subcommand_with_budget_and_format() {
+        let args = Args::try_parse_from(["rr", "pack", "retry pool", "--budget", "2000"])
+            .expect("pack subcommand should parse");
+
+        assert_eq!(
+            args.mode(),
+            CliMode::Pack {
+                query: "retry pool",
+                budget: 2000,
+                format: PackFormat::Markdown,
+            }
+        );
+
+        let args = Args::try_parse_from(["rr", "pack", "retry pool", "--format", "json"])
+            .expect("pack json format should parse");
+        assert_eq!(
+            args.mode(),
+            CliMode::Pack {
+                query: "retry pool",
+                budget: 8000,
+                format: PackFormat::Json,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_serve_subcommand_with_listen_address() {
+        let args = Args::try_parse_from(["rr", "serve", "--listen", "unix:/tmp/rr.sock"])
+            .expect("serve subcommand should parse");
+
+        assert_eq!(
+            args.mode(),
+            CliMode::Serve {
+                listen: &ListenAddr::Unix("/tmp/rr.sock".into())
+            }
+        );
+    }
+
+    #[test]
+    fn parse_mcp_subcommand() {
+        let args = Args::try_parse_from(["rr", "--ranking-algorithm", "bm25f", "mcp"])
+            .expect("mcp subcommand should parse");
+
+        assert_eq!(args.mode(), CliMode::Mcp);
+    }
+
+    #[test]
+    fn parse_lsp_subcommand() {
+        let args = Args::try_parse_from(["rr", "lsp"]).expect("lsp subcommand should parse");
+
+        assert_eq!(args.mode(), CliMode::Lsp);
+    }
+
+    #[test]
+    fn parse_tui_subcommand() {
+        let args = Args::try_parse_from(["rr", "--ranking-algorithm", "bm25", "tui"])
+            .expect("tui subcommand should parse");
+
+        assert_eq!(args.mode(), CliMode::Tui);
+    }
+
+    #[test]
+    fn parse_tune_subcommand() {
+        let args = Args::try_parse_from([
+            "rr", "tune", "--algo", "bm25f", "--metric", "mrr@5", "--search", "random", "--trials",
+            "50",
+        ])
+        .expect("tune subcommand should parse");
+
+        assert_eq!(args.ranking_algorithm.name(), "bm25f");
+        assert_eq!(
+            args.mode(),
+            CliMode::Tune {
+                metric: "mrr@5".parse().unwrap(),
+                strategy: SearchStrategy::Random {
+                    trials: 50,
+                    seed: 0
+                },
+                folds: 5,
+                output: Path::new("reaper.tuned.toml"),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_train_subcommand() {
+        let args = Args::try_parse_from([
+            "rr",
+            "--ranking-algorithm",
+            "bm25f",
+            "train",
+            "features.jsonl",
+            "--output",
+            "ranker.json",
+            "--folds",
+            "3",
+        ])
+        .expect("train subcommand should parse");
+
+        assert_eq!(
+            args.mode(),
+            CliMode::Train {
+                features: Path::new("features.jsonl"),
+                output: Path::new("ranker.json"),
+                folds: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_train_intent_subcommand() {
+        let args = Args::try_parse_from([
+            "rr",
+            "train-intent",
+            "data/eval/repo_reaper.json",
+            "data/intent/queries.json",
+            "--folds",
+            "4",
+        ])
+        .expect("train-intent subcommand should parse");
+
+        assert_eq!(
+            args.mode(),
+            CliMode::TrainIntent {
+                data: &[
+                    "data/eval/repo_reaper.json".into(),
+                    "data/intent/queries.json".into()
+                ],
+                output: Path::new("intent_model.json"),
+                folds: 4,
+            }
+        );
+    }
+
     #[test]
     fn parse_respect_gitignore_false_with_query_reaches_one_shot_config() {
         let args = Args::try_parse_from([
@@ -303,4 +1112,124 @@ mod tests {
         assert!(!args.respect_gitignore);
         assert_eq!(args.mode(), CliMode::RankedOneShot { query: "auth()" });
     }
+
+    #[test]
+    fn parse_one_shot_output_format() {
+        let args = Args::try_parse_from(["rr", "auth()"]).expect("one-shot query should parse");
+        assert_eq!(args.format, OutputFormat::Human);
+
+        for (flag, format) in [
+            ("json", OutputFormat::Json),
+            ("jsonl", OutputFormat::Jsonl),
+            ("vimgrep", OutputFormat::Vimgrep),
+        ] {
+            let args = Args::try_parse_from(["rr", "--format", flag, "auth()"])
+                .expect("output format should parse");
+            assert_eq!(args.format, format);
+            assert_eq!(args.mode(), CliMode::RankedOneShot { query: "auth()" });
+        }
+
+        let args = Args::try_parse_from(["rr", "--explain", "--format", "json", "auth()"])
+            .expect("explain flag should parse");
+        assert!(args.explain);
+        assert_eq!(args.mode(), CliMode::RankedOneShot { query: "auth()" });
+    }
+
+    #[test]
+    fn parse_search_filter_flags() {
+        let args = Args::try_parse_from([
+            "rr",
+            "--type",
+            "rust",
+            "--glob",
+            "crates/core/**",
+            "--glob",
+            "!**/tests/**",
+            "--exclude-tests",
+            "--exclude-generated",
+            "--max-size",
+            "4096",
+            "bm25",
+        ])
+        .expect("filter flags should parse");
+
+        assert_eq!(args.globs, vec!["crates/core/**", "!**/tests/**"]);
+        assert_eq!(args.max_size, Some(4096));
+        assert!(!search_filter(&args).unwrap().is_empty());
+        assert_eq!(args.mode(), CliMode::RankedOneShot { query: "bm25" });
+    }
+
+    #[test]
+    fn parse_rejects_unknown_file_type() {
+        assert!(Args::try_parse_from(["rr", "--type", "cobol", "bm25"]).is_err());
+    }
+
+    #[test]
+    fn project_config_fills_settings_the_command_line_leaves_unset() {
+        let project = ProjectConfig {
+            analyzer: AnalyzerSettings {
+                language: Some(Language::German),
+                n_grams: Some(2),
+                ..AnalyzerSettings::default()
+            },
+            ranking: RankingSettings {
+                algorithm: Some("bm25".to_string()),
+                top_n: Some(25),
+                k1: Some(2.0),
+                ..RankingSettings::default()
+            },
+            index: IndexSettings {
+                exclude: vec!["**/fixtures/**".to_string()],
+                respect_gitignore: Some(false),
+                history: Some(true),
+                symbols: Some(true),
+                ..IndexSettings::default()
+            },
+            output: OutputSettings {
+                format: Some("jsonl".to_string()),
+                explain: Some(true),
+            },
+        };
+        let parse = |argv: &[&str]| {
+            let matches = Args::command().try_get_matches_from(argv).unwrap();
+            let mut args = Args::from_arg_matches(&matches).unwrap();
+            args.apply_project_config(&project, &matches).unwrap();
+            args
+        };
+
+        let args = parse(&["rr", "auth()"]);
+        assert_eq!(args.language, Language::German);
+        assert_eq!(args.n_grams, 2);
+        assert_eq!(args.top_n, 25);
+        assert!(!args.respect_gitignore);
+        assert!(args.history);
+        assert!(args.symbols);
+        assert_eq!(args.globs, vec!["!**/fixtures/**"]);
+        assert_eq!(args.format, OutputFormat::Jsonl);
+        assert!(args.explain);
+        assert!(matches!(
+            args.ranking_algorithm,
+            RankingAlgo::BM25(BM25HyperParams { k1: 2.0, .. })
+        ));
+
+        let args = parse(&[
+            "rr",
+            "--top-n",
+            "5",
+            "--ranking-algorithm",
+            "bm25f",
+            "--glob",
+            "src/**",
+            "--format",
+            "human",
+            "auth()",
+        ]);
+        assert_eq!(args.top_n, 5);
+        assert_eq!(args.globs, vec!["src/**"]);
+        assert_eq!(args.format, OutputFormat::Human);
+        assert!(matches!(
+            &args.ranking_algorithm,
+            RankingAlgo::BM25F(params) if params.k1 == 2.0
+        ));
+    }
 }
@@ -1,32 +1,44 @@
 use std::{
     collections::HashMap,
-    env,
-    fs::{self, OpenOptions},
-    io::{IsTerminal, Write},
+    env, fs,
+    io::{self, IsTerminal, Write},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     thread,
 };
 
 use anyhow::{Context, Result};
-use chrono::Utc;
 use notify::{
     Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
     event::{ModifyKind, RemoveKind},
 };
 use repo_reaper_core::{
-    config::Config as ReaperConfig,
+    code_intelligence::ByteSpan,
+    config::{Config as ReaperConfig, RankingSettings},
     index::{
-        FileSystemIndexCorpus, InvertedIndex, SearchEngine,
+        DenseConfig, FileSystemIndexCorpus, FilteredIndex, GitHistory, HistoryError, IndexHistory,
+        InvertedIndex, SearchEngine, SearchFilter,
         event_log::{IndexEvent, append_event, clear_events, read_events, replay_events},
         inverted_file::InvertedFileLayout,
         snapshot::{load_snapshot, snapshot_path, write_snapshot},
     },
+    pack::ContextPack,
     query::{AnalyzedQuery, QueryExpansionConfig},
-    ranking::{RankingAlgo, Score, Scored},
+    ranking::{
+        DiversityConfig, Passage, PassageConfig, RankingAlgo, Score, ScoreExplanation, Scored,
+        Snippet, attach_passages, highlight_spans, snippet,
+    },
     tokenizer::n_gram_transform,
 };
 
+use crate::{
+    output::{OutputFormat, SearchDocument, vimgrep_line},
+    repl,
+};
+
 pub(crate) struct LiveSearchOptions {
     pub(crate) top_n: usize,
     pub(crate) query_expansion: bool,
@@ -34,6 +46,47 @@ pub(crate) struct LiveSearchOptions {
     pub(crate) index_dir: Option<PathBuf>,
     pub(crate) reindex: bool,
     pub(crate) respect_gitignore: bool,
+    /// Add recency, churn and co-change signals from the Git history.
+    pub(crate) history: bool,
+    /// Spread results across distinct documents and fold near-duplicates
+    /// into the result they resemble.
+    pub(crate) diversify: bool,
+    /// Rank functions, types and sections instead of whole files.
+    pub(crate) symbols: bool,
+    pub(crate) filter: SearchFilter,
+    /// Parameters and quality priors from `reaper.toml`, reapplied whenever
+    /// the algorithm is switched by name.
+    pub(crate) ranking: RankingSettings,
+    /// Latent semantic model built for algorithms that rank by it.
+    pub(crate) dense: DenseConfig,
+}
+
+impl LiveSearchOptions {
+    pub(crate) fn search_modes(&self) -> SearchModes<'_> {
+        SearchModes {
+            symbols: self.symbols,
+            diversity: self.diversify.then_some(&self.ranking.diversity),
+            feedback_expansion: self.feedback_expansion,
+        }
+    }
+}
+
+/// Ways a ranked search departs from plain ranking, in the order
+/// [`search_ranked`] checks them. Symbols are ranked plainly: diversity and
+/// feedback expansion only apply to file rankings.
+#[derive(Clone, Copy)]
+pub(crate) struct SearchModes<'a> {
+    /// Rank symbols and sections instead of whole files.
+    pub(crate) symbols: bool,
+    /// Diversification settings when results should be diversified.
+    pub(crate) diversity: Option<&'a DiversityConfig>,
+    pub(crate) feedback_expansion: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum PackFormat {
+    Markdown,
+    Json,
 }
 
 pub(crate) fn run(
@@ -45,22 +98,17 @@ pub(crate) fn run(
     let prepared = prepare_ranked_search(&directory, Arc::clone(&config), &algo, &options, true)?;
     let transformer = build_transformer(Arc::clone(&config));
 
+    let fielded = Arc::new(AtomicBool::new(prepared.fielded));
+
     spawn_watcher(
-        directory,
+        directory.clone(),
         prepared.engine.clone(),
         transformer,
         Arc::clone(&config),
-        prepared.fielded,
-        options.index_dir,
+        Arc::clone(&fielded),
+        options.index_dir.clone(),
     );
-    run_repl(
-        config,
-        algo,
-        options.top_n,
-        prepared.engine,
-        options.query_expansion,
-        options.feedback_expansion,
-    )
+    repl::run(directory, config, algo, prepared.engine, options, fielded)
 }
 
 pub(crate) fn run_once(
@@ -69,6 +117,8 @@ pub(crate) fn run_once(
     algo: RankingAlgo,
     options: LiveSearchOptions,
     query: &str,
+    format: OutputFormat,
+    explain: bool,
 ) -> Result<()> {
     let prepared = prepare_ranked_search(&directory, Arc::clone(&config), &algo, &options, false)?;
     let analyzed_query = analyze_query(
@@ -83,18 +133,77 @@ pub(crate) fn run_once(
         &algo,
         &analyzed_query,
         options.top_n,
+        options.search_modes(),
+        &options.filter,
+    )?;
+    let results = ranking.as_ref().map_or(&[][..], |ranking| &ranking.0[..]);
+    let explanations = if explain {
+        explain_results(
+            &prepared.engine,
+            &algo,
+            &analyzed_query,
+            results,
+            &options.filter,
+        )?
+    } else {
+        Vec::new()
+    };
+    print_one_shot_results(
+        results,
+        explanations,
+        &prepared.engine,
+        &analyzed_query,
+        format,
+    )
+}
+
+pub(crate) fn run_pack(
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    options: LiveSearchOptions,
+    query: &str,
+    budget_tokens: usize,
+    format: PackFormat,
+) -> Result<()> {
+    let prepared = prepare_ranked_search(&directory, Arc::clone(&config), &algo, &options, false)?;
+    let analyzed_query = analyze_query(
+        &config,
+        &algo,
+        query,
+        options.query_expansion,
         options.feedback_expansion,
+    );
+    let ranking = search_pack_files(
+        &prepared.engine,
+        &algo,
+        &analyzed_query,
+        options.top_n,
+        options.search_modes(),
+        &options.filter,
     )?;
-    print_one_shot_results(&ranking);
+
+    let mut pack = prepared
+        .engine
+        .with_read(|index| ContextPack::select(index, query, &ranking.0, budget_tokens))?;
+    pack.load_text(|path| fs::read_to_string(path).ok());
+
+    match format {
+        PackFormat::Markdown => print!("{}", pack.to_markdown()),
+        PackFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&pack).context("failed to serialize context pack")?
+        ),
+    }
     Ok(())
 }
 
-struct PreparedRankedSearch {
-    engine: SearchEngine,
-    fielded: bool,
+pub(crate) struct PreparedRankedSearch {
+    pub(crate) engine: SearchEngine,
+    pub(crate) fielded: bool,
 }
 
-fn prepare_ranked_search(
+pub(crate) fn prepare_ranked_search(
     directory: &PathBuf,
     config: Arc<ReaperConfig>,
     algo: &RankingAlgo,
@@ -108,7 +217,8 @@ fn prepare_ranked_search(
         ui.status("index", &format!("{} preparing", directory.display()));
     }
 
-    let fielded = algo.needs_fielded_index();
+    // Sub-documents are carved with the fielded analysis.
+    let fielded = algo.needs_fielded_index() || options.symbols;
     let should_write_cache;
     let index = if let Some(index_dir) = options.index_dir.as_ref().filter(|_| !options.reindex) {
         let path = snapshot_path(index_dir);
@@ -194,11 +304,32 @@ fn prepare_ranked_search(
         )
     };
 
-    let engine = SearchEngine::new(index);
+    let mut index = index;
+    let history_changed = match sync_history(&mut index, directory, options.history) {
+        Ok(changed) => changed,
+        Err(error) => {
+            ui.status("history", "unavailable; ranking without it");
+            ui.detail(&error.to_string());
+            false
+        }
+    };
+
+    if verbose && needs_dense(&index, algo, &options.dense) {
+        ui.status("dense", "fitting latent semantic vectors");
+    }
+    let dense_changed = sync_dense(&mut index, algo, &options.dense);
+
+    if verbose && options.symbols && index.sub_documents().is_none() {
+        ui.status("symbols", "indexing functions, types and sections");
+    }
+    let symbols_changed = sync_sub_documents(&mut index, options.symbols, &config);
+
+    let engine =
+        SearchEngine::new(index.with_quality_priors(options.ranking.quality_priors.clone()));
     let document_count = engine.num_docs()?;
 
     if let Some(index_dir) = &options.index_dir
-        && should_write_cache
+        && (should_write_cache || history_changed || dense_changed || symbols_changed)
     {
         if verbose {
             ui.status(
@@ -206,12 +337,7 @@ fn prepare_ranked_search(
                 &format!("{} writing snapshot", index_dir.display()),
             );
         }
-        engine.with_read(|index| {
-            write_snapshot(index, index_dir, directory, &config)?;
-            InvertedFileLayout::write(index, index_dir)?;
-            clear_events(index_dir)?;
-            Ok::<_, anyhow::Error>(())
-        })??;
+        write_index_cache(&engine, index_dir, directory, &config)?;
         if verbose {
             ui.status("cache", &format!("{} snapshot ready", index_dir.display()));
         }
@@ -224,13 +350,106 @@ fn prepare_ranked_search(
     Ok(PreparedRankedSearch { engine, fielded })
 }
 
-fn build_transformer(
+/// Brings the index's Git history in line with `enabled`: reads it for an
+/// index without one, folds in new commits for an index that has one and
+/// drops it when disabled. Returns whether the index changed.
+pub(crate) fn sync_history(
+    index: &mut InvertedIndex,
+    directory: &Path,
+    enabled: bool,
+) -> Result<bool, HistoryError> {
+    match (enabled, index.history().is_some()) {
+        (true, true) => index.refresh_history(directory),
+        (true, false) => {
+            index.set_history(Some(IndexHistory {
+                history: GitHistory::read(directory)?,
+                base: PathBuf::new(),
+            }));
+            Ok(true)
+        }
+        (false, true) => {
+            index.set_history(None);
+            Ok(true)
+        }
+        (false, false) => Ok(false),
+    }
+}
+
+/// Builds the sub-document index when `enabled` and drops it otherwise.
+/// Returns whether the index changed.
+pub(crate) fn sync_sub_documents(
+    index: &mut InvertedIndex,
+    enabled: bool,
+    config: &ReaperConfig,
+) -> bool {
+    match (enabled, index.sub_documents().is_some()) {
+        (true, false) => {
+            index.build_sub_documents(config);
+            true
+        }
+        (false, true) => {
+            index.set_sub_documents(None);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Whether `algo` ranks by dense vectors the index lacks or built with
+/// other settings.
+fn needs_dense(index: &InvertedIndex, algo: &RankingAlgo, config: &DenseConfig) -> bool {
+    algo.needs_dense_index() && index.dense().is_none_or(|dense| dense.config() != config)
+}
+
+/// Fits dense vectors when `algo` needs them and the index has none built
+/// with `config`. Returns whether the index changed.
+pub(crate) fn sync_dense(
+    index: &mut InvertedIndex,
+    algo: &RankingAlgo,
+    config: &DenseConfig,
+) -> bool {
+    let needed = needs_dense(index, algo, config);
+    if needed {
+        index.build_dense(config);
+    }
+    needed
+}
+
+/// [`sync_dense`] for the index behind a running engine.
+pub(crate) fn ensure_dense(
+    engine: &SearchEngine,
+    algo: &RankingAlgo,
+    config: &DenseConfig,
+) -> Result<bool> {
+    if !engine.with_read(|index| needs_dense(index, algo, config))? {
+        return Ok(false);
+    }
+    Ok(engine.with_write(|index| sync_dense(index, algo, config))?)
+}
+
+/// Persists the engine's index as a snapshot and inverted-file layout and
+/// drops the events it already contains.
+pub(crate) fn write_index_cache(
+    engine: &SearchEngine,
+    index_dir: &Path,
+    directory: &Path,
+    config: &ReaperConfig,
+) -> Result<()> {
+    engine.with_read(|index| {
+        write_snapshot(index, index_dir, directory, config)?;
+        InvertedFileLayout::write(index, index_dir)?;
+        clear_events(index_dir)?;
+        Ok::<_, anyhow::Error>(())
+    })?
+}
+
+pub(crate) fn build_transformer(
     config: Arc<ReaperConfig>,
 ) -> Arc<impl Fn(&str) -> HashMap<repo_reaper_core::index::Term, u32> + Send + Sync + 'static> {
     Arc::new(move |content: &str| n_gram_transform(content, &config))
 }
 
-fn build_index(
+pub(crate) fn build_index(
     directory: &PathBuf,
     config: &ReaperConfig,
     transformer: &(impl Fn(&str) -> HashMap<repo_reaper_core::index::Term, u32> + Sync),
@@ -247,6 +466,40 @@ fn build_index(
     }
 }
 
+/// Swaps in a freshly built fielded index, for switching to an algorithm
+/// that needs fields while the index has none. `fielded` is the flag shared
+/// with the watcher.
+pub(crate) fn rebuild_fielded(
+    engine: &SearchEngine,
+    directory: &PathBuf,
+    config: &Arc<ReaperConfig>,
+    respect_gitignore: bool,
+    fielded: &AtomicBool,
+) -> Result<()> {
+    let mut index = build_index(
+        directory,
+        config,
+        build_transformer(Arc::clone(config)).as_ref(),
+        true,
+        respect_gitignore,
+    );
+    let (history, dense, symbols) = engine.with_read(|index| {
+        (
+            index.history().cloned(),
+            index.dense().map(|dense| dense.config().clone()),
+            index.sub_documents().is_some(),
+        )
+    })?;
+    index.set_history(history);
+    if let Some(config) = dense {
+        index.build_dense(&config);
+    }
+    sync_sub_documents(&mut index, symbols, config);
+    engine.replace(index)?;
+    fielded.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
 fn file_size_label(path: &Path) -> String {
     fs::metadata(path)
         .map(|metadata| human_bytes(metadata.len()))
@@ -266,14 +519,14 @@ fn human_bytes(bytes: u64) -> String {
     }
 }
 
-fn spawn_watcher(
+pub(crate) fn spawn_watcher(
     path: PathBuf,
     engine: SearchEngine,
     transformer: Arc<
         impl Fn(&str) -> HashMap<repo_reaper_core::index::Term, u32> + Send + Sync + 'static,
     >,
     config: Arc<ReaperConfig>,
-    fielded: bool,
+    fielded: Arc<AtomicBool>,
     index_dir: Option<PathBuf>,
 ) {
     let (tx, rx) = std::sync::mpsc::channel();
@@ -292,6 +545,23 @@ fn spawn_watcher(
             eprintln!("failed to watch directory: {error}");
             return;
         }
+        // Commits move `HEAD` or a ref; a repository root above the
+        // directory is outside the recursive watch, so its refs are watched
+        // on their own.
+        if matches!(
+            engine.with_read(|index| index.history().is_some()),
+            Ok(true)
+        ) && let Some(git_dir) = outer_git_dir(&path)
+        {
+            for (watched, mode) in [
+                (git_dir.clone(), RecursiveMode::NonRecursive),
+                (git_dir.join("refs"), RecursiveMode::Recursive),
+            ] {
+                if let Err(error) = watcher.watch(&watched, mode) {
+                    eprintln!("failed to watch {}: {error}", watched.display());
+                }
+            }
+        }
 
         loop {
             let event = match rx.lock() {
@@ -303,47 +573,63 @@ fn spawn_watcher(
             };
 
             match event {
-                Ok(Ok(event)) => match event.kind {
-                    EventKind::Modify(ModifyKind::Metadata(_)) => continue,
-                    EventKind::Remove(RemoveKind::File | RemoveKind::Any) => {
-                        for path in &event.paths {
-                            let event = IndexEvent::FileDeleted { path: path.clone() };
-                            if let Some(index_dir) = &index_dir
-                                && let Err(error) = append_event(index_dir, &event)
-                            {
-                                eprintln!("watch error: failed to append index event: {error}");
-                                return;
-                            }
-                            if let Err(error) =
-                                engine.apply_event(&event, transformer.as_ref(), &config, fielded)
-                            {
-                                eprintln!("watch error: {error}");
-                                return;
-                            }
-                        }
+                Ok(Ok(mut event)) => {
+                    if !matches!(event.kind, EventKind::Modify(ModifyKind::Metadata(_)))
+                        && event.paths.iter().any(|path| is_git_ref(path))
+                        && let Err(error) = refresh_history(&engine, &path)
+                    {
+                        eprintln!("watch error: failed to refresh Git history: {error}");
                     }
-                    _ => {
-                        for path in &event.paths {
-                            let event = if path.exists() {
-                                IndexEvent::FileModified { path: path.clone() }
-                            } else {
-                                IndexEvent::FileDeleted { path: path.clone() }
-                            };
-                            if let Some(index_dir) = &index_dir
-                                && let Err(error) = append_event(index_dir, &event)
-                            {
-                                eprintln!("watch error: failed to append index event: {error}");
-                                return;
+                    event.paths.retain(|path| !in_git_dir(path));
+
+                    match event.kind {
+                        EventKind::Modify(ModifyKind::Metadata(_)) => continue,
+                        EventKind::Remove(RemoveKind::File | RemoveKind::Any) => {
+                            for path in &event.paths {
+                                let event = IndexEvent::FileDeleted { path: path.clone() };
+                                if let Some(index_dir) = &index_dir
+                                    && let Err(error) = append_event(index_dir, &event)
+                                {
+                                    eprintln!("watch error: failed to append index event: {error}");
+                                    return;
+                                }
+                                if let Err(error) = engine.apply_event(
+                                    &event,
+                                    transformer.as_ref(),
+                                    &config,
+                                    fielded.load(Ordering::SeqCst),
+                                ) {
+                                    eprintln!("watch error: {error}");
+                                    return;
+                                }
                             }
-                            if let Err(error) =
-                                engine.apply_event(&event, transformer.as_ref(), &config, fielded)
-                            {
-                                eprintln!("watch error: {error}");
-                                return;
+                        }
+                        _ => {
+                            for path in &event.paths {
+                                let event = if path.exists() {
+                                    IndexEvent::FileModified { path: path.clone() }
+                                } else {
+                                    IndexEvent::FileDeleted { path: path.clone() }
+                                };
+                                if let Some(index_dir) = &index_dir
+                                    && let Err(error) = append_event(index_dir, &event)
+                                {
+                                    eprintln!("watch error: failed to append index event: {error}");
+                                    return;
+                                }
+                                if let Err(error) = engine.apply_event(
+                                    &event,
+                                    transformer.as_ref(),
+                                    &config,
+                                    fielded.load(Ordering::SeqCst),
+                                ) {
+                                    eprintln!("watch error: {error}");
+                                    return;
+                                }
                             }
                         }
                     }
-                },
+                }
                 Ok(Err(error)) => eprintln!("watch error: {error:?}"),
                 Err(error) => eprintln!("watch error: {error:?}"),
             }
@@ -351,58 +637,48 @@ fn spawn_watcher(
     });
 }
 
-fn run_repl(
-    config: Arc<ReaperConfig>,
-    algo: RankingAlgo,
-    top_n: usize,
-    engine: SearchEngine,
-    query_expansion: bool,
-    feedback_expansion: bool,
-) -> Result<()> {
-    let ui = TerminalUi::new();
-
-    loop {
-        ui.prompt()?;
-
-        let mut query = String::new();
-        let bytes_read = std::io::stdin()
-            .read_line(&mut query)
-            .context("failed to read from stdin")?;
-        if bytes_read == 0 {
-            ui.notice("bye");
-            break;
-        }
-
-        let trimmed_query = query.trim();
-        if matches!(trimmed_query, "exit" | "quit") {
-            break;
-        }
-        if trimmed_query.is_empty() {
-            println!();
-            continue;
-        }
-
-        let query = analyze_query(&config, &algo, &query, query_expansion, feedback_expansion);
-        let ranking = search_ranked(&engine, &algo, &query, top_n, feedback_expansion)?;
+/// The `.git` directory of the repository holding `directory`, when it
+/// lies above `directory`.
+fn outer_git_dir(directory: &Path) -> Option<PathBuf> {
+    let directory = std::path::absolute(directory).ok()?;
+    let repository = directory
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())?;
+    let git_dir = repository.join(".git");
+    (repository != directory && git_dir.is_dir()).then_some(git_dir)
+}
 
-        log_query(&query, &ranking, &algo, top_n)?;
+fn in_git_dir(path: &Path) -> bool {
+    path.components()
+        .any(|component| component.as_os_str() == ".git")
+}
 
-        match ranking {
-            Some(ranking) => {
-                print_results(&ranking, &ui);
-                println!();
-            }
-            None => {
-                ui.notice("no results found");
-                println!();
-            }
-        }
+/// Whether `path` is `HEAD`, `packed-refs` or a ref in a `.git` directory.
+fn is_git_ref(path: &Path) -> bool {
+    let mut components = path.components().map(|component| component.as_os_str());
+    if !components.any(|component| component == ".git") {
+        return false;
     }
+    match (components.next(), components.next()) {
+        (Some(name), None) => name == "HEAD" || name == "packed-refs",
+        (Some(name), Some(_)) => name == "refs",
+        (None, _) => false,
+    }
+}
 
+/// Folds the commits behind a moved `HEAD` into the index's history; an
+/// index without history is left alone.
+pub(crate) fn refresh_history(engine: &SearchEngine, directory: &Path) -> Result<()> {
+    let Some(mut history) = engine.with_read(|index| index.history().cloned())? else {
+        return Ok(());
+    };
+    if history.history.refresh(directory)? {
+        engine.with_write(|index| index.set_history(Some(history)))?;
+    }
     Ok(())
 }
 
-fn analyze_query(
+pub(crate) fn analyze_query(
     config: &ReaperConfig,
     algo: &RankingAlgo,
     query: &str,
@@ -423,43 +699,233 @@ fn analyze_query(
     }
 }
 
-fn search_ranked(
+pub(crate) fn search_ranked(
     engine: &SearchEngine,
     algo: &RankingAlgo,
     query: &AnalyzedQuery,
     top_n: usize,
-    feedback_expansion: bool,
+    modes: SearchModes<'_>,
+    filter: &SearchFilter,
 ) -> Result<Option<Scored>> {
-    if feedback_expansion {
-        Ok(engine
-            .with_read(|index| algo.rank_with_feedback(index, query, top_n, top_n.min(3), 6))?)
+    if modes.symbols {
+        // Sub-documents come back with their line range as their passage.
+        return Ok(engine.with_read(|index| algo.rank_sub_documents(index, query, top_n, filter))?);
+    }
+
+    // With both modes on, the feedback-expanded query is the one diversified.
+    let mut ranking = if let Some(config) = modes.diversity {
+        engine.with_read(|index| {
+            let index = FilteredIndex::new(index, filter);
+            let expanded = modes
+                .feedback_expansion
+                .then(|| algo.expand_with_feedback(&index, query, top_n.min(3), 6))
+                .flatten();
+            algo.rank_diversified(&index, expanded.as_ref().unwrap_or(query), top_n, config)
+        })?
+    } else if modes.feedback_expansion {
+        engine.with_read(|index| {
+            let index = FilteredIndex::new(index, filter);
+            algo.rank_with_feedback(&index, query, top_n, top_n.min(3), 6)
+        })?
     } else {
-        Ok(engine.search(algo, query, top_n)?)
+        engine.search(algo, query, top_n, filter)?
+    };
+
+    if let Some(ranking) = ranking.as_mut() {
+        engine
+            .with_read(|index| attach_passages(index, query, ranking, &PassageConfig::default()))?;
     }
+    Ok(ranking)
 }
 
-fn print_one_shot_results(ranking: &Option<Scored>) {
-    let ui = TerminalUi::new();
+/// Ranks the files a context pack is assembled from. Ranked symbols are
+/// folded into their files, which keep the symbols' line ranges as their
+/// passages.
+pub(crate) fn search_pack_files(
+    engine: &SearchEngine,
+    algo: &RankingAlgo,
+    query: &AnalyzedQuery,
+    top_n: usize,
+    modes: SearchModes<'_>,
+    filter: &SearchFilter,
+) -> Result<Scored> {
+    let ranking =
+        search_ranked(engine, algo, query, top_n, modes, filter)?.unwrap_or(Scored(Vec::new()));
+    if !modes.symbols {
+        return Ok(ranking);
+    }
+    Ok(engine.with_read(|index| {
+        Scored(
+            index
+                .sub_documents()
+                .map(|sub_documents| sub_documents.aggregate_by_file(&ranking.0))
+                .unwrap_or_default(),
+        )
+    })?)
+}
 
-    match ranking {
-        Some(ranking) => print_results(ranking, &ui),
-        None => ui.notice("no results found"),
+/// Explains each result's score against the same filtered view of the
+/// index it was ranked on, in ranking order. Symbols are explained against
+/// the sub-document index they were ranked in.
+pub(crate) fn explain_results(
+    engine: &SearchEngine,
+    algo: &RankingAlgo,
+    query: &AnalyzedQuery,
+    results: &[Score],
+    filter: &SearchFilter,
+) -> Result<Vec<Option<ScoreExplanation>>> {
+    Ok(engine.with_read(|index| {
+        let sub_documents = index.sub_documents().filter(|sub_documents| {
+            results
+                .iter()
+                .any(|score| sub_documents.sub_document(&score.doc_path).is_some())
+        });
+        let mut explanations =
+            algo.explain_ranking(&FilteredIndex::new(index, filter), query, results);
+        if let Some(sub_documents) = sub_documents {
+            let symbols = algo.explain_ranking(sub_documents.index(), query, results);
+            for (explanation, symbol) in explanations.iter_mut().zip(symbols) {
+                if explanation.is_none() {
+                    *explanation = symbol;
+                }
+            }
+        }
+        explanations
+    })?)
+}
+
+fn print_one_shot_results(
+    results: &[Score],
+    explanations: Vec<Option<ScoreExplanation>>,
+    engine: &SearchEngine,
+    query: &AnalyzedQuery,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Human => {
+            let ui = TerminalUi::new();
+            if results.is_empty() {
+                ui.notice("no results found");
+            } else {
+                print_results(results, &explanations, &ui, engine, query)?;
+            }
+        }
+        OutputFormat::Json => {
+            SearchDocument::new(query, results, explanations).write_json(io::stdout().lock())?
+        }
+        OutputFormat::Jsonl => {
+            SearchDocument::new(query, results, explanations).write_jsonl(io::stdout().lock())?
+        }
+        OutputFormat::Vimgrep => {
+            let highlights = result_highlights(results, engine, query)?;
+            let mut stdout = io::stdout().lock();
+            for (score, spans) in results.iter().zip(highlights) {
+                // Sub-documents point into their parent file, which is
+                // what an editor can open.
+                let score = Score {
+                    doc_path: engine
+                        .with_read(|index| index.file_path(&score.doc_path).to_path_buf())?,
+                    ..score.clone()
+                };
+                let content = fs::read_to_string(&score.doc_path).unwrap_or_default();
+                writeln!(stdout, "{}", vimgrep_line(&score, &content, spans))?;
+            }
+        }
     }
+    Ok(())
 }
 
-fn print_results(ranking: &Scored, ui: &TerminalUi) {
-    for score in &ranking.0 {
+/// Lines of context shown under each result.
+const SNIPPET_LINES: usize = 3;
+
+/// Prints each result with its snippet, followed by its score breakdown
+/// when `explanations` has one for it.
+pub(crate) fn print_results(
+    results: &[Score],
+    explanations: &[Option<ScoreExplanation>],
+    ui: &TerminalUi,
+    engine: &SearchEngine,
+    query: &AnalyzedQuery,
+) -> Result<()> {
+    let highlights = result_highlights(results, engine, query)?;
+
+    let files = engine.with_read(|index| {
+        results
+            .iter()
+            .map(|score| index.file_path(&score.doc_path).to_path_buf())
+            .collect::<Vec<_>>()
+    })?;
+
+    for (i, ((score, spans), file)) in results.iter().zip(highlights).zip(files).enumerate() {
         println!("{}", ui.format_score(score));
+        if let Some(Some(explanation)) = explanations.get(i) {
+            for line in ui.format_explanation(explanation) {
+                println!("{line}");
+            }
+        }
+        if spans.is_empty() {
+            continue;
+        }
+        let snippet = fs::read_to_string(&file).ok().and_then(|content| {
+            let spans = match score.passages.first() {
+                Some(passage) => spans_in_passage(&content, spans, passage),
+                None => spans,
+            };
+            snippet(&content, &spans, SNIPPET_LINES)
+        });
+        if let Some(snippet) = snippet {
+            for line in ui.format_snippet(&snippet) {
+                println!("{line}");
+            }
+        }
     }
+    Ok(())
+}
+
+/// Query-term highlight spans for each result, in ranking order.
+pub(crate) fn result_highlights(
+    results: &[Score],
+    engine: &SearchEngine,
+    query: &AnalyzedQuery,
+) -> Result<Vec<Vec<ByteSpan>>> {
+    Ok(engine.with_read(|index| {
+        results
+            .iter()
+            .map(|score| {
+                index
+                    .doc_id(index.file_path(&score.doc_path))
+                    .map(|doc_id| highlight_spans(index, query, doc_id))
+                    .unwrap_or_default()
+            })
+            .collect()
+    })?)
 }
 
-struct TerminalUi {
+/// Keeps the spans on the lines of `passage`, so the snippet shows the
+/// passage the result is reported with.
+pub(crate) fn spans_in_passage(
+    content: &str,
+    spans: Vec<ByteSpan>,
+    passage: &Passage,
+) -> Vec<ByteSpan> {
+    let lines = passage.start_line..=passage.end_line;
+    spans
+        .into_iter()
+        .filter(|span| {
+            content
+                .get(..span.start)
+                .is_some_and(|before| lines.contains(&(before.matches('\n').count() + 1)))
+        })
+        .collect()
+}
+
+pub(crate) struct TerminalUi {
     stdout_color: bool,
     stderr_color: bool,
 }
 
 impl TerminalUi {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         let color_allowed = env::var_os("NO_COLOR").is_none();
         Self {
             stdout_color: color_allowed && std::io::stdout().is_terminal(),
@@ -467,7 +933,7 @@ impl TerminalUi {
         }
     }
 
-    fn status(&self, label: &str, message: &str) {
+    pub(crate) fn status(&self, label: &str, message: &str) {
         let style = if label == "ready" {
             Style::GreenBold
         } else {
@@ -480,7 +946,7 @@ impl TerminalUi {
         );
     }
 
-    fn notice(&self, message: &str) {
+    pub(crate) fn notice(&self, message: &str) {
         println!(
             "{} {}",
             self.style_stdout(&format!("{:<6}", "notice"), Style::YellowBold),
@@ -492,19 +958,168 @@ impl TerminalUi {
         eprintln!("       {}", self.style_stderr(message, Style::Dim));
     }
 
-    fn prompt(&self) -> Result<()> {
-        print!("{} ", self.style_stdout("query>", Style::CyanBold));
-        std::io::stdout().flush().context("failed to flush prompt")
+    pub(crate) fn prompt(&self) -> String {
+        format!("{} ", self.style_stdout("query>", Style::CyanBold))
     }
 
-    fn format_score(&self, score: &Score) -> String {
+    pub(crate) fn format_score(&self, score: &Score) -> String {
+        let lines = score
+            .passages
+            .first()
+            .map(|passage| format!(":{}-{}", passage.start_line, passage.end_line))
+            .unwrap_or_default();
+        let similar = match score.similar.len() {
+            0 => String::new(),
+            count => format!(" +{count} similar"),
+        };
         format!(
-            "{} {}",
+            "{}{} {}{}",
             self.style_stdout(&score.doc_path.display().to_string(), Style::Path),
+            self.style_stdout(&lines, Style::Dim),
             self.style_stdout(&format!("score={:.6}", score.score), Style::Dim),
+            self.style_stdout(&similar, Style::Dim),
         )
     }
 
+    /// Score breakdown: the total, then each term with its per-field
+    /// contributions, the static quality signals that moved the score and
+    /// what each fused run added.
+    pub(crate) fn format_explanation(&self, explanation: &ScoreExplanation) -> Vec<String> {
+        // Folded from 0.0 because an empty f64 `sum` is -0.0.
+        let total = |contributions: &mut dyn Iterator<Item = f64>| {
+            contributions.fold(0.0, |total, contribution| total + contribution)
+        };
+        let term_total = total(&mut explanation.terms.iter().map(|term| term.contribution));
+        let quality_total = total(
+            &mut explanation
+                .static_quality
+                .iter()
+                .map(|signal| signal.contribution),
+        );
+        let runs_total = if explanation.runs.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " + runs {:.6}",
+                total(&mut explanation.runs.iter().map(|run| run.contribution))
+            )
+        };
+        let mut lines = vec![format!(
+            "  {} {:.6} = terms {:.6} + proximity {:.6} + quality {:.6}{} {}",
+            self.style_stdout("why", Style::CyanBold),
+            explanation.final_score,
+            term_total,
+            explanation.proximity_bonus,
+            quality_total,
+            runs_total,
+            self.style_stdout(
+                &format!("(intent {:?})", explanation.query_intent),
+                Style::Dim
+            ),
+        )];
+
+        let mut terms = explanation.terms.iter().collect::<Vec<_>>();
+        terms.sort_by(|a, b| {
+            b.contribution
+                .total_cmp(&a.contribution)
+                .then_with(|| a.term.cmp(&b.term))
+        });
+        for term in terms {
+            lines.push(format!(
+                "    {} {:+.6} {}",
+                self.style_stdout(&term.term, Style::Match),
+                term.contribution,
+                self.style_stdout(
+                    &format!(
+                        "idf={:.4} tf={} df={} weight={:.2} {}",
+                        term.idf,
+                        term.term_frequency,
+                        term.document_frequency,
+                        term.query_weight,
+                        term.provenance
+                    ),
+                    Style::Dim
+                ),
+            ));
+            for field in &term.matched_fields {
+                lines.push(format!(
+                    "      {} {:+.6} {}",
+                    field.field,
+                    field.contribution,
+                    self.style_stdout(
+                        &format!(
+                            "tf={} len={} weight={:.2}",
+                            field.term_frequency, field.field_length, field.field_weight
+                        ),
+                        Style::Dim
+                    ),
+                ));
+            }
+        }
+
+        for signal in explanation
+            .static_quality
+            .iter()
+            .filter(|signal| signal.contribution != 0.0)
+        {
+            lines.push(format!(
+                "    {} {} {:+.6}",
+                self.style_stdout("quality", Style::Dim),
+                signal.signal,
+                signal.contribution
+            ));
+        }
+
+        for run in &explanation.runs {
+            lines.push(format!(
+                "    {} {} {:+.6} {}",
+                self.style_stdout("run", Style::Dim),
+                run.run,
+                run.contribution,
+                self.style_stdout(
+                    &format!(
+                        "rank={} score={:.4} weight={:.2}",
+                        run.rank, run.score, run.weight
+                    ),
+                    Style::Dim
+                ),
+            ));
+        }
+        lines
+    }
+
+    fn format_snippet(&self, snippet: &Snippet) -> Vec<String> {
+        let mut line_start = 0;
+        snippet
+            .text
+            .split('\n')
+            .enumerate()
+            .map(|(offset, line)| {
+                let line_end = line_start + line.len();
+                let mut formatted = String::new();
+                let mut cursor = line_start;
+                for range in &snippet.highlights {
+                    let start = range.start.clamp(cursor, line_end);
+                    let end = range.end.clamp(start, line_end);
+                    if start == end {
+                        continue;
+                    }
+                    formatted.push_str(&snippet.text[cursor..start]);
+                    formatted.push_str(&self.style_stdout(&snippet.text[start..end], Style::Match));
+                    cursor = end;
+                }
+                formatted.push_str(&snippet.text[cursor..line_end]);
+                line_start = line_end + 1;
+
+                format!(
+                    "{} {}",
+                    self.style_stdout(&format!("{:>6}:", snippet.line_number + offset), Style::Dim),
+                    formatted.trim_end_matches('\r')
+                )
+            })
+            .collect()
+    }
+
     fn style_stdout(&self, text: &str, style: Style) -> String {
         style.apply(text, self.stdout_color)
     }
@@ -521,6 +1136,7 @@ enum Style {
     YellowBold,
     Dim,
     Path,
+    Match,
 }
 
 impl Style {
@@ -535,60 +1151,222 @@ impl Style {
             Style::YellowBold => "1;33",
             Style::Dim => "2",
             Style::Path => "36",
+            Style::Match => "1;31",
         };
         format!("\x1b[{code}m{text}\x1b[0m")
     }
 }
 
-fn log_query(
-    query: &AnalyzedQuery,
-    ranking: &Option<repo_reaper_core::ranking::Scored>,
-    algo: &RankingAlgo,
-    top_n: usize,
-) -> Result<()> {
-    let mut query_log = HashMap::new();
+#[cfg(test)]
+mod tests {
+    use repo_reaper_core::{
+        index::DocumentField,
+        query::QueryIntent,
+        ranking::{
+            FieldContribution, RunContribution, ScoreExplanation, Snippet,
+            StaticQualityContribution, TermExplanation,
+        },
+    };
 
-    query_log.insert("query".to_string(), query.to_string());
-    query_log.insert("top_n".to_string(), top_n.to_string());
+    use std::{fs, path::Path, process::Command};
 
-    match ranking {
-        Some(ranking) => {
-            query_log.insert("ranking".to_string(), format!("{ranking:?}"));
-        }
-        None => {
-            query_log.insert("ranking".to_string(), String::new());
+    use repo_reaper_core::index::{GitHistory, InvertedIndex, SearchEngine};
+
+    use super::{TerminalUi, human_bytes, is_git_ref, refresh_history};
+
+    fn commit(dir: &Path, name: &str, content: &str) {
+        fs::write(dir.join(name), content).unwrap();
+        for args in [
+            &["add", "--all"][..],
+            &[
+                "-c",
+                "user.name=dev",
+                "-c",
+                "user.email=dev@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "--quiet",
+                "-m",
+                name,
+            ],
+        ] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
         }
     }
 
-    query_log.insert("ranking_algo".to_string(), format!("{algo:?}"));
-    query_log.insert(
-        "timestamp".to_string(),
-        Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-    );
+    #[test]
+    fn human_bytes_formats_cache_sizes_for_status_lines() {
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(2 * 1024 * 1024), "2.0 MiB");
+        assert_eq!(human_bytes(2 * 1024 * 1024 * 1024), "2.0 GiB");
+    }
 
-    let query_log = serde_json::to_string(&query_log).context("failed to serialize query log")?;
+    #[test]
+    fn git_refs_are_told_apart_from_other_git_files() {
+        assert!(is_git_ref(Path::new("/repo/.git/HEAD")));
+        assert!(is_git_ref(Path::new("/repo/.git/packed-refs")));
+        assert!(is_git_ref(Path::new("/repo/.git/refs/heads/main")));
+        assert!(!is_git_ref(Path::new("/repo/.git/logs/HEAD")));
+        assert!(!is_git_ref(Path::new("/repo/.git/objects/ab/cdef")));
+        assert!(!is_git_ref(Path::new("/repo/src/HEAD")));
+    }
 
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open("./query_log.txt")
-        .context("failed to open query log file")?;
+    #[test]
+    fn refreshing_history_follows_new_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir.path())
+            .args(["init", "--quiet"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        commit(dir.path(), "pool.rs", "fn connect_pool() {}");
 
-    file.write_all(query_log.as_bytes())
-        .context("failed to write query log")?;
+        let history = GitHistory::read(dir.path()).unwrap();
+        let engine = SearchEngine::new(
+            InvertedIndex::new(dir.path(), |_: &str| Default::default(), Some(dir.path()))
+                .with_history(history, Path::new("")),
+        );
+        let head = |engine: &SearchEngine| {
+            engine
+                .with_read(|index| index.history().unwrap().history.head().map(str::to_owned))
+                .unwrap()
+        };
+        let first = head(&engine);
 
-    Ok(())
-}
+        commit(dir.path(), "retry.rs", "fn retry() {}");
+        refresh_history(&engine, dir.path()).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::human_bytes;
+        assert!(head(&engine).is_some());
+        assert_ne!(head(&engine), first);
+    }
 
     #[test]
-    fn human_bytes_formats_cache_sizes_for_status_lines() {
-        assert_eq!(human_bytes(512), "512 B");
-        assert_eq!(human_bytes(1536), "1.5 KiB");
-        assert_eq!(human_bytes(2 * 1024 * 1024), "2.0 MiB");
-        assert_eq!(human_bytes(2 * 1024 * 1024 * 1024), "2.0 GiB");
+    fn format_snippet_marks_highlights_per_line() {
+        let snippet = Snippet {
+            line_number: 41,
+            text: "let pool = connect();\nretry(pool)".to_string(),
+            highlights: vec![4..8, 28..32],
+        };
+        let plain = TerminalUi {
+            stdout_color: false,
+            stderr_color: false,
+        };
+        let colored = TerminalUi {
+            stdout_color: true,
+            stderr_color: false,
+        };
+
+        assert_eq!(
+            plain.format_snippet(&snippet),
+            vec!["    41: let pool = connect();", "    42: retry(pool)"]
+        );
+        assert_eq!(
+            colored.format_snippet(&snippet)[1],
+            "\x1b[2m    42:\x1b[0m retry(\x1b[1;31mpool\x1b[0m)"
+        );
+    }
+
+    #[test]
+    fn format_explanation_lists_terms_fields_and_moving_quality_signals() {
+        let term =
+            |term: &str, contribution: f64, fields: Vec<FieldContribution>| TermExplanation {
+                term: term.to_string(),
+                provenance: "original".to_string(),
+                query_weight: 1.0,
+                term_frequency: 2,
+                document_frequency: 3,
+                idf: 0.5,
+                matched_fields: fields,
+                contribution,
+            };
+        let explanation = ScoreExplanation {
+            final_score: 3.5,
+            query_intent: QueryIntent::NaturalLanguage,
+            terms: vec![
+                term("retri", 1.0, Vec::new()),
+                term(
+                    "pool",
+                    2.0,
+                    vec![FieldContribution {
+                        field: DocumentField::Symbol,
+                        term_frequency: 2,
+                        field_length: 4,
+                        field_weight: 3.0,
+                        contribution: 2.0,
+                    }],
+                ),
+            ],
+            proximity_bonus: 0.25,
+            static_quality: vec![
+                StaticQualityContribution {
+                    signal: "entry_point".to_string(),
+                    value: 1.0,
+                    contribution: 0.25,
+                },
+                StaticQualityContribution {
+                    signal: "vendor".to_string(),
+                    value: 0.0,
+                    contribution: 0.0,
+                },
+            ],
+            runs: Vec::new(),
+        };
+        let plain = TerminalUi {
+            stdout_color: false,
+            stderr_color: false,
+        };
+
+        assert_eq!(
+            plain.format_explanation(&explanation),
+            vec![
+                "  why 3.500000 = terms 3.000000 + proximity 0.250000 + quality 0.250000 \
+                 (intent NaturalLanguage)",
+                "    pool +2.000000 idf=0.5000 tf=2 df=3 weight=1.00 original",
+                "      symbols +2.000000 tf=2 len=4 weight=3.00",
+                "    retri +1.000000 idf=0.5000 tf=2 df=3 weight=1.00 original",
+                "    quality entry_point +0.250000",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_explanation_adds_fused_runs() {
+        let explanation = ScoreExplanation {
+            final_score: 0.05,
+            query_intent: QueryIntent::ErrorMessage,
+            terms: Vec::new(),
+            proximity_bonus: 0.0,
+            static_quality: Vec::new(),
+            runs: vec![RunContribution {
+                run: "literal".to_string(),
+                rank: 1,
+                score: 2.0,
+                weight: 3.0,
+                contribution: 0.05,
+            }],
+        };
+        let plain = TerminalUi {
+            stdout_color: false,
+            stderr_color: false,
+        };
+
+        assert_eq!(
+            plain.format_explanation(&explanation),
+            vec![
+                "  why 0.050000 = terms 0.000000 + proximity 0.000000 + quality 0.000000 \
+                 + runs 0.050000 (intent ErrorMessage)",
+                "    run literal +0.050000 rank=1 score=2.0000 weight=3.00",
+            ]
+        );
     }
 }
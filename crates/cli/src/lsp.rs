@@ -0,0 +1,480 @@
+//! `rr lsp`: a minimal language server answering `workspace/symbol` from
+//! the symbol field, plus a `repoReaper/search` request for ranked search.
+//!
+//! Messages are JSON-RPC 2.0 framed with `Content-Length` headers on stdio.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+use repo_reaper_core::{config::Config as ReaperConfig, ranking::RankingAlgo};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::{
+    live_search::LiveSearchOptions,
+    server::{METHOD_NOT_FOUND, QueryParams, RpcError, SearchService, parse_params, respond},
+};
+
+/// Largest message body read; a bigger `Content-Length` is an error.
+const MAX_MESSAGE_BYTES: usize = 8 * 1024 * 1024;
+
+pub(crate) fn run(
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    options: LiveSearchOptions,
+) -> Result<()> {
+    let service = SearchService::start(directory, config, algo, options, true)?;
+    service.watch();
+
+    serve(std::io::stdin().lock(), std::io::stdout().lock(), &service)
+}
+
+/// Answers messages until the client sends `exit` or closes stdin.
+fn serve(mut reader: impl BufRead, mut writer: impl Write, service: &SearchService) -> Result<()> {
+    while let Some(message) = read_message(&mut reader)? {
+        let method = serde_json::from_str::<Value>(&message)
+            .ok()
+            .and_then(|message| message.get("method")?.as_str().map(str::to_string));
+        if method.as_deref() == Some("exit") {
+            break;
+        }
+        if let Some(response) = handle(&message, service) {
+            write_message(&mut writer, &response)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_message(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            if content_length.is_none() {
+                return Ok(None);
+            }
+            bail!("stream ended inside message headers");
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .context("invalid Content-Length header")?,
+            );
+        }
+    }
+
+    let content_length = content_length.context("message without Content-Length header")?;
+    if content_length > MAX_MESSAGE_BYTES {
+        bail!("message of {content_length} bytes exceeds the {MAX_MESSAGE_BYTES} byte limit");
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(
+        String::from_utf8(body).context("message body is not UTF-8")?,
+    ))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceSymbolParams {
+    query: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchParams {
+    query: String,
+    #[serde(default)]
+    top_n: Option<usize>,
+}
+
+fn handle(message: &str, service: &SearchService) -> Option<Value> {
+    respond(message, |method, params| match method {
+        "initialize" => Ok(json!({
+            "capabilities": {
+                "workspaceSymbolProvider": true,
+                "experimental": { "repoReaperSearch": true },
+            },
+            "serverInfo": { "name": "repo_reaper", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "initialized" | "shutdown" => Ok(Value::Null),
+        "workspace/symbol" => workspace_symbols(service, parse_params(params)?),
+        "repoReaper/search" => search(service, parse_params(params)?),
+        _ if method.starts_with("$/") || is_notification(method) => Ok(Value::Null),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unsupported method {method:?}"),
+        )),
+    })
+}
+
+/// Notifications the server accepts and ignores. Their responses are
+/// dropped by [`respond`] because they carry no id.
+fn is_notification(method: &str) -> bool {
+    method.starts_with("textDocument/did")
+        || method.starts_with("workspace/did")
+        || method.starts_with("notebookDocument/did")
+}
+
+/// `SymbolInformation[]` for the query. An empty query, which clients send
+/// to list everything, returns nothing rather than every symbol.
+fn workspace_symbols(
+    service: &SearchService,
+    params: WorkspaceSymbolParams,
+) -> Result<Value, RpcError> {
+    if params.query.trim().is_empty() {
+        return Ok(json!([]));
+    }
+    let hits = service.symbol_search(&params.query, service.top_n())?;
+
+    let mut files = SourceFiles::default();
+    Ok(Value::Array(
+        hits.into_iter()
+            .filter_map(|hit| {
+                let span = hit.symbol.span?;
+                let content = files.get(&hit.symbol.path)?;
+                Some(json!({
+                    "name": hit.symbol.name,
+                    "kind": symbol_kind(content, span.start),
+                    "location": {
+                        "uri": file_uri(&hit.symbol.path)?,
+                        "range": {
+                            "start": position(content, span.start),
+                            "end": position(content, span.end),
+                        },
+                    },
+                }))
+            })
+            .collect(),
+    ))
+}
+
+/// Ranked files with the range of their best passage.
+fn search(service: &SearchService, params: SearchParams) -> Result<Value, RpcError> {
+    let hits = service.search(QueryParams {
+        query: params.query,
+        top_n: params.top_n,
+        algorithm: None,
+        diversify: None,
+    })?;
+
+    Ok(Value::Array(
+        hits.into_iter()
+            .filter_map(|hit| {
+                let (start, end) = hit
+                    .passages
+                    .first()
+                    .map_or((0, 0), |passage| (passage.start_line - 1, passage.end_line));
+                Some(json!({
+                    "uri": file_uri(&hit.path)?,
+                    "score": hit.score,
+                    "range": {
+                        "start": { "line": start, "character": 0 },
+                        "end": { "line": end, "character": 0 },
+                    },
+                }))
+            })
+            .collect(),
+    ))
+}
+
+/// Contents of the files a response refers to, read once each.
+#[derive(Default)]
+struct SourceFiles(HashMap<PathBuf, Option<String>>);
+
+impl SourceFiles {
+    fn get(&mut self, path: &Path) -> Option<&str> {
+        self.0
+            .entry(path.to_path_buf())
+            .or_insert_with(|| std::fs::read_to_string(path).ok())
+            .as_deref()
+    }
+}
+
+fn file_uri(path: &Path) -> Option<String> {
+    let path = std::path::absolute(path).ok()?;
+    Url::from_file_path(path).ok().map(String::from)
+}
+
+/// LSP position of a byte offset: zero-based line and UTF-16 column.
+fn position(content: &str, offset: usize) -> Value {
+    let offset = char_boundary(content, offset);
+    let line_start = line_start(content, offset);
+    json!({
+        "line": content[..line_start].matches('\n').count(),
+        "character": content[line_start..offset].encode_utf16().count(),
+    })
+}
+
+/// Guesses the LSP `SymbolKind` from the keyword declaring the name at
+/// `offset`, since features do not record what kind of symbol they are.
+fn symbol_kind(content: &str, offset: usize) -> u32 {
+    const FUNCTION: u32 = 12;
+    const VARIABLE: u32 = 13;
+
+    let offset = char_boundary(content, offset);
+    content[line_start(content, offset)..offset]
+        .split(|c: char| !c.is_alphanumeric())
+        .rev()
+        .find_map(|word| match word {
+            "fn" | "def" | "func" | "function" => Some(FUNCTION),
+            "class" => Some(5),
+            "enum" => Some(10),
+            "trait" | "interface" => Some(11),
+            "mod" | "module" | "namespace" => Some(2),
+            "const" | "static" => Some(14),
+            "struct" => Some(23),
+            "type" => Some(26),
+            _ => None,
+        })
+        .unwrap_or(VARIABLE)
+}
+
+/// `offset` clamped into `content` and moved back to a character boundary,
+/// in case the file changed since it was indexed.
+fn char_boundary(content: &str, offset: usize) -> usize {
+    let mut offset = offset.min(content.len());
+    while !content.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn line_start(content: &str, offset: usize) -> usize {
+    content[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::Cursor, path::Path, sync::Arc};
+
+    use repo_reaper_core::{
+        config::Config as ReaperConfig, index::SearchFilter, ranking::RankingAlgo,
+    };
+    use rust_stemmers::{Algorithm, Stemmer};
+    use serde_json::{Value, json};
+
+    use super::{MAX_MESSAGE_BYTES, position, read_message, serve, symbol_kind, write_message};
+    use crate::{live_search::LiveSearchOptions, server::SearchService};
+
+    fn test_config() -> ReaperConfig {
+        ReaperConfig {
+            n_grams: 1,
+            stemmer: Stemmer::create(Algorithm::English),
+            stop_words: HashSet::new(),
+            analyzers: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    fn write_temp_file(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    fn service(dir: &Path, algorithm: &str) -> SearchService {
+        SearchService::start(
+            dir.to_path_buf(),
+            Arc::new(test_config()),
+            algorithm.parse::<RankingAlgo>().unwrap(),
+            LiveSearchOptions {
+                top_n: 10,
+                query_expansion: false,
+                feedback_expansion: false,
+                index_dir: None,
+                reindex: false,
+                respect_gitignore: false,
+                history: false,
+                diversify: false,
+                symbols: false,
+                filter: SearchFilter::new(),
+                ranking: Default::default(),
+                dense: Default::default(),
+            },
+            false,
+        )
+        .unwrap()
+    }
+
+    /// Frames `messages` as a client would, runs the server over them and
+    /// returns every response it wrote.
+    fn exchange(service: &SearchService, messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        serve(Cursor::new(input), &mut output, service).unwrap();
+
+        let mut reader = Cursor::new(output);
+        std::iter::from_fn(|| read_message(&mut reader).unwrap())
+            .map(|message| serde_json::from_str(&message).unwrap())
+            .collect()
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": {} })
+    }
+
+    #[test]
+    fn oversized_messages_are_an_error() {
+        let input = format!("Content-Length: {}\r\n\r\n{{", MAX_MESSAGE_BYTES + 1);
+
+        let error = read_message(&mut Cursor::new(input)).unwrap_err();
+
+        assert!(error.to_string().contains("byte limit"));
+    }
+
+    #[test]
+    fn lifecycle_advertises_workspace_symbols_and_stops_at_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = service(dir.path(), "bm25f");
+
+        let responses = exchange(
+            &service,
+            &[
+                request(1, "initialize", json!({ "capabilities": {} })),
+                notification("initialized"),
+                notification("textDocument/didOpen"),
+                request(2, "shutdown", Value::Null),
+                notification("exit"),
+                request(3, "workspace/symbol", json!({ "query": "never answered" })),
+            ],
+        );
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["workspaceSymbolProvider"],
+            true
+        );
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["result"], Value::Null);
+    }
+
+    #[test]
+    fn workspace_symbol_returns_ranked_symbol_locations() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "fn open_pool() {}\n\npub struct RetryPool;\n",
+        );
+        write_temp_file(dir.path(), "notes.rs", "// retry the pool\n");
+        let service = service(dir.path(), "bm25f");
+
+        let responses = exchange(
+            &service,
+            &[
+                request(1, "workspace/symbol", json!({ "query": "RetryPool" })),
+                request(2, "workspace/symbol", json!({ "query": "" })),
+            ],
+        );
+
+        let symbols = responses[0]["result"].as_array().unwrap();
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| symbol["name"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["RetryPool", "open_pool"]
+        );
+        assert_eq!(symbols[0]["kind"], 23);
+        assert_eq!(
+            symbols[0]["location"]["range"],
+            json!({
+                "start": { "line": 2, "character": 11 },
+                "end": { "line": 2, "character": 20 },
+            })
+        );
+        assert!(
+            symbols[0]["location"]["uri"]
+                .as_str()
+                .unwrap()
+                .starts_with("file://")
+        );
+        assert_eq!(responses[1]["result"], json!([]));
+    }
+
+    #[test]
+    fn custom_search_request_returns_best_passage_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", "fn open() {}\n// pool timeout\n");
+        let service = service(dir.path(), "bm25f");
+
+        let responses = exchange(
+            &service,
+            &[
+                request(
+                    1,
+                    "repoReaper/search",
+                    json!({ "query": "timeout", "topN": 1 }),
+                ),
+                request(2, "textDocument/hover", json!({})),
+            ],
+        );
+
+        let results = responses[0]["result"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0]["uri"].as_str().unwrap().ends_with("/pool.rs"));
+        assert_eq!(results[0]["range"]["start"]["line"], 0);
+        assert_eq!(responses[1]["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn workspace_symbol_needs_a_fielded_index() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", "fn retry_pool() {}\n");
+        let service = service(dir.path(), "bm25");
+
+        let responses = exchange(
+            &service,
+            &[request(1, "workspace/symbol", json!({ "query": "retry" }))],
+        );
+
+        assert_eq!(responses[0]["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn positions_count_utf16_columns_and_kinds_follow_keywords() {
+        let content = "// é😀\nfn naïve() {}\n";
+        let name = content.find("naïve").unwrap();
+
+        assert_eq!(
+            position(content, name),
+            json!({ "line": 1, "character": 3 })
+        );
+        assert_eq!(
+            position(content, content.find('\n').unwrap()),
+            json!({ "line": 0, "character": 6 })
+        );
+        assert_eq!(symbol_kind(content, name), 12);
+        assert_eq!(symbol_kind("class Pool:", 6), 5);
+        assert_eq!(symbol_kind("let pool = 1;", 4), 13);
+    }
+}
@@ -0,0 +1,418 @@
+//! `rr mcp`: the warm index as Model Context Protocol tools over stdio.
+//!
+//! Messages are newline-delimited JSON-RPC 2.0 objects. Logs go to stderr so
+//! stdout carries only protocol traffic.
+
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use repo_reaper_core::{config::Config as ReaperConfig, ranking::RankingAlgo};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    live_search::LiveSearchOptions,
+    server::{INVALID_PARAMS, METHOD_NOT_FOUND, RpcError, SearchService, parse_params, respond},
+};
+
+/// Protocol revisions this server speaks, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+pub(crate) fn run(
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    options: LiveSearchOptions,
+) -> Result<()> {
+    let service = SearchService::start(directory, config, algo, options, true)?;
+    service.watch();
+
+    serve(std::io::stdin().lock(), std::io::stdout().lock(), &service)
+}
+
+/// Answers messages from `reader` until it is closed.
+fn serve(reader: impl BufRead, mut writer: impl Write, service: &SearchService) -> Result<()> {
+    for line in reader.lines() {
+        let line = line.context("failed to read MCP message")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(&line, service) {
+            writeln!(writer, "{response}")?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Answers one message. Notifications get no response.
+fn handle(message: &str, service: &SearchService) -> Option<Value> {
+    respond(message, |method, params| match method {
+        "initialize" => Ok(initialize(&params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => call_tool(service, params),
+        _ if method.starts_with("notifications/") => Ok(Value::Null),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {method:?}"),
+        )),
+    })
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let version = PROTOCOL_VERSIONS
+        .into_iter()
+        .find(|version| Some(*version) == requested)
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "repo_reaper", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search",
+            "description": "Rank repository files against a natural-language or code query. Returns paths, scores and the best line ranges.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "algorithm": {
+                        "type": "string",
+                        "description": "Ranking algorithm: bm25, bm25f, bm25-proximity, bm25+, bm25l, ql, ql-jm, pl2, inl2, dph, tfidf, dense or cosim",
+                    },
+                    "top_k": { "type": "integer", "minimum": 1 },
+                    "diversify": {
+                        "type": "boolean",
+                        "description": "Spread results across distinct files and fold near-duplicates into the result they resemble",
+                    },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "regex_search",
+            "description": "Find exact matches of a regular expression across the repository.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string" },
+                    "max_results": { "type": "integer", "minimum": 1 },
+                },
+                "required": ["pattern"],
+            },
+        },
+        {
+            "name": "find_symbol",
+            "description": "Look up where functions, types and other symbols are declared, by name.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 },
+                },
+                "required": ["name"],
+            },
+        },
+        {
+            "name": "fetch_snippets",
+            "description": "Bundle the most relevant code passages for a query into markdown that fits a token budget.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "budget_tokens": { "type": "integer", "minimum": 1 },
+                    "top_k": { "type": "integer", "minimum": 1 },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "read_file",
+            "description": "Read lines of an indexed file, cut short at a token budget.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer", "minimum": 1 },
+                    "end_line": { "type": "integer", "minimum": 1 },
+                    "max_tokens": { "type": "integer", "minimum": 1 },
+                },
+                "required": ["path"],
+            },
+        },
+    ])
+}
+
+/// Runs a tool. Failures of the tool itself are reported in the result with
+/// `isError` so the model can see them; only malformed calls are protocol
+/// errors.
+fn call_tool(service: &SearchService, params: Value) -> Result<Value, RpcError> {
+    let name = params
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing tool name"))?;
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+    let output = match name {
+        "search" => json_text(parse_params(arguments).and_then(|params| service.search(params))),
+        "regex_search" => {
+            json_text(parse_params(arguments).and_then(|params| service.regex(params)))
+        }
+        "find_symbol" => {
+            json_text(parse_params(arguments).and_then(|params| service.symbols(params)))
+        }
+        "fetch_snippets" => parse_params(arguments)
+            .and_then(|params| service.pack(params))
+            .map(|pack| pack.to_markdown()),
+        "read_file" => parse_params(arguments)
+            .and_then(|params| service.read(params))
+            .map(|lines| lines.text),
+        _ => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("unknown tool {name:?}"),
+            ));
+        }
+    };
+
+    Ok(match output {
+        Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+        Err(error) => {
+            json!({ "content": [{ "type": "text", "text": error.message }], "isError": true })
+        }
+    })
+}
+
+fn json_text(result: Result<impl Serialize, RpcError>) -> Result<String, RpcError> {
+    serde_json::to_string_pretty(&result?).map_err(RpcError::internal)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::Cursor, path::Path, sync::Arc};
+
+    use repo_reaper_core::{
+        config::Config as ReaperConfig, index::SearchFilter, ranking::RankingAlgo,
+    };
+    use rust_stemmers::{Algorithm, Stemmer};
+    use serde_json::{Value, json};
+
+    use super::serve;
+    use crate::{live_search::LiveSearchOptions, server::SearchService};
+
+    fn test_config() -> ReaperConfig {
+        ReaperConfig {
+            n_grams: 1,
+            stemmer: Stemmer::create(Algorithm::English),
+            stop_words: HashSet::new(),
+            analyzers: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    fn write_temp_file(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    /// Drives the server in-process: sends each message and collects the
+    /// responses by id.
+    struct Client {
+        service: SearchService,
+        messages: Vec<Value>,
+        next_id: u64,
+    }
+
+    impl Client {
+        fn new(dir: &Path) -> Self {
+            let service = SearchService::start(
+                dir.to_path_buf(),
+                Arc::new(test_config()),
+                "bm25f".parse::<RankingAlgo>().unwrap(),
+                LiveSearchOptions {
+                    top_n: 10,
+                    query_expansion: false,
+                    feedback_expansion: false,
+                    index_dir: None,
+                    reindex: false,
+                    respect_gitignore: false,
+                    history: false,
+                    diversify: false,
+                    symbols: false,
+                    filter: SearchFilter::new(),
+                    ranking: Default::default(),
+                    dense: Default::default(),
+                },
+                false,
+            )
+            .unwrap();
+            Self {
+                service,
+                messages: Vec::new(),
+                next_id: 0,
+            }
+        }
+
+        fn request(&mut self, method: &str, params: Value) -> u64 {
+            self.next_id += 1;
+            self.messages.push(
+                json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params }),
+            );
+            self.next_id
+        }
+
+        fn notify(&mut self, method: &str) {
+            self.messages
+                .push(json!({ "jsonrpc": "2.0", "method": method }));
+        }
+
+        fn call_tool(&mut self, name: &str, arguments: Value) -> u64 {
+            self.request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+        }
+
+        fn run(&self) -> Vec<Value> {
+            let input = self
+                .messages
+                .iter()
+                .map(|message| format!("{message}\n"))
+                .collect::<String>();
+            let mut output = Vec::new();
+            serve(Cursor::new(input), &mut output, &self.service).unwrap();
+
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    fn response(responses: &[Value], id: u64) -> &Value {
+        responses
+            .iter()
+            .find(|response| response["id"] == id)
+            .unwrap()
+    }
+
+    fn tool_text(responses: &[Value], id: u64) -> &str {
+        response(responses, id)["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+    }
+
+    #[test]
+    fn handshake_lists_tools_and_ignores_notifications() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = Client::new(dir.path());
+        let initialize = client.request(
+            "initialize",
+            json!({ "protocolVersion": "2024-11-05", "capabilities": {} }),
+        );
+        client.notify("notifications/initialized");
+        let list = client.request("tools/list", Value::Null);
+
+        let responses = client.run();
+
+        assert_eq!(responses.len(), 2);
+        let result = &response(&responses, initialize)["result"];
+        assert_eq!(result["protocolVersion"], "2024-11-05");
+        assert_eq!(result["serverInfo"]["name"], "repo_reaper");
+        let tools = response(&responses, list)["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tools,
+            vec![
+                "search",
+                "regex_search",
+                "find_symbol",
+                "fetch_snippets",
+                "read_file"
+            ]
+        );
+    }
+
+    #[test]
+    fn tools_answer_from_the_warm_index() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "fn retry_pool() {\n    // retry after timeout\n}\n",
+        );
+        write_temp_file(dir.path(), "other.rs", "fn unrelated() {}\n");
+        let pool_path = dir.path().join("pool.rs");
+        let mut client = Client::new(dir.path());
+        let search = client.call_tool("search", json!({ "query": "timeout", "top_k": 1 }));
+        let search_tfidf = client.call_tool(
+            "search",
+            json!({ "query": "timeout", "algorithm": "tfidf" }),
+        );
+        let regex = client.call_tool("regex_search", json!({ "pattern": "fn \\w+" }));
+        let snippets = client.call_tool("fetch_snippets", json!({ "query": "retry timeout" }));
+        let read = client.call_tool(
+            "read_file",
+            json!({ "path": pool_path, "start_line": 2, "end_line": 2 }),
+        );
+
+        let responses = client.run();
+
+        let hits: Value = serde_json::from_str(tool_text(&responses, search)).unwrap();
+        assert_eq!(hits.as_array().unwrap().len(), 1);
+        assert!(hits[0]["path"].as_str().unwrap().ends_with("pool.rs"));
+        let hits: Value = serde_json::from_str(tool_text(&responses, search_tfidf)).unwrap();
+        assert!(hits[0]["path"].as_str().unwrap().ends_with("pool.rs"));
+        let matches: Value = serde_json::from_str(tool_text(&responses, regex)).unwrap();
+        assert_eq!(matches.as_array().unwrap().len(), 2);
+        assert!(tool_text(&responses, snippets).starts_with("# Context for: retry timeout\n"));
+        assert_eq!(tool_text(&responses, read), "    // retry after timeout\n");
+    }
+
+    #[test]
+    fn find_symbol_reports_declaration_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", "\nfn retry_pool() {}\n");
+        let mut client = Client::new(dir.path());
+        let symbol = client.call_tool("find_symbol", json!({ "name": "retry_pool" }));
+
+        let responses = client.run();
+
+        let symbols: Value = serde_json::from_str(tool_text(&responses, symbol)).unwrap();
+        assert_eq!(symbols[0]["name"], "retry_pool");
+        assert_eq!(symbols[0]["line"], 2);
+    }
+
+    #[test]
+    fn tool_failures_are_results_and_bad_calls_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let mut client = Client::new(dir.path());
+        let bad_regex = client.call_tool("regex_search", json!({ "pattern": "(" }));
+        let outside = client.call_tool("read_file", json!({ "path": "/etc/passwd" }));
+        let unknown = client.call_tool("delete_everything", json!({}));
+        let method = client.request("resources/list", Value::Null);
+
+        let responses = client.run();
+
+        assert_eq!(response(&responses, bad_regex)["result"]["isError"], true);
+        assert_eq!(response(&responses, outside)["result"]["isError"], true);
+        assert_eq!(response(&responses, unknown)["error"]["code"], -32602);
+        assert_eq!(response(&responses, method)["error"]["code"], -32601);
+    }
+}
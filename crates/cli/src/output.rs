@@ -0,0 +1,325 @@
+//! Machine-readable output of one-shot ranked search.
+//!
+//! JSON and JSONL records carry [`SCHEMA_VERSION`]. Adding a field keeps the
+//! version; renaming, removing or retyping one bumps it.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use repo_reaper_core::{
+    code_intelligence::ByteSpan,
+    query::{AnalyzedQuery, QueryIntent},
+    ranking::{Passage, Score, ScoreExplanation},
+};
+use serde::Serialize;
+
+use crate::live_search::spans_in_passage;
+
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// Coloured results with snippets
+    #[default]
+    Human,
+    /// One JSON document with the query and all results
+    Json,
+    /// A query record, then one record per result
+    Jsonl,
+    /// `path:line:col:text`, one line per result, for quickfix lists
+    Vimgrep,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SearchDocument {
+    schema_version: u32,
+    #[serde(flatten)]
+    query: QueryOutput,
+    results: Vec<ResultOutput>,
+}
+
+#[derive(Debug, Serialize)]
+struct QueryOutput {
+    query: String,
+    intent: QueryIntent,
+    terms: Vec<TermOutput>,
+}
+
+#[derive(Debug, Serialize)]
+struct TermOutput {
+    term: String,
+    weight: f64,
+    provenance: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct ResultOutput {
+    /// One-based position in the ranking.
+    rank: usize,
+    path: PathBuf,
+    score: f64,
+    passages: Vec<Passage>,
+    /// Near-duplicates collapsed into this result.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    similar: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<ScoreExplanation>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonlRecord<'a> {
+    Query {
+        schema_version: u32,
+        #[serde(flatten)]
+        query: &'a QueryOutput,
+    },
+    Result {
+        schema_version: u32,
+        #[serde(flatten)]
+        result: &'a ResultOutput,
+    },
+}
+
+impl SearchDocument {
+    /// `explanations` are matched to `ranking` by position; leave it empty
+    /// to omit them.
+    pub(crate) fn new(
+        query: &AnalyzedQuery,
+        ranking: &[Score],
+        explanations: Vec<Option<ScoreExplanation>>,
+    ) -> Self {
+        let mut terms = query
+            .terms()
+            .map(|(term, query_term)| TermOutput {
+                term: term.0.clone(),
+                weight: query_term.weight,
+                provenance: query_term.provenance.as_str(),
+            })
+            .collect::<Vec<_>>();
+        terms.sort_by(|a, b| a.term.cmp(&b.term));
+
+        let mut explanations = explanations.into_iter();
+        let results = ranking
+            .iter()
+            .enumerate()
+            .map(|(i, score)| ResultOutput {
+                rank: i + 1,
+                path: score.doc_path.clone(),
+                score: score.score,
+                passages: score.passages.clone(),
+                similar: score.similar.clone(),
+                explanation: explanations.next().flatten(),
+            })
+            .collect();
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            query: QueryOutput {
+                query: query.original_text().to_string(),
+                intent: query.intent(),
+                terms,
+            },
+            results,
+        }
+    }
+
+    pub(crate) fn write_json(&self, mut writer: impl Write) -> Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    pub(crate) fn write_jsonl(&self, mut writer: impl Write) -> Result<()> {
+        let query = JsonlRecord::Query {
+            schema_version: self.schema_version,
+            query: &self.query,
+        };
+        writeln!(writer, "{}", serde_json::to_string(&query)?)?;
+        for result in &self.results {
+            let result = JsonlRecord::Result {
+                schema_version: self.schema_version,
+                result,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&result)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// `path:line:col:text` for a result, pointing at its first highlighted
+/// match inside its best passage. Falls back to the passage's first line,
+/// then the file's first line. Columns are one-based bytes, as in Vim.
+pub(crate) fn vimgrep_line(score: &Score, content: &str, spans: Vec<ByteSpan>) -> String {
+    let spans = match score.passages.first() {
+        Some(passage) => spans_in_passage(content, spans, passage),
+        None => spans,
+    };
+    let offset = match (spans.first(), score.passages.first()) {
+        (Some(span), _) => span.start,
+        (None, Some(passage)) => content
+            .split_inclusive('\n')
+            .take(passage.start_line - 1)
+            .map(str::len)
+            .sum(),
+        (None, None) => 0,
+    }
+    .min(content.len());
+
+    let line_start = content[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line_end = content[offset..]
+        .find('\n')
+        .map_or(content.len(), |newline| offset + newline);
+    format!(
+        "{}:{}:{}:{}",
+        score.doc_path.display(),
+        content[..line_start].matches('\n').count() + 1,
+        offset - line_start + 1,
+        content[line_start..line_end].trim_end_matches('\r'),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use repo_reaper_core::{
+        code_intelligence::ByteSpan,
+        config::Config as ReaperConfig,
+        query::AnalyzedQuery,
+        ranking::{Passage, Score},
+    };
+    use rust_stemmers::{Algorithm, Stemmer};
+    use serde_json::{Value, json};
+
+    use super::{SCHEMA_VERSION, SearchDocument, vimgrep_line};
+
+    fn test_config() -> ReaperConfig {
+        ReaperConfig {
+            n_grams: 1,
+            stemmer: Stemmer::create(Algorithm::English),
+            stop_words: HashSet::new(),
+            analyzers: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    fn score(path: &str, score: f64, passages: Vec<Passage>) -> Score {
+        Score {
+            doc_path: PathBuf::from(path),
+            score,
+            passages,
+            similar: Vec::new(),
+        }
+    }
+
+    fn passage(start_line: usize, end_line: usize) -> Passage {
+        Passage {
+            start_line,
+            end_line,
+            score: 1.0,
+        }
+    }
+
+    fn document() -> SearchDocument {
+        let query = AnalyzedQuery::new_code_search("retry pool", &test_config());
+        SearchDocument::new(
+            &query,
+            &[
+                score("src/pool.rs", 2.5, vec![passage(3, 12)]),
+                score("src/retry.rs", 1.0, Vec::new()),
+            ],
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn json_document_follows_schema_v1() {
+        let mut output = Vec::new();
+        document().write_json(&mut output).unwrap();
+        let document: Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(SCHEMA_VERSION, 1);
+        assert_eq!(
+            document,
+            json!({
+                "schema_version": 1,
+                "query": "retry pool",
+                "intent": "identifier",
+                "terms": [
+                    { "term": "pool", "weight": 1.0, "provenance": "original" },
+                    { "term": "retry", "weight": 1.0, "provenance": "original" },
+                ],
+                "results": [
+                    {
+                        "rank": 1,
+                        "path": "src/pool.rs",
+                        "score": 2.5,
+                        "passages": [{ "start_line": 3, "end_line": 12, "score": 1.0 }],
+                    },
+                    { "rank": 2, "path": "src/retry.rs", "score": 1.0, "passages": [] },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn jsonl_streams_a_query_record_then_one_record_per_result() {
+        let mut output = Vec::new();
+        document().write_jsonl(&mut output).unwrap();
+        let records = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["type"], "query");
+        assert_eq!(records[0]["schema_version"], 1);
+        assert_eq!(records[0]["query"], "retry pool");
+        assert_eq!(records[1]["type"], "result");
+        assert_eq!(records[1]["schema_version"], 1);
+        assert_eq!(records[1]["rank"], 1);
+        assert_eq!(records[2]["path"], "src/retry.rs");
+    }
+
+    #[test]
+    fn vimgrep_points_at_the_first_match_in_the_best_passage() {
+        let content = "fn open() {}\nlet pool = 1;\n\n// retry the pool\n";
+        let spans = content
+            .match_indices("pool")
+            .map(|(start, text)| ByteSpan {
+                start,
+                end: start + text.len(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vimgrep_line(
+                &score("a.rs", 1.0, vec![passage(3, 4)]),
+                content,
+                spans.clone()
+            ),
+            "a.rs:4:14:// retry the pool"
+        );
+        assert_eq!(
+            vimgrep_line(&score("a.rs", 1.0, Vec::new()), content, spans),
+            "a.rs:2:5:let pool = 1;"
+        );
+        assert_eq!(
+            vimgrep_line(
+                &score("a.rs", 1.0, vec![passage(3, 4)]),
+                content,
+                Vec::new()
+            ),
+            "a.rs:3:1:"
+        );
+        assert_eq!(
+            vimgrep_line(&score("a.rs", 1.0, Vec::new()), "", Vec::new()),
+            "a.rs:1:1:"
+        );
+    }
+}
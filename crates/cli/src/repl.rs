@@ -0,0 +1,677 @@
+//! The interactive search prompt. Colon-commands change search settings in
+//! place, so switching strategy mid-investigation needs no restart.
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command as Process,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use repo_reaper_core::{
+    config::Config as ReaperConfig,
+    index::{SearchEngine, SearchFilter},
+    query::AnalyzedQuery,
+    ranking::{RankingAlgo, Scored},
+    tokenizer::FileType,
+};
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use crate::{
+    live_search::{
+        LiveSearchOptions, TerminalUi, analyze_query, ensure_dense, explain_results, print_results,
+        rebuild_fielded, search_ranked,
+    },
+    print_corpus_stats,
+};
+
+/// History file kept in the home directory.
+const HISTORY_FILE: &str = ".repo_reaper_history";
+/// Entries listed by `:history`.
+const HISTORY_SHOWN: usize = 20;
+
+const HELP: &str = "\
+:algo NAME       switch ranking algorithm (cosim, bm25, bm25f, bm25-proximity, bm25+, bm25l, ql, ql-jm, pl2, inl2, dph, tfidf, dense)
+:top N           show N results
+:expand on|off   controlled abbreviation query expansion
+:feedback on|off pseudo-relevance feedback expansion
+:diversify on|off spread results and fold near-duplicates into \"+N similar\"
+:filter [F ...]  restrict results: ext:EXT type:TYPE glob:GLOB max:BYTES -tests -generated -vendor;
+                 no filters clears the filter
+:open N          open result N at its best passage in $EDITOR
+:explain N       break down the score of result N
+:stats           show settings and corpus statistics
+:history         show recent queries
+:quit            leave";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Algo(String),
+    Top(usize),
+    Expand(bool),
+    Feedback(bool),
+    Diversify(bool),
+    Filter(Vec<String>),
+    Open(usize),
+    Explain(usize),
+    Stats,
+    History,
+    Help,
+    Quit,
+}
+
+impl Command {
+    /// Parses a colon-command; `None` means the line is a query. `Err`
+    /// carries the message to show for a malformed command.
+    fn parse(line: &str) -> Option<Result<Self, String>> {
+        let line = line.strip_prefix(':')?;
+        let (name, argument) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(name, argument)| (name, argument.trim()));
+
+        Some(match name {
+            "algo" if !argument.is_empty() => Ok(Self::Algo(argument.to_string())),
+            "algo" => Err("usage: :algo NAME".to_string()),
+            "top" => parse_rank(argument, ":top N").map(Self::Top),
+            "expand" => parse_toggle(argument, ":expand on|off").map(Self::Expand),
+            "feedback" => parse_toggle(argument, ":feedback on|off").map(Self::Feedback),
+            "diversify" => parse_toggle(argument, ":diversify on|off").map(Self::Diversify),
+            "filter" => Ok(Self::Filter(
+                argument.split_whitespace().map(str::to_string).collect(),
+            )),
+            "open" => parse_rank(argument, ":open N").map(Self::Open),
+            "explain" => parse_rank(argument, ":explain N").map(Self::Explain),
+            "stats" => Ok(Self::Stats),
+            "history" => Ok(Self::History),
+            "help" | "?" => Ok(Self::Help),
+            "quit" | "q" | "exit" => Ok(Self::Quit),
+            _ => Err(format!("unknown command :{name}; try :help")),
+        })
+    }
+}
+
+fn parse_rank(argument: &str, usage: &str) -> Result<usize, String> {
+    argument
+        .parse::<usize>()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| format!("usage: {usage}, with N at least 1"))
+}
+
+fn parse_toggle(argument: &str, usage: &str) -> Result<bool, String> {
+    match argument {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("usage: {usage}")),
+    }
+}
+
+/// Builds a [`SearchFilter`] from `:filter` arguments. Several `ext:` and
+/// `glob:` filters admit a file matching any of them.
+fn parse_filter(filters: &[String]) -> Result<SearchFilter, String> {
+    let mut filter = SearchFilter::new();
+    let mut globs = Vec::new();
+
+    for token in filters {
+        filter = match (token.as_str(), token.split_once(':')) {
+            ("-tests", _) => filter.with_exclude_tests(true),
+            ("-generated", _) => filter.with_exclude_generated(true),
+            ("-vendor", _) => filter.with_exclude_vendor(true),
+            (_, Some(("ext", extension))) => {
+                globs.push(format!("*.{}", extension.trim_start_matches('.')));
+                filter
+            }
+            (_, Some(("glob", glob))) => {
+                globs.push(glob.to_string());
+                filter
+            }
+            (_, Some(("type", file_type))) => {
+                filter.with_file_types([file_type.parse::<FileType>()?])
+            }
+            (_, Some(("max", bytes))) => filter.with_max_size_bytes(
+                bytes
+                    .parse()
+                    .map_err(|_| format!("{bytes} is not a size in bytes"))?,
+            ),
+            _ => return Err(format!("unknown filter {token}; try :help")),
+        };
+    }
+
+    filter.with_globs(globs).map_err(|error| error.to_string())
+}
+
+/// The command opening `path` at `line` in `editor`, a `$EDITOR`-style
+/// program with optional arguments. VS Code-like editors take
+/// `--goto path:line`, Sublime and Zed `path:line`, and everything else the
+/// vi, Emacs and nano `+line path` convention.
+pub(crate) fn editor_command(editor: &str, path: &Path, line: usize) -> Option<Process> {
+    let mut words = editor.split_whitespace();
+    let program = words.next()?;
+    let mut command = Process::new(program);
+    command.args(words);
+
+    let location = format!("{}:{line}", path.display());
+    match Path::new(program)
+        .file_stem()
+        .and_then(|name| name.to_str())
+    {
+        Some("code" | "code-insiders" | "codium" | "cursor") => {
+            command.arg("--goto").arg(location);
+        }
+        Some("subl" | "zed") => {
+            command.arg(location);
+        }
+        _ => {
+            command.arg(format!("+{line}")).arg(path);
+        }
+    }
+    Some(command)
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+pub(crate) fn run(
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    engine: SearchEngine,
+    options: LiveSearchOptions,
+    fielded: Arc<AtomicBool>,
+) -> Result<()> {
+    let mut editor = DefaultEditor::new().context("failed to start the line editor")?;
+    let history = history_path();
+    if let Some(history) = &history {
+        // There is no history file before the first session.
+        let _ = editor.load_history(history);
+    }
+
+    let filter_label = if options.filter.is_empty() {
+        "none"
+    } else {
+        "from the command line"
+    }
+    .to_string();
+    let mut session = Session {
+        directory,
+        config,
+        algo,
+        engine,
+        options,
+        fielded,
+        filter_label,
+        last: None,
+        ui: TerminalUi::new(),
+    };
+    let result = session.read_eval_loop(&mut editor);
+
+    if let Some(history) = &history
+        && let Err(error) = editor.save_history(history)
+    {
+        session.ui.status(
+            "history",
+            &format!("failed to save {}: {error}", history.display()),
+        );
+    }
+    result
+}
+
+struct Session {
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    engine: SearchEngine,
+    options: LiveSearchOptions,
+    /// Whether the index has fields. Shared with the watcher, which must
+    /// index updates the same way once `:algo` rebuilds it with fields.
+    fielded: Arc<AtomicBool>,
+    filter_label: String,
+    /// The previous query and its results, for `:open` and `:explain`.
+    last: Option<(AnalyzedQuery, Scored)>,
+    ui: TerminalUi,
+}
+
+impl Session {
+    fn read_eval_loop(&mut self, editor: &mut DefaultEditor) -> Result<()> {
+        loop {
+            let line = match editor.readline(&self.ui.prompt()) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => {
+                    self.ui.notice("bye");
+                    return Ok(());
+                }
+                Err(error) => return Err(error).context("failed to read from stdin"),
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                println!();
+                continue;
+            }
+            editor
+                .add_history_entry(line)
+                .context("failed to record history")?;
+            if matches!(line, "exit" | "quit") {
+                return Ok(());
+            }
+
+            match Command::parse(line) {
+                None => self.search(line)?,
+                Some(Ok(Command::Quit)) => return Ok(()),
+                Some(Ok(Command::History)) => {
+                    let entries = editor.history().iter().collect::<Vec<_>>();
+                    let start = entries.len().saturating_sub(HISTORY_SHOWN);
+                    for (i, entry) in entries.iter().enumerate().skip(start) {
+                        println!("{:>5}  {entry}", i + 1);
+                    }
+                }
+                Some(Ok(command)) => self.run_command(command)?,
+                Some(Err(message)) => self.ui.notice(&message),
+            }
+            println!();
+        }
+    }
+
+    fn search(&mut self, line: &str) -> Result<()> {
+        let query = analyze_query(
+            &self.config,
+            &self.algo,
+            line,
+            self.options.query_expansion,
+            self.options.feedback_expansion,
+        );
+        let ranking = search_ranked(
+            &self.engine,
+            &self.algo,
+            &query,
+            self.options.top_n,
+            self.options.search_modes(),
+            &self.options.filter,
+        )?;
+
+        log_query(&query, &ranking, &self.algo, self.options.top_n)?;
+
+        match ranking {
+            Some(ranking) => {
+                print_results(&ranking.0, &[], &self.ui, &self.engine, &query)?;
+                self.last = Some((query, ranking));
+            }
+            None => {
+                self.ui.notice("no results found");
+                self.last = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Algo(name) => self.switch_algorithm(&name)?,
+            Command::Top(top_n) => {
+                self.options.top_n = top_n;
+                self.ui.notice(&format!("showing {top_n} results"));
+            }
+            Command::Expand(on) => {
+                self.options.query_expansion = on;
+                self.notice_expansion("query expansion", on);
+            }
+            Command::Feedback(on) => {
+                self.options.feedback_expansion = on;
+                self.notice_expansion("feedback expansion", on);
+            }
+            Command::Diversify(on) => {
+                self.options.diversify = on;
+                self.ui
+                    .notice(&format!("diversify {}", if on { "on" } else { "off" }));
+            }
+            Command::Filter(filters) => match parse_filter(&filters) {
+                Ok(filter) => {
+                    self.options.filter = filter.with_root(&self.directory);
+                    self.filter_label = if filters.is_empty() {
+                        "none".to_string()
+                    } else {
+                        filters.join(" ")
+                    };
+                    self.ui.notice(&format!("filter {}", self.filter_label));
+                }
+                Err(message) => self.ui.notice(&message),
+            },
+            Command::Open(rank) => self.open(rank)?,
+            Command::Explain(rank) => self.explain(rank)?,
+            Command::Stats => self.stats()?,
+            Command::Help => println!("{HELP}"),
+            Command::History | Command::Quit => {}
+        }
+        Ok(())
+    }
+
+    /// Switches algorithm, rebuilding the index with fields first when the
+    /// new algorithm needs them and the current index lacks them.
+    fn switch_algorithm(&mut self, name: &str) -> Result<()> {
+        let algo = match name.parse::<RankingAlgo>() {
+            Ok(algo) => self.options.ranking.apply(algo),
+            Err(message) => {
+                self.ui.notice(&message);
+                return Ok(());
+            }
+        };
+
+        if algo.needs_fielded_index() && !self.fielded.load(Ordering::SeqCst) {
+            self.ui.status(
+                "index",
+                &format!("rebuilding with fields for {}", algo.name()),
+            );
+            rebuild_fielded(
+                &self.engine,
+                &self.directory,
+                &self.config,
+                self.options.respect_gitignore,
+                &self.fielded,
+            )?;
+        }
+        if ensure_dense(&self.engine, &algo, &self.options.dense)? {
+            self.ui.status(
+                "dense",
+                &format!("fitted latent semantic vectors for {}", algo.name()),
+            );
+        }
+
+        self.algo = algo;
+        self.last = None;
+        self.ui.notice(&format!("algorithm {}", self.algo.name()));
+        Ok(())
+    }
+
+    fn notice_expansion(&self, name: &str, on: bool) {
+        let state = if on { "on" } else { "off" };
+        if self.algo.needs_fielded_index() {
+            self.ui.notice(&format!("{name} {state}"));
+        } else {
+            self.ui.notice(&format!(
+                "{name} {state}; it applies to bm25f and bm25-proximity only"
+            ));
+        }
+    }
+
+    fn last_result(&self, rank: usize) -> Option<(&AnalyzedQuery, &Scored, usize)> {
+        match &self.last {
+            None => {
+                self.ui.notice("run a query first");
+                None
+            }
+            Some((_, ranking)) if rank > ranking.0.len() => {
+                self.ui
+                    .notice(&format!("there are only {} results", ranking.0.len()));
+                None
+            }
+            Some((query, ranking)) => Some((query, ranking, rank - 1)),
+        }
+    }
+
+    fn open(&self, rank: usize) -> Result<()> {
+        let Some((_, ranking, i)) = self.last_result(rank) else {
+            return Ok(());
+        };
+        let score = &ranking.0[i];
+        let line = score
+            .passages
+            .first()
+            .map_or(1, |passage| passage.start_line);
+        let editor = env::var("EDITOR")
+            .or_else(|_| env::var("VISUAL"))
+            .unwrap_or_else(|_| "vi".to_string());
+        // A symbol opens its file at the symbol's first line.
+        let file = self
+            .engine
+            .with_read(|index| index.file_path(&score.doc_path).to_path_buf())?;
+        let Some(mut command) = editor_command(&editor, &file, line) else {
+            self.ui.notice("EDITOR is empty");
+            return Ok(());
+        };
+
+        match command.status() {
+            Ok(status) if !status.success() => {
+                self.ui.notice(&format!("{editor} exited with {status}"));
+            }
+            Ok(_) => {}
+            Err(error) => self.ui.notice(&format!("failed to run {editor}: {error}")),
+        }
+        Ok(())
+    }
+
+    fn explain(&self, rank: usize) -> Result<()> {
+        let Some((query, ranking, i)) = self.last_result(rank) else {
+            return Ok(());
+        };
+        let score = &ranking.0[i];
+        let explanations = explain_results(
+            &self.engine,
+            &self.algo,
+            query,
+            std::slice::from_ref(score),
+            &self.options.filter,
+        )?;
+
+        println!("{}", self.ui.format_score(score));
+        match explanations.into_iter().next().flatten() {
+            Some(explanation) => {
+                for line in self.ui.format_explanation(&explanation) {
+                    println!("{line}");
+                }
+            }
+            None => self.ui.notice("the result is no longer indexed"),
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> Result<()> {
+        let toggle = |on: bool| if on { "on" } else { "off" };
+        println!("algorithm: {}", self.algo.name());
+        println!("top: {}", self.options.top_n);
+        println!("query expansion: {}", toggle(self.options.query_expansion));
+        println!(
+            "feedback expansion: {}",
+            toggle(self.options.feedback_expansion)
+        );
+        println!("diversify: {}", toggle(self.options.diversify));
+        println!("filter: {}", self.filter_label);
+        println!(
+            "fielded index: {}",
+            if self.fielded.load(Ordering::SeqCst) {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+        print_corpus_stats(&self.engine.with_read(|index| index.corpus_stats(10))?);
+        Ok(())
+    }
+}
+
+fn log_query(
+    query: &AnalyzedQuery,
+    ranking: &Option<Scored>,
+    algo: &RankingAlgo,
+    top_n: usize,
+) -> Result<()> {
+    let mut query_log = HashMap::new();
+
+    query_log.insert("query".to_string(), query.to_string());
+    query_log.insert("top_n".to_string(), top_n.to_string());
+
+    match ranking {
+        Some(ranking) => {
+            query_log.insert("ranking".to_string(), format!("{ranking:?}"));
+        }
+        None => {
+            query_log.insert("ranking".to_string(), String::new());
+        }
+    }
+
+    query_log.insert("ranking_algo".to_string(), format!("{algo:?}"));
+    query_log.insert(
+        "timestamp".to_string(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    );
+
+    let query_log = serde_json::to_string(&query_log).context("failed to serialize query log")?;
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open("./query_log.txt")
+        .context("failed to open query log file")?;
+
+    file.write_all(query_log.as_bytes())
+        .context("failed to write query log")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, ffi::OsStr, path::Path};
+
+    use repo_reaper_core::{
+        config::Config as ReaperConfig, index::InvertedIndex, tokenizer::n_gram_transform,
+    };
+    use rust_stemmers::{Algorithm, Stemmer};
+
+    use super::{Command, editor_command, parse_filter};
+
+    fn test_config() -> ReaperConfig {
+        ReaperConfig {
+            n_grams: 1,
+            stemmer: Stemmer::create(Algorithm::English),
+            stop_words: HashSet::new(),
+            analyzers: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    fn write_temp_file(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    fn args(command: &std::process::Command) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    #[test]
+    fn parses_colon_commands_and_leaves_queries_alone() {
+        assert_eq!(Command::parse("retry pool"), None);
+        assert_eq!(
+            Command::parse(":algo bm25f"),
+            Some(Ok(Command::Algo("bm25f".to_string())))
+        );
+        assert_eq!(Command::parse(":top 20"), Some(Ok(Command::Top(20))));
+        assert_eq!(
+            Command::parse(":expand on"),
+            Some(Ok(Command::Expand(true)))
+        );
+        assert_eq!(
+            Command::parse(":feedback off"),
+            Some(Ok(Command::Feedback(false)))
+        );
+        assert_eq!(
+            Command::parse(":diversify on"),
+            Some(Ok(Command::Diversify(true)))
+        );
+        assert_eq!(
+            Command::parse(":filter ext:rs  -tests"),
+            Some(Ok(Command::Filter(vec![
+                "ext:rs".to_string(),
+                "-tests".to_string()
+            ])))
+        );
+        assert_eq!(
+            Command::parse(":filter"),
+            Some(Ok(Command::Filter(Vec::new())))
+        );
+        assert_eq!(Command::parse(":open 3"), Some(Ok(Command::Open(3))));
+        assert_eq!(Command::parse(":explain 1"), Some(Ok(Command::Explain(1))));
+        assert_eq!(Command::parse(":stats"), Some(Ok(Command::Stats)));
+        assert_eq!(Command::parse(":history"), Some(Ok(Command::History)));
+        assert_eq!(Command::parse(":q"), Some(Ok(Command::Quit)));
+
+        for malformed in [
+            ":algo",
+            ":top 0",
+            ":top many",
+            ":expand maybe",
+            ":open",
+            ":nope",
+        ] {
+            assert!(
+                matches!(Command::parse(malformed), Some(Err(_))),
+                "{malformed} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn filter_arguments_build_a_search_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("tests")).unwrap();
+        write_temp_file(dir.path(), "src/pool.rs", "fn pool() {}");
+        write_temp_file(dir.path(), "tests/pool.rs", "fn pool() {}");
+        write_temp_file(dir.path(), "README.md", "pool");
+        let config = test_config();
+        let index = InvertedIndex::new(
+            dir.path(),
+            |content: &str| n_gram_transform(content, &config),
+            Some(dir.path()),
+        );
+        let admitted = |filters: &[&str]| {
+            let filters = filters.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+            let filter = parse_filter(&filters).unwrap();
+            let mut paths = index
+                .documents()
+                .filter(|metadata| filter.matches(metadata))
+                .map(|metadata| metadata.path.display().to_string())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(admitted(&[]).len(), 3);
+        assert_eq!(admitted(&["ext:rs"]), vec!["src/pool.rs", "tests/pool.rs"]);
+        assert_eq!(admitted(&["ext:rs", "ext:md"]).len(), 3);
+        assert_eq!(
+            admitted(&["glob:!tests/**"]),
+            vec!["README.md", "src/pool.rs"]
+        );
+        assert!(parse_filter(&["type:rust".to_string(), "-tests".to_string()]).is_ok());
+        assert!(parse_filter(&["type:cobol".to_string()]).is_err());
+        assert!(parse_filter(&["max:lots".to_string()]).is_err());
+        assert!(parse_filter(&["rs".to_string()]).is_err());
+    }
+
+    #[test]
+    fn editor_command_jumps_to_the_line_in_common_editors() {
+        let path = Path::new("src/pool.rs");
+
+        let vim = editor_command("vim", path, 12).unwrap();
+        assert_eq!(vim.get_program(), "vim");
+        assert_eq!(args(&vim), vec!["+12", "src/pool.rs"]);
+
+        let code = editor_command("/usr/bin/code -w", path, 12).unwrap();
+        assert_eq!(args(&code), vec!["-w", "--goto", "src/pool.rs:12"]);
+
+        let zed = editor_command("zed", path, 3).unwrap();
+        assert_eq!(args(&zed), vec!["src/pool.rs:3"]);
+
+        assert!(editor_command("  ", path, 1).is_none());
+    }
+}
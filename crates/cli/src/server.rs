@@ -0,0 +1,1154 @@
+//! `rr serve`: a warm index behind JSON-RPC 2.0 over HTTP.
+//!
+//! Every request is a `POST` whose body is one JSON-RPC request object. The
+//! same protocol is spoken on TCP and Unix sockets.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use repo_reaper_core::{
+    config::Config as ReaperConfig,
+    index::{CorpusStats, FilteredIndex, SearchEngine, SymbolLocation, find_symbols},
+    pack::{ContextPack, DEFAULT_BUDGET_TOKENS, estimate_tokens_from_bytes},
+    query::AnalyzedQuery,
+    ranking::{Passage, RankingAlgo, ScoreExplanation, SymbolHit, search_symbols},
+    regex_search::RegexSearchEngine,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::live_search::{
+    LiveSearchOptions, SearchModes, TerminalUi, analyze_query, build_index, build_transformer,
+    ensure_dense, prepare_ranked_search, search_pack_files, search_ranked, spawn_watcher,
+    sync_dense, sync_history, write_index_cache,
+};
+
+/// Where `rr serve` accepts connections: `HOST:PORT` or `unix:PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ListenAddr {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.strip_prefix("unix:") {
+            Some("") => Err("unix socket path is empty".to_string()),
+            Some(path) => Ok(Self::Unix(PathBuf::from(path))),
+            None if value.contains(':') => Ok(Self::Tcp(value.to_string())),
+            None => Err(format!("expected HOST:PORT or unix:PATH, got {value:?}")),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "http://{address}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Largest request body read; bigger requests get a 413.
+const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+/// Longest request or header line read; longer ones get a 431.
+const MAX_LINE_BYTES: usize = 8 * 1024;
+/// Most headers read per request; more get a 431.
+const MAX_HEADERS: usize = 100;
+/// Connections served at once. Further clients get a 503.
+const MAX_CONNECTIONS: usize = 64;
+/// How long a connection may stall reading or writing before it is dropped.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub(crate) fn run(
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    options: LiveSearchOptions,
+    listen: &ListenAddr,
+) -> Result<()> {
+    let service = Arc::new(SearchService::start(
+        directory, config, algo, options, true,
+    )?);
+    service.watch();
+    let slots = Arc::new(ConnectionSlots::default());
+
+    match listen {
+        ListenAddr::Tcp(address) => {
+            let listener = std::net::TcpListener::bind(address)
+                .with_context(|| format!("failed to listen on {address}"))?;
+            TerminalUi::new().status("serve", &format!("listening on {listen}"));
+            for stream in listener.incoming() {
+                let stream = stream.context("failed to accept connection")?;
+                if let Err(error) = stream
+                    .set_read_timeout(Some(IO_TIMEOUT))
+                    .and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT)))
+                {
+                    eprintln!("serve error: {error}");
+                    continue;
+                }
+                spawn_connection(stream, &service, &slots);
+            }
+        }
+        #[cfg(unix)]
+        ListenAddr::Unix(path) => {
+            use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+
+            if std::fs::symlink_metadata(path)
+                .is_ok_and(|metadata| metadata.file_type().is_socket())
+            {
+                std::fs::remove_file(path)
+                    .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+            }
+            let listener = UnixListener::bind(path)
+                .with_context(|| format!("failed to listen on {}", path.display()))?;
+            TerminalUi::new().status("serve", &format!("listening on {listen}"));
+            for stream in listener.incoming() {
+                let stream = stream.context("failed to accept connection")?;
+                if let Err(error) = stream
+                    .set_read_timeout(Some(IO_TIMEOUT))
+                    .and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT)))
+                {
+                    eprintln!("serve error: {error}");
+                    continue;
+                }
+                spawn_connection(stream, &service, &slots);
+            }
+        }
+        #[cfg(not(unix))]
+        ListenAddr::Unix(_) => anyhow::bail!("unix sockets are not supported on this platform"),
+    }
+
+    Ok(())
+}
+
+/// Serves `stream` on its own thread, or turns it away with a 503 when
+/// [`MAX_CONNECTIONS`] are already being served.
+fn spawn_connection<S>(stream: S, service: &Arc<SearchService>, slots: &Arc<ConnectionSlots>)
+where
+    S: Send + 'static,
+    for<'a> &'a S: Read + Write,
+{
+    let Some(slot) = slots.try_acquire() else {
+        if let Err(error) = write_response(&stream, "503 Service Unavailable", "", false) {
+            eprintln!("serve error: {error}");
+        }
+        return;
+    };
+    let service = Arc::clone(service);
+    thread::spawn(move || {
+        let _slot = slot;
+        if let Err(error) = serve_connection(BufReader::new(&stream), &stream, &service) {
+            eprintln!("serve error: {error}");
+        }
+    });
+}
+
+/// Counts the connections being served.
+#[derive(Default)]
+struct ConnectionSlots {
+    open: AtomicUsize,
+}
+
+impl ConnectionSlots {
+    /// Takes a slot until the returned guard drops, or `None` when every
+    /// slot is taken.
+    fn try_acquire(self: &Arc<Self>) -> Option<ConnectionSlot> {
+        self.open
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                (open < MAX_CONNECTIONS).then_some(open + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(Arc::clone(self)))
+    }
+}
+
+struct ConnectionSlot(Arc<ConnectionSlots>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.open.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The warm index and the settings every request is answered with.
+pub(crate) struct SearchService {
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    options: LiveSearchOptions,
+    engine: SearchEngine,
+    fielded: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct QueryParams {
+    pub(crate) query: String,
+    #[serde(default, alias = "top_k")]
+    pub(crate) top_n: Option<usize>,
+    /// Ranking algorithm name, as accepted by `--ranking-algorithm`.
+    #[serde(default)]
+    pub(crate) algorithm: Option<String>,
+    /// Overrides `--diversify` for this query.
+    #[serde(default)]
+    pub(crate) diversify: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RegexParams {
+    pub(crate) pattern: String,
+    #[serde(default)]
+    pub(crate) max_results: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SymbolParams {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PackParams {
+    pub(crate) query: String,
+    #[serde(default)]
+    pub(crate) budget_tokens: Option<usize>,
+    #[serde(default, alias = "top_k")]
+    pub(crate) top_n: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReadParams {
+    pub(crate) path: PathBuf,
+    #[serde(default)]
+    pub(crate) start_line: Option<usize>,
+    #[serde(default)]
+    pub(crate) end_line: Option<usize>,
+    #[serde(default)]
+    pub(crate) max_tokens: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SearchHit {
+    pub(crate) path: PathBuf,
+    pub(crate) score: f64,
+    pub(crate) passages: Vec<Passage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) similar: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ExplainHit {
+    path: PathBuf,
+    score: f64,
+    explanation: ScoreExplanation,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RegexHit {
+    path: PathBuf,
+    byte_start: usize,
+    byte_end: usize,
+    line_start: usize,
+    line_end: usize,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ReindexResult {
+    documents: usize,
+}
+
+/// Lines of one indexed file, cut short when they exceed the token budget.
+#[derive(Debug, Serialize)]
+pub(crate) struct FileLines {
+    pub(crate) path: PathBuf,
+    pub(crate) start_line: usize,
+    pub(crate) end_line: usize,
+    pub(crate) truncated: bool,
+    pub(crate) text: String,
+}
+
+impl SearchService {
+    /// Loads or builds the index for `directory`.
+    pub(crate) fn start(
+        directory: PathBuf,
+        config: Arc<ReaperConfig>,
+        algo: RankingAlgo,
+        options: LiveSearchOptions,
+        verbose: bool,
+    ) -> Result<Self> {
+        let prepared =
+            prepare_ranked_search(&directory, Arc::clone(&config), &algo, &options, verbose)?;
+        Ok(Self {
+            directory,
+            config,
+            algo,
+            options,
+            engine: prepared.engine,
+            fielded: prepared.fielded,
+        })
+    }
+
+    /// Keeps the index in step with the directory from a background thread.
+    pub(crate) fn watch(&self) {
+        spawn_watcher(
+            self.directory.clone(),
+            self.engine.clone(),
+            build_transformer(Arc::clone(&self.config)),
+            Arc::clone(&self.config),
+            Arc::new(AtomicBool::new(self.fielded)),
+            self.options.index_dir.clone(),
+        );
+    }
+
+    /// Answers one JSON-RPC request body. Returns `None` for notifications,
+    /// which get no response.
+    pub(crate) fn handle(&self, body: &str) -> Option<Value> {
+        respond(body, |method, params| self.call(method, params))
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "search" => to_value(self.search(parse_params(params)?)?),
+            "explain" => to_value(self.explain(parse_params(params)?)?),
+            "regex" => to_value(self.regex(parse_params(params)?)?),
+            "symbols" => to_value(self.symbols(parse_params(params)?)?),
+            "pack" => to_value(self.pack(parse_params(params)?)?),
+            "read" => to_value(self.read(parse_params(params)?)?),
+            "stats" => to_value(self.stats()?),
+            "reindex" => to_value(self.reindex()?),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method {method:?}"),
+            )),
+        }
+    }
+
+    pub(crate) fn search(&self, params: QueryParams) -> Result<Vec<SearchHit>, RpcError> {
+        let algo = self.algorithm(params.algorithm.as_deref())?;
+        let query = analyze_query(
+            &self.config,
+            &algo,
+            &params.query,
+            self.options.query_expansion,
+            self.options.feedback_expansion,
+        );
+        let ranking = search_ranked(
+            &self.engine,
+            &algo,
+            &query,
+            params.top_n.unwrap_or(self.options.top_n),
+            SearchModes {
+                diversity: params
+                    .diversify
+                    .unwrap_or(self.options.diversify)
+                    .then_some(&self.options.ranking.diversity),
+                ..self.options.search_modes()
+            },
+            &self.options.filter,
+        )
+        .map_err(RpcError::internal)?;
+
+        Ok(ranking
+            .map(|ranking| ranking.0)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|score| SearchHit {
+                path: score.doc_path,
+                score: score.score,
+                passages: score.passages,
+                similar: score.similar,
+            })
+            .collect())
+    }
+
+    pub(crate) fn explain(&self, params: QueryParams) -> Result<Vec<ExplainHit>, RpcError> {
+        let algo = self.algorithm(params.algorithm.as_deref())?;
+        let query = analyze_query(
+            &self.config,
+            &algo,
+            &params.query,
+            self.options.query_expansion,
+            false,
+        );
+        let top_n = params.top_n.unwrap_or(self.options.top_n);
+        let explained = self
+            .engine
+            .with_read(|index| {
+                let index = FilteredIndex::new(index, &self.options.filter);
+                algo.rank_with_explanations(&index, &query, top_n)
+            })
+            .map_err(RpcError::internal)?;
+
+        Ok(explained
+            .map(|explained| explained.results)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|result| ExplainHit {
+                path: result.score.doc_path,
+                score: result.score.score,
+                explanation: result.explanation,
+            })
+            .collect())
+    }
+
+    pub(crate) fn regex(&self, params: RegexParams) -> Result<Vec<RegexHit>, RpcError> {
+        let matches = RegexSearchEngine::new(&self.directory)
+            .with_respect_gitignore(self.options.respect_gitignore)
+            .search(&params.pattern)
+            .map_err(|error| RpcError::new(INVALID_PARAMS, error.to_string()))?;
+
+        Ok(matches
+            .into_iter()
+            .take(params.max_results.unwrap_or(usize::MAX))
+            .map(|matched| RegexHit {
+                path: matched.path,
+                byte_start: matched.byte_range.start,
+                byte_end: matched.byte_range.end,
+                line_start: *matched.line_range.start(),
+                line_end: *matched.line_range.end(),
+                text: matched.matched_text,
+            })
+            .collect())
+    }
+
+    pub(crate) fn symbols(&self, params: SymbolParams) -> Result<Vec<SymbolLocation>, RpcError> {
+        self.engine
+            .with_read(|index| {
+                find_symbols(
+                    index,
+                    &params.name,
+                    params.limit.unwrap_or(self.options.top_n),
+                )
+            })
+            .map_err(RpcError::internal)
+    }
+
+    /// Declared symbols ranked by BM25F over the symbol field alone.
+    pub(crate) fn symbol_search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SymbolHit>, RpcError> {
+        if !self.fielded {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "symbol search needs a fielded index; restart with a fielded ranking algorithm",
+            ));
+        }
+        let query = AnalyzedQuery::new_code_search(query, &self.config);
+        self.engine
+            .with_read(|index| search_symbols(index, &query, limit))
+            .map_err(RpcError::internal)
+    }
+
+    pub(crate) fn top_n(&self) -> usize {
+        self.options.top_n
+    }
+
+    /// The best passages for `query`, cut to the token budget, with their
+    /// text loaded.
+    pub(crate) fn pack(&self, params: PackParams) -> Result<ContextPack, RpcError> {
+        let query = analyze_query(
+            &self.config,
+            &self.algo,
+            &params.query,
+            self.options.query_expansion,
+            self.options.feedback_expansion,
+        );
+        let ranking = search_pack_files(
+            &self.engine,
+            &self.algo,
+            &query,
+            params.top_n.unwrap_or(self.options.top_n),
+            self.options.search_modes(),
+            &self.options.filter,
+        )
+        .map_err(RpcError::internal)?;
+
+        let mut pack = self
+            .engine
+            .with_read(|index| {
+                ContextPack::select(
+                    index,
+                    &params.query,
+                    &ranking.0,
+                    params.budget_tokens.unwrap_or(DEFAULT_BUDGET_TOKENS),
+                )
+            })
+            .map_err(RpcError::internal)?;
+        pack.load_text(|path| std::fs::read_to_string(path).ok());
+        Ok(pack)
+    }
+
+    /// Reads lines of an indexed file. Paths outside the index are refused so
+    /// clients cannot read arbitrary files through the server.
+    pub(crate) fn read(&self, params: ReadParams) -> Result<FileLines, RpcError> {
+        let indexed = self
+            .engine
+            .with_read(|index| index.doc_id(&params.path).is_some())
+            .map_err(RpcError::internal)?;
+        if !indexed {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("{} is not an indexed file", params.path.display()),
+            ));
+        }
+        let content = std::fs::read_to_string(&params.path).map_err(RpcError::internal)?;
+
+        let start_line = params.start_line.unwrap_or(1).max(1);
+        let end_line = params.end_line.unwrap_or(usize::MAX);
+        let budget_tokens = params.max_tokens.unwrap_or(DEFAULT_BUDGET_TOKENS);
+        let mut text = String::new();
+        let mut last_line = start_line.saturating_sub(1);
+        let mut truncated = false;
+        for (number, line) in content.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            if number < start_line {
+                continue;
+            }
+            if number > end_line {
+                break;
+            }
+            if estimate_tokens_from_bytes(text.len() + line.len() + 1) > budget_tokens {
+                truncated = true;
+                break;
+            }
+            text.push_str(line);
+            text.push('\n');
+            last_line = number;
+        }
+
+        Ok(FileLines {
+            path: params.path,
+            start_line,
+            end_line: last_line,
+            truncated,
+            text,
+        })
+    }
+
+    fn algorithm(&self, name: Option<&str>) -> Result<RankingAlgo, RpcError> {
+        let Some(name) = name else {
+            return Ok(self.algo.clone());
+        };
+        // Loading a model would let clients make the server read any file.
+        if name.starts_with("learned:") {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "learned models are chosen with --ranking-algorithm when the server starts",
+            ));
+        }
+        let algo = name
+            .parse::<RankingAlgo>()
+            .map(|algo| self.options.ranking.apply(algo))
+            .map_err(|error| RpcError::new(INVALID_PARAMS, error))?;
+        if algo.needs_fielded_index() && !self.fielded {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("{name} needs a fielded index; restart with a fielded ranking algorithm"),
+            ));
+        }
+        ensure_dense(&self.engine, &algo, &self.options.dense).map_err(RpcError::internal)?;
+        Ok(algo)
+    }
+
+    pub(crate) fn stats(&self) -> Result<CorpusStats, RpcError> {
+        self.engine
+            .with_read(|index| index.corpus_stats(10))
+            .map_err(RpcError::internal)
+    }
+
+    /// Rebuilds the index from disk and swaps it in; searches keep running
+    /// against the old index until the new one is ready.
+    pub(crate) fn reindex(&self) -> Result<ReindexResult, RpcError> {
+        let mut index = build_index(
+            &self.directory,
+            &self.config,
+            build_transformer(Arc::clone(&self.config)).as_ref(),
+            self.algo.needs_fielded_index(),
+            self.options.respect_gitignore,
+        );
+        if let Err(error) = sync_history(&mut index, &self.directory, self.options.history) {
+            eprintln!("history unavailable, reindexing without it: {error}");
+        }
+        if let Some(config) = self
+            .engine
+            .with_read(|index| index.dense().map(|dense| dense.config().clone()))
+            .map_err(RpcError::internal)?
+        {
+            index.build_dense(&config);
+        }
+        sync_dense(&mut index, &self.algo, &self.options.dense);
+        self.engine.replace(index).map_err(RpcError::internal)?;
+        if let Some(index_dir) = &self.options.index_dir {
+            write_index_cache(&self.engine, index_dir, &self.directory, &self.config)
+                .map_err(RpcError::internal)?;
+        }
+
+        Ok(ReindexResult {
+            documents: self.engine.num_docs().map_err(RpcError::internal)?,
+        })
+    }
+}
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RpcError {
+    pub(crate) code: i64,
+    pub(crate) message: String,
+}
+
+impl RpcError {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn internal(error: impl fmt::Display) -> Self {
+        Self::new(INTERNAL_ERROR, error.to_string())
+    }
+}
+
+/// Parses one JSON-RPC message and answers it with `call(method, params)`.
+/// Notifications are still run but get no response.
+pub(crate) fn respond(
+    message: &str,
+    call: impl FnOnce(&str, Value) -> Result<Value, RpcError>,
+) -> Option<Value> {
+    let message = match serde_json::from_str::<Value>(message) {
+        Ok(message) => message,
+        Err(error) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, error.to_string()),
+            ));
+        }
+    };
+    let id = message.get("id").cloned();
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        return Some(error_response(
+            id.unwrap_or(Value::Null),
+            RpcError::new(INVALID_REQUEST, "missing method"),
+        ));
+    };
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+    let result = call(method, params);
+    let id = id?;
+    Some(match result {
+        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+pub(crate) fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|error| RpcError::new(INVALID_PARAMS, error.to_string()))
+}
+
+pub(crate) fn to_value(result: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(result).map_err(RpcError::internal)
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+/// Serves HTTP/1.1 requests from one connection until the client closes it
+/// or asks to.
+fn serve_connection(
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+    service: &SearchService,
+) -> Result<()> {
+    while let Some(incoming) = read_http_request(&mut reader)? {
+        let request = match incoming {
+            Incoming::Request(request) => request,
+            Incoming::Refused(status) => {
+                write_response(&mut writer, status, "", false)?;
+                break;
+            }
+        };
+        let (status, body) = if request.method != "POST" {
+            ("405 Method Not Allowed", None)
+        } else {
+            match service.handle(&request.body) {
+                Some(response) => ("200 OK", Some(response.to_string())),
+                None => ("204 No Content", None),
+            }
+        };
+        write_response(
+            &mut writer,
+            status,
+            &body.unwrap_or_default(),
+            request.keep_alive,
+        )?;
+        if !request.keep_alive {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn write_response(
+    mut writer: impl Write,
+    status: &str,
+    body: &str,
+    keep_alive: bool,
+) -> Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\n{body}",
+        body.len(),
+        if keep_alive {
+            ""
+        } else {
+            "Connection: close\r\n"
+        },
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+struct HttpRequest {
+    method: String,
+    body: String,
+    keep_alive: bool,
+}
+
+/// What reading one request off a connection produced.
+enum Incoming {
+    Request(HttpRequest),
+    /// A request turned away with this status before it was fully read,
+    /// which leaves the connection unusable for further requests.
+    Refused(&'static str),
+}
+
+const HEADERS_TOO_LARGE: &str = "431 Request Header Fields Too Large";
+const REQUEST_TIMEOUT: &str = "408 Request Timeout";
+
+/// Reads one request, or `None` at end of stream.
+fn read_http_request(reader: &mut impl BufRead) -> Result<Option<Incoming>> {
+    let request_line = match read_line(reader)? {
+        Line::Text(line) => line,
+        Line::End => return Ok(None),
+        Line::Refused(status) => return Ok(Some(Incoming::Refused(status))),
+    };
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().context("empty HTTP request line")?.to_string();
+    let version = parts.nth(1).unwrap_or("HTTP/1.0");
+
+    let mut content_length = 0;
+    let mut keep_alive = version == "HTTP/1.1";
+    let mut headers = 0;
+    loop {
+        let header = match read_line(reader)? {
+            Line::Text(header) if !header.trim().is_empty() => header,
+            Line::Text(_) | Line::End => break,
+            Line::Refused(status) => return Ok(Some(Incoming::Refused(status))),
+        };
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Ok(Some(Incoming::Refused(HEADERS_TOO_LARGE)));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().context("invalid Content-Length header")?;
+        } else if name.eq_ignore_ascii_case("connection") {
+            keep_alive = !value.eq_ignore_ascii_case("close");
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Ok(Some(Incoming::Refused("413 Payload Too Large")));
+    }
+    let mut body = vec![0; content_length];
+    match reader.read_exact(&mut body) {
+        Err(error) if timed_out(&error) => return Ok(Some(Incoming::Refused(REQUEST_TIMEOUT))),
+        result => result?,
+    }
+
+    Ok(Some(Incoming::Request(HttpRequest {
+        method,
+        body: String::from_utf8(body).context("request body is not UTF-8")?,
+        keep_alive,
+    })))
+}
+
+enum Line {
+    Text(String),
+    End,
+    Refused(&'static str),
+}
+
+/// Reads one line of at most [`MAX_LINE_BYTES`].
+fn read_line(reader: &mut impl BufRead) -> Result<Line> {
+    let mut line = String::new();
+    match reader.take(MAX_LINE_BYTES as u64 + 1).read_line(&mut line) {
+        Ok(0) => Ok(Line::End),
+        Ok(_) if line.len() > MAX_LINE_BYTES => Ok(Line::Refused(HEADERS_TOO_LARGE)),
+        Ok(_) => Ok(Line::Text(line)),
+        Err(error) if timed_out(&error) => Ok(Line::Refused(REQUEST_TIMEOUT)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Read timeouts surface as `WouldBlock` on Unix and `TimedOut` on Windows.
+fn timed_out(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        io::{BufReader, Cursor, Read},
+        path::Path,
+        sync::Arc,
+    };
+
+    use repo_reaper_core::{
+        config::Config as ReaperConfig,
+        index::SearchFilter,
+        ranking::{BM25HyperParams, RankingAlgo},
+    };
+    use rust_stemmers::{Algorithm, Stemmer};
+    use serde_json::{Value, json};
+
+    use super::{
+        ConnectionSlots, ListenAddr, MAX_BODY_BYTES, MAX_CONNECTIONS, MAX_HEADERS, MAX_LINE_BYTES,
+        SearchService, serve_connection, spawn_connection,
+    };
+    use crate::live_search::LiveSearchOptions;
+
+    fn test_config() -> ReaperConfig {
+        ReaperConfig {
+            n_grams: 1,
+            stemmer: Stemmer::create(Algorithm::English),
+            stop_words: HashSet::new(),
+            analyzers: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    fn write_temp_file(dir: &Path, name: &str, content: &str) {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    fn service(dir: &Path) -> SearchService {
+        SearchService::start(
+            dir.to_path_buf(),
+            Arc::new(test_config()),
+            RankingAlgo::BM25(BM25HyperParams { k1: 1.2, b: 0.75 }),
+            LiveSearchOptions {
+                top_n: 10,
+                query_expansion: false,
+                feedback_expansion: false,
+                index_dir: None,
+                reindex: false,
+                respect_gitignore: false,
+                history: false,
+                diversify: false,
+                symbols: false,
+                filter: SearchFilter::new(),
+                ranking: Default::default(),
+                dense: Default::default(),
+            },
+            false,
+        )
+        .unwrap()
+    }
+
+    fn call(service: &SearchService, method: &str, params: Value) -> Value {
+        service
+            .handle(
+                &json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+                    .to_string(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn listen_addr_accepts_tcp_and_unix_forms() {
+        assert_eq!(
+            "127.0.0.1:7878".parse(),
+            Ok(ListenAddr::Tcp("127.0.0.1:7878".to_string()))
+        );
+        assert_eq!(
+            "unix:/tmp/rr.sock".parse(),
+            Ok(ListenAddr::Unix("/tmp/rr.sock".into()))
+        );
+        assert!("7878".parse::<ListenAddr>().is_err());
+        assert!("unix:".parse::<ListenAddr>().is_err());
+    }
+
+    #[test]
+    fn search_regex_and_stats_answer_from_the_warm_index() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "fn retry_pool() {}\n// pool timeout\n",
+        );
+        write_temp_file(dir.path(), "other.rs", "fn unrelated() {}\n");
+        let service = service(dir.path());
+
+        let search = call(
+            &service,
+            "search",
+            json!({ "query": "timeout", "top_n": 5 }),
+        );
+        let results = search["result"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0]["path"].as_str().unwrap().ends_with("pool.rs"));
+
+        let regex = call(&service, "regex", json!({ "pattern": "retry_\\w+" }));
+        assert_eq!(regex["result"][0]["text"], "retry_pool");
+        assert_eq!(regex["result"][0]["line_start"], 1);
+
+        let stats = call(&service, "stats", Value::Null);
+        assert_eq!(stats["result"]["document_count"], 2);
+    }
+
+    #[test]
+    fn search_collapses_near_duplicates_when_asked_to_diversify() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn pool_timeout() { retry(pool) }\n");
+        write_temp_file(dir.path(), "b.rs", "fn pool_timeout() { retry(pool) }\n");
+        let service = service(dir.path());
+
+        let plain = call(&service, "search", json!({ "query": "pool timeout" }));
+        assert_eq!(plain["result"].as_array().unwrap().len(), 2);
+
+        let diversified = call(
+            &service,
+            "search",
+            json!({ "query": "pool timeout", "diversify": true }),
+        );
+        let results = diversified["result"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["similar"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn explain_reports_term_contributions() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", "pool timeout pool\n");
+        let service = service(dir.path());
+
+        let explain = call(&service, "explain", json!({ "query": "pool" }));
+
+        let terms = &explain["result"][0]["explanation"]["terms"];
+        assert_eq!(terms[0]["term"], "pool");
+        assert_eq!(terms[0]["term_frequency"], 2);
+    }
+
+    #[test]
+    fn read_serves_indexed_files_only_within_the_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "one\ntwo\nthree\nfour\n");
+        let service = service(dir.path());
+        let path = dir.path().join("a.rs");
+
+        let read = call(
+            &service,
+            "read",
+            json!({ "path": path, "start_line": 2, "max_tokens": 3 }),
+        );
+        assert_eq!(read["result"]["text"], "two\nthree\n");
+        assert_eq!(read["result"]["end_line"], 3);
+        assert_eq!(read["result"]["truncated"], true);
+
+        let outside = call(
+            &service,
+            "read",
+            json!({ "path": dir.path().join("missing.rs") }),
+        );
+        assert_eq!(outside["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn reindex_picks_up_files_the_watcher_missed() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let service = service(dir.path());
+        write_temp_file(dir.path(), "b.rs", "fn b() {}\n");
+
+        let reindex = call(&service, "reindex", Value::Null);
+
+        assert_eq!(reindex["result"]["documents"], 2);
+    }
+
+    #[test]
+    fn errors_follow_json_rpc_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = service(dir.path());
+
+        assert_eq!(call(&service, "nope", Value::Null)["error"]["code"], -32601);
+        assert_eq!(
+            call(&service, "search", json!({ "top_n": 1 }))["error"]["code"],
+            -32602
+        );
+        assert_eq!(
+            call(&service, "regex", json!({ "pattern": "(" }))["error"]["code"],
+            -32602
+        );
+        assert_eq!(service.handle("{").unwrap()["error"]["code"], -32700);
+        assert!(
+            service
+                .handle(r#"{"jsonrpc":"2.0","method":"stats"}"#)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn http_connection_serves_keep_alive_requests_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let service = service(dir.path());
+        let body = r#"{"jsonrpc":"2.0","id":7,"method":"stats"}"#;
+        let input = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {len}\r\n\r\n{body}GET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+            len = body.len()
+        );
+        let mut output = Vec::new();
+
+        serve_connection(Cursor::new(input), &mut output, &service).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let responses = output.split("HTTP/1.1 ").skip(1).collect::<Vec<_>>();
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0].starts_with("200 OK"));
+        assert!(responses[0].contains(r#""id":7"#));
+        assert!(responses[1].starts_with("405 Method Not Allowed"));
+        assert!(responses[1].contains("Connection: close"));
+    }
+
+    #[test]
+    fn http_connection_refuses_oversized_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let service = service(dir.path());
+        let input = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{{",
+            MAX_BODY_BYTES + 1
+        );
+        let mut output = Vec::new();
+
+        serve_connection(Cursor::new(input), &mut output, &service).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 413 Payload Too Large"));
+        assert!(output.contains("Connection: close"));
+    }
+
+    #[test]
+    fn http_connection_refuses_oversized_and_stalled_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let service = service(dir.path());
+        let respond = |input: Box<dyn Read>| {
+            let mut output = Vec::new();
+            serve_connection(BufReader::new(input), &mut output, &service).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let long_line = format!(
+            "POST / HTTP/1.1\r\nX-Long: {}\r\n\r\n",
+            "a".repeat(MAX_LINE_BYTES)
+        );
+        assert!(respond(Box::new(Cursor::new(long_line))).starts_with("HTTP/1.1 431 "));
+        let many_headers = format!(
+            "POST / HTTP/1.1\r\n{}\r\n",
+            "X-Header: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert!(respond(Box::new(Cursor::new(many_headers))).starts_with("HTTP/1.1 431 "));
+
+        /// A client that sent part of a request and went quiet.
+        struct Stalled(Cursor<&'static str>);
+        impl Read for Stalled {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                match self.0.read(buf)? {
+                    0 => Err(std::io::ErrorKind::WouldBlock.into()),
+                    read => Ok(read),
+                }
+            }
+        }
+        let stalled = Stalled(Cursor::new("POST / HTTP/1.1\r\nContent-"));
+        let output = respond(Box::new(stalled));
+        assert!(output.starts_with("HTTP/1.1 408 Request Timeout"));
+        assert!(output.contains("Connection: close"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn connections_beyond_the_limit_get_a_503() {
+        use std::os::unix::net::UnixStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let service = Arc::new(service(dir.path()));
+        let slots = Arc::new(ConnectionSlots::default());
+        let held = (0..MAX_CONNECTIONS)
+            .map(|_| slots.try_acquire().unwrap())
+            .collect::<Vec<_>>();
+        assert!(slots.try_acquire().is_none());
+
+        let (mut client, server) = UnixStream::pair().unwrap();
+        spawn_connection(server, &service, &slots);
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+
+        drop(held);
+        assert!(slots.try_acquire().is_some());
+    }
+
+    #[test]
+    fn rpc_params_cannot_load_learned_models() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", "fn a() {}\n");
+        let service = service(dir.path());
+
+        let response = call(
+            &service,
+            "search",
+            json!({ "query": "a", "algorithm": "learned:/etc/passwd" }),
+        );
+
+        assert_eq!(response["error"]["code"], -32602);
+        assert!(
+            !response["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("passwd")
+        );
+    }
+}
@@ -0,0 +1,731 @@
+//! `rr tui`: search as you type, with a results list and a preview pane
+//! scrolled to the best matching passage.
+
+use std::{
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+use repo_reaper_core::{
+    config::Config as ReaperConfig, index::SearchEngine, ranking::RankingAlgo,
+    regex_search::RegexSearchEngine,
+};
+
+use crate::{
+    live_search::{
+        LiveSearchOptions, analyze_query, build_transformer, ensure_dense, prepare_ranked_search,
+        rebuild_fielded, result_highlights, search_ranked, spawn_watcher,
+    },
+    repl::editor_command,
+};
+
+/// Quiet time after the last keystroke before the query runs.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+/// How often a ranked query reruns, so results follow the watcher.
+const REFRESH: Duration = Duration::from_secs(1);
+/// Algorithms cycled by Ctrl-R.
+const ALGORITHMS: [&str; 10] = [
+    "bm25-proximity",
+    "bm25f",
+    "bm25",
+    "ql",
+    "ql-jm",
+    "bm25+",
+    "dph",
+    "tfidf",
+    "cosim",
+    "fusion",
+];
+/// Lines shown above the focused line in the preview.
+const PREVIEW_CONTEXT: usize = 3;
+/// Files listed for a regex search.
+const MAX_REGEX_FILES: usize = 500;
+const TAB_WIDTH: usize = 4;
+
+const KEY_HELP: &str =
+    "Tab ranked/regex · Ctrl-R algorithm · ↑↓ select · PgUp/PgDn scroll · Enter open · Esc quit";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Ranked,
+    Regex,
+}
+
+/// A file in the results list.
+#[derive(Debug, Clone, PartialEq)]
+struct Hit {
+    path: PathBuf,
+    /// File previewed and opened: a symbol's parent file, else `path`.
+    file: PathBuf,
+    /// Score for ranked hits, match count for regex hits.
+    label: String,
+    /// One-based line the preview opens at, when known up front.
+    line: Option<usize>,
+    /// Byte ranges highlighted in the preview.
+    spans: Vec<Range<usize>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    None,
+    SwitchAlgorithm,
+    Open,
+    Quit,
+}
+
+struct App {
+    query: String,
+    mode: Mode,
+    algo: RankingAlgo,
+    hits: Vec<Hit>,
+    selected: usize,
+    /// Zero-based first line of the preview.
+    scroll: usize,
+    /// Path and content of the previewed file.
+    preview: Option<(PathBuf, String)>,
+    status: String,
+    /// When the query last changed, until it runs.
+    pending: Option<Instant>,
+    last_search: Option<Instant>,
+}
+
+impl App {
+    fn new(algo: RankingAlgo) -> Self {
+        Self {
+            query: String::new(),
+            mode: Mode::Ranked,
+            algo,
+            hits: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            preview: None,
+            status: String::new(),
+            pending: None,
+            last_search: None,
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent, now: Instant) -> Action {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if control => return Action::Quit,
+            KeyCode::Char('r') if control => {
+                let next = ALGORITHMS
+                    .iter()
+                    .position(|name| *name == self.algo.name())
+                    .map_or(0, |i| (i + 1) % ALGORITHMS.len());
+                self.algo = ALGORITHMS[next]
+                    .parse()
+                    .expect("ALGORITHMS holds valid names");
+                self.pending = Some(now);
+                return Action::SwitchAlgorithm;
+            }
+            KeyCode::Char('u') if control => {
+                self.query.clear();
+                self.pending = Some(now);
+            }
+            KeyCode::Char('n') if control => self.select(self.selected + 1),
+            KeyCode::Char('p') if control => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Char(c) if !control => {
+                self.query.push(c);
+                self.pending = Some(now);
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.pending = Some(now);
+            }
+            KeyCode::Tab => {
+                self.mode = match self.mode {
+                    Mode::Ranked => Mode::Regex,
+                    Mode::Regex => Mode::Ranked,
+                };
+                self.pending = Some(now);
+            }
+            KeyCode::Down => self.select(self.selected + 1),
+            KeyCode::Up => self.select(self.selected.saturating_sub(1)),
+            KeyCode::PageDown => self.scroll += 10,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Enter if !self.hits.is_empty() => return Action::Open,
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn select(&mut self, selected: usize) {
+        let selected = selected.min(self.hits.len().saturating_sub(1));
+        if selected != self.selected {
+            self.selected = selected;
+            self.load_preview();
+        }
+    }
+
+    /// Replaces the results, keeping the selected file selected when it is
+    /// still among them.
+    fn set_hits(&mut self, hits: Vec<Hit>) {
+        let previous = self.selected_hit().cloned();
+        self.selected = previous
+            .as_ref()
+            .and_then(|previous| hits.iter().position(|hit| hit.path == previous.path))
+            .unwrap_or(0);
+        self.hits = hits;
+        // Leave the preview where the user scrolled it unless the hit moved.
+        if self.selected_hit() != previous.as_ref() {
+            self.load_preview();
+        }
+    }
+
+    fn selected_hit(&self) -> Option<&Hit> {
+        self.hits.get(self.selected)
+    }
+
+    /// Reads the selected file and scrolls to its focus line: the best
+    /// passage, else the first highlight, else the top.
+    fn load_preview(&mut self) {
+        let Some(hit) = self.selected_hit() else {
+            self.preview = None;
+            self.scroll = 0;
+            return;
+        };
+        let content = std::fs::read_to_string(&hit.file).unwrap_or_default();
+        let line = hit.line.unwrap_or_else(|| {
+            hit.spans.first().map_or(1, |span| {
+                // Spans can come from an older copy of the file.
+                let mut end = span.start.min(content.len());
+                while !content.is_char_boundary(end) {
+                    end -= 1;
+                }
+                content[..end].matches('\n').count() + 1
+            })
+        });
+        self.preview = Some((hit.file.clone(), content));
+        self.scroll = line.saturating_sub(1 + PREVIEW_CONTEXT);
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [input, body, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [results, preview] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(body);
+
+        let title = match self.mode {
+            Mode::Ranked => format!(" ranked · {} ", self.algo.name()),
+            Mode::Regex => " regex ".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(self.query.as_str()).block(Block::bordered().title(title)),
+            input,
+        );
+        frame.set_cursor_position((input.x + 1 + self.query.chars().count() as u16, input.y + 1));
+
+        self.render_results(frame, results);
+        self.render_preview(frame, preview);
+
+        let footer_text = if self.status.is_empty() {
+            KEY_HELP
+        } else {
+            self.status.as_str()
+        };
+        frame.render_widget(
+            Paragraph::new(footer_text).style(Style::new().add_modifier(Modifier::DIM)),
+            footer,
+        );
+    }
+
+    fn render_results(&self, frame: &mut Frame, area: Rect) {
+        let items = self
+            .hits
+            .iter()
+            .map(|hit| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{:>10} ", hit.label),
+                        Style::new().add_modifier(Modifier::DIM),
+                    ),
+                    Span::styled(hit.path.display().to_string(), Style::new().fg(Color::Blue)),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" {} results ", self.hits.len())))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state =
+            ListState::default().with_selected((!self.hits.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn render_preview(&self, frame: &mut Frame, area: Rect) {
+        let (title, lines) = match (&self.preview, self.selected_hit()) {
+            (Some((path, content)), Some(hit)) => (
+                format!(" {}:{} ", path.display(), self.scroll + 1),
+                preview_lines(
+                    content,
+                    &hit.spans,
+                    self.scroll,
+                    area.height.saturating_sub(2) as usize,
+                ),
+            ),
+            _ => (" preview ".to_string(), Vec::new()),
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+/// `height` lines of `content` from zero-based line `first`, numbered, with
+/// the byte ranges in `spans` highlighted.
+fn preview_lines(
+    content: &str,
+    spans: &[Range<usize>],
+    first: usize,
+    height: usize,
+) -> Vec<Line<'static>> {
+    let gutter = Style::new().add_modifier(Modifier::DIM);
+    let highlight = Style::new().fg(Color::Red).add_modifier(Modifier::BOLD);
+    let expand = |text: &str| text.replace('\t', &" ".repeat(TAB_WIDTH));
+
+    let mut line_start = 0;
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            let start = line_start;
+            line_start += line.len();
+            (i, start, line.trim_end_matches(['\n', '\r']))
+        })
+        .skip(first)
+        .take(height)
+        .map(|(i, start, line)| {
+            let end = start + line.len();
+            let mut parts = vec![Span::styled(format!("{:>5} ", i + 1), gutter)];
+            let mut cursor = start;
+            for span in spans
+                .iter()
+                .filter(|span| span.start < end && span.end > start)
+            {
+                let span_start = span.start.clamp(cursor, end);
+                let span_end = span.end.clamp(span_start, end);
+                let (Some(before), Some(matched)) = (
+                    content.get(cursor..span_start),
+                    content.get(span_start..span_end),
+                ) else {
+                    continue;
+                };
+                parts.push(Span::raw(expand(before)));
+                parts.push(Span::styled(expand(matched), highlight));
+                cursor = span_end;
+            }
+            parts.push(Span::raw(expand(
+                content.get(cursor..end).unwrap_or_default(),
+            )));
+            Line::from(parts)
+        })
+        .collect()
+}
+
+/// Runs queries for the app against the live index or, in regex mode, the
+/// files on disk.
+struct Searcher {
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    engine: SearchEngine,
+    options: LiveSearchOptions,
+    fielded: Arc<AtomicBool>,
+}
+
+impl Searcher {
+    fn search(&self, app: &mut App) -> Result<()> {
+        app.last_search = Some(Instant::now());
+        if app.query.trim().is_empty() {
+            app.status.clear();
+            app.set_hits(Vec::new());
+            return Ok(());
+        }
+
+        let hits = match app.mode {
+            Mode::Ranked => self.ranked(&app.algo, &app.query)?,
+            Mode::Regex => match self.regex(&app.query) {
+                Ok(hits) => hits,
+                Err(message) => {
+                    // Keep the last results while the pattern is half typed.
+                    app.status = message;
+                    return Ok(());
+                }
+            },
+        };
+        app.status.clear();
+        app.set_hits(hits);
+        Ok(())
+    }
+
+    fn ranked(&self, algo: &RankingAlgo, text: &str) -> Result<Vec<Hit>> {
+        let query = analyze_query(
+            &self.config,
+            algo,
+            text,
+            self.options.query_expansion,
+            self.options.feedback_expansion,
+        );
+        let results = search_ranked(
+            &self.engine,
+            algo,
+            &query,
+            self.options.top_n,
+            self.options.search_modes(),
+            &self.options.filter,
+        )?
+        .map(|ranking| ranking.0)
+        .unwrap_or_default();
+        let highlights = result_highlights(&results, &self.engine, &query)?;
+        let files = self.engine.with_read(|index| {
+            results
+                .iter()
+                .map(|score| index.file_path(&score.doc_path).to_path_buf())
+                .collect::<Vec<_>>()
+        })?;
+
+        Ok(results
+            .into_iter()
+            .zip(highlights)
+            .zip(files)
+            .map(|((score, spans), file)| Hit {
+                label: format!("{:.3}", score.score),
+                line: score.passages.first().map(|passage| passage.start_line),
+                spans: spans.iter().map(|span| span.start..span.end).collect(),
+                path: score.doc_path,
+                file,
+            })
+            .collect())
+    }
+
+    fn regex(&self, pattern: &str) -> Result<Vec<Hit>, String> {
+        let matches = RegexSearchEngine::new(&self.directory)
+            .with_respect_gitignore(self.options.respect_gitignore)
+            .search(pattern)
+            .map_err(|error| error.to_string())?;
+
+        let mut hits = Vec::<Hit>::new();
+        for matched in matches {
+            if let Some(hit) = hits.last_mut().filter(|hit| hit.path == matched.path) {
+                hit.spans.push(matched.byte_range);
+            } else if hits.len() == MAX_REGEX_FILES {
+                break;
+            } else {
+                hits.push(Hit {
+                    file: matched.path.clone(),
+                    path: matched.path,
+                    label: String::new(),
+                    line: Some(*matched.line_range.start()),
+                    spans: vec![matched.byte_range],
+                });
+            }
+        }
+        for hit in &mut hits {
+            hit.label = match hit.spans.len() {
+                1 => "1 match".to_string(),
+                n => format!("{n} matches"),
+            };
+        }
+        Ok(hits)
+    }
+
+    /// Applies the configured parameters to the algorithm the app switched
+    /// to and rebuilds the index with fields when it needs them.
+    fn prepare(&self, terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+        app.algo = self.options.ranking.apply(app.algo.clone());
+        if app.algo.needs_fielded_index() && !self.fielded.load(Ordering::SeqCst) {
+            app.status = format!("rebuilding the index with fields for {}", app.algo.name());
+            terminal.draw(|frame| app.render(frame))?;
+            rebuild_fielded(
+                &self.engine,
+                &self.directory,
+                &self.config,
+                self.options.respect_gitignore,
+                &self.fielded,
+            )?;
+            app.status.clear();
+        }
+        if app.algo.needs_dense_index() {
+            app.status = format!("fitting latent semantic vectors for {}", app.algo.name());
+            terminal.draw(|frame| app.render(frame))?;
+            ensure_dense(&self.engine, &app.algo, &self.options.dense)?;
+            app.status.clear();
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn run(
+    directory: PathBuf,
+    config: Arc<ReaperConfig>,
+    algo: RankingAlgo,
+    options: LiveSearchOptions,
+) -> Result<()> {
+    let prepared = prepare_ranked_search(&directory, Arc::clone(&config), &algo, &options, true)?;
+    let fielded = Arc::new(AtomicBool::new(prepared.fielded));
+    spawn_watcher(
+        directory.clone(),
+        prepared.engine.clone(),
+        build_transformer(Arc::clone(&config)),
+        Arc::clone(&config),
+        Arc::clone(&fielded),
+        options.index_dir.clone(),
+    );
+
+    let searcher = Searcher {
+        directory,
+        config,
+        engine: prepared.engine,
+        options,
+        fielded,
+    };
+    let mut app = App::new(algo);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, &searcher);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, searcher: &Searcher) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.render(frame))?;
+
+        let timeout = match app.pending {
+            Some(changed) => DEBOUNCE.saturating_sub(changed.elapsed()),
+            None => REFRESH,
+        };
+        if event::poll(timeout).context("failed to poll terminal events")?
+            && let Event::Key(key) = event::read().context("failed to read terminal event")?
+            && key.kind == KeyEventKind::Press
+        {
+            match app.on_key(key, Instant::now()) {
+                Action::None => {}
+                Action::Quit => return Ok(()),
+                Action::SwitchAlgorithm => searcher.prepare(terminal, app)?,
+                Action::Open => open_selected(terminal, app)?,
+            }
+        }
+
+        let due = match app.pending {
+            Some(changed) => changed.elapsed() >= DEBOUNCE,
+            None => {
+                app.mode == Mode::Ranked
+                    && app
+                        .last_search
+                        .is_some_and(|searched| searched.elapsed() >= REFRESH)
+            }
+        };
+        if due {
+            app.pending = None;
+            searcher.search(app)?;
+        }
+    }
+}
+
+/// Hands the terminal to `$EDITOR` at the preview's focus line, then takes
+/// it back.
+fn open_selected(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    let Some(hit) = app.selected_hit() else {
+        return Ok(());
+    };
+    let editor = std::env::var("EDITOR")
+        .or_else(|_| std::env::var("VISUAL"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let Some(mut command) = editor_command(&editor, &hit.file, app.scroll + PREVIEW_CONTEXT + 1)
+    else {
+        app.status = "EDITOR is empty".to_string();
+        return Ok(());
+    };
+
+    ratatui::restore();
+    let status = command.status();
+    *terminal = ratatui::init();
+    terminal.clear()?;
+
+    app.status = match status {
+        Ok(status) if status.success() => String::new(),
+        Ok(status) => format!("{editor} exited with {status}"),
+        Err(error) => format!("failed to run {editor}: {error}"),
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        time::Instant,
+    };
+
+    use ratatui::{
+        Terminal,
+        backend::TestBackend,
+        crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    };
+    use repo_reaper_core::ranking::RankingAlgo;
+
+    use super::{Action, App, Hit, Mode, PREVIEW_CONTEXT, preview_lines};
+
+    fn write_temp_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn control(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn app() -> App {
+        App::new("bm25".parse::<RankingAlgo>().unwrap())
+    }
+
+    fn hit(path: PathBuf, line: Option<usize>, span: Option<std::ops::Range<usize>>) -> Hit {
+        Hit {
+            file: path.clone(),
+            path,
+            label: "1.000".to_string(),
+            line,
+            spans: span.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn typing_debounces_and_keys_switch_mode_and_algorithm() {
+        let mut app = app();
+        let now = Instant::now();
+
+        for c in "pool".chars() {
+            assert_eq!(app.on_key(key(KeyCode::Char(c)), now), Action::None);
+        }
+        app.on_key(key(KeyCode::Backspace), now);
+        assert_eq!(app.query, "poo");
+        assert_eq!(app.pending, Some(now));
+
+        app.on_key(key(KeyCode::Tab), now);
+        assert_eq!(app.mode, Mode::Regex);
+        app.on_key(key(KeyCode::Tab), now);
+        assert_eq!(app.mode, Mode::Ranked);
+
+        assert_eq!(app.on_key(control('r'), now), Action::SwitchAlgorithm);
+        assert_eq!(app.algo.name(), "ql");
+        app.on_key(control('u'), now);
+        assert!(app.query.is_empty());
+
+        assert_eq!(app.on_key(key(KeyCode::Enter), now), Action::None);
+        assert_eq!(app.on_key(key(KeyCode::Esc), now), Action::Quit);
+        assert_eq!(app.on_key(control('c'), now), Action::Quit);
+    }
+
+    #[test]
+    fn preview_scrolls_to_the_best_passage_and_follows_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let numbered = (1..=30).map(|n| format!("line {n}\n")).collect::<String>();
+        let first = write_temp_file(dir.path(), "first.rs", &numbered);
+        let second = write_temp_file(dir.path(), "second.rs", "a\nb\nneedle\n");
+        let mut app = app();
+
+        app.set_hits(vec![
+            hit(first.clone(), Some(20), None),
+            hit(second.clone(), None, Some(4..10)),
+        ]);
+        assert_eq!(app.scroll, 16);
+        assert_eq!(app.preview.as_ref().unwrap().0, first);
+
+        app.on_key(key(KeyCode::Down), Instant::now());
+        assert_eq!(app.selected, 1);
+        assert_eq!(app.scroll, 0);
+        app.on_key(key(KeyCode::Down), Instant::now());
+        assert_eq!(app.selected, 1);
+
+        // A refresh that reorders results keeps the selected file.
+        app.set_hits(vec![
+            hit(second.clone(), None, Some(4..10)),
+            hit(first, Some(20), None),
+        ]);
+        assert_eq!(app.selected, 0);
+        assert_eq!(app.preview.as_ref().unwrap().0, second);
+    }
+
+    #[test]
+    fn preview_survives_spans_inside_a_character() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = format!("{}é needle\n", "line\n".repeat(30));
+        let path = write_temp_file(dir.path(), "accent.rs", &content);
+        let mut app = app();
+
+        app.set_hits(vec![hit(path, None, Some(151..157))]);
+
+        assert_eq!(app.scroll, 30 - PREVIEW_CONTEXT);
+    }
+
+    #[test]
+    fn preview_lines_number_and_highlight_the_window() {
+        let content = "fn open() {}\n\tlet pool = 1;\nretry(pool)\n";
+        let spans = content
+            .match_indices("pool")
+            .map(|(start, text)| start..start + text.len())
+            .collect::<Vec<_>>();
+        let lines = preview_lines(content, &spans, 1, 1);
+
+        assert_eq!(lines.len(), 1);
+        let text = lines[0]
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(text, vec!["    2 ", "    let ", "pool", " = 1;"]);
+        assert_ne!(lines[0].spans[2].style, lines[0].spans[1].style);
+    }
+
+    #[test]
+    fn renders_query_results_and_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_temp_file(dir.path(), "pool.rs", "fn retry_pool() {}\n");
+        let mut app = app();
+        app.query = "retry pool".to_string();
+        app.set_hits(vec![hit(path, Some(1), Some(3..8))]);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+
+        assert!(screen.contains("ranked · bm25"));
+        assert!(screen.contains("retry pool"));
+        assert!(screen.contains("1 results"));
+        assert!(screen.contains("pool.rs"));
+        assert!(screen.contains("fn retry_pool() {}"));
+        assert!(screen.contains("Ctrl-R algorithm"));
+    }
+}
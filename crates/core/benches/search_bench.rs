@@ -6,7 +6,8 @@ use repo_reaper_core::{
     index::InvertedIndex,
     query::AnalyzedQuery,
     ranking::{
-        BM25FHyperParams, BM25HyperParams, ProximityConfig, QueryLikelihoodParams, RankingAlgo,
+        BM25FHyperParams, BM25HyperParams, BM25Variant, DfrModel, ProximityConfig,
+        QueryLikelihoodParams, RankingAlgo,
     },
     regex_search::{RegexSearchEngine, TrigramIndex},
     tokenizer::n_gram_transform,
@@ -27,6 +28,8 @@ fn bench_config() -> Config {
             .iter()
             .map(|word| word.to_string())
             .collect::<HashSet<_>>(),
+        analyzers: Default::default(),
+        ..Default::default()
     }
 }
 
@@ -49,6 +52,26 @@ fn query_likelihood() -> RankingAlgo {
     RankingAlgo::QueryLikelihood(QueryLikelihoodParams::dirichlet_defaults())
 }
 
+fn bm25_plus() -> RankingAlgo {
+    RankingAlgo::BM25Variant(BM25HyperParams::default(), BM25Variant::plus_defaults())
+}
+
+fn bm25l() -> RankingAlgo {
+    RankingAlgo::BM25Variant(BM25HyperParams::default(), BM25Variant::l_defaults())
+}
+
+fn pl2() -> RankingAlgo {
+    RankingAlgo::DivergenceFromRandomness(DfrModel::pl2_defaults())
+}
+
+fn inl2() -> RankingAlgo {
+    RankingAlgo::DivergenceFromRandomness(DfrModel::inl2_defaults())
+}
+
+fn dph() -> RankingAlgo {
+    RankingAlgo::DivergenceFromRandomness(DfrModel::DPH)
+}
+
 fn deterministic_corpus(doc_count: usize, tokens_per_doc: usize, seed: u64) -> Vec<String> {
     let mut rng = Lcg::new(seed);
     let vocabulary = [
@@ -189,6 +212,17 @@ fn bench_ranked_search(c: &mut Criterion) {
     group.bench_function("query_likelihood", |b| {
         b.iter(|| query_likelihood.rank(black_box(&index), black_box(&query), black_box(10)));
     });
+    for (name, algo) in [
+        ("bm25_plus", bm25_plus()),
+        ("bm25l", bm25l()),
+        ("pl2", pl2()),
+        ("inl2", inl2()),
+        ("dph", dph()),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| algo.rank(black_box(&index), black_box(&query), black_box(10)));
+        });
+    }
     group.bench_function("cosine", |b| {
         b.iter(|| {
             RankingAlgo::CosineSimilarity.rank(black_box(&index), black_box(&query), black_box(10))
@@ -251,6 +285,23 @@ fn bench_search_comparison(c: &mut Criterion) {
             )
         });
     });
+    for (name, algo) in [
+        ("bm25_plus", bm25_plus()),
+        ("bm25l", bm25l()),
+        ("pl2", pl2()),
+        ("inl2", inl2()),
+        ("dph", dph()),
+    ] {
+        group.bench_function(format!("traditional_ir/{name}"), |b| {
+            b.iter(|| {
+                algo.rank(
+                    black_box(&ranked_index),
+                    black_box(&ranked_query),
+                    black_box(10),
+                )
+            });
+        });
+    }
     group.bench_function("traditional_ir/cosine", |b| {
         b.iter(|| {
             RankingAlgo::CosineSimilarity.rank(
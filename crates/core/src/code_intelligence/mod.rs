@@ -11,6 +11,9 @@ pub struct DocumentFeature {
     pub field: DocumentField,
     pub text: String,
     pub span: Option<ByteSpan>,
+    /// For symbols, the whole declaration or section the name belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<ByteSpan>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -95,6 +98,7 @@ mod tests {
                 field: DocumentField::Symbol,
                 text: "score_bm25".to_string(),
                 span: Some(ByteSpan { start: 3, end: 13 }),
+                scope: Some(ByteSpan { start: 0, end: 20 }),
             }],
         );
 
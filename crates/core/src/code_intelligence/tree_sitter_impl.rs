@@ -148,6 +148,15 @@ fn query_fields(
             if text.is_empty() {
                 continue;
             }
+            // Symbol captures are names; their parent is the declaration,
+            // or for a Markdown heading the section it opens.
+            let scope = (field == DocumentField::Symbol)
+                .then(|| capture.node.parent())
+                .flatten()
+                .map(|parent| ByteSpan {
+                    start: parent.start_byte(),
+                    end: parent.end_byte(),
+                });
             fields.push(DocumentFeature {
                 field,
                 text,
@@ -155,6 +164,7 @@ fn query_fields(
                     start: capture.node.start_byte(),
                     end: capture.node.end_byte(),
                 }),
+                scope,
             });
         }
     }
@@ -255,6 +265,7 @@ fn markdown_frontmatter(content: &str) -> Vec<DocumentFeature> {
             start,
             end: end_byte,
         }),
+        scope: None,
     }]
 }
 
@@ -324,6 +335,22 @@ impl Scorer for SearchHit {
         );
         assert!(strings.iter().any(|text| text.contains("BM25 score")));
         assert!(fields.iter().all(|field| field.span.is_some()));
+
+        let scope = fields
+            .iter()
+            .find(|feature| feature.field == DocumentField::Symbol && feature.text == "Scorer")
+            .and_then(|feature| feature.scope)
+            .unwrap();
+        assert_eq!(
+            &source[scope.start..scope.end],
+            "trait Scorer { fn score(&self); }"
+        );
+        assert!(
+            fields
+                .iter()
+                .filter(|feature| feature.field != DocumentField::Symbol)
+                .all(|feature| feature.scope.is_none())
+        );
     }
 
     #[test]
@@ -1,9 +1,686 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
 
 use rust_stemmers::Stemmer;
 
+use crate::{
+    index::{DenseConfig, DocumentField, QualityPriors},
+    query::{ExpansionTable, QueryIntent},
+    ranking::{
+        DfrModel, DiversityConfig, FusionRanker, FusionRun, FusionSource, ProximityConfig,
+        QueryLikelihoodSmoothing, RankingAlgo,
+    },
+    tokenizer::{AnalyzerConfig, AnalyzerConfigError, Language},
+};
+
+/// Name of the project configuration file looked up in the indexed
+/// directory and the user config directory.
+pub const PROJECT_CONFIG_FILE: &str = "reaper.toml";
+
 pub struct Config {
     pub n_grams: usize,
     pub stemmer: Stemmer,
+    /// Language `stemmer` stems for.
+    pub language: Language,
     pub stop_words: HashSet<String>,
+    pub analyzers: AnalyzerConfig,
+    pub expansions: ExpansionTable,
+}
+
+impl Config {
+    /// Unigram config with `language`'s stemmer and stop words, or `None`
+    /// for languages without a stemmer.
+    pub fn for_language(language: Language) -> Option<Self> {
+        Some(Self {
+            n_grams: 1,
+            stemmer: Stemmer::create(language.stemmer_algorithm()?),
+            language,
+            stop_words: language.resources()?.stop_words.clone(),
+            analyzers: AnalyzerConfig::default(),
+            expansions: ExpansionTable::default(),
+        })
+    }
+
+    /// Builds the analysis config from the `[analyzer]` section.
+    pub fn from_settings(settings: &AnalyzerSettings) -> Result<Self, ProjectConfigError> {
+        let language = settings.language.unwrap_or(Language::English);
+        let mut config =
+            Self::for_language(language).ok_or(ProjectConfigError::Unstemmed(language))?;
+
+        if let Some(n_grams) = settings.n_grams {
+            config.n_grams = n_grams;
+        }
+        config
+            .stop_words
+            .extend(settings.stop_words.iter().cloned());
+        if let Some(path) = &settings.analyzer_config {
+            config.analyzers = AnalyzerConfig::from_json_file(path)?;
+        }
+        config.expansions = config
+            .expansions
+            .with_overrides(settings.expansions.clone());
+        if let Some(weight) = settings.expansion_weight {
+            config.expansions = config.expansions.with_weight(weight);
+        }
+        Ok(config)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::for_language(Language::English).expect("English has a stemmer")
+    }
+}
+
+/// Settings read from `reaper.toml`. Every key is optional; command-line
+/// flags override whatever the file sets.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub analyzer: AnalyzerSettings,
+    pub ranking: RankingSettings,
+    pub index: IndexSettings,
+    pub output: OutputSettings,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyzerSettings {
+    /// Default language for stemming and stop words.
+    #[serde(deserialize_with = "deserialize_language")]
+    pub language: Option<Language>,
+    pub n_grams: Option<usize>,
+    /// Added to the language's stop words.
+    pub stop_words: Vec<String>,
+    /// JSON file declaring token-filter chains per field and file type.
+    pub analyzer_config: Option<PathBuf>,
+    /// Controlled query expansions by term, replacing the built-in entry
+    /// for each term listed. An empty list disables a built-in expansion.
+    pub expansions: BTreeMap<String, Vec<String>>,
+    pub expansion_weight: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankingSettings {
+    pub algorithm: Option<String>,
+    pub top_n: Option<usize>,
+    pub query_expansion: Option<bool>,
+    pub feedback_expansion: Option<bool>,
+    /// Term-frequency saturation for every BM25 variant.
+    pub k1: Option<f64>,
+    /// Length normalization for every BM25 variant.
+    pub b: Option<f64>,
+    /// BM25F scale for phrase and `NEAR` matches.
+    pub phrase_boost: Option<f64>,
+    /// BM25F weights, replacing the default for each field listed.
+    pub field_weights: HashMap<DocumentField, f64>,
+    /// BM25F per-intent field multipliers, replacing the built-in ones.
+    pub intent_field_weights: HashMap<QueryIntent, HashMap<DocumentField, f64>>,
+    pub proximity: Option<ProximityConfig>,
+    /// Lower bound on the term-frequency component of `bm25+` and `bm25l`.
+    pub delta: Option<f64>,
+    /// Length normalisation strength for `pl2` and `inl2`.
+    pub c: Option<f64>,
+    /// Dirichlet prior for `ql`.
+    pub mu: Option<f64>,
+    /// Jelinek-Mercer collection weight for `ql-jm`.
+    pub lambda: Option<f64>,
+    pub quality_priors: QualityPriors,
+    pub fusion: Option<FusionSettings>,
+    /// Diversify results and collapse near-duplicates by default.
+    pub diversify: Option<bool>,
+    pub diversity: DiversityConfig,
+}
+
+/// How `fusion` and `fusion-combsum` merge their runs.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FusionSettings {
+    pub rrf_k: Option<f64>,
+    /// Results taken from each run before fusing.
+    pub depth: Option<usize>,
+    /// Replaces the default runs when non-empty.
+    pub runs: Vec<FusionRunSettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FusionRunSettings {
+    /// A ranking algorithm, or `literal` for verbatim quoted phrases.
+    pub algorithm: String,
+    #[serde(default = "default_run_weight")]
+    pub weight: f64,
+    /// Weights replacing `weight` for queries of these intents.
+    #[serde(default)]
+    pub intents: HashMap<QueryIntent, f64>,
+}
+
+fn default_run_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexSettings {
+    /// Only search paths matching one of these globs, relative to the
+    /// searched directory.
+    pub include: Vec<String>,
+    /// Never search paths matching these globs.
+    pub exclude: Vec<String>,
+    /// Directory for snapshots and update event logs.
+    pub dir: Option<PathBuf>,
+    pub respect_gitignore: Option<bool>,
+    /// Add recency, churn and co-change signals from the Git history.
+    pub history: Option<bool>,
+    /// Also index each symbol and Markdown section as its own document.
+    pub symbols: Option<bool>,
+    /// Latent semantic model built for `dense` ranking.
+    pub dense: DenseConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    /// One-shot output format: human, json, jsonl or vimgrep.
+    pub format: Option<String>,
+    pub explain: Option<bool>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProjectConfigError {
+    #[error("failed to read project config {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse project config {path}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error(transparent)]
+    Analyzer(#[from] AnalyzerConfigError),
+    #[error("{0:?} has no stemmer; choose a stemmed default language")]
+    Unstemmed(Language),
+    #[error("invalid fusion run: {0}")]
+    FusionRun(String),
+}
+
+impl ProjectConfig {
+    /// Reads `path`. Relative paths inside the file are resolved against
+    /// the directory holding it.
+    pub fn load(path: &Path) -> Result<Self, ProjectConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ProjectConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut config: Self =
+            toml::from_str(&content).map_err(|source| ProjectConfigError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for relative in [&mut config.analyzer.analyzer_config, &mut config.index.dir]
+            .into_iter()
+            .flatten()
+        {
+            *relative = base.join(&*relative);
+        }
+        let runs = config
+            .ranking
+            .fusion
+            .iter_mut()
+            .flat_map(|fusion| &mut fusion.runs);
+        for algorithm in config
+            .ranking
+            .algorithm
+            .iter_mut()
+            .chain(runs.map(|run| &mut run.algorithm))
+        {
+            if let Some(model) = algorithm.strip_prefix("learned:") {
+                *algorithm = format!("learned:{}", base.join(model).display());
+            }
+        }
+        for run in config.ranking.fusion.iter().flat_map(|fusion| &fusion.runs) {
+            run.algorithm
+                .parse::<FusionSource>()
+                .map_err(ProjectConfigError::FusionRun)?;
+        }
+        Ok(config)
+    }
+
+    /// Loads the first `reaper.toml` found walking up from `root` to the
+    /// repository root (the nearest directory holding `.git`), falling back
+    /// to the user config, and returns its path with it. Outside a
+    /// repository only `root` itself is searched.
+    pub fn discover(root: &Path) -> Result<Option<(PathBuf, Self)>, ProjectConfigError> {
+        let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let repository = root.ancestors().position(|dir| dir.join(".git").exists());
+        let candidates = root
+            .ancestors()
+            .take(repository.map_or(1, |depth| depth + 1))
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .chain(user_config_path());
+        for path in candidates {
+            if path.is_file() {
+                let config = Self::load(&path)?;
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/repo_reaper/reaper.toml`, falling back to
+/// `~/.config/repo_reaper/reaper.toml`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("repo_reaper").join(PROJECT_CONFIG_FILE))
+}
+
+impl RankingSettings {
+    /// `algo` with the parameters this section sets; parameters that do
+    /// not apply to `algo` are ignored.
+    pub fn apply(&self, algo: RankingAlgo) -> RankingAlgo {
+        match algo {
+            RankingAlgo::BM25(mut params) => {
+                params.k1 = self.k1.unwrap_or(params.k1);
+                params.b = self.b.unwrap_or(params.b);
+                RankingAlgo::BM25(params)
+            }
+            RankingAlgo::BM25F(mut params) => {
+                params.k1 = self.k1.unwrap_or(params.k1);
+                params.b = self.b.unwrap_or(params.b);
+                params.phrase_boost = self.phrase_boost.unwrap_or(params.phrase_boost);
+                params.field_weights.extend(&self.field_weights);
+                for (intent, weights) in &self.intent_field_weights {
+                    params
+                        .intent_field_weights
+                        .entry(*intent)
+                        .or_default()
+                        .extend(weights);
+                }
+                RankingAlgo::BM25F(params)
+            }
+            RankingAlgo::BM25Proximity(mut params, proximity) => {
+                params.k1 = self.k1.unwrap_or(params.k1);
+                params.b = self.b.unwrap_or(params.b);
+                RankingAlgo::BM25Proximity(params, self.proximity.clone().unwrap_or(proximity))
+            }
+            RankingAlgo::BM25Variant(mut params, variant) => {
+                params.k1 = self.k1.unwrap_or(params.k1);
+                params.b = self.b.unwrap_or(params.b);
+                RankingAlgo::BM25Variant(
+                    params,
+                    variant.with_delta(self.delta.unwrap_or(variant.delta())),
+                )
+            }
+            RankingAlgo::DivergenceFromRandomness(model) => {
+                RankingAlgo::DivergenceFromRandomness(match model {
+                    DfrModel::PL2 { c } => DfrModel::PL2 {
+                        c: self.c.unwrap_or(c),
+                    },
+                    DfrModel::InL2 { c } => DfrModel::InL2 {
+                        c: self.c.unwrap_or(c),
+                    },
+                    DfrModel::DPH => DfrModel::DPH,
+                })
+            }
+            RankingAlgo::QueryLikelihood(mut params) => {
+                params.smoothing = match params.smoothing {
+                    QueryLikelihoodSmoothing::Dirichlet { mu } => {
+                        QueryLikelihoodSmoothing::Dirichlet {
+                            mu: self.mu.unwrap_or(mu),
+                        }
+                    }
+                    QueryLikelihoodSmoothing::JelinekMercer { lambda } => {
+                        QueryLikelihoodSmoothing::JelinekMercer {
+                            lambda: self.lambda.unwrap_or(lambda),
+                        }
+                    }
+                };
+                RankingAlgo::QueryLikelihood(params)
+            }
+            RankingAlgo::Fusion(mut ranker) => {
+                if let Some(fusion) = &self.fusion {
+                    fusion.apply(&mut ranker);
+                }
+                ranker.runs = ranker
+                    .runs
+                    .into_iter()
+                    .map(|mut run| {
+                        if let FusionSource::Ranked(algo) = run.source {
+                            run.source = FusionSource::Ranked(self.apply(algo));
+                        }
+                        run
+                    })
+                    .collect();
+                RankingAlgo::Fusion(ranker)
+            }
+            RankingAlgo::Learned(ranker) => {
+                let first_stage = self.apply(ranker.first_stage().clone());
+                RankingAlgo::Learned(ranker.with_first_stage(first_stage))
+            }
+            algo @ (RankingAlgo::CosineSimilarity | RankingAlgo::TFIDF | RankingAlgo::Dense(_)) => {
+                algo
+            }
+        }
+    }
+}
+
+impl FusionSettings {
+    fn apply(&self, ranker: &mut FusionRanker) {
+        ranker.rrf_k = self.rrf_k.unwrap_or(ranker.rrf_k);
+        ranker.depth = self.depth.unwrap_or(ranker.depth);
+        if !self.runs.is_empty() {
+            ranker.runs = self
+                .runs
+                .iter()
+                .filter_map(|run| {
+                    let mut fused =
+                        FusionRun::new(run.algorithm.parse().ok()?).with_weight(run.weight);
+                    fused.intent_weights = run.intents.clone();
+                    Some(fused)
+                })
+                .collect();
+        }
+    }
+}
+
+impl IndexSettings {
+    /// `include` and `exclude` as search filter globs, exclusions prefixed
+    /// with `!`.
+    pub fn globs(&self) -> Vec<String> {
+        self.include
+            .iter()
+            .cloned()
+            .chain(self.exclude.iter().map(|glob| format!("!{glob}")))
+            .collect()
+    }
+}
+
+fn deserialize_language<'de, D>(deserializer: D) -> Result<Option<Language>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let name = <Option<String> as serde::Deserialize>::deserialize(deserializer)?;
+    name.map(|name| name.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{AnalyzerSettings, Config, PROJECT_CONFIG_FILE, ProjectConfig};
+    use crate::{
+        index::DocumentField,
+        query::QueryIntent,
+        ranking::{
+            BM25Variant, DfrModel, FusionSource, QueryLikelihoodSmoothing, RankingAlgo,
+            SimilarityMeasure,
+        },
+        test_support::write_temp_file,
+        tokenizer::Language,
+    };
+
+    const PROJECT: &str = r#"
+        [analyzer]
+        language = "german"
+        n_grams = 2
+        stop_words = ["foo"]
+        expansion_weight = 0.5
+
+        [analyzer.expansions]
+        k8s = ["kubernetes"]
+        db = []
+
+        [ranking]
+        algorithm = "bm25f"
+        top_n = 25
+        k1 = 1.5
+        mu = 900.0
+        delta = 0.75
+        c = 2.0
+
+        [ranking.field_weights]
+        Symbol = 6.0
+
+        [ranking.proximity]
+        window = 4
+
+        [ranking.quality_priors]
+        vendor = -1.0
+
+        [ranking.diversity]
+        lambda = 0.5
+        similarity = "minhash"
+
+        [index]
+        include = ["src/**"]
+        exclude = ["**/fixtures/**"]
+        dir = ".reaper"
+        respect_gitignore = false
+        history = true
+        symbols = true
+
+        [index.dense]
+        dimensions = 32
+
+        [output]
+        format = "json"
+        explain = true
+    "#;
+
+    #[test]
+    fn loads_every_section_and_resolves_paths_next_to_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), PROJECT_CONFIG_FILE, PROJECT);
+
+        let config = ProjectConfig::load(&dir.path().join(PROJECT_CONFIG_FILE)).unwrap();
+
+        assert_eq!(config.analyzer.language, Some(Language::German));
+        assert_eq!(config.analyzer.n_grams, Some(2));
+        assert_eq!(config.ranking.algorithm.as_deref(), Some("bm25f"));
+        assert_eq!(config.ranking.top_n, Some(25));
+        assert_eq!(config.ranking.quality_priors.vendor, -1.0);
+        assert_eq!(config.ranking.quality_priors.generated, -0.55);
+        assert_eq!(config.ranking.proximity.as_ref().unwrap().window, 4);
+        assert_eq!(config.ranking.diversity.lambda, 0.5);
+        assert_eq!(
+            config.ranking.diversity.similarity,
+            SimilarityMeasure::Minhash
+        );
+        assert_eq!(config.ranking.diversity.duplicate_threshold, 0.9);
+        assert_eq!(config.index.dir, Some(dir.path().join(".reaper")));
+        assert_eq!(config.index.globs(), vec!["src/**", "!**/fixtures/**"]);
+        assert_eq!(config.index.respect_gitignore, Some(false));
+        assert_eq!(config.index.dense.dimensions, 32);
+        assert_eq!(config.index.dense.max_terms, 20_000);
+        assert_eq!(config.index.history, Some(true));
+        assert_eq!(config.index.symbols, Some(true));
+        assert_eq!(config.output.format.as_deref(), Some("json"));
+        assert_eq!(config.output.explain, Some(true));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), PROJECT_CONFIG_FILE, "[ranking]\nkl = 1.2\n");
+
+        assert!(ProjectConfig::load(&dir.path().join(PROJECT_CONFIG_FILE)).is_err());
+    }
+
+    #[test]
+    fn discovers_the_project_file_first() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), PROJECT_CONFIG_FILE, "[ranking]\ntop_n = 3\n");
+
+        let (path, config) = ProjectConfig::discover(dir.path()).unwrap().unwrap();
+
+        assert_eq!(path, dir.path().join(PROJECT_CONFIG_FILE));
+        assert_eq!(config.ranking.top_n, Some(3));
+    }
+
+    #[test]
+    fn discovers_the_project_file_up_to_the_repository_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("repo/src/bin");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(dir.path().join("repo/.git")).unwrap();
+        write_temp_file(dir.path(), PROJECT_CONFIG_FILE, "[ranking]\ntop_n = 1\n");
+        write_temp_file(
+            &dir.path().join("repo"),
+            PROJECT_CONFIG_FILE,
+            "[ranking]\ntop_n = 2\n",
+        );
+
+        let (path, config) = ProjectConfig::discover(&nested).unwrap().unwrap();
+
+        assert_eq!(path, dir.path().join("repo").join(PROJECT_CONFIG_FILE));
+        assert_eq!(config.ranking.top_n, Some(2));
+    }
+
+    #[test]
+    fn resolves_learned_models_next_to_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            PROJECT_CONFIG_FILE,
+            "[ranking]\nalgorithm = \"learned:models/ltr.json\"\n",
+        );
+
+        let config = ProjectConfig::load(&dir.path().join(PROJECT_CONFIG_FILE)).unwrap();
+
+        assert_eq!(
+            config.ranking.algorithm,
+            Some(format!(
+                "learned:{}",
+                dir.path().join("models/ltr.json").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn analyzer_settings_build_the_config() {
+        let project: ProjectConfig = toml::from_str(PROJECT).unwrap();
+        let config = Config::from_settings(&project.analyzer).unwrap();
+
+        assert_eq!(config.language, Language::German);
+        assert_eq!(config.n_grams, 2);
+        assert!(config.stop_words.contains("foo"));
+        assert!(config.stop_words.contains("und"));
+        assert_eq!(config.expansions.weight, 0.5);
+        assert_eq!(config.expansions.expansions_for("k8s"), ["kubernetes"]);
+        assert!(config.expansions.expansions_for("db").is_empty());
+        assert_eq!(config.expansions.expansions_for("repo"), ["repository"]);
+
+        assert!(
+            Config::from_settings(&AnalyzerSettings {
+                language: Some(Language::Japanese),
+                ..AnalyzerSettings::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn ranking_settings_override_algorithm_parameters() {
+        let project: ProjectConfig = toml::from_str(PROJECT).unwrap();
+
+        let RankingAlgo::BM25F(params) = project.ranking.apply("bm25f".parse().unwrap()) else {
+            panic!("expected bm25f");
+        };
+        assert_eq!(params.k1, 1.5);
+        assert_eq!(params.field_weight(DocumentField::Symbol), 6.0);
+        assert_eq!(params.field_weight(DocumentField::FileName), 8.0);
+
+        let RankingAlgo::BM25Proximity(params, proximity) =
+            project.ranking.apply("bm25-proximity".parse().unwrap())
+        else {
+            panic!("expected bm25-proximity");
+        };
+        assert_eq!(params.k1, 1.5);
+        assert_eq!(proximity.window, 4);
+        assert_eq!(proximity.phrase_boost, 2.0);
+
+        let RankingAlgo::QueryLikelihood(params) = project.ranking.apply("ql".parse().unwrap())
+        else {
+            panic!("expected ql");
+        };
+        assert_eq!(
+            params.smoothing,
+            QueryLikelihoodSmoothing::Dirichlet { mu: 900.0 }
+        );
+
+        let RankingAlgo::BM25Variant(params, variant) =
+            project.ranking.apply("bm25l".parse().unwrap())
+        else {
+            panic!("expected bm25l");
+        };
+        assert_eq!(params.k1, 1.5);
+        assert_eq!(variant, BM25Variant::L { delta: 0.75 });
+
+        let RankingAlgo::DivergenceFromRandomness(model) =
+            project.ranking.apply("pl2".parse().unwrap())
+        else {
+            panic!("expected pl2");
+        };
+        assert_eq!(model, DfrModel::PL2 { c: 2.0 });
+    }
+
+    #[test]
+    fn fusion_settings_replace_runs_and_reject_unknown_algorithms() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            PROJECT_CONFIG_FILE,
+            r#"
+                [ranking]
+                k1 = 1.5
+
+                [ranking.fusion]
+                rrf_k = 20.0
+
+                [[ranking.fusion.runs]]
+                algorithm = "bm25"
+                intents = { identifier = 2.5 }
+
+                [[ranking.fusion.runs]]
+                algorithm = "literal"
+                weight = 0.5
+            "#,
+        );
+        let project = ProjectConfig::load(&dir.path().join(PROJECT_CONFIG_FILE)).unwrap();
+
+        let RankingAlgo::Fusion(fusion) = project.ranking.apply("fusion".parse().unwrap()) else {
+            panic!("expected fusion");
+        };
+        assert_eq!(fusion.rrf_k, 20.0);
+        assert_eq!(fusion.runs.len(), 2);
+        let FusionSource::Ranked(RankingAlgo::BM25(params)) = &fusion.runs[0].source else {
+            panic!("expected a bm25 run");
+        };
+        assert_eq!(params.k1, 1.5);
+        assert_eq!(fusion.runs[0].weight_for(QueryIntent::Identifier), 2.5);
+        assert_eq!(fusion.runs[1].source.name(), "literal");
+        assert_eq!(fusion.runs[1].weight_for(QueryIntent::Identifier), 0.5);
+
+        write_temp_file(
+            dir.path(),
+            PROJECT_CONFIG_FILE,
+            "[[ranking.fusion.runs]]\nalgorithm = \"bm99\"\n",
+        );
+        assert!(ProjectConfig::load(&dir.path().join(PROJECT_CONFIG_FILE)).is_err());
+    }
 }
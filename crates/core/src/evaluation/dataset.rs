@@ -152,6 +152,19 @@ pub enum QueryShape {
     TestFinding,
 }
 
+/// The labelled queries of an evaluation data file, or of a file holding
+/// nothing but queries and their shapes, for training the intent model.
+#[derive(serde::Deserialize, Debug)]
+pub struct LabelledQueries {
+    pub examples: Vec<LabelledQuery>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LabelledQuery {
+    pub query: String,
+    pub query_shape: QueryShape,
+}
+
 #[derive(serde::Deserialize, Debug)]
 pub struct RawResultData {
     pub path: String,
@@ -202,22 +215,8 @@ pub enum Relevance {
 
 #[cfg(test)]
 mod tests {
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
-
     use super::{EvaluationCorpus, EvaluationData, QueryShape, RawEvaluationData};
-    use crate::config::Config;
-
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect(),
-        }
-    }
+    use crate::test_support::test_config;
 
     #[test]
     fn parse_accepts_local_root_with_query_shape_and_evidence() {
@@ -1,10 +1,12 @@
+mod context_pack;
 mod file_retrieval;
 mod groundedness;
 mod report;
 mod token_efficiency;
 mod types;
-mod util;
+pub(crate) mod util;
 
+pub use context_pack::{ContextPackEvaluation, aggregate_context_pack, evaluate_context_pack};
 pub use file_retrieval::{average_evaluations, evaluate_query_at_k};
 pub use groundedness::{
     GroundednessEvaluation, aggregate_groundedness, evaluate_groundedness_at_k,
@@ -0,0 +1,108 @@
+use std::{fmt, path::Path};
+
+use super::{GroundednessResult, util::ratio};
+use crate::{
+    evaluation::dataset::EvidenceSpan,
+    pack::{ContextPack, PackEntry},
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+pub struct ContextPackEvaluation {
+    pub budget_tokens: usize,
+    pub packed_tokens: usize,
+    pub entries: usize,
+    pub grounded_entries: usize,
+    pub expected_evidence: usize,
+    pub packed_evidence: usize,
+    pub evidence_recall: f64,
+    pub entry_precision: f64,
+    pub budget_utilization: f64,
+}
+
+impl ContextPackEvaluation {
+    fn from_counts(
+        budget_tokens: usize,
+        packed_tokens: usize,
+        entries: usize,
+        grounded_entries: usize,
+        expected_evidence: usize,
+        packed_evidence: usize,
+    ) -> Self {
+        ContextPackEvaluation {
+            budget_tokens,
+            packed_tokens,
+            entries,
+            grounded_entries,
+            expected_evidence,
+            packed_evidence,
+            evidence_recall: ratio(packed_evidence, expected_evidence),
+            entry_precision: ratio(grounded_entries, entries),
+            budget_utilization: ratio(packed_tokens, budget_tokens),
+        }
+    }
+}
+
+/// Scores a pack against the gold evidence of relevant results: how much of
+/// the evidence the pack's line ranges cover, and how many entries cover
+/// any of it.
+pub fn evaluate_context_pack(
+    pack: &ContextPack,
+    results: &[GroundednessResult],
+) -> ContextPackEvaluation {
+    let expected = results
+        .iter()
+        .filter(|result| result.relevant)
+        .flat_map(|result| result.evidence.iter().map(move |span| (&result.path, span)))
+        .collect::<Vec<_>>();
+    let packed_evidence = expected
+        .iter()
+        .filter(|(path, span)| pack.entries.iter().any(|entry| covers(entry, path, span)))
+        .count();
+    let grounded_entries = pack
+        .entries
+        .iter()
+        .filter(|entry| {
+            expected
+                .iter()
+                .any(|(path, span)| covers(entry, path, span))
+        })
+        .count();
+
+    ContextPackEvaluation::from_counts(
+        pack.budget_tokens,
+        pack.estimated_tokens,
+        pack.entries.len(),
+        grounded_entries,
+        expected.len(),
+        packed_evidence,
+    )
+}
+
+fn covers(entry: &PackEntry, path: &Path, span: &EvidenceSpan) -> bool {
+    entry.path == path && entry.start_line <= span.end_line && span.start_line <= entry.end_line
+}
+
+pub fn aggregate_context_pack(evaluations: &[ContextPackEvaluation]) -> ContextPackEvaluation {
+    let total = |count: fn(&ContextPackEvaluation) -> usize| evaluations.iter().map(count).sum();
+
+    ContextPackEvaluation::from_counts(
+        total(|evaluation| evaluation.budget_tokens),
+        total(|evaluation| evaluation.packed_tokens),
+        total(|evaluation| evaluation.entries),
+        total(|evaluation| evaluation.grounded_entries),
+        total(|evaluation| evaluation.expected_evidence),
+        total(|evaluation| evaluation.packed_evidence),
+    )
+}
+
+impl fmt::Display for ContextPackEvaluation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pack evidence recall: {evidence_recall:.4}\npack entry precision: {entry_precision:.4}\npack budget utilization: {budget_utilization:.4}",
+            evidence_recall = self.evidence_recall,
+            entry_precision = self.entry_precision,
+            budget_utilization = self.budget_utilization,
+        )
+    }
+}
@@ -70,7 +70,8 @@ pub fn evaluate_groundedness_at_k(
             results.iter().any(|result| {
                 result.path == doc.doc_path
                     && result.evidence.iter().any(|span| {
-                        expected_evidence.contains(&(result.path.clone(), span.clone()))
+                        cites(doc, span)
+                            && expected_evidence.contains(&(result.path.clone(), span.clone()))
                     })
             })
         })
@@ -118,10 +119,11 @@ fn cited_evidence(
             results
                 .iter()
                 .filter(move |result| result.path == doc.doc_path)
-                .flat_map(|result| {
+                .flat_map(move |result| {
                     result
                         .evidence
                         .iter()
+                        .filter(move |span| cites(doc, span))
                         .cloned()
                         .map(|span| (result.path.clone(), span))
                 })
@@ -129,6 +131,16 @@ fn cited_evidence(
         .collect()
 }
 
+/// A result with passages cites only the evidence its passages overlap; a
+/// whole-file result cites all of the file's evidence.
+fn cites(doc: &Score, span: &EvidenceSpan) -> bool {
+    doc.passages.is_empty()
+        || doc
+            .passages
+            .iter()
+            .any(|passage| passage.overlaps_lines(span.start_line, span.end_line))
+}
+
 pub fn aggregate_groundedness(evaluations: &[GroundednessEvaluation]) -> GroundednessEvaluation {
     if evaluations.is_empty() {
         return GroundednessEvaluation::default();
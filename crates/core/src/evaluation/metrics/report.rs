@@ -1,13 +1,19 @@
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use super::{
-    Evaluation, EvaluationReport, EvidenceReport, EvidenceReportStatus, FileRetrievalReport,
-    GroundednessEvaluation, QueryEvaluation, TestQuery, TestSet, TokenEfficiencyEvaluation,
-    aggregate_groundedness, aggregate_token_efficiency, average_evaluations,
+    ContextPackEvaluation, Evaluation, EvaluationReport, EvidenceReport, EvidenceReportStatus,
+    FileRetrievalReport, GroundednessEvaluation, QueryEvaluation, TestQuery, TestSet,
+    TokenEfficiencyEvaluation, aggregate_context_pack, aggregate_groundedness,
+    aggregate_token_efficiency, average_evaluations, evaluate_context_pack,
     evaluate_groundedness_at_k, evaluate_query_at_k, evaluate_token_efficiency_at_k,
     file_retrieval::evaluation_slices,
 };
-use crate::{index::InvertedIndex, ranking::RankingAlgo};
+use crate::{
+    index::InvertedIndex,
+    pack::{ContextPack, DEFAULT_BUDGET_TOKENS},
+    query::intent::IntentConfusion,
+    ranking::{DiversityConfig, PassageConfig, RankingAlgo, attach_passages, redundancy_at_k},
+};
 
 impl TestSet {
     pub fn evaluate(&self, inverted_index: &InvertedIndex, top_n: usize) -> Evaluation {
@@ -31,6 +37,7 @@ impl TestSet {
                     query,
                     top_n,
                     self.feedback_expansion,
+                    self.diversity.as_ref(),
                 )
             })
             .collect();
@@ -56,6 +63,11 @@ impl TestSet {
             .iter()
             .map(|query| query.evidence_span_count)
             .sum();
+        let redundancy_at_k = queries
+            .iter()
+            .map(|query| query.redundancy_at_k)
+            .sum::<f64>()
+            / queries.len().max(1) as f64;
         let slice_inputs = queries
             .iter()
             .map(|query| (query.query_shape, query.metrics.clone()))
@@ -66,6 +78,7 @@ impl TestSet {
                 aggregate,
                 slices: evaluation_slices(&slice_inputs),
                 queries,
+                redundancy_at_k,
             },
             evidence: EvidenceReport {
                 status: EvidenceReportStatus::Scored,
@@ -83,7 +96,19 @@ impl TestSet {
                         .map(|query| query.token_efficiency.clone())
                         .collect::<Vec<_>>(),
                 ),
+                context_pack: aggregate_context_pack(
+                    &evaluated_queries
+                        .iter()
+                        .map(|query| query.context_pack.clone())
+                        .collect::<Vec<_>>(),
+                ),
             },
+            intents: IntentConfusion::from_predictions(self.queries.iter().filter_map(|query| {
+                query
+                    .query_shape
+                    .intent()
+                    .map(|labelled| (labelled, query.query.intent()))
+            })),
         }
     }
 }
@@ -92,6 +117,7 @@ struct EvaluatedQuery {
     file_retrieval: QueryEvaluation,
     groundedness: GroundednessEvaluation,
     token_efficiency: TokenEfficiencyEvaluation,
+    context_pack: ContextPackEvaluation,
 }
 
 fn evaluate_query(
@@ -100,13 +126,31 @@ fn evaluate_query(
     query: &TestQuery,
     top_n: usize,
     feedback_expansion: bool,
+    diversity: Option<&DiversityConfig>,
 ) -> EvaluatedQuery {
-    let ranked_docs = if feedback_expansion {
-        ranking_algorithm.rank_with_feedback(inverted_index, &query.query, top_n, top_n.min(3), 6)
-    } else {
-        ranking_algorithm.rank(inverted_index, &query.query, top_n)
+    // Feedback expands the query first; the expanded query's ranking is
+    // what gets diversified.
+    let expanded = feedback_expansion
+        .then(|| {
+            ranking_algorithm.expand_with_feedback(inverted_index, &query.query, top_n.min(3), 6)
+        })
+        .flatten();
+    let ranked_query = expanded.as_ref().unwrap_or(&query.query);
+    let ranked_docs = match diversity {
+        Some(config) => {
+            ranking_algorithm.rank_diversified(inverted_index, ranked_query, top_n, config)
+        }
+        None => ranking_algorithm.rank(inverted_index, ranked_query, top_n),
     }
-    .map(|ranking| ranking.0)
+    .map(|mut ranking| {
+        attach_passages(
+            inverted_index,
+            &query.query,
+            &mut ranking,
+            &PassageConfig::default(),
+        );
+        ranking.0
+    })
     .unwrap_or_default();
 
     let metrics = evaluate_query_at_k(&ranked_docs, &query.relevant_docs, top_n);
@@ -117,6 +161,21 @@ fn evaluate_query(
         inverted_index,
         top_n,
     );
+    let context_pack = evaluate_context_pack(
+        &ContextPack::select(
+            inverted_index,
+            query.query.original_text(),
+            &ranked_docs,
+            DEFAULT_BUDGET_TOKENS,
+        ),
+        &query.groundedness_results,
+    );
+    let redundancy_at_k = redundancy_at_k(
+        inverted_index,
+        &ranked_docs,
+        top_n,
+        &diversity.cloned().unwrap_or_default(),
+    );
     let retrieved_docs = ranked_docs
         .into_iter()
         .map(|score| score.doc_path)
@@ -130,8 +189,10 @@ fn evaluate_query(
             relevant_docs: query.relevant_docs.clone(),
             retrieved_docs,
             token_efficiency: token_efficiency.clone(),
+            redundancy_at_k,
         },
         groundedness,
         token_efficiency,
+        context_pack,
     }
 }
@@ -2,12 +2,15 @@ use std::path::PathBuf;
 
 use super::{
     Evaluation, GroundednessResult, MetricFamily, TestQuery, TestSet, average_evaluations,
-    evaluate_groundedness_at_k, evaluate_query_at_k, evaluate_token_efficiency_at_k,
+    evaluate_context_pack, evaluate_groundedness_at_k, evaluate_query_at_k,
+    evaluate_token_efficiency_at_k,
 };
 use crate::{
     evaluation::dataset::{EvidenceSpan, QueryShape},
+    pack::{ContextPack, PackEntry},
     query::AnalyzedQuery,
-    ranking::{RankingAlgo, Score},
+    ranking::{Passage, RankingAlgo, Score},
+    test_support::{test_config, write_temp_file},
 };
 
 fn scored(paths: &[&str]) -> Vec<Score> {
@@ -17,6 +20,8 @@ fn scored(paths: &[&str]) -> Vec<Score> {
         .map(|(i, p)| Score {
             doc_path: PathBuf::from(p),
             score: (paths.len() - i) as f64,
+            passages: Vec::new(),
+            similar: Vec::new(),
         })
         .collect()
 }
@@ -250,6 +255,37 @@ fn groundedness_recall_drops_when_expected_evidence_is_not_retrieved() {
     assert!((eval.highlight_recall - 0.5).abs() < 1e-10);
 }
 
+#[test]
+fn groundedness_cites_only_evidence_inside_returned_passages() {
+    let mut ranked = scored(&["a.rs"]);
+    ranked[0].passages = vec![Passage {
+        start_line: 5,
+        end_line: 12,
+        score: 1.0,
+    }];
+    let results = vec![grounded_result(
+        "a.rs",
+        true,
+        vec![span(10, "inside"), span(40, "outside")],
+    )];
+
+    let eval = evaluate_groundedness_at_k(&ranked, &results, 1);
+
+    assert_eq!(eval.expected_evidence, 2);
+    assert_eq!(eval.cited_evidence, 1);
+    assert_eq!(eval.matching_evidence, 1);
+    assert!((eval.highlight_recall - 0.5).abs() < 1e-10);
+    assert!((eval.highlight_precision - 1.0).abs() < 1e-10);
+
+    ranked[0].passages[0].start_line = 20;
+    ranked[0].passages[0].end_line = 30;
+    let eval = evaluate_groundedness_at_k(&ranked, &results, 1);
+
+    assert_eq!(eval.cited_evidence, 0);
+    assert_eq!(eval.grounded_citations, 0);
+    assert_eq!(eval.total_citations, 1);
+}
+
 #[test]
 fn groundedness_precision_drops_for_non_relevant_cited_evidence() {
     let ranked = scored(&["a.rs", "noise.rs"]);
@@ -336,6 +372,45 @@ fn token_efficiency_zero_returned_bytes_stays_finite() {
     assert!(eval.evidence_density.is_finite());
 }
 
+fn pack_entry(path: &str, start_line: usize, end_line: usize) -> PackEntry {
+    PackEntry {
+        path: PathBuf::from(path),
+        start_line,
+        end_line,
+        score: 1.0,
+        estimated_tokens: 50,
+        text: None,
+    }
+}
+
+#[test]
+fn context_pack_scores_entries_covering_relevant_evidence() {
+    let pack = ContextPack {
+        query: "needle".to_string(),
+        budget_tokens: 200,
+        estimated_tokens: 150,
+        entries: vec![
+            pack_entry("a.rs", 1, 20),
+            pack_entry("a.rs", 40, 60),
+            pack_entry("b.rs", 1, 20),
+        ],
+    };
+    let results = vec![
+        grounded_result("a.rs", true, vec![span(10, "needle"), span(30, "gap")]),
+        grounded_result("b.rs", false, vec![span(5, "distractor")]),
+        grounded_result("c.rs", true, vec![span(3, "not packed")]),
+    ];
+
+    let eval = evaluate_context_pack(&pack, &results);
+
+    assert_eq!(eval.expected_evidence, 3);
+    assert_eq!(eval.packed_evidence, 1);
+    assert_eq!(eval.grounded_entries, 1);
+    assert!((eval.evidence_recall - 1.0 / 3.0).abs() < 1e-10);
+    assert!((eval.entry_precision - 1.0 / 3.0).abs() < 1e-10);
+    assert!((eval.budget_utilization - 0.75).abs() < 1e-10);
+}
+
 #[test]
 fn evaluation_report_deserializes_without_token_efficiency_fields() {
     let content = r#"{
@@ -404,6 +479,7 @@ fn evaluate_report_includes_query_metrics_and_evidence_boundary() {
         ranking_algorithm: RankingAlgo::TFIDF,
         queries,
         feedback_expansion: false,
+        diversity: None,
     };
     let temp = tempfile::tempdir().unwrap();
     let index =
@@ -420,4 +496,39 @@ fn evaluate_report_includes_query_metrics_and_evidence_boundary() {
     assert_eq!(report.evidence.total_evidence_spans, 2);
     assert_eq!(report.evidence.groundedness.expected_evidence, 1);
     assert_eq!(report.evidence.token_efficiency.k, 10);
+    assert_eq!(report.intents.total(), 1);
+}
+
+#[test]
+fn evaluate_report_diversifies_the_feedback_expanded_ranking() {
+    let dir = tempfile::tempdir().unwrap();
+    write_temp_file(dir.path(), "src/auth_repository.rs", "fn login() {}");
+    write_temp_file(dir.path(), "src/repository_cache.rs", "fn evict() {}");
+    let config = test_config();
+    let index = crate::index::InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+    let test_set = |feedback_expansion| TestSet {
+        ranking_algorithm: RankingAlgo::BM25F(
+            crate::ranking::BM25FHyperParams::code_search_defaults(),
+        ),
+        queries: vec![TestQuery {
+            query: AnalyzedQuery::new_code_search("auth", &config),
+            query_shape: QueryShape::Identifier,
+            relevant_docs: relevant(&["src/repository_cache.rs"]),
+            groundedness_results: Vec::new(),
+            evidence_span_count: 0,
+        }],
+        feedback_expansion,
+        diversity: Some(crate::ranking::DiversityConfig::default()),
+    };
+    let retrieved = |feedback_expansion| {
+        test_set(feedback_expansion)
+            .evaluate_report(&index, 10)
+            .file_retrieval
+            .queries[0]
+            .retrieved_docs
+            .clone()
+    };
+
+    assert_eq!(retrieved(false), relevant(&["src/auth_repository.rs"]));
+    assert!(retrieved(true).contains(&PathBuf::from("src/repository_cache.rs")));
 }
@@ -1,10 +1,7 @@
 use std::{collections::HashSet, fmt, path::PathBuf};
 
-use super::{
-    GroundednessResult,
-    util::{estimate_tokens_from_bytes, ratio},
-};
-use crate::{index::InvertedIndex, ranking::Score};
+use super::{GroundednessResult, util::ratio};
+use crate::{index::InvertedIndex, ranking::Score, util::estimate_tokens_from_bytes};
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
 pub struct TokenEfficiencyEvaluation {
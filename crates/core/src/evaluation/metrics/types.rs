@@ -1,16 +1,19 @@
 use std::{fmt, path::PathBuf};
 
-use super::{GroundednessEvaluation, TokenEfficiencyEvaluation};
+use super::{ContextPackEvaluation, GroundednessEvaluation, TokenEfficiencyEvaluation};
 use crate::{
     evaluation::dataset::{EvidenceSpan, QueryShape},
-    query::AnalyzedQuery,
-    ranking::RankingAlgo,
+    query::{AnalyzedQuery, QueryIntent, intent::IntentConfusion},
+    ranking::{DiversityConfig, RankingAlgo},
 };
 
 pub struct TestSet {
     pub ranking_algorithm: RankingAlgo,
     pub queries: Vec<TestQuery>,
     pub feedback_expansion: bool,
+    /// Diversifies every ranking when set. Redundancy is measured either
+    /// way, with these settings or the defaults.
+    pub diversity: Option<DiversityConfig>,
 }
 
 pub struct TestQuery {
@@ -85,6 +88,19 @@ impl QueryShape {
             | QueryShape::TestFinding => MetricFamily::TopDocument,
         }
     }
+
+    /// The query intent this shape is labelled as; regex queries never
+    /// reach intent classification.
+    pub fn intent(self) -> Option<QueryIntent> {
+        match self {
+            QueryShape::Conceptual | QueryShape::TestFinding => Some(QueryIntent::NaturalLanguage),
+            QueryShape::Configuration => Some(QueryIntent::Config),
+            QueryShape::ErrorMessage => Some(QueryIntent::ErrorMessage),
+            QueryShape::Identifier => Some(QueryIntent::Identifier),
+            QueryShape::Navigational | QueryShape::Path => Some(QueryIntent::Path),
+            QueryShape::Regex => None,
+        }
+    }
 }
 
 impl fmt::Display for QueryShape {
@@ -115,6 +131,9 @@ pub struct EvaluationSlice {
 pub struct EvaluationReport {
     pub file_retrieval: FileRetrievalReport,
     pub evidence: EvidenceReport,
+    /// Classified query intents against those the query shapes label.
+    #[serde(default)]
+    pub intents: IntentConfusion,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -125,6 +144,10 @@ pub struct FileRetrievalReport {
     pub slices: Vec<EvaluationSlice>,
     #[serde(default)]
     pub queries: Vec<QueryEvaluation>,
+    /// Mean share of the top results that near-duplicate a result ranked
+    /// above them.
+    #[serde(default)]
+    pub redundancy_at_k: f64,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -136,6 +159,8 @@ pub struct QueryEvaluation {
     pub retrieved_docs: Vec<PathBuf>,
     #[serde(default)]
     pub token_efficiency: TokenEfficiencyEvaluation,
+    #[serde(default)]
+    pub redundancy_at_k: f64,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -147,6 +172,8 @@ pub struct EvidenceReport {
     pub groundedness: GroundednessEvaluation,
     #[serde(default)]
     pub token_efficiency: TokenEfficiencyEvaluation,
+    #[serde(default)]
+    pub context_pack: ContextPackEvaluation,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -5,7 +5,3 @@ pub(crate) fn ratio(numerator: usize, denominator: usize) -> f64 {
         numerator as f64 / denominator as f64
     }
 }
-
-pub(crate) fn estimate_tokens_from_bytes(bytes: usize) -> usize {
-    bytes.div_ceil(4)
-}
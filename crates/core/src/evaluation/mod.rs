@@ -1,5 +1,6 @@
 pub mod dataset;
 pub mod metrics;
+pub mod tuning;
 
 pub use dataset::{EvaluationCorpus, EvaluationData, RawEvaluationData};
 pub use metrics::{Evaluation, EvaluationReport, TestQuery, TestSet};
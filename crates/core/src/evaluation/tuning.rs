@@ -0,0 +1,713 @@
+//! Hyperparameter search: scores ranking parameters against an evaluation
+//! set and keeps the ones that do best on a chosen metric.
+//!
+//! Parameters are named by their key under `[ranking]` in `reaper.toml`,
+//! so a winning assignment is written out as a project config as-is.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    config::RankingSettings,
+    evaluation::metrics::{Evaluation, TestQuery, average_evaluations, evaluate_query_at_k},
+    index::{DocumentField, RankedIndexReader},
+    query::QueryIntent,
+    ranking::{DfrModel, QueryLikelihoodSmoothing, RankingAlgo, bm25f::default_intent_multiplier},
+};
+
+/// Largest grid searched exhaustively; bigger spaces need another strategy.
+pub const MAX_GRID_POINTS: usize = 4_096;
+
+/// Parameter values keyed like [`Parameter::key`].
+pub type Assignment = BTreeMap<String, f64>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TuningError {
+    #[error("{0} has no tunable parameters")]
+    NothingToTune(&'static str),
+    #[error(
+        "the grid has {0} points, more than {MAX_GRID_POINTS}; use random or coordinate search"
+    )]
+    GridTooLarge(usize),
+    #[error("no evaluation queries to tune against")]
+    NoQueries,
+    #[error("invalid ranking parameters: {0}")]
+    Settings(#[from] toml::de::Error),
+    #[error("unknown metric {0}; expected p, r, map, mrr or ndcg, optionally @k")]
+    UnknownMetric(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Precision,
+    Recall,
+    MeanAveragePrecision,
+    MeanReciprocalRank,
+    Ndcg,
+}
+
+/// The metric a search maximises, cut off at `k` results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metric {
+    pub kind: MetricKind,
+    pub k: usize,
+}
+
+impl Metric {
+    pub fn value(&self, evaluation: &Evaluation) -> f64 {
+        match self.kind {
+            MetricKind::Precision => evaluation.precision_at_k,
+            MetricKind::Recall => evaluation.recall_at_k,
+            MetricKind::MeanAveragePrecision => evaluation.mean_average_precision,
+            MetricKind::MeanReciprocalRank => evaluation.mean_reciprocal_rank,
+            MetricKind::Ndcg => evaluation.normalized_discounted_cumulative_gain,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = TuningError;
+
+    /// Parses `ndcg@10`, `MAP@5` or a bare name, which cuts off at 10.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_ascii_lowercase();
+        let (name, k) = match lowercase.split_once('@') {
+            Some((name, k)) => (
+                name,
+                k.parse()
+                    .map_err(|_| TuningError::UnknownMetric(s.to_string()))?,
+            ),
+            None => (lowercase.as_str(), 10),
+        };
+        let kind = match name {
+            "p" | "precision" => MetricKind::Precision,
+            "r" | "recall" => MetricKind::Recall,
+            "map" => MetricKind::MeanAveragePrecision,
+            "mrr" => MetricKind::MeanReciprocalRank,
+            "ndcg" => MetricKind::Ndcg,
+            _ => return Err(TuningError::UnknownMetric(s.to_string())),
+        };
+        Ok(Self { kind, k })
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.kind {
+            MetricKind::Precision => "P",
+            MetricKind::Recall => "R",
+            MetricKind::MeanAveragePrecision => "MAP",
+            MetricKind::MeanReciprocalRank => "MRR",
+            MetricKind::Ndcg => "NDCG",
+        };
+        write!(f, "{name}@{}", self.k)
+    }
+}
+
+/// One searchable dimension: a dotted `[ranking]` key, the value the
+/// algorithm currently uses and the candidates tried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub key: String,
+    pub current: f64,
+    pub values: Vec<f64>,
+    /// Written as a TOML integer rather than a float.
+    pub integer: bool,
+}
+
+impl Parameter {
+    fn new(key: impl Into<String>, current: f64, values: &[f64]) -> Self {
+        Self {
+            key: key.into(),
+            current,
+            values: values.to_vec(),
+            integer: false,
+        }
+    }
+}
+
+const K1: [f64; 7] = [0.5, 0.8, 1.0, 1.2, 1.5, 1.8, 2.2];
+const B: [f64; 6] = [0.2, 0.4, 0.55, 0.75, 0.9, 1.0];
+const FIELD_WEIGHTS: [f64; 7] = [0.0, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const INTENT_MULTIPLIERS: [f64; 5] = [0.5, 0.75, 1.0, 1.5, 2.0];
+
+/// The parameters `algo` exposes, starting from its current values.
+/// BM25F's per-intent multipliers are only searched for `intents`, the
+/// intents the evaluation queries actually have.
+pub fn parameter_space(algo: &RankingAlgo, intents: &[QueryIntent]) -> Vec<Parameter> {
+    match algo {
+        RankingAlgo::BM25(params) => vec![
+            Parameter::new("k1", params.k1, &K1),
+            Parameter::new("b", params.b, &B),
+        ],
+        RankingAlgo::BM25F(params) => {
+            let mut space = vec![
+                Parameter::new("k1", params.k1, &K1),
+                Parameter::new("b", params.b, &B),
+                Parameter::new(
+                    "phrase_boost",
+                    params.phrase_boost,
+                    &[0.0, 0.5, 1.0, 2.0, 4.0],
+                ),
+            ];
+            space.extend(DocumentField::ALL.into_iter().map(|field| {
+                Parameter::new(
+                    format!("field_weights.{field:?}"),
+                    params.field_weight(field),
+                    &FIELD_WEIGHTS,
+                )
+            }));
+            for intent in intents {
+                let intent_key = intent_key(*intent);
+                space.extend(DocumentField::ALL.into_iter().map(|field| {
+                    let current = params
+                        .intent_field_weights
+                        .get(intent)
+                        .and_then(|weights| weights.get(&field))
+                        .copied()
+                        .unwrap_or_else(|| default_intent_multiplier(field, *intent));
+                    Parameter::new(
+                        format!("intent_field_weights.{intent_key}.{field:?}"),
+                        current,
+                        &INTENT_MULTIPLIERS,
+                    )
+                }));
+            }
+            space
+        }
+        RankingAlgo::BM25Proximity(params, proximity) => vec![
+            Parameter::new("k1", params.k1, &K1),
+            Parameter::new("b", params.b, &B),
+            Parameter {
+                integer: true,
+                ..Parameter::new(
+                    "proximity.window",
+                    proximity.window as f64,
+                    &[2.0, 4.0, 8.0, 16.0],
+                )
+            },
+            Parameter::new(
+                "proximity.phrase_boost",
+                proximity.phrase_boost,
+                &[0.5, 1.0, 2.0, 4.0],
+            ),
+            Parameter::new(
+                "proximity.proximity_boost",
+                proximity.proximity_boost,
+                &[0.0, 0.25, 0.75, 1.5],
+            ),
+        ],
+        RankingAlgo::BM25Variant(params, variant) => vec![
+            Parameter::new("k1", params.k1, &K1),
+            Parameter::new("b", params.b, &B),
+            Parameter::new("delta", variant.delta(), &[0.25, 0.5, 1.0, 1.5]),
+        ],
+        RankingAlgo::DivergenceFromRandomness(model) => match model {
+            DfrModel::PL2 { c } | DfrModel::InL2 { c } => {
+                vec![Parameter::new("c", *c, &[0.25, 0.5, 1.0, 2.0, 4.0, 7.0])]
+            }
+            DfrModel::DPH => Vec::new(),
+        },
+        RankingAlgo::QueryLikelihood(params) => match params.smoothing {
+            QueryLikelihoodSmoothing::Dirichlet { mu } => vec![Parameter::new(
+                "mu",
+                mu,
+                &[100.0, 250.0, 500.0, 1_000.0, 1_500.0, 2_500.0, 5_000.0],
+            )],
+            QueryLikelihoodSmoothing::JelinekMercer { lambda } => vec![Parameter::new(
+                "lambda",
+                lambda,
+                &[0.05, 0.1, 0.2, 0.3, 0.5, 0.7],
+            )],
+        },
+        RankingAlgo::CosineSimilarity
+        | RankingAlgo::TFIDF
+        | RankingAlgo::Dense(_)
+        | RankingAlgo::Learned(_)
+        | RankingAlgo::Fusion(_) => Vec::new(),
+    }
+}
+
+fn intent_key(intent: QueryIntent) -> String {
+    serde_json::to_value(intent)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// `assignment` as a `[ranking]` table.
+pub fn ranking_table(parameters: &[Parameter], assignment: &Assignment) -> toml::Table {
+    let mut table = toml::Table::new();
+    for parameter in parameters {
+        let Some(value) = assignment.get(&parameter.key) else {
+            continue;
+        };
+        let mut keys = parameter.key.split('.').peekable();
+        let mut section = &mut table;
+        while let Some(key) = keys.next() {
+            if keys.peek().is_none() {
+                let value = if parameter.integer {
+                    toml::Value::Integer(value.round() as i64)
+                } else {
+                    toml::Value::Float(*value)
+                };
+                section.insert(key.to_string(), value);
+            } else {
+                section = section
+                    .entry(key)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .expect("parameter keys do not prefix one another");
+            }
+        }
+    }
+    table
+}
+
+/// A project config selecting `algo` with `assignment` under `[ranking]`.
+pub fn project_config(
+    algo: &RankingAlgo,
+    parameters: &[Parameter],
+    assignment: &Assignment,
+) -> toml::Table {
+    let mut ranking = toml::Table::from_iter([(
+        "algorithm".to_string(),
+        toml::Value::String(algo.name().to_string()),
+    )]);
+    ranking.extend(ranking_table(parameters, assignment));
+    toml::Table::from_iter([("ranking".to_string(), toml::Value::Table(ranking))])
+}
+
+/// The settings a project config holding `assignment` would load.
+pub fn ranking_settings(
+    parameters: &[Parameter],
+    assignment: &Assignment,
+) -> Result<RankingSettings, TuningError> {
+    Ok(toml::Value::Table(ranking_table(parameters, assignment)).try_into()?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStrategy {
+    /// Every combination of parameter values.
+    Grid,
+    /// `trials` combinations drawn with a seeded generator.
+    Random { trials: usize, seed: u64 },
+    /// Tries each parameter's values in turn, keeping improvements, until
+    /// a pass changes nothing or `passes` run out.
+    CoordinateAscent { passes: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Trial {
+    pub assignment: Assignment,
+    pub score: f64,
+}
+
+/// The parameters picked on one fold's training queries and how they
+/// score on its held-out queries, next to the untuned baseline.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FoldTuning {
+    pub fold: usize,
+    pub queries: usize,
+    pub training: Trial,
+    pub held_out: f64,
+    pub baseline_held_out: f64,
+}
+
+pub struct Tuner<'a, I> {
+    index: &'a I,
+    algo: RankingAlgo,
+    metric: Metric,
+    parameters: Vec<Parameter>,
+    strategy: SearchStrategy,
+}
+
+impl<'a, I> Tuner<'a, I>
+where
+    I: RankedIndexReader + Sync,
+{
+    pub fn new(
+        index: &'a I,
+        algo: RankingAlgo,
+        metric: Metric,
+        parameters: Vec<Parameter>,
+        strategy: SearchStrategy,
+    ) -> Result<Self, TuningError> {
+        if parameters.is_empty() {
+            return Err(TuningError::NothingToTune(algo.name()));
+        }
+        if strategy == SearchStrategy::Grid {
+            let points = parameters
+                .iter()
+                .map(|parameter| parameter.values.len().max(1))
+                .try_fold(1usize, |points, values| points.checked_mul(values))
+                .unwrap_or(usize::MAX);
+            if points > MAX_GRID_POINTS {
+                return Err(TuningError::GridTooLarge(points));
+            }
+        }
+
+        Ok(Self {
+            index,
+            algo,
+            metric,
+            parameters,
+            strategy,
+        })
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// The algorithm's current parameters.
+    pub fn baseline(&self) -> Assignment {
+        self.parameters
+            .iter()
+            .map(|parameter| (parameter.key.clone(), parameter.current))
+            .collect()
+    }
+
+    /// The best assignment on `queries`; never worse than the baseline.
+    pub fn search(&self, queries: &[&TestQuery]) -> Result<Trial, TuningError> {
+        if queries.is_empty() {
+            return Err(TuningError::NoQueries);
+        }
+
+        let baseline = self.baseline();
+        let mut best = Trial {
+            score: self.score(&baseline, queries)?,
+            assignment: baseline,
+        };
+        let consider = |assignment: Assignment, best: &mut Trial| {
+            let score = self.score(&assignment, queries)?;
+            if score > best.score + 1e-12 {
+                *best = Trial { assignment, score };
+            }
+            Ok::<_, TuningError>(())
+        };
+
+        match self.strategy {
+            SearchStrategy::Grid => {
+                let mut assignments = vec![Assignment::new()];
+                for parameter in &self.parameters {
+                    assignments = assignments
+                        .into_iter()
+                        .flat_map(|assignment| {
+                            parameter.values.iter().map(move |value| {
+                                let mut assignment = assignment.clone();
+                                assignment.insert(parameter.key.clone(), *value);
+                                assignment
+                            })
+                        })
+                        .collect();
+                }
+                for assignment in assignments {
+                    consider(assignment, &mut best)?;
+                }
+            }
+            SearchStrategy::Random { trials, seed } => {
+                let mut rng = SplitMix64(seed);
+                for _ in 0..trials {
+                    let assignment = self
+                        .parameters
+                        .iter()
+                        .map(|parameter| {
+                            let pick = rng.next() as usize % parameter.values.len().max(1);
+                            let value = parameter.values.get(pick).copied();
+                            (parameter.key.clone(), value.unwrap_or(parameter.current))
+                        })
+                        .collect();
+                    consider(assignment, &mut best)?;
+                }
+            }
+            SearchStrategy::CoordinateAscent { passes } => {
+                for _ in 0..passes {
+                    let before = best.score;
+                    for parameter in &self.parameters {
+                        for value in &parameter.values {
+                            let mut assignment = best.assignment.clone();
+                            assignment.insert(parameter.key.clone(), *value);
+                            consider(assignment, &mut best)?;
+                        }
+                    }
+                    if best.score <= before {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Searches on all but one of `folds` query folds and scores the pick
+    /// on the fold left out, which estimates how well tuning generalises.
+    pub fn cross_validate(
+        &self,
+        queries: &[TestQuery],
+        folds: usize,
+    ) -> Result<Vec<FoldTuning>, TuningError> {
+        let folds = folds.clamp(2, queries.len().max(2));
+        let baseline = self.baseline();
+
+        (0..folds)
+            .filter_map(|fold| {
+                let (held_out, training): (Vec<_>, Vec<_>) = queries
+                    .iter()
+                    .enumerate()
+                    .partition(|(position, _)| position % folds == fold);
+                let held_out = held_out
+                    .into_iter()
+                    .map(|(_, query)| query)
+                    .collect::<Vec<_>>();
+                let training = training
+                    .into_iter()
+                    .map(|(_, query)| query)
+                    .collect::<Vec<_>>();
+                if held_out.is_empty() || training.is_empty() {
+                    return None;
+                }
+
+                Some(self.search(&training).and_then(|trial| {
+                    Ok(FoldTuning {
+                        fold: fold + 1,
+                        queries: held_out.len(),
+                        held_out: self.score(&trial.assignment, &held_out)?,
+                        baseline_held_out: self.score(&baseline, &held_out)?,
+                        training: trial,
+                    })
+                }))
+            })
+            .collect()
+    }
+
+    /// Mean `metric` over `queries` ranked with `assignment`.
+    pub fn score(
+        &self,
+        assignment: &Assignment,
+        queries: &[&TestQuery],
+    ) -> Result<f64, TuningError> {
+        let algo = ranking_settings(&self.parameters, assignment)?.apply(self.algo.clone());
+        let k = self.metric.k;
+        let evaluations = queries
+            .par_iter()
+            .map(|query| {
+                let ranked = algo
+                    .rank(self.index, &query.query, k)
+                    .map(|scored| scored.0)
+                    .unwrap_or_default();
+                evaluate_query_at_k(&ranked, &query.relevant_docs, k)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(self.metric.value(&average_evaluations(&evaluations)))
+    }
+}
+
+/// Small deterministic generator for random search.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{
+        Assignment, Metric, MetricKind, SearchStrategy, Tuner, TuningError, parameter_space,
+        project_config, ranking_settings, ranking_table,
+    };
+    use crate::{
+        config::ProjectConfig,
+        evaluation::{dataset::QueryShape, metrics::TestQuery},
+        index::{DocumentField, InvertedIndex},
+        query::{AnalyzedQuery, QueryIntent},
+        ranking::RankingAlgo,
+        test_support::{test_config, write_temp_file},
+    };
+
+    fn test_query(text: &str, relevant: &str) -> TestQuery {
+        TestQuery {
+            query: AnalyzedQuery::new(text, &test_config()),
+            query_shape: QueryShape::Conceptual,
+            relevant_docs: vec![PathBuf::from(relevant)],
+            groundedness_results: Vec::new(),
+            evidence_span_count: 0,
+        }
+    }
+
+    #[test]
+    fn metrics_parse_with_and_without_a_cutoff() {
+        assert_eq!(
+            "ndcg@5".parse::<Metric>().unwrap(),
+            Metric {
+                kind: MetricKind::Ndcg,
+                k: 5
+            }
+        );
+        assert_eq!("MAP".parse::<Metric>().unwrap().k, 10);
+        assert_eq!("mrr@3".parse::<Metric>().unwrap().to_string(), "MRR@3");
+        assert!("auc@10".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn assignments_load_as_ranking_settings() {
+        let algo = "bm25f".parse::<RankingAlgo>().unwrap();
+        let parameters = parameter_space(&algo, &[QueryIntent::Identifier]);
+        let assignment = Assignment::from([
+            ("k1".to_string(), 1.5),
+            ("field_weights.Symbol".to_string(), 2.0),
+            ("intent_field_weights.identifier.Symbol".to_string(), 0.5),
+        ]);
+
+        let settings = ranking_settings(&parameters, &assignment).unwrap();
+        let RankingAlgo::BM25F(params) = settings.apply(algo) else {
+            panic!("expected bm25f");
+        };
+
+        assert_eq!(params.k1, 1.5);
+        assert_eq!(params.field_weight(DocumentField::Symbol), 2.0);
+        assert_eq!(
+            params.intent_field_weight(DocumentField::Symbol, QueryIntent::Identifier),
+            1.0
+        );
+        assert!(
+            ranking_table(&parameters, &assignment)
+                .to_string()
+                .contains("[intent_field_weights.identifier]")
+        );
+    }
+
+    #[test]
+    fn project_configs_select_the_tuned_algorithm() {
+        let algo = "ql-jm".parse::<RankingAlgo>().unwrap();
+        let parameters = parameter_space(&algo, &[]);
+        let assignment = Assignment::from([("lambda".to_string(), 0.3)]);
+
+        let project: ProjectConfig =
+            toml::from_str(&project_config(&algo, &parameters, &assignment).to_string()).unwrap();
+
+        assert_eq!(project.ranking.algorithm.as_deref(), Some("ql-jm"));
+        assert_eq!(project.ranking.lambda, Some(0.3));
+    }
+
+    #[test]
+    fn proximity_windows_are_written_as_integers() {
+        let algo = "bm25-proximity".parse::<RankingAlgo>().unwrap();
+        let parameters = parameter_space(&algo, &[]);
+        let assignment = Assignment::from([("proximity.window".to_string(), 4.0)]);
+
+        let settings = ranking_settings(&parameters, &assignment).unwrap();
+
+        assert_eq!(settings.proximity.unwrap().window, 4);
+    }
+
+    #[test]
+    fn grid_search_finds_a_b_that_beats_the_baseline() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "short.rs", "pool");
+        write_temp_file(
+            dir.path(),
+            "long.rs",
+            "pool pool pool pool filler filler filler filler filler filler filler filler \
+             filler filler filler filler filler filler filler filler filler filler filler",
+        );
+        write_temp_file(dir.path(), "other.rs", "unrelated words only");
+        let index = InvertedIndex::new(
+            dir.path(),
+            |content| crate::tokenizer::n_gram_transform(content, &test_config()),
+            Some(dir.path()),
+        );
+        let algo = "bm25".parse::<RankingAlgo>().unwrap();
+        let mut parameters = parameter_space(&algo, &[]);
+        parameters[1].current = 1.0;
+        let tuner = Tuner::new(
+            &index,
+            algo,
+            "mrr@1".parse().unwrap(),
+            parameters,
+            SearchStrategy::Grid,
+        )
+        .unwrap();
+        let queries = [test_query("pool", "long.rs")];
+        let queries = queries.iter().collect::<Vec<_>>();
+
+        assert_eq!(tuner.score(&tuner.baseline(), &queries).unwrap(), 0.0);
+        let best = tuner.search(&queries).unwrap();
+
+        assert_eq!(best.score, 1.0);
+        assert!(best.assignment["b"] < 1.0);
+    }
+
+    #[test]
+    fn cross_validation_reports_each_held_out_fold() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", "connection pool");
+        write_temp_file(dir.path(), "retry.rs", "retry backoff");
+        let index = InvertedIndex::new(
+            dir.path(),
+            |content| crate::tokenizer::n_gram_transform(content, &test_config()),
+            Some(dir.path()),
+        );
+        let algo = "ql".parse::<RankingAlgo>().unwrap();
+        let tuner = Tuner::new(
+            &index,
+            algo.clone(),
+            "ndcg@10".parse().unwrap(),
+            parameter_space(&algo, &[]),
+            SearchStrategy::CoordinateAscent { passes: 2 },
+        )
+        .unwrap();
+        let queries = [
+            test_query("connection pool", "pool.rs"),
+            test_query("retry backoff", "retry.rs"),
+            test_query("pool", "pool.rs"),
+        ];
+
+        let folds = tuner.cross_validate(&queries, 3).unwrap();
+
+        assert_eq!(folds.len(), 3);
+        assert!(folds.iter().all(|fold| fold.queries == 1));
+        assert!(folds.iter().all(|fold| fold.held_out == 1.0));
+    }
+
+    #[test]
+    fn algorithms_without_parameters_and_huge_grids_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = InvertedIndex::new(dir.path(), |_| Default::default(), Some(dir.path()));
+        let metric = "ndcg".parse().unwrap();
+
+        assert!(matches!(
+            Tuner::new(
+                &index,
+                RankingAlgo::TFIDF,
+                metric,
+                Vec::new(),
+                SearchStrategy::Grid
+            ),
+            Err(TuningError::NothingToTune("tfidf"))
+        ));
+
+        let bm25f = "bm25f".parse::<RankingAlgo>().unwrap();
+        assert!(matches!(
+            Tuner::new(
+                &index,
+                bm25f.clone(),
+                metric,
+                parameter_space(&bm25f, &[]),
+                SearchStrategy::Grid
+            ),
+            Err(TuningError::GridTooLarge(_))
+        ));
+    }
+}
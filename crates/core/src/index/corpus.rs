@@ -14,6 +14,9 @@ pub struct IndexCorpusDocument {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexCorpusScan {
+    /// The corpus root as document paths start with it; empty when they
+    /// are relative to it.
+    pub root: PathBuf,
     pub documents: Vec<IndexCorpusDocument>,
     pub skipped_documents: Vec<SkippedDocument>,
 }
@@ -117,6 +120,7 @@ impl IndexCorpus for FileSystemIndexCorpus {
         documents.sort_by(|left, right| left.path.cmp(&right.path));
 
         IndexCorpusScan {
+            root: self.index_path(&self.root),
             documents,
             skipped_documents,
         }
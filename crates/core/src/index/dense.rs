@@ -0,0 +1,477 @@
+//! Dense document vectors from latent semantic indexing, searched through
+//! an HNSW graph.
+//!
+//! The model is a truncated SVD of the log-TF·IDF term-document matrix,
+//! computed with a randomized range finder so only a few passes over the
+//! postings are needed. Terms that co-occur across documents end up close
+//! together in the latent space, which lets a query match files that use
+//! related words rather than the same ones. Documents and queries are
+//! folded in by summing the latent vectors of their terms, so an updated
+//! file gets a fresh vector without refitting the model.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::index::{
+    DocId, DocumentCatalog, InvertedIndex, Term,
+    hnsw::{Hnsw, dot},
+};
+
+/// Extra random directions sampled beyond `dimensions`, which makes the
+/// randomized SVD accurate for the leading ones.
+const OVERSAMPLING: usize = 10;
+const JACOBI_SWEEPS: usize = 64;
+const SEED: u64 = 0x5eed_1a7e_4e5e_ed00;
+
+/// How the latent space and its search graph are built, read from
+/// `[index.dense]` in `reaper.toml`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DenseConfig {
+    /// Latent dimensions kept from the SVD.
+    pub dimensions: usize,
+    /// Power iterations sharpening the randomized SVD.
+    pub power_iterations: usize,
+    /// Vocabulary cap; the terms found in the most documents are kept.
+    pub max_terms: usize,
+    /// HNSW neighbours per document on the upper layers.
+    pub neighbours: usize,
+    pub ef_construction: usize,
+}
+
+impl Default for DenseConfig {
+    fn default() -> Self {
+        Self {
+            dimensions: 64,
+            power_iterations: 2,
+            max_terms: 20_000,
+            neighbours: 16,
+            ef_construction: 100,
+        }
+    }
+}
+
+/// Term vectors of the latent space, each already weighted by the term's
+/// IDF when the model was fitted.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LatentSemanticModel {
+    dimensions: usize,
+    terms: HashMap<Term, Vec<f32>>,
+}
+
+impl LatentSemanticModel {
+    /// Fits the model to `index`. Terms found in a single document carry no
+    /// co-occurrence and are left out.
+    pub fn fit<R: DocumentCatalog>(index: &InvertedIndex<R>, config: &DenseConfig) -> Self {
+        let num_docs = index.num_docs();
+        let mut vocabulary = index
+            .postings_iter()
+            .filter(|(_, documents)| documents.len() > 1)
+            .map(|(term, documents)| (term, documents.len()))
+            .collect::<Vec<_>>();
+        vocabulary.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        vocabulary.truncate(config.max_terms);
+
+        let mut columns = BTreeMap::new();
+        let mut entries = Vec::new();
+        let mut idfs = Vec::with_capacity(vocabulary.len());
+        for (row, (term, doc_freq)) in vocabulary.iter().enumerate() {
+            let idf = (num_docs as f64 / *doc_freq as f64).ln_1p();
+            idfs.push(idf);
+            for (doc_id, term_doc) in index.get_postings(term).into_iter().flatten() {
+                let next = columns.len();
+                let column = *columns.entry(*doc_id).or_insert(next);
+                entries.push(Entry {
+                    row,
+                    column,
+                    value: log_tf(term_doc.term_freq) * idf,
+                });
+            }
+        }
+
+        let rank = (config.dimensions + OVERSAMPLING)
+            .min(vocabulary.len())
+            .min(columns.len());
+        let basis = truncated_left_singular_vectors(
+            &entries,
+            vocabulary.len(),
+            columns.len(),
+            rank,
+            config.power_iterations,
+        );
+        let dimensions = config.dimensions.min(basis.len());
+
+        let terms = vocabulary
+            .iter()
+            .zip(idfs)
+            .enumerate()
+            .map(|(row, ((term, _), idf))| {
+                let vector = basis[..dimensions]
+                    .iter()
+                    .map(|direction| (direction[row] * idf) as f32)
+                    .collect();
+                ((*term).clone(), vector)
+            })
+            .collect();
+
+        Self { dimensions, terms }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Unit-length latent vector of weighted terms, or `None` when none of
+    /// them are in the model.
+    pub fn embed<'a>(&self, terms: impl IntoIterator<Item = (&'a Term, f64)>) -> Option<Vec<f32>> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        let mut matched = false;
+        for (term, weight) in terms {
+            if let Some(term_vector) = self.terms.get(term) {
+                matched = true;
+                for (value, component) in vector.iter_mut().zip(term_vector) {
+                    *value += weight as f32 * component;
+                }
+            }
+        }
+
+        let norm = dot(&vector, &vector).sqrt();
+        if !matched || norm == 0.0 {
+            return None;
+        }
+        vector.iter_mut().for_each(|value| *value /= norm);
+        Some(vector)
+    }
+
+    /// Latent vector of a document from its postings in `index`. Looks up
+    /// every model term, so building vectors for the whole index goes
+    /// through the postings instead.
+    fn embed_document<R: DocumentCatalog>(
+        &self,
+        index: &InvertedIndex<R>,
+        doc_id: DocId,
+    ) -> Option<Vec<f32>> {
+        self.embed(self.terms.keys().filter_map(|term| {
+            let term_doc = index.get_postings(term)?.get(&doc_id)?;
+            Some((term, log_tf(term_doc.term_freq)))
+        }))
+    }
+}
+
+/// An index's latent semantic model with every document's vector in an
+/// HNSW graph.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DenseIndex {
+    config: DenseConfig,
+    model: LatentSemanticModel,
+    graph: Hnsw,
+}
+
+impl DenseIndex {
+    pub fn build<R: DocumentCatalog>(index: &InvertedIndex<R>, config: &DenseConfig) -> Self {
+        let model = LatentSemanticModel::fit(index, config);
+        let mut vectors = BTreeMap::<DocId, Vec<f32>>::new();
+        for (term, term_vector) in &model.terms {
+            for (doc_id, term_doc) in index.get_postings(term).into_iter().flatten() {
+                let weight = log_tf(term_doc.term_freq) as f32;
+                let vector = vectors
+                    .entry(*doc_id)
+                    .or_insert_with(|| vec![0.0; model.dimensions]);
+                for (value, component) in vector.iter_mut().zip(term_vector) {
+                    *value += weight * component;
+                }
+            }
+        }
+
+        let mut graph = Hnsw::new(config.neighbours, config.ef_construction);
+        for (doc_id, mut vector) in vectors {
+            let norm = dot(&vector, &vector).sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|value| *value /= norm);
+                graph.insert(doc_id, vector);
+            }
+        }
+
+        Self {
+            config: config.clone(),
+            model,
+            graph,
+        }
+    }
+
+    pub fn config(&self) -> &DenseConfig {
+        &self.config
+    }
+
+    pub fn model(&self) -> &LatentSemanticModel {
+        &self.model
+    }
+
+    pub fn len(&self) -> usize {
+        self.graph.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graph.is_empty()
+    }
+
+    /// Re-embeds `doc_id` from its current postings, dropping it when none
+    /// of its terms are in the model.
+    pub fn refresh_document<R: DocumentCatalog>(
+        &mut self,
+        index: &InvertedIndex<R>,
+        doc_id: DocId,
+    ) {
+        match self.model.embed_document(index, doc_id) {
+            Some(vector) => self.graph.insert(doc_id, vector),
+            None => self.graph.remove(doc_id),
+        }
+    }
+
+    pub fn remove_document(&mut self, doc_id: DocId) {
+        self.graph.remove(doc_id);
+    }
+
+    /// Up to `k` documents most similar to `query` among those `admit`
+    /// accepts, best first. Falls back to comparing every admitted vector
+    /// when the graph search turns up too few.
+    pub fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+        admit: impl Fn(DocId) -> bool,
+    ) -> Vec<(DocId, f32)> {
+        let found = self
+            .graph
+            .search(query, k, ef)
+            .into_iter()
+            .filter(|(doc_id, _)| admit(*doc_id))
+            .collect::<Vec<_>>();
+        if found.len() >= k.min(self.graph.len()) {
+            return found;
+        }
+
+        let mut exhaustive = self
+            .graph
+            .vectors()
+            .filter(|(doc_id, _)| admit(*doc_id))
+            .map(|(doc_id, vector)| (doc_id, dot(query, vector)))
+            .collect::<Vec<_>>();
+        exhaustive.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        exhaustive.truncate(k);
+        exhaustive
+    }
+}
+
+/// One non-zero cell of the term-document matrix.
+struct Entry {
+    row: usize,
+    column: usize,
+    value: f64,
+}
+
+fn log_tf(term_freq: usize) -> f64 {
+    if term_freq == 0 {
+        0.0
+    } else {
+        1.0 + (term_freq as f64).ln()
+    }
+}
+
+/// The leading `rank` left singular vectors of the `rows` × `columns`
+/// matrix `entries`, each as a column of length `rows`, strongest first.
+fn truncated_left_singular_vectors(
+    entries: &[Entry],
+    rows: usize,
+    columns: usize,
+    rank: usize,
+    power_iterations: usize,
+) -> Vec<Vec<f64>> {
+    if rank == 0 {
+        return Vec::new();
+    }
+
+    let mut random = SplitMix64(SEED);
+    let sketch = (0..rank)
+        .map(|_| (0..columns).map(|_| random.gaussian()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut range = orthonormalize(multiply(entries, &sketch, rows, false));
+    for _ in 0..power_iterations {
+        let transposed = orthonormalize(multiply(entries, &range, columns, true));
+        range = orthonormalize(multiply(entries, &transposed, rows, false));
+    }
+
+    // B = Qᵀ A is small; the eigenvectors of B Bᵀ rotate Q onto the
+    // singular directions of A.
+    let projected = multiply(entries, &range, columns, true);
+    let gram = projected
+        .iter()
+        .map(|left| {
+            projected
+                .iter()
+                .map(|right| left.iter().zip(right).map(|(a, b)| a * b).sum())
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<_>>();
+    let (eigenvalues, eigenvectors) = symmetric_eigen(gram);
+
+    let mut order = (0..rank).collect::<Vec<_>>();
+    order.sort_by(|a, b| eigenvalues[*b].total_cmp(&eigenvalues[*a]));
+    order
+        .into_iter()
+        .filter(|component| eigenvalues[*component] > f64::EPSILON)
+        .map(|component| {
+            let mut direction = vec![0.0; rows];
+            for (basis, weights) in range.iter().zip(&eigenvectors) {
+                let weight = weights[component];
+                for (value, basis_value) in direction.iter_mut().zip(basis) {
+                    *value += weight * basis_value;
+                }
+            }
+            direction
+        })
+        .collect()
+}
+
+/// `A · vectors`, or `Aᵀ · vectors` when `transpose` is set, for a set of
+/// column vectors. `length` is the length of each result.
+fn multiply(
+    entries: &[Entry],
+    vectors: &[Vec<f64>],
+    length: usize,
+    transpose: bool,
+) -> Vec<Vec<f64>> {
+    vectors
+        .par_iter()
+        .map(|vector| {
+            let mut result = vec![0.0; length];
+            for entry in entries {
+                let (to, from) = if transpose {
+                    (entry.column, entry.row)
+                } else {
+                    (entry.row, entry.column)
+                };
+                result[to] += entry.value * vector[from];
+            }
+            result
+        })
+        .collect()
+}
+
+/// Modified Gram-Schmidt, run twice for numerical stability. Vectors that
+/// are dependent on earlier ones come out as zeros.
+fn orthonormalize(mut vectors: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    for _ in 0..2 {
+        for i in 0..vectors.len() {
+            let (done, rest) = vectors.split_at_mut(i);
+            let vector = &mut rest[0];
+            for basis in done.iter() {
+                let projection = basis
+                    .iter()
+                    .zip(vector.iter())
+                    .map(|(a, b)| a * b)
+                    .sum::<f64>();
+                vector
+                    .iter_mut()
+                    .zip(basis)
+                    .for_each(|(value, basis_value)| *value -= projection * basis_value);
+            }
+            let norm = vector.iter().map(|value| value * value).sum::<f64>().sqrt();
+            if norm > 1e-10 {
+                vector.iter_mut().for_each(|value| *value /= norm);
+            } else {
+                vector.iter_mut().for_each(|value| *value = 0.0);
+            }
+        }
+    }
+    vectors
+}
+
+/// Eigenvalues and eigenvectors of a symmetric matrix by cyclic Jacobi
+/// rotations. Eigenvector `k` is column `k` of the returned matrix.
+fn symmetric_eigen(mut matrix: Vec<Vec<f64>>) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let size = matrix.len();
+    let mut vectors = (0..size)
+        .map(|row| (0..size).map(|column| f64::from(row == column)).collect())
+        .collect::<Vec<Vec<f64>>>();
+
+    for _ in 0..JACOBI_SWEEPS {
+        let off_diagonal = (0..size)
+            .flat_map(|p| (p + 1..size).map(move |q| (p, q)))
+            .map(|(p, q)| matrix[p][q] * matrix[p][q])
+            .sum::<f64>();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+
+        for p in 0..size {
+            for q in p + 1..size {
+                if matrix[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (matrix[q][q] - matrix[p][p]) / (2.0 * matrix[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let cos = 1.0 / (t * t + 1.0).sqrt();
+                let sin = t * cos;
+
+                for row in matrix.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = cos * kp - sin * kq;
+                    row[q] = sin * kp + cos * kq;
+                }
+                let (upper, lower) = matrix.split_at_mut(q);
+                for (pk, qk) in upper[p].iter_mut().zip(lower[0].iter_mut()) {
+                    let (left, right) = (*pk, *qk);
+                    *pk = cos * left - sin * right;
+                    *qk = sin * left + cos * right;
+                }
+                for row in vectors.iter_mut() {
+                    let (vp, vq) = (row[p], row[q]);
+                    row[p] = cos * vp - sin * vq;
+                    row[q] = sin * vp + cos * vq;
+                }
+            }
+        }
+    }
+
+    ((0..size).map(|i| matrix[i][i]).collect(), vectors)
+}
+
+/// Small deterministic generator, so the same corpus always gets the same
+/// model.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    /// Standard normal sample by the Box-Muller transform.
+    fn gaussian(&mut self) -> f64 {
+        (-2.0 * self.uniform().ln()).sqrt() * (std::f64::consts::TAU * self.uniform()).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::symmetric_eigen;
+
+    #[test]
+    fn jacobi_recovers_the_eigenvalues_of_a_symmetric_matrix() {
+        let (mut values, _) = symmetric_eigen(vec![vec![2.0, 1.0], vec![1.0, 2.0]]);
+        values.sort_by(f64::total_cmp);
+
+        assert!((values[0] - 1.0).abs() < 1e-9);
+        assert!((values[1] - 3.0).abs() < 1e-9);
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 
 use crate::{
     code_intelligence::DocumentFeature,
-    index::{DocumentField, StaticQualitySignals},
+    index::{DocumentField, ImportTarget, StaticQualitySignals, TokenOffsets},
     tokenizer::FileType,
 };
 
@@ -31,7 +31,7 @@ pub struct FieldSpan {
     pub end_byte: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct DocumentMetadataUpdate {
     pub path: PathBuf,
     pub token_length: usize,
@@ -41,6 +41,8 @@ pub struct DocumentMetadataUpdate {
     pub field_spans: Vec<FieldSpan>,
     pub features: Vec<DocumentFeature>,
     pub quality_signals: StaticQualitySignals,
+    pub token_offsets: Option<TokenOffsets>,
+    pub imports: Vec<ImportTarget>,
 }
 
 impl DocumentMetadataUpdate {
@@ -55,11 +57,13 @@ impl DocumentMetadataUpdate {
             field_spans: Vec::new(),
             features: Vec::new(),
             quality_signals,
+            token_offsets: None,
+            imports: Vec::new(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct DocumentMetadata {
     pub id: DocId,
     pub path: PathBuf,
@@ -73,6 +77,12 @@ pub struct DocumentMetadata {
     pub features: Vec<DocumentFeature>,
     #[serde(default)]
     pub quality_signals: StaticQualitySignals,
+    #[serde(default)]
+    pub token_offsets: Option<TokenOffsets>,
+    /// Unresolved imports; resolved against the whole index into the
+    /// import signals of `quality_signals`.
+    #[serde(default)]
+    pub imports: Vec<ImportTarget>,
 }
 
 impl DocumentMetadata {
@@ -87,6 +97,8 @@ impl DocumentMetadata {
             field_spans: update.field_spans,
             features: update.features,
             quality_signals: update.quality_signals,
+            token_offsets: update.token_offsets,
+            imports: update.imports,
         }
     }
 
@@ -100,6 +112,8 @@ impl DocumentMetadata {
             field_spans: self.field_spans,
             features: self.features,
             quality_signals: self.quality_signals,
+            token_offsets: self.token_offsets,
+            imports: self.imports,
         }
     }
 
@@ -150,6 +164,8 @@ pub trait DocumentCatalog {
             field_spans: Vec::new(),
             features: Vec::new(),
             quality_signals,
+            token_offsets: None,
+            imports: Vec::new(),
         })
     }
 
@@ -159,6 +175,8 @@ pub trait DocumentCatalog {
 
     fn remove(&mut self, path: &Path) -> Option<DocumentMetadata>;
     fn get(&self, id: DocId) -> Option<&DocumentMetadata>;
+    /// For signals computed across documents, such as import centrality.
+    fn quality_signals_mut(&mut self, id: DocId) -> Option<&mut StaticQualitySignals>;
     fn iter(&self) -> Box<dyn Iterator<Item = &DocumentMetadata> + '_>;
     fn get_by_path(&self, path: &Path) -> Option<&DocumentMetadata>;
     fn doc_id(&self, path: &Path) -> Option<DocId>;
@@ -210,6 +228,12 @@ impl DocumentCatalog for DocumentRegistry {
         self.documents.get(&id)
     }
 
+    fn quality_signals_mut(&mut self, id: DocId) -> Option<&mut StaticQualitySignals> {
+        self.documents
+            .get_mut(&id)
+            .map(|metadata| &mut metadata.quality_signals)
+    }
+
     fn iter(&self) -> Box<dyn Iterator<Item = &DocumentMetadata> + '_> {
         Box::new(self.documents.values())
     }
@@ -304,6 +328,7 @@ mod tests {
             field: DocumentField::Symbol,
             text: "score_bm25".to_string(),
             span: Some(ByteSpan { start: 3, end: 13 }),
+            scope: None,
         };
         let span = FieldSpan {
             field: DocumentField::Symbol,
@@ -320,6 +345,8 @@ mod tests {
             field_spans: vec![span.clone()],
             features: vec![feature.clone()],
             quality_signals: StaticQualitySignals::default(),
+            token_offsets: None,
+            imports: Vec::new(),
         });
 
         let metadata = registry.get_by_path(&path).unwrap();
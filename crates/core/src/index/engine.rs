@@ -6,7 +6,7 @@ use std::{
 
 use crate::{
     config::Config,
-    index::{InvertedIndex, Term, event_log::IndexEvent},
+    index::{InvertedIndex, SearchFilter, Term, event_log::IndexEvent},
     query::AnalyzedQuery,
     ranking::{RankingAlgo, Scored},
 };
@@ -44,12 +44,13 @@ impl SearchEngine {
         algo: &RankingAlgo,
         query: &AnalyzedQuery,
         top_n: usize,
+        filter: &SearchFilter,
     ) -> Result<Option<Scored>, SearchEngineError> {
         let index = self
             .index
             .read()
             .map_err(|_| SearchEngineError::ReadLockPoisoned)?;
-        Ok(algo.rank(&*index, query, top_n))
+        Ok(algo.rank_filtered(&*index, query, top_n, filter))
     }
 
     pub fn update<F>(
@@ -92,6 +93,18 @@ impl SearchEngine {
         Ok(())
     }
 
+    /// Swaps in a freshly built index, e.g. after a full reindex. Searches
+    /// already holding the read lock finish against the old index. The new
+    /// index keeps the old one's quality priors.
+    pub fn replace(&self, index: InvertedIndex) -> Result<(), SearchEngineError> {
+        let mut current = self
+            .index
+            .write()
+            .map_err(|_| SearchEngineError::WriteLockPoisoned)?;
+        *current = index.with_quality_priors(current.quality_priors().clone());
+        Ok(())
+    }
+
     pub fn with_read<T>(
         &self,
         f: impl FnOnce(&InvertedIndex) -> T,
@@ -102,6 +115,18 @@ impl SearchEngine {
             .map_err(|_| SearchEngineError::ReadLockPoisoned)?;
         Ok(f(&index))
     }
+
+    /// Changes the index in place, blocking searches until `f` returns.
+    pub fn with_write<T>(
+        &self,
+        f: impl FnOnce(&mut InvertedIndex) -> T,
+    ) -> Result<T, SearchEngineError> {
+        let mut index = self
+            .index
+            .write()
+            .map_err(|_| SearchEngineError::WriteLockPoisoned)?;
+        Ok(f(&mut index))
+    }
 }
 
 #[cfg(test)]
@@ -109,10 +134,10 @@ mod tests {
     use std::{collections::HashMap, sync::Arc, thread};
 
     use crate::{
-        config::Config,
-        index::{InvertedIndex, SearchEngine, Term},
+        index::{InvertedIndex, QualityPriors, SearchEngine, SearchFilter, Term},
         query::AnalyzedQuery,
         ranking::{BM25HyperParams, RankingAlgo},
+        test_support::config_without_stop_words,
     };
 
     fn transform(content: &str) -> HashMap<Term, u32> {
@@ -124,14 +149,6 @@ mod tests {
             })
     }
 
-    fn config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English),
-            stop_words: Default::default(),
-        }
-    }
-
     #[test]
     fn searches_can_hold_shared_access_concurrently() {
         let engine = Arc::new(SearchEngine::new(InvertedIndex::from_documents(&[
@@ -139,14 +156,18 @@ mod tests {
             ("b.rs", &[("rust", 1)]),
         ])));
         let algo = RankingAlgo::BM25(BM25HyperParams { k1: 1.2, b: 0.75 });
-        let query = AnalyzedQuery::new("rust", &config());
+        let query = AnalyzedQuery::new("rust", &config_without_stop_words());
 
         let handles = (0..4)
             .map(|_| {
                 let engine = Arc::clone(&engine);
                 let algo = algo.clone();
                 let query = query.clone();
-                thread::spawn(move || engine.search(&algo, &query, 10).unwrap())
+                thread::spawn(move || {
+                    engine
+                        .search(&algo, &query, 10, &SearchFilter::default())
+                        .unwrap()
+                })
             })
             .collect::<Vec<_>>();
 
@@ -167,7 +188,9 @@ mod tests {
         ));
         std::fs::write(&path, "new").unwrap();
 
-        engine.update(&path, &transform, &config(), false).unwrap();
+        engine
+            .update(&path, &transform, &config_without_stop_words(), false)
+            .unwrap();
 
         assert!(
             engine
@@ -175,4 +198,36 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn replace_swaps_the_whole_index() {
+        let priors = QualityPriors {
+            readme: 1.0,
+            ..QualityPriors::default()
+        };
+        let engine = SearchEngine::new(
+            InvertedIndex::from_documents(&[("a.rs", &[("old", 1)])])
+                .with_quality_priors(priors.clone()),
+        );
+
+        engine
+            .replace(InvertedIndex::from_documents(&[
+                ("a.rs", &[("new", 1)]),
+                ("b.rs", &[("new", 1)]),
+            ]))
+            .unwrap();
+
+        assert_eq!(
+            engine
+                .with_read(|index| index.quality_priors().clone())
+                .unwrap(),
+            priors
+        );
+        assert_eq!(engine.num_docs().unwrap(), 2);
+        assert!(
+            engine
+                .with_read(|index| index.get_postings(&Term("old".to_string())).is_none())
+                .unwrap()
+        );
+    }
 }
@@ -138,8 +138,8 @@ mod tests {
 
     use super::{IndexEvent, append_event, clear_events, read_events, replay_events};
     use crate::{
-        config::Config,
         index::{InvertedIndex, Term},
+        test_support::config_without_stop_words,
     };
 
     fn transform(content: &str) -> HashMap<Term, u32> {
@@ -151,14 +151,6 @@ mod tests {
             })
     }
 
-    fn config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English),
-            stop_words: Default::default(),
-        }
-    }
-
     #[test]
     fn event_log_replays_updates_in_order() {
         let source = tempfile::tempdir().unwrap();
@@ -170,7 +162,13 @@ mod tests {
 
         append_event(index_dir.path(), &IndexEvent::FileModified { path }).unwrap();
         let events = read_events(index_dir.path()).unwrap();
-        replay_events(&mut index, &events, &transform, &config(), false);
+        replay_events(
+            &mut index,
+            &events,
+            &transform,
+            &config_without_stop_words(),
+            false,
+        );
 
         assert!(index.get_postings(&Term("old".to_string())).is_none());
         assert!(index.get_postings(&Term("new".to_string())).is_some());
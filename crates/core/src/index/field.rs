@@ -47,6 +47,21 @@ impl DocumentField {
         }
     }
 
+    pub fn from_analyzer_field(field: AnalyzerField) -> Self {
+        match field {
+            AnalyzerField::FileName => Self::FileName,
+            AnalyzerField::RelativePath => Self::RelativePath,
+            AnalyzerField::Extension => Self::Extension,
+            AnalyzerField::Content => Self::Content,
+            AnalyzerField::Identifier => Self::Identifier,
+            AnalyzerField::Symbol => Self::Symbol,
+            AnalyzerField::Import => Self::Import,
+            AnalyzerField::Comment => Self::Comment,
+            AnalyzerField::StringLiteral => Self::StringLiteral,
+            AnalyzerField::Frontmatter => Self::Frontmatter,
+        }
+    }
+
     pub fn analyzer_field(self) -> AnalyzerField {
         match self {
             Self::FileName => AnalyzerField::FileName,
@@ -0,0 +1,429 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::{
+    index::{
+        DenseIndex, DocId, DocumentField, DocumentMetadata, PostingList, QualityPriors,
+        RankedIndexReader, Term,
+    },
+    tokenizer::FileType,
+};
+
+/// Query-time restrictions on which documents may be scored.
+///
+/// An empty filter admits every document. Globs are matched against the
+/// indexed path relative to the corpus root set with
+/// [`SearchFilter::with_root`], so `src/**` matches whether the corpus was
+/// indexed as `.`, `./` or an absolute directory; a leading `!` turns a glob
+/// into an exclusion.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    file_types: Vec<FileType>,
+    globs: Vec<String>,
+    root: Option<PathBuf>,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    max_size_bytes: Option<u64>,
+    exclude_tests: bool,
+    exclude_generated: bool,
+    exclude_vendor: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchFilterError {
+    #[error("invalid glob {pattern:?}: {source}")]
+    InvalidGlob {
+        pattern: String,
+        #[source]
+        source: globset::Error,
+    },
+}
+
+impl SearchFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file_types(mut self, file_types: impl IntoIterator<Item = FileType>) -> Self {
+        self.file_types.extend(file_types);
+        self
+    }
+
+    pub fn with_globs<S: AsRef<str>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Result<Self, SearchFilterError> {
+        self.globs.extend(
+            patterns
+                .into_iter()
+                .map(|pattern| pattern.as_ref().to_string()),
+        );
+
+        let mut include = GlobSetBuilder::new();
+        let mut exclude = GlobSetBuilder::new();
+        let mut has_include = false;
+        let mut has_exclude = false;
+        for pattern in &self.globs {
+            let (builder, raw) = match pattern.strip_prefix('!') {
+                Some(raw) => {
+                    has_exclude = true;
+                    (&mut exclude, raw)
+                }
+                None => {
+                    has_include = true;
+                    (&mut include, pattern.as_str())
+                }
+            };
+            let glob = Glob::new(raw).map_err(|source| SearchFilterError::InvalidGlob {
+                pattern: pattern.clone(),
+                source,
+            })?;
+            builder.add(glob);
+        }
+
+        let build = |builder: GlobSetBuilder| {
+            builder
+                .build()
+                .map_err(|source| SearchFilterError::InvalidGlob {
+                    pattern: self.globs.join(","),
+                    source,
+                })
+        };
+        let include = has_include.then(|| build(include)).transpose()?;
+        let exclude = has_exclude.then(|| build(exclude)).transpose()?;
+        self.include = include;
+        self.exclude = exclude;
+        Ok(self)
+    }
+
+    /// Strips `root` off indexed paths before matching globs against them.
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    pub fn with_max_size_bytes(mut self, max_size_bytes: u64) -> Self {
+        self.max_size_bytes = Some(max_size_bytes);
+        self
+    }
+
+    pub fn with_exclude_tests(mut self, exclude_tests: bool) -> Self {
+        self.exclude_tests = exclude_tests;
+        self
+    }
+
+    pub fn with_exclude_generated(mut self, exclude_generated: bool) -> Self {
+        self.exclude_generated = exclude_generated;
+        self
+    }
+
+    pub fn with_exclude_vendor(mut self, exclude_vendor: bool) -> Self {
+        self.exclude_vendor = exclude_vendor;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.file_types.is_empty()
+            && self.globs.is_empty()
+            && self.max_size_bytes.is_none()
+            && !self.exclude_tests
+            && !self.exclude_generated
+            && !self.exclude_vendor
+    }
+
+    pub fn matches(&self, metadata: &DocumentMetadata) -> bool {
+        let signals = &metadata.quality_signals;
+
+        (self.file_types.is_empty() || self.file_types.contains(&metadata.file_type))
+            && self.matches_path(&metadata.path)
+            && self
+                .max_size_bytes
+                .is_none_or(|max_size_bytes| metadata.file_size_bytes <= max_size_bytes)
+            && !(self.exclude_tests && signals.test)
+            && !(self.exclude_generated && signals.generated)
+            && !(self.exclude_vendor && signals.vendor)
+    }
+
+    fn matches_path(&self, path: &Path) -> bool {
+        let path = self
+            .root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(path))
+            && self
+                .exclude
+                .as_ref()
+                .is_none_or(|exclude| !exclude.is_match(path))
+    }
+}
+
+/// A reader view that hides documents rejected by a [`SearchFilter`].
+///
+/// Collection statistics (document counts, document frequencies, average
+/// lengths) still come from the full index so filtered scores stay
+/// comparable with unfiltered ones; only iteration skips excluded documents,
+/// which keeps scorers from doing any work on them.
+pub struct FilteredIndex<'a, I> {
+    inner: &'a I,
+    allowed: HashSet<DocId>,
+}
+
+impl<'a, I: RankedIndexReader> FilteredIndex<'a, I> {
+    pub fn new(inner: &'a I, filter: &SearchFilter) -> Self {
+        let allowed = inner
+            .documents()
+            .into_iter()
+            .filter(|metadata| filter.matches(metadata))
+            .map(|metadata| metadata.id)
+            .collect();
+
+        Self { inner, allowed }
+    }
+
+    /// Admits exactly the documents in `allowed`.
+    pub fn with_allowed(inner: &'a I, allowed: HashSet<DocId>) -> Self {
+        Self { inner, allowed }
+    }
+
+    pub fn inner(&self) -> &'a I {
+        self.inner
+    }
+
+    pub fn num_allowed(&self) -> usize {
+        self.allowed.len()
+    }
+}
+
+pub struct FilteredPostings<'a, P> {
+    inner: P,
+    allowed: &'a HashSet<DocId>,
+}
+
+pub struct FilteredPostingIter<'a, T> {
+    inner: T,
+    allowed: &'a HashSet<DocId>,
+}
+
+impl<'a, T, D> Iterator for FilteredPostingIter<'a, T>
+where
+    T: Iterator<Item = (DocId, D)>,
+{
+    type Item = (DocId, D);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .by_ref()
+            .find(|(doc_id, _)| self.allowed.contains(doc_id))
+    }
+}
+
+impl<P: PostingList> PostingList for FilteredPostings<'_, P> {
+    type Iter<'b>
+        = FilteredPostingIter<'b, P::Iter<'b>>
+    where
+        Self: 'b;
+
+    /// Reports the unfiltered length: scorers use it as document frequency.
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn get(&self, doc_id: DocId) -> Option<&crate::index::TermDocument> {
+        if self.allowed.contains(&doc_id) {
+            self.inner.get(doc_id)
+        } else {
+            None
+        }
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        FilteredPostingIter {
+            inner: self.inner.iter(),
+            allowed: self.allowed,
+        }
+    }
+}
+
+impl<I: RankedIndexReader> RankedIndexReader for FilteredIndex<'_, I> {
+    type Postings<'b>
+        = FilteredPostings<'b, I::Postings<'b>>
+    where
+        Self: 'b;
+
+    fn postings(&self, term: &Term) -> Option<Self::Postings<'_>> {
+        self.inner.postings(term).map(|inner| FilteredPostings {
+            inner,
+            allowed: &self.allowed,
+        })
+    }
+
+    fn documents(&self) -> Vec<&DocumentMetadata> {
+        self.inner
+            .documents()
+            .into_iter()
+            .filter(|metadata| self.allowed.contains(&metadata.id))
+            .collect()
+    }
+
+    fn document(&self, id: DocId) -> Option<&DocumentMetadata> {
+        self.inner.document(id)
+    }
+
+    fn contains(&self, id: DocId) -> bool {
+        self.allowed.contains(&id)
+    }
+
+    fn doc_id(&self, path: &Path) -> Option<DocId> {
+        self.inner.doc_id(path)
+    }
+
+    fn document_norm(&self, doc_id: DocId) -> Option<f64> {
+        self.inner.document_norm(doc_id)
+    }
+
+    fn num_docs(&self) -> usize {
+        self.inner.num_docs()
+    }
+
+    fn avg_doc_length(&self) -> f64 {
+        self.inner.avg_doc_length()
+    }
+
+    fn avg_field_length(&self, field: DocumentField) -> f64 {
+        self.inner.avg_field_length(field)
+    }
+
+    fn doc_freq(&self, term: &Term) -> usize {
+        self.inner.doc_freq(term)
+    }
+
+    fn total_token_count(&self) -> u64 {
+        self.inner.total_token_count()
+    }
+
+    fn vocabulary_size(&self) -> usize {
+        self.inner.vocabulary_size()
+    }
+
+    fn collection_frequency(&self, term: &Term) -> usize {
+        self.inner.collection_frequency(term)
+    }
+
+    fn quality_priors(&self) -> &QualityPriors {
+        self.inner.quality_priors()
+    }
+
+    fn dense(&self) -> Option<&DenseIndex> {
+        self.inner.dense()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{FilteredIndex, SearchFilter};
+    use crate::{
+        index::{InvertedIndex, RankedIndexReader},
+        test_support::{config_without_stop_words, write_temp_file},
+        tokenizer::FileType,
+    };
+
+    fn allowed_paths(index: &InvertedIndex, filter: &SearchFilter) -> Vec<PathBuf> {
+        let mut paths = FilteredIndex::new(index, filter)
+            .documents()
+            .into_iter()
+            .map(|metadata| metadata.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    fn index(dir: &Path) -> InvertedIndex {
+        write_temp_file(dir, "crates/core/src/lib.rs", "fn search() {}");
+        write_temp_file(dir, "crates/core/tests/search.rs", "fn search_test() {}");
+        write_temp_file(
+            dir,
+            "crates/core/src/generated/api.rs",
+            "// generated\nfn search() {}",
+        );
+        write_temp_file(dir, "docs/guide.md", &"search guide ".repeat(200));
+        InvertedIndex::new_fielded(dir, &config_without_stop_words(), Some(dir))
+    }
+
+    #[test]
+    fn empty_filter_admits_every_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+
+        assert!(SearchFilter::new().is_empty());
+        assert_eq!(allowed_paths(&index, &SearchFilter::new()).len(), 4);
+    }
+
+    #[test]
+    fn filters_by_type_glob_size_and_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+
+        let markdown = SearchFilter::new().with_file_types([FileType::Markdown]);
+        assert_eq!(
+            allowed_paths(&index, &markdown),
+            vec![PathBuf::from("docs/guide.md")]
+        );
+
+        let core_sources = SearchFilter::new()
+            .with_globs(["crates/core/**", "!**/tests/**"])
+            .unwrap()
+            .with_exclude_generated(true);
+        assert_eq!(
+            allowed_paths(&index, &core_sources),
+            vec![PathBuf::from("crates/core/src/lib.rs")]
+        );
+
+        let small_non_test = SearchFilter::new()
+            .with_max_size_bytes(1_000)
+            .with_exclude_tests(true);
+        assert_eq!(
+            allowed_paths(&index, &small_non_test),
+            vec![
+                PathBuf::from("crates/core/src/generated/api.rs"),
+                PathBuf::from("crates/core/src/lib.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn globs_match_paths_relative_to_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        index(dir.path());
+        let index =
+            InvertedIndex::new_fielded(dir.path(), &config_without_stop_words(), None::<&Path>);
+        let core_sources = SearchFilter::new()
+            .with_globs(["crates/core/src/**", "!**/generated/**"])
+            .unwrap();
+
+        assert!(allowed_paths(&index, &core_sources).is_empty());
+        assert_eq!(
+            allowed_paths(&index, &core_sources.clone().with_root(dir.path())),
+            vec![dir.path().join("crates/core/src/lib.rs")]
+        );
+        assert!(
+            core_sources
+                .with_root(".")
+                .matches_path(Path::new("./crates/core/src/lib.rs"))
+        );
+    }
+
+    #[test]
+    fn invalid_globs_are_reported() {
+        assert!(SearchFilter::new().with_globs(["a[b"]).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! Per-file signals from the local repository's commit log, read through
+//! the `git` command-line tool.
+//!
+//! The log is summarised once per file: when it last changed, how many
+//! commits and authors touched it, and which files changed alongside it.
+//! [`GitHistory::refresh`] folds in only the commits made since the
+//! history was read.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Commits read when there is no earlier history to extend.
+pub const HISTORY_COMMIT_LIMIT: usize = 10_000;
+/// Commits touching more files than this are left out of co-change
+/// counts; they are usually mass renames or formatting passes.
+const MAX_CO_CHANGE_FILES: usize = 50;
+/// Co-change neighbours kept per file.
+const MAX_NEIGHBOURS: usize = 8;
+/// Commits two files must share before they count as neighbours.
+const MIN_SHARED_COMMITS: usize = 2;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryError {
+    #[error("failed to run git in {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("git {command} failed in {path}: {stderr}")]
+    Git {
+        command: &'static str,
+        path: PathBuf,
+        stderr: String,
+    },
+}
+
+/// What the commit log says about one file.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct HistorySignals {
+    /// Days between the file's last commit and the newest commit in the
+    /// repository, so the value does not drift as the clock moves.
+    pub days_since_change: f64,
+    pub commit_count: usize,
+    pub author_count: usize,
+    /// Files most often committed together with this one, relative to the
+    /// repository root, most frequent first.
+    pub co_changed: Vec<PathBuf>,
+}
+
+impl HistorySignals {
+    /// Halves every `half_life_days` since the last change.
+    pub fn recency(&self, half_life_days: f64) -> f64 {
+        if half_life_days <= 0.0 {
+            return 0.0;
+        }
+
+        0.5_f64.powf(self.days_since_change / half_life_days)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+struct FileRecord {
+    last_commit_unix_secs: i64,
+    commit_count: usize,
+    authors: BTreeSet<String>,
+    co_changes: BTreeMap<PathBuf, usize>,
+}
+
+/// The commit log of one repository, summarised per file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GitHistory {
+    /// Repository root as `git` reports it; file keys are relative to it.
+    toplevel: PathBuf,
+    /// The commit the summary runs up to, if the repository has any.
+    head: Option<String>,
+    latest_unix_secs: i64,
+    files: BTreeMap<PathBuf, FileRecord>,
+}
+
+impl GitHistory {
+    /// Reads the history of the repository containing `dir`.
+    pub fn read(dir: &Path) -> Result<Self, HistoryError> {
+        let toplevel = PathBuf::from(git(dir, "rev-parse", &["--show-toplevel"])?.trim());
+        let mut history = Self {
+            toplevel,
+            head: None,
+            latest_unix_secs: 0,
+            files: BTreeMap::new(),
+        };
+        history.head = head(dir)?;
+        if history.head.is_some() {
+            let max_count = format!("--max-count={HISTORY_COMMIT_LIMIT}");
+            history.merge(&log(dir, &[&max_count, "HEAD"])?);
+        }
+        Ok(history)
+    }
+
+    /// Folds in the commits made since the history was read. Returns
+    /// whether anything changed. Rewritten history is read again in full.
+    pub fn refresh(&mut self, dir: &Path) -> Result<bool, HistoryError> {
+        let head = head(dir)?;
+        if head == self.head {
+            return Ok(false);
+        }
+
+        match (&self.head, &head) {
+            (Some(old), Some(new)) if is_ancestor(dir, old, new)? => {
+                let range = format!("{old}..{new}");
+                self.merge(&log(dir, &[&range])?);
+                self.head = head;
+            }
+            _ => *self = Self::read(dir)?,
+        }
+        Ok(true)
+    }
+
+    pub fn head(&self) -> Option<&str> {
+        self.head.as_deref()
+    }
+
+    /// Signals for `path`, which may be relative to the current directory
+    /// or absolute. Files `git` never saw have none.
+    pub fn signals(&self, path: &Path) -> Option<HistorySignals> {
+        let relative = self.repository_path(path)?;
+        let record = self.files.get(&relative)?;
+
+        let mut neighbours = record
+            .co_changes
+            .iter()
+            .filter(|(_, shared)| **shared >= MIN_SHARED_COMMITS)
+            .collect::<Vec<_>>();
+        neighbours.sort_by(|left, right| right.1.cmp(left.1).then_with(|| left.0.cmp(right.0)));
+
+        Some(HistorySignals {
+            days_since_change: (self.latest_unix_secs - record.last_commit_unix_secs).max(0) as f64
+                / SECONDS_PER_DAY,
+            commit_count: record.commit_count,
+            author_count: record.authors.len(),
+            co_changed: neighbours
+                .into_iter()
+                .take(MAX_NEIGHBOURS)
+                .map(|(path, _)| path.clone())
+                .collect(),
+        })
+    }
+
+    fn repository_path(&self, path: &Path) -> Option<PathBuf> {
+        let absolute = path
+            .canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .ok()?;
+        absolute
+            .strip_prefix(&self.toplevel)
+            .ok()
+            .map(Path::to_path_buf)
+    }
+
+    fn merge(&mut self, commits: &[Commit]) {
+        for commit in commits {
+            self.latest_unix_secs = self.latest_unix_secs.max(commit.unix_secs);
+            let co_change = commit.files.len() <= MAX_CO_CHANGE_FILES;
+            for file in &commit.files {
+                let record = self.files.entry(file.clone()).or_default();
+                record.last_commit_unix_secs = record.last_commit_unix_secs.max(commit.unix_secs);
+                record.commit_count += 1;
+                record.authors.insert(commit.author.clone());
+                if co_change {
+                    for other in commit.files.iter().filter(|other| *other != file) {
+                        *record.co_changes.entry(other.clone()).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Commit {
+    unix_secs: i64,
+    author: String,
+    files: Vec<PathBuf>,
+}
+
+fn head(dir: &Path) -> Result<Option<String>, HistoryError> {
+    let output = git_output(dir, &["rev-parse", "--verify", "--quiet", "HEAD"])?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+fn is_ancestor(dir: &Path, ancestor: &str, descendant: &str) -> Result<bool, HistoryError> {
+    let output = git_output(dir, &["merge-base", "--is-ancestor", ancestor, descendant])?;
+    Ok(output.status.success())
+}
+
+fn log(dir: &Path, range: &[&str]) -> Result<Vec<Commit>, HistoryError> {
+    let mut args = vec!["--no-merges", "--format=%x1e%ct%x1f%aE", "--name-only"];
+    args.extend(range);
+    Ok(parse_log(&git(dir, "log", &args)?))
+}
+
+/// Parses records of the form `\x1e<unix secs>\x1f<author>` followed by
+/// one changed path per line.
+fn parse_log(output: &str) -> Vec<Commit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let (unix_secs, author) = lines.next()?.split_once('\x1f')?;
+            Some(Commit {
+                unix_secs: unix_secs.trim().parse().ok()?,
+                author: author.trim().to_ascii_lowercase(),
+                files: lines
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+fn git(dir: &Path, command: &'static str, args: &[&str]) -> Result<String, HistoryError> {
+    let mut full_args = vec![command];
+    full_args.extend(args);
+    let output = git_output(dir, &full_args)?;
+    if !output.status.success() {
+        return Err(HistoryError::Git {
+            command,
+            path: dir.to_path_buf(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> Result<std::process::Output, HistoryError> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .output()
+        .map_err(|source| HistoryError::Io {
+            path: dir.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, path::Path, process::Command};
+
+    use super::GitHistory;
+
+    const DAY: i64 = 86_400;
+
+    pub(crate) fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    pub(crate) fn init_repo(dir: &Path) {
+        git(dir, &["init", "--quiet"]);
+        git(dir, &["config", "commit.gpgsign", "false"]);
+    }
+
+    /// Writes `files` and commits them as `author` at `day` days past the
+    /// epoch.
+    pub(crate) fn commit(dir: &Path, author: &str, day: i64, files: &[(&str, &str)]) {
+        for (name, content) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let date = format!("@{} +0000", day * DAY);
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["add", "--all"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                &format!("user.name={author}"),
+                "-c",
+                &format!("user.email={author}@example.com"),
+                "commit",
+                "--quiet",
+                "-m",
+                "change",
+            ])
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn summarises_age_churn_authors_and_co_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path());
+        commit(
+            dir.path(),
+            "ann",
+            1,
+            &[("src/a.rs", "1"), ("src/b.rs", "1")],
+        );
+        commit(
+            dir.path(),
+            "bob",
+            3,
+            &[("src/a.rs", "2"), ("src/b.rs", "2")],
+        );
+        commit(
+            dir.path(),
+            "ann",
+            5,
+            &[("src/a.rs", "3"), ("README.md", "x")],
+        );
+        commit(dir.path(), "ann", 11, &[("src/c.rs", "1")]);
+
+        let history = GitHistory::read(dir.path()).unwrap();
+        let a = history.signals(&dir.path().join("src/a.rs")).unwrap();
+        let b = history.signals(&dir.path().join("src/b.rs")).unwrap();
+
+        assert_eq!(a.commit_count, 3);
+        assert_eq!(a.author_count, 2);
+        assert_eq!(a.days_since_change, 6.0);
+        assert_eq!(a.co_changed, [Path::new("src/b.rs")]);
+        assert_eq!(b.days_since_change, 8.0);
+        assert!(a.recency(30.0) > b.recency(30.0));
+        assert!(history.signals(&dir.path().join("untracked.rs")).is_none());
+    }
+
+    #[test]
+    fn refresh_reads_only_new_commits_and_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path());
+        commit(dir.path(), "ann", 1, &[("a.rs", "1")]);
+        let mut history = GitHistory::read(dir.path()).unwrap();
+
+        assert!(!history.refresh(dir.path()).unwrap());
+
+        commit(dir.path(), "bob", 2, &[("a.rs", "2")]);
+        assert!(history.refresh(dir.path()).unwrap());
+
+        let signals = history.signals(&dir.path().join("a.rs")).unwrap();
+        assert_eq!(signals.commit_count, 2);
+        assert_eq!(signals.author_count, 2);
+        assert_eq!(history, GitHistory::read(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn empty_repository_has_no_signals() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path());
+        fs::write(dir.path().join("a.rs"), "").unwrap();
+
+        let history = GitHistory::read(dir.path()).unwrap();
+
+        assert!(history.head().is_none());
+        assert!(history.signals(&dir.path().join("a.rs")).is_none());
+    }
+}
@@ -0,0 +1,376 @@
+//! Hierarchical navigable small world graph over unit-length document
+//! vectors, for approximate nearest-neighbour search by cosine similarity.
+//!
+//! Every document sits on layer 0 and, with exponentially falling
+//! probability, on the sparser layers above it. A search descends greedily
+//! from the single entry point on the top layer and widens into a beam of
+//! `ef` candidates on layer 0. A document's layer comes from a hash of its
+//! id, so rebuilding the same corpus gives the same graph.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::index::DocId;
+
+/// Highest layer a document can be placed on.
+const MAX_LAYER: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Hnsw {
+    /// Neighbours kept per document on the upper layers; layer 0 keeps
+    /// twice as many.
+    max_neighbours: usize,
+    ef_construction: usize,
+    nodes: BTreeMap<DocId, HnswNode>,
+    entry_point: Option<DocId>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct HnswNode {
+    vector: Vec<f32>,
+    /// Neighbours on each layer from 0 up to the node's own layer.
+    neighbours: Vec<Vec<DocId>>,
+}
+
+/// A similarity ordered by value, so heaps can hold it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    similarity: f32,
+    id: DocId,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl Hnsw {
+    pub fn new(max_neighbours: usize, ef_construction: usize) -> Self {
+        Self {
+            max_neighbours: max_neighbours.max(2),
+            ef_construction: ef_construction.max(1),
+            nodes: BTreeMap::new(),
+            entry_point: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn vector(&self, id: DocId) -> Option<&[f32]> {
+        self.nodes.get(&id).map(|node| node.vector.as_slice())
+    }
+
+    pub fn vectors(&self) -> impl Iterator<Item = (DocId, &[f32])> {
+        self.nodes
+            .iter()
+            .map(|(id, node)| (*id, node.vector.as_slice()))
+    }
+
+    /// Adds `vector`, which must have unit length, replacing any vector
+    /// already stored for `id`.
+    pub fn insert(&mut self, id: DocId, vector: Vec<f32>) {
+        self.remove(id);
+
+        let layer = node_layer(id, self.max_neighbours);
+        let Some(mut entry) = self.entry_point else {
+            self.nodes.insert(
+                id,
+                HnswNode {
+                    vector,
+                    neighbours: vec![Vec::new(); layer + 1],
+                },
+            );
+            self.entry_point = Some(id);
+            return;
+        };
+
+        let top_layer = self.layer(entry);
+        for current in (layer + 1..=top_layer).rev() {
+            entry = self.greedy_closest(&vector, entry, current);
+        }
+
+        let mut neighbours = vec![Vec::new(); layer + 1];
+        let mut entries = vec![entry];
+        for current in (0..=layer.min(top_layer)).rev() {
+            let found = self.search_layer(&vector, &entries, self.ef_construction, current);
+            neighbours[current] = found
+                .iter()
+                .take(self.capacity(current))
+                .map(|candidate| candidate.id)
+                .collect();
+            entries = found.iter().map(|candidate| candidate.id).collect();
+        }
+
+        self.nodes.insert(
+            id,
+            HnswNode {
+                vector,
+                neighbours: neighbours.clone(),
+            },
+        );
+        for (current, layer_neighbours) in neighbours.into_iter().enumerate() {
+            for neighbour in layer_neighbours {
+                self.connect(neighbour, id, current);
+            }
+        }
+        if layer > top_layer {
+            self.entry_point = Some(id);
+        }
+    }
+
+    /// Drops `id` and links its former neighbours to each other so the
+    /// graph stays navigable.
+    pub fn remove(&mut self, id: DocId) {
+        let Some(node) = self.nodes.remove(&id) else {
+            return;
+        };
+
+        for (current, neighbours) in node.neighbours.iter().enumerate() {
+            for neighbour in neighbours {
+                if let Some(links) = self
+                    .nodes
+                    .get_mut(neighbour)
+                    .and_then(|node| node.neighbours.get_mut(current))
+                {
+                    links.retain(|link| *link != id);
+                }
+            }
+            for neighbour in neighbours {
+                for other in neighbours {
+                    if other != neighbour {
+                        self.connect(*neighbour, *other, current);
+                    }
+                }
+            }
+        }
+
+        if self.entry_point == Some(id) {
+            self.entry_point = self
+                .nodes
+                .iter()
+                .max_by_key(|(id, node)| (node.neighbours.len(), Reverse(**id)))
+                .map(|(id, _)| *id);
+        }
+    }
+
+    /// Up to `k` documents most similar to `query`, best first, searched
+    /// with a beam of `ef` candidates.
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(DocId, f32)> {
+        let Some(mut entry) = self.entry_point else {
+            return Vec::new();
+        };
+
+        for current in (1..=self.layer(entry)).rev() {
+            entry = self.greedy_closest(query, entry, current);
+        }
+        self.search_layer(query, &[entry], ef.max(k), 0)
+            .into_iter()
+            .take(k)
+            .map(|candidate| (candidate.id, candidate.similarity))
+            .collect()
+    }
+
+    fn layer(&self, id: DocId) -> usize {
+        self.nodes
+            .get(&id)
+            .map_or(0, |node| node.neighbours.len().saturating_sub(1))
+    }
+
+    fn capacity(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.max_neighbours * 2
+        } else {
+            self.max_neighbours
+        }
+    }
+
+    fn similarity(&self, query: &[f32], id: DocId) -> f32 {
+        self.nodes
+            .get(&id)
+            .map_or(f32::NEG_INFINITY, |node| dot(query, &node.vector))
+    }
+
+    fn greedy_closest(&self, query: &[f32], mut closest: DocId, layer: usize) -> DocId {
+        let mut best = self.similarity(query, closest);
+        loop {
+            let next = self
+                .neighbours(closest, layer)
+                .iter()
+                .map(|id| (*id, self.similarity(query, *id)))
+                .filter(|(_, similarity)| *similarity > best)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            match next {
+                Some((id, similarity)) => {
+                    closest = id;
+                    best = similarity;
+                }
+                None => return closest,
+            }
+        }
+    }
+
+    /// The `ef` closest documents reachable from `entries` on `layer`,
+    /// best first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[DocId],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited = entries.iter().copied().collect::<HashSet<_>>();
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for id in entries {
+            let candidate = Candidate {
+                similarity: self.similarity(query, *id),
+                id: *id,
+            };
+            candidates.push(candidate);
+            found.push(Reverse(candidate));
+        }
+        while found.len() > ef {
+            found.pop();
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = found
+                .peek()
+                .map_or(f32::NEG_INFINITY, |worst| worst.0.similarity);
+            if candidate.similarity < worst && found.len() >= ef {
+                break;
+            }
+            for id in self.neighbours(candidate.id, layer) {
+                if !visited.insert(*id) {
+                    continue;
+                }
+                let neighbour = Candidate {
+                    similarity: self.similarity(query, *id),
+                    id: *id,
+                };
+                let worst = found
+                    .peek()
+                    .map_or(f32::NEG_INFINITY, |worst| worst.0.similarity);
+                if found.len() < ef || neighbour.similarity > worst {
+                    candidates.push(neighbour);
+                    found.push(Reverse(neighbour));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        let mut found = found
+            .into_iter()
+            .map(|candidate| candidate.0)
+            .collect::<Vec<_>>();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+
+    fn neighbours(&self, id: DocId, layer: usize) -> &[DocId] {
+        self.nodes
+            .get(&id)
+            .and_then(|node| node.neighbours.get(layer))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Links `from` to `to` on `layer`, keeping only the closest links when
+    /// `from` has more than its capacity.
+    fn connect(&mut self, from: DocId, to: DocId, layer: usize) {
+        let capacity = self.capacity(layer);
+        let Some(node) = self.nodes.get(&from) else {
+            return;
+        };
+        let Some(links) = node.neighbours.get(layer) else {
+            return;
+        };
+        if links.contains(&to) || !self.nodes.contains_key(&to) {
+            return;
+        }
+
+        let mut links = links.clone();
+        links.push(to);
+        if links.len() > capacity {
+            let vector = &node.vector;
+            links.sort_by(|a, b| {
+                self.similarity(vector, *b)
+                    .total_cmp(&self.similarity(vector, *a))
+                    .then_with(|| a.cmp(b))
+            });
+            links.truncate(capacity);
+        }
+        if let Some(node) = self.nodes.get_mut(&from) {
+            node.neighbours[layer] = links;
+        }
+    }
+}
+
+pub(crate) fn dot(left: &[f32], right: &[f32]) -> f32 {
+    left.iter().zip(right).map(|(a, b)| a * b).sum()
+}
+
+/// Layer drawn from the exponential distribution HNSW uses, seeded by the
+/// document id.
+fn node_layer(id: DocId, max_neighbours: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    let uniform = ((hasher.finish() >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    let layer = -uniform.ln() / (max_neighbours as f64).ln();
+    (layer as usize).min(MAX_LAYER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hnsw;
+    use crate::index::DocId;
+
+    fn unit(angle: f32) -> Vec<f32> {
+        vec![angle.cos(), angle.sin()]
+    }
+
+    #[test]
+    fn finds_the_nearest_vectors_and_forgets_removed_ones() {
+        let mut graph = Hnsw::new(4, 16);
+        for i in 0..200 {
+            graph.insert(DocId::from_u32(i), unit(i as f32 * 0.03));
+        }
+
+        let nearest = graph.search(&unit(3.01), 3, 32);
+        let ids = nearest
+            .iter()
+            .map(|(id, _)| id.as_u32())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![100, 101, 99]);
+
+        graph.remove(DocId::from_u32(100));
+        let nearest = graph.search(&unit(3.01), 2, 32);
+        let ids = nearest
+            .iter()
+            .map(|(id, _)| id.as_u32())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![101, 99]);
+        assert_eq!(graph.len(), 199);
+    }
+}
@@ -0,0 +1,779 @@
+//! Resolves each file's imports to indexed files and measures how central
+//! every file is in the resulting graph.
+//!
+//! Imports are read from the `Import` features code intelligence extracts
+//! when there are any, and from the file content otherwise. Only imports
+//! that can point inside the repository are resolved: Rust `mod` and
+//! `crate`/`self`/`super` paths, relative and top-level Python modules,
+//! relative JavaScript/TypeScript specifiers and Go package paths.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Component, Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+use crate::tokenizer::FileType;
+
+/// Share of PageRank that follows import edges rather than jumping to a
+/// random file.
+const DAMPING: f64 = 0.85;
+const MAX_ITERATIONS: usize = 50;
+const CONVERGENCE: f64 = 1e-9;
+
+/// Where an import may point, before it is checked against the index.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportTarget {
+    /// The first of these paths that is indexed.
+    File(Vec<PathBuf>),
+    /// The first of these paths, relative to the corpus root, that is
+    /// indexed, the way top-level Python modules resolve.
+    Module(Vec<PathBuf>),
+    /// Every indexed file directly inside a directory the import path ends
+    /// with, the way Go imports a package.
+    Package(PathBuf),
+}
+
+/// How central a file is in the import graph.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImportCentrality {
+    /// Indexed files importing this one.
+    pub imported_by: usize,
+    /// PageRank scaled by the number of files, so 1.0 is average.
+    pub rank: f64,
+}
+
+/// The imports in `content`, or in `import_statements` when code
+/// intelligence found any, as they could resolve from `path`.
+pub fn import_targets(path: &Path, content: &str, import_statements: &[&str]) -> Vec<ImportTarget> {
+    let file_type = FileType::detect(path);
+    let statements = import_statements.join("\n");
+    let source = if statements.is_empty() {
+        content
+    } else {
+        &statements
+    };
+
+    match file_type {
+        FileType::Rust => rust_targets(path, source, content),
+        FileType::Python => python_targets(path, source),
+        FileType::JavaScript | FileType::TypeScript => script_targets(path, source),
+        FileType::Go => go_targets(source),
+        _ => Vec::new(),
+    }
+}
+
+/// Resolved import edges between indexed files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportGraph {
+    nodes: Vec<PathBuf>,
+    edges: Vec<BTreeSet<usize>>,
+}
+
+impl ImportGraph {
+    /// Resolves every document's import targets against the documents
+    /// themselves, matching paths relative to `root`, the corpus root the
+    /// document paths start with. Imports of files outside the index are
+    /// dropped.
+    pub fn resolve<'a>(
+        root: &Path,
+        documents: impl IntoIterator<Item = (&'a Path, &'a [ImportTarget])>,
+    ) -> Self {
+        let relative = |path: &'a Path| path.strip_prefix(root).unwrap_or(path);
+        let mut documents = documents.into_iter().collect::<Vec<_>>();
+        documents.sort_by_key(|(path, _)| *path);
+        let nodes = documents
+            .iter()
+            .map(|(path, _)| path.to_path_buf())
+            .collect::<Vec<_>>();
+        let by_path = documents
+            .iter()
+            .enumerate()
+            .map(|(node, (path, _))| (relative(path), node))
+            .collect::<HashMap<_, _>>();
+        let mut packages: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+        for (node, (path, _)) in documents.iter().enumerate() {
+            if let Some(dir) = relative(path)
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+            {
+                packages.entry(dir).or_default().push(node);
+            }
+        }
+        let first_indexed = |candidates: &'a [PathBuf], relative_to_root: bool| {
+            candidates
+                .iter()
+                .find_map(|candidate| {
+                    let candidate = if relative_to_root {
+                        candidate.as_path()
+                    } else {
+                        relative(candidate)
+                    };
+                    by_path.get(candidate).copied()
+                })
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        let edges = documents
+            .iter()
+            .enumerate()
+            .map(|(from, (_, targets))| {
+                targets
+                    .iter()
+                    .flat_map(|target| match target {
+                        ImportTarget::File(candidates) => first_indexed(candidates, false),
+                        ImportTarget::Module(candidates) => first_indexed(candidates, true),
+                        ImportTarget::Package(import_path) => package_suffixes(import_path)
+                            .find_map(|suffix| {
+                                packages.iter().find_map(|(dir, files)| {
+                                    dir.ends_with(&suffix).then(|| files.clone())
+                                })
+                            })
+                            .unwrap_or_default(),
+                    })
+                    .filter(|to| *to != from)
+                    .collect()
+            })
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    /// Files `path` imports.
+    pub fn imports(&self, path: &Path) -> Vec<&Path> {
+        self.nodes
+            .binary_search_by(|node| node.as_path().cmp(path))
+            .map(|node| {
+                self.edges[node]
+                    .iter()
+                    .map(|to| self.nodes[*to].as_path())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// In-degree and PageRank for every file in the graph.
+    pub fn centrality(&self) -> BTreeMap<&Path, ImportCentrality> {
+        let count = self.nodes.len();
+        let mut imported_by = vec![0; count];
+        for targets in &self.edges {
+            for to in targets {
+                imported_by[*to] += 1;
+            }
+        }
+
+        let ranks = self.pagerank();
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(node, path)| {
+                (
+                    path.as_path(),
+                    ImportCentrality {
+                        imported_by: imported_by[node],
+                        rank: ranks[node] * count as f64,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// PageRank with files that import nothing spreading their rank evenly.
+    fn pagerank(&self) -> Vec<f64> {
+        let count = self.nodes.len();
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let uniform = 1.0 / count as f64;
+        let mut ranks = vec![uniform; count];
+        for _ in 0..MAX_ITERATIONS {
+            let dangling = self
+                .edges
+                .iter()
+                .zip(&ranks)
+                .filter(|(targets, _)| targets.is_empty())
+                .map(|(_, rank)| rank)
+                .sum::<f64>();
+            let base = (1.0 - DAMPING) * uniform + DAMPING * dangling * uniform;
+            let mut next = vec![base; count];
+            for (from, targets) in self.edges.iter().enumerate() {
+                let share = DAMPING * ranks[from] / targets.len().max(1) as f64;
+                for to in targets {
+                    next[*to] += share;
+                }
+            }
+
+            let change = next
+                .iter()
+                .zip(&ranks)
+                .map(|(next, rank)| (next - rank).abs())
+                .sum::<f64>();
+            ranks = next;
+            if change < CONVERGENCE {
+                break;
+            }
+        }
+
+        ranks
+    }
+}
+
+/// `import_path` and each shorter suffix of it, longest first.
+fn package_suffixes(import_path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    let components = import_path.components().collect::<Vec<_>>();
+    (0..components.len()).map(move |skip| components[skip..].iter().collect())
+}
+
+static RUST_USE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+([^;]+);").expect("valid regex")
+});
+static RUST_MOD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;")
+        .expect("valid regex")
+});
+static PYTHON_FROM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*from\s+(\.*)([\w.]*)\s+import\s+(\([^)]*\)|[^\n#]+)").expect("valid regex")
+});
+static PYTHON_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*import\s+([\w.]+(?:\s*,\s*[\w.]+)*)").expect("valid regex")
+});
+static SCRIPT_SPECIFIER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)['"]([^'"]+)['"]"#)
+        .expect("valid regex")
+});
+static GO_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)\bimport\s*(?:\(([^)]*)\)|(?:[\w.]+\s+)?"([^"]+)")"#).expect("valid regex")
+});
+static GO_QUOTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""([^"]+)""#).expect("valid regex"));
+
+fn rust_targets(path: &Path, statements: &str, content: &str) -> Vec<ImportTarget> {
+    let module_dir = rust_module_dir(path);
+    let mut targets = RUST_MOD
+        .captures_iter(content)
+        .map(|captures| {
+            let child = module_dir.join(&captures[1]);
+            ImportTarget::File(vec![child.with_extension("rs"), child.join("mod.rs")])
+        })
+        .collect::<Vec<_>>();
+
+    for captures in RUST_USE.captures_iter(statements) {
+        for segments in expand_use_tree(&captures[1]) {
+            let Some((base, rest)) = rust_use_base(path, &module_dir, &segments) else {
+                continue;
+            };
+            // The longest module prefix that is a file wins; the remaining
+            // segments name items inside it.
+            let candidates = (0..=rest.len())
+                .rev()
+                .flat_map(|len| {
+                    let module = rest[..len]
+                        .iter()
+                        .fold(base.clone(), |dir, segment| dir.join(segment));
+                    rust_module_files(&module)
+                })
+                .collect::<Vec<_>>();
+            targets.push(ImportTarget::File(candidates));
+        }
+    }
+
+    targets
+}
+
+/// The directory holding the modules `path` declares.
+fn rust_module_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some("mod.rs" | "lib.rs" | "main.rs") => parent.to_path_buf(),
+        _ => match path.file_stem() {
+            Some(stem) => parent.join(stem),
+            None => parent.to_path_buf(),
+        },
+    }
+}
+
+/// The files that can define the module whose children live in `dir`.
+fn rust_module_files(dir: &Path) -> Vec<PathBuf> {
+    vec![
+        dir.with_extension("rs"),
+        dir.join("mod.rs"),
+        dir.join("lib.rs"),
+        dir.join("main.rs"),
+    ]
+}
+
+/// Splits a use path into the directory it starts from and the segments
+/// after `crate`, `self` or `super`. Paths into other crates have no base.
+fn rust_use_base<'a>(
+    path: &Path,
+    module_dir: &Path,
+    segments: &'a [String],
+) -> Option<(PathBuf, &'a [String])> {
+    let first = segments.first()?;
+    match first.as_str() {
+        "crate" => {
+            let root = path
+                .ancestors()
+                .skip(1)
+                .find(|dir| dir.file_name().is_some_and(|name| name == "src"))
+                .or_else(|| path.parent())
+                .unwrap_or(Path::new(""));
+            Some((root.to_path_buf(), &segments[1..]))
+        }
+        "self" => Some((module_dir.to_path_buf(), &segments[1..])),
+        "super" => {
+            let supers = segments
+                .iter()
+                .take_while(|segment| *segment == "super")
+                .count();
+            let mut dir = module_dir.to_path_buf();
+            for _ in 0..supers {
+                dir = dir.parent()?.to_path_buf();
+            }
+            Some((dir, &segments[supers..]))
+        }
+        _ => None,
+    }
+}
+
+/// Every path a use tree names: `a::{b, c::d as e}` gives `a::b` and
+/// `a::c::d`. Globs and trailing `self`s stop at the module they name.
+fn expand_use_tree(tree: &str) -> Vec<Vec<String>> {
+    let tree = tree.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut paths = Vec::new();
+    expand_into(&[], tree.trim(), &mut paths);
+    paths
+}
+
+fn expand_into(prefix: &[String], tree: &str, paths: &mut Vec<Vec<String>>) {
+    let tree = tree.trim().trim_start_matches("::");
+    if let Some(open) = tree.find('{') {
+        let mut path = prefix.to_vec();
+        path.extend(use_segments(&tree[..open]));
+        let inner = tree[open + 1..].trim_end().trim_end_matches('}');
+        for item in split_top_level(inner) {
+            expand_into(&path, item, paths);
+        }
+        return;
+    }
+
+    let tree = tree.split(" as ").next().unwrap_or_default();
+    let mut path = prefix.to_vec();
+    path.extend(use_segments(tree));
+    while path
+        .last()
+        .is_some_and(|last| last == "self" || last == "*")
+    {
+        path.pop();
+    }
+    if !path.is_empty() {
+        paths.push(path);
+    }
+}
+
+fn use_segments(path: &str) -> impl Iterator<Item = String> + '_ {
+    path.split("::")
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+}
+
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, character) in list.char_indices() {
+        match character {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(&list[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+    items.retain(|item| !item.trim().is_empty());
+    items
+}
+
+fn python_targets(path: &Path, statements: &str) -> Vec<ImportTarget> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut targets = Vec::new();
+
+    for captures in PYTHON_FROM.captures_iter(statements) {
+        let dots = captures[1].len();
+        let base = if dots == 0 {
+            Some(PathBuf::new())
+        } else {
+            dir.ancestors().nth(dots - 1).map(Path::to_path_buf)
+        };
+        let Some(base) = base else {
+            continue;
+        };
+        let module = python_module_path(&base, &captures[2]);
+        let names = captures[3].trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace());
+        for name in names.split(',') {
+            let name = name.split_whitespace().next().unwrap_or_default();
+            let mut candidates = if name.is_empty() || name == "*" {
+                Vec::new()
+            } else {
+                python_module_files(&module.join(name))
+            };
+            if !captures[2].is_empty() {
+                candidates.extend(python_module_files(&module));
+            }
+            if candidates.is_empty() {
+                continue;
+            }
+            // Absolute imports name modules from the root; relative ones
+            // from the importing file.
+            targets.push(if dots == 0 {
+                ImportTarget::Module(candidates)
+            } else {
+                ImportTarget::File(candidates)
+            });
+        }
+    }
+
+    for captures in PYTHON_IMPORT.captures_iter(statements) {
+        for module in captures[1].split(',') {
+            let module = python_module_path(Path::new(""), module.trim());
+            targets.push(ImportTarget::Module(python_module_files(&module)));
+        }
+    }
+
+    targets
+}
+
+fn python_module_path(base: &Path, dotted: &str) -> PathBuf {
+    dotted
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .fold(base.to_path_buf(), |path, segment| path.join(segment))
+}
+
+fn python_module_files(module: &Path) -> Vec<PathBuf> {
+    vec![module.with_extension("py"), module.join("__init__.py")]
+}
+
+const SCRIPT_EXTENSIONS: [&str; 6] = ["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+fn script_targets(path: &Path, statements: &str) -> Vec<ImportTarget> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    SCRIPT_SPECIFIER
+        .captures_iter(statements)
+        .filter(|captures| captures[1].starts_with("./") || captures[1].starts_with("../"))
+        .filter_map(|captures| {
+            let target = normalize(&dir.join(&captures[1]))?;
+            let mut candidates = Vec::new();
+            if let Some(extension) = target.extension().and_then(|extension| extension.to_str()) {
+                candidates.push(target.clone());
+                // TypeScript imports name the emitted `.js` file.
+                if matches!(extension, "js" | "jsx" | "mjs" | "cjs") {
+                    candidates
+                        .extend(["ts", "tsx"].map(|extension| target.with_extension(extension)));
+                }
+            }
+            let name = target.as_os_str().to_string_lossy();
+            candidates.extend(
+                SCRIPT_EXTENSIONS
+                    .iter()
+                    .map(|extension| PathBuf::from(format!("{name}.{extension}"))),
+            );
+            candidates.extend(
+                SCRIPT_EXTENSIONS
+                    .iter()
+                    .map(|extension| target.join(format!("index.{extension}"))),
+            );
+            Some(ImportTarget::File(candidates))
+        })
+        .collect()
+}
+
+fn go_targets(statements: &str) -> Vec<ImportTarget> {
+    GO_IMPORT
+        .captures_iter(statements)
+        .flat_map(|captures| match (captures.get(1), captures.get(2)) {
+            (Some(block), _) => GO_QUOTED
+                .captures_iter(block.as_str())
+                .map(|quoted| quoted[1].to_string())
+                .collect::<Vec<_>>(),
+            (None, Some(single)) => vec![single.as_str().to_string()],
+            (None, None) => Vec::new(),
+        })
+        .map(|import_path| ImportTarget::Package(PathBuf::from(import_path)))
+        .collect()
+}
+
+/// `path` with `.` and `..` resolved lexically, keeping a leading `./`
+/// so the result stays comparable with the indexed paths; `None` if it
+/// climbs above its first component.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    let mut floor = 0;
+    for (position, component) in path.components().enumerate() {
+        match component {
+            Component::CurDir if position == 0 => {
+                normalized.push(component);
+                floor = 1;
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if normalized.components().count() <= floor || !normalized.pop() {
+                    return None;
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{ImportGraph, ImportTarget, import_targets};
+
+    fn graph(files: &[(&str, &str)]) -> ImportGraph {
+        let targets = files
+            .iter()
+            .map(|(path, content)| {
+                (
+                    Path::new(*path),
+                    import_targets(Path::new(path), content, &[]),
+                )
+            })
+            .collect::<Vec<_>>();
+        ImportGraph::resolve(
+            Path::new(""),
+            targets
+                .iter()
+                .map(|(path, targets)| (*path, targets.as_slice())),
+        )
+    }
+
+    /// `files` indexed under `root` the way a corpus walked from it
+    /// stores their paths.
+    fn graph_under(root: &Path, files: &[(&str, &str)]) -> ImportGraph {
+        let targets = files
+            .iter()
+            .map(|(path, content)| {
+                let path = root.join(path);
+                let targets = import_targets(&path, content, &[]);
+                (path, targets)
+            })
+            .collect::<Vec<_>>();
+        ImportGraph::resolve(
+            root,
+            targets
+                .iter()
+                .map(|(path, targets)| (path.as_path(), targets.as_slice())),
+        )
+    }
+
+    #[test]
+    fn resolves_imports_under_dot_prefixed_and_absolute_roots() {
+        let files = [
+            (
+                "src/app.ts",
+                "import { util } from './util';
+import ui from '../ui';
+",
+            ),
+            ("src/util.ts", ""),
+            ("ui/index.ts", ""),
+            (
+                "pkg/models.py",
+                "from .db import session
+",
+            ),
+            ("pkg/db.py", ""),
+            (
+                "main.py",
+                "import pkg.models
+from pkg.db import session
+",
+            ),
+        ];
+
+        for root in [Path::new("."), Path::new("/work/repo")] {
+            let graph = graph_under(root, &files);
+
+            assert_eq!(
+                graph.imports(&root.join("src/app.ts")),
+                [root.join("src/util.ts"), root.join("ui/index.ts")],
+                "{}",
+                root.display()
+            );
+            assert_eq!(
+                graph.imports(&root.join("main.py")),
+                [root.join("pkg/db.py"), root.join("pkg/models.py")],
+                "{}",
+                root.display()
+            );
+            assert_eq!(
+                graph.imports(&root.join("pkg/models.py")),
+                [root.join("pkg/db.py")]
+            );
+            assert_eq!(
+                graph.centrality()[root.join("pkg/db.py").as_path()].imported_by,
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_rust_mod_declarations_and_crate_paths() {
+        let graph = graph(&[
+            ("src/lib.rs", "pub mod index;\nmod ranking;\n"),
+            (
+                "src/index/mod.rs",
+                "pub mod term;\nuse crate::ranking::{Scorer, bm25::BM25};\n",
+            ),
+            (
+                "src/index/term.rs",
+                "use super::super::ranking::Scorer;\nuse std::fmt;\n",
+            ),
+            ("src/ranking.rs", "pub mod bm25;\n"),
+            ("src/ranking/bm25.rs", "use crate::index::term::Term;\n"),
+        ]);
+
+        assert_eq!(
+            graph.imports(Path::new("src/lib.rs")),
+            [Path::new("src/index/mod.rs"), Path::new("src/ranking.rs")]
+        );
+        assert_eq!(
+            graph.imports(Path::new("src/index/mod.rs")),
+            [
+                Path::new("src/index/term.rs"),
+                Path::new("src/ranking/bm25.rs"),
+                Path::new("src/ranking.rs")
+            ]
+        );
+        assert_eq!(
+            graph.imports(Path::new("src/index/term.rs")),
+            [Path::new("src/ranking.rs")]
+        );
+        assert_eq!(
+            graph.imports(Path::new("src/ranking/bm25.rs")),
+            [Path::new("src/index/term.rs")]
+        );
+    }
+
+    #[test]
+    fn resolves_python_relative_and_top_level_imports() {
+        let graph = graph(&[
+            ("pkg/__init__.py", ""),
+            (
+                "pkg/models.py",
+                "from .db import session\nfrom . import util\n",
+            ),
+            ("pkg/db.py", "import os\n"),
+            (
+                "pkg/util.py",
+                "from ..pkg.models import (User,\n    Group)\n",
+            ),
+            ("main.py", "import pkg.models, json\n"),
+        ]);
+
+        assert_eq!(
+            graph.imports(Path::new("pkg/models.py")),
+            [Path::new("pkg/db.py"), Path::new("pkg/util.py")]
+        );
+        assert_eq!(
+            graph.imports(Path::new("pkg/util.py")),
+            [Path::new("pkg/models.py")]
+        );
+        assert_eq!(
+            graph.imports(Path::new("main.py")),
+            [Path::new("pkg/models.py")]
+        );
+    }
+
+    #[test]
+    fn resolves_relative_script_specifiers_and_index_files() {
+        let graph = graph(&[
+            (
+                "web/app.ts",
+                "import { api } from './api.js';\nimport ui from \"../ui\";\nimport React from 'react';\n",
+            ),
+            ("web/api.ts", "const x = require('./missing');\n"),
+            ("ui/index.tsx", "export * from './button';\n"),
+            ("ui/button.jsx", ""),
+        ]);
+
+        assert_eq!(
+            graph.imports(Path::new("web/app.ts")),
+            [Path::new("ui/index.tsx"), Path::new("web/api.ts")]
+        );
+        assert_eq!(
+            graph.imports(Path::new("ui/index.tsx")),
+            [Path::new("ui/button.jsx")]
+        );
+        assert!(graph.imports(Path::new("web/api.ts")).is_empty());
+    }
+
+    #[test]
+    fn resolves_go_packages_to_every_file_in_the_directory() {
+        let graph = graph(&[
+            (
+                "cmd/main.go",
+                "package main\n\nimport (\n\t\"fmt\"\n\tstore \"example.com/app/internal/store\"\n)\n",
+            ),
+            (
+                "internal/store/db.go",
+                "package store\nimport \"strings\"\n",
+            ),
+            ("internal/store/cache.go", "package store\n"),
+        ]);
+
+        assert_eq!(
+            graph.imports(Path::new("cmd/main.go")),
+            [
+                Path::new("internal/store/cache.go"),
+                Path::new("internal/store/db.go")
+            ]
+        );
+    }
+
+    #[test]
+    fn code_intelligence_statements_replace_the_content_scan() {
+        let targets = import_targets(
+            Path::new("src/lib.rs"),
+            "// use crate::commented::Out;\nmod search;\n",
+            &["use crate::index::Term;"],
+        );
+
+        assert_eq!(targets.len(), 2);
+        assert!(matches!(&targets[1], ImportTarget::File(candidates)
+            if candidates.contains(&PathBuf::from("src/index.rs"))));
+    }
+
+    #[test]
+    fn widely_imported_files_rank_highest() {
+        let graph = graph(&[
+            ("a.py", "from .core import x\n"),
+            ("b.py", "from .core import y\n"),
+            ("c.py", "from .core import z\nfrom .b import w\n"),
+            ("core.py", ""),
+        ]);
+
+        let centrality = graph.centrality();
+
+        assert_eq!(centrality[Path::new("core.py")].imported_by, 3);
+        assert_eq!(centrality[Path::new("a.py")].imported_by, 0);
+        assert!(centrality[Path::new("core.py")].rank > centrality[Path::new("b.py")].rank);
+        assert!(centrality[Path::new("b.py")].rank > centrality[Path::new("a.py")].rank);
+        let total = centrality.values().map(|node| node.rank).sum::<f64>();
+        assert!((total - 4.0).abs() < 1e-6);
+    }
+}
@@ -8,12 +8,17 @@ use crate::{
     config::Config,
     index::{
         corpus::{FileSystemIndexCorpus, IndexCorpus, IndexCorpusDocument, SkippedDocument},
+        dense::{DenseConfig, DenseIndex},
         document_registry::{
             DocId, DocumentCatalog, DocumentMetadata, DocumentMetadataUpdate, DocumentRegistry,
             FieldSpan,
         },
         field::DocumentField,
-        quality::StaticQualitySignals,
+        history::{GitHistory, HistoryError},
+        import_graph::{ImportGraph, ImportTarget, import_targets},
+        offsets::{FieldSegment, TokenOffsets, file_span},
+        quality::{QualityPriors, StaticQualitySignals},
+        sub_documents::SubDocumentIndex,
         term::Term,
     },
     ranking::idf,
@@ -131,6 +136,33 @@ pub struct InvertedIndex<R = DocumentRegistry> {
     postings: HashMap<Term, BTreeMap<DocId, TermDocument>>,
     documents: R,
     document_norms: HashMap<DocId, f64>,
+    /// Applied when ranking; not part of the indexed data.
+    #[serde(skip)]
+    quality_priors: QualityPriors,
+    /// Commit log the documents' history signals came from, kept to apply
+    /// to updated documents and to refresh from later commits.
+    #[serde(default)]
+    history: Option<IndexHistory>,
+    /// Latent semantic vectors for dense retrieval, kept in step with
+    /// document updates.
+    #[serde(default)]
+    dense: Option<DenseIndex>,
+    /// Symbols and sections indexed on their own, kept in step with their
+    /// files.
+    #[serde(default)]
+    sub_documents: Option<Box<SubDocumentIndex>>,
+    /// The corpus root document paths start with; imports resolve
+    /// relative to it.
+    #[serde(default)]
+    root: PathBuf,
+}
+
+/// A [`GitHistory`] and the directory document paths are resolved against
+/// when looking files up in it.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IndexHistory {
+    pub history: GitHistory,
+    pub base: PathBuf,
 }
 
 #[derive(Debug)]
@@ -146,6 +178,8 @@ struct ProcessedDocument {
     file_size_bytes: u64,
     file_type: FileType,
     quality_signals: StaticQualitySignals,
+    token_offsets: Option<TokenOffsets>,
+    imports: Vec<ImportTarget>,
 }
 
 #[cfg(test)]
@@ -162,6 +196,11 @@ impl InvertedIndex<DocumentRegistry> {
             postings,
             documents,
             document_norms,
+            quality_priors: QualityPriors::default(),
+            history: None,
+            dense: None,
+            sub_documents: None,
+            root: PathBuf::new(),
         }
     }
 
@@ -203,6 +242,11 @@ impl InvertedIndex<DocumentRegistry> {
             postings,
             documents,
             document_norms,
+            quality_priors: QualityPriors::default(),
+            history: None,
+            dense: None,
+            sub_documents: None,
+            root: PathBuf::new(),
         }
     }
 
@@ -235,6 +279,7 @@ impl InvertedIndex<DocumentRegistry> {
             let document = Self::analyze_document(document, &transform_fn);
             Self::insert_processed_document(&mut registry, &mut postings, document);
         }
+        Self::apply_import_centrality(&mut registry, &scan.root);
 
         let document_norms = Self::compute_document_norms(&postings, registry.len());
 
@@ -242,6 +287,11 @@ impl InvertedIndex<DocumentRegistry> {
             postings,
             documents: registry,
             document_norms,
+            quality_priors: QualityPriors::default(),
+            history: None,
+            dense: None,
+            sub_documents: None,
+            root: scan.root.clone(),
         };
         let report = IndexBuildReport {
             indexed_document_count: scan.indexed_document_count(),
@@ -277,6 +327,7 @@ impl InvertedIndex<DocumentRegistry> {
             let document = Self::analyze_fielded_document(document, config);
             Self::insert_processed_document(&mut registry, &mut postings, document);
         }
+        Self::apply_import_centrality(&mut registry, &scan.root);
 
         let document_norms = Self::compute_document_norms(&postings, registry.len());
 
@@ -284,6 +335,11 @@ impl InvertedIndex<DocumentRegistry> {
             postings,
             documents: registry,
             document_norms,
+            quality_priors: QualityPriors::default(),
+            history: None,
+            dense: None,
+            sub_documents: None,
+            root: scan.root.clone(),
         };
         let report = IndexBuildReport {
             indexed_document_count: scan.indexed_document_count(),
@@ -350,6 +406,8 @@ where
                 &document.content,
                 document.file_size_bytes,
             ),
+            token_offsets: None,
+            imports: import_targets(&document.path, &document.content, &[]),
         }
     }
 
@@ -358,29 +416,70 @@ where
         config: &Config,
     ) -> ProcessedDocument {
         let file_type = FileType::detect(&document.path);
-        let profile = AnalyzerProfile::for_file_type(file_type);
         let raw_fields = raw_document_fields(&document.path, &document.content);
+        let import_statements = raw_fields
+            .features
+            .iter()
+            .filter(|feature| feature.field == DocumentField::Import)
+            .map(|feature| feature.text.as_str())
+            .collect::<Vec<_>>();
+        let imports = import_targets(&document.path, &document.content, &import_statements);
+        let quality_signals = StaticQualitySignals::analyze(
+            &document.path,
+            &document.content,
+            document.file_size_bytes,
+        );
+
+        Self::analyze_fields(
+            document,
+            file_type,
+            raw_fields,
+            quality_signals,
+            imports,
+            config,
+        )
+    }
+
+    fn analyze_fields(
+        document: &IndexCorpusDocument,
+        file_type: FileType,
+        raw_fields: RawDocumentFields,
+        quality_signals: StaticQualitySignals,
+        imports: Vec<ImportTarget>,
+        config: &Config,
+    ) -> ProcessedDocument {
+        let profile = AnalyzerProfile::for_file_type(file_type);
         let mut field_term_frequencies = HashMap::new();
         let mut field_term_positions = HashMap::new();
         let mut field_lengths = HashMap::new();
         let mut term_frequencies = HashMap::new();
+        let mut token_offsets = TokenOffsets::for_content(&document.content);
 
         for field in DocumentField::ALL {
             let Some(raw_value) = raw_fields.values.get(&field) else {
                 continue;
             };
-            let tokens = profile.analyze(field.analyzer_field(), raw_value, config);
+            let tokens = profile.analyze_with_spans(field.analyzer_field(), raw_value, config);
             if tokens.is_empty() {
                 continue;
             }
 
             let mut positions: HashMap<Term, Vec<u32>> = HashMap::new();
-            for (position, token) in tokens.iter().enumerate() {
+            for (position, (token, _)) in tokens.iter().enumerate() {
                 positions
                     .entry(Term(token.clone()))
                     .or_default()
                     .push(position as u32);
             }
+            if let Some(segments) = raw_fields.segments.get(&field) {
+                token_offsets.insert(
+                    field,
+                    tokens
+                        .iter()
+                        .map(|(_, span)| file_span(segments, *span))
+                        .collect(),
+                );
+            }
 
             let mut frequencies = HashMap::new();
             for (term, term_positions) in &positions {
@@ -417,11 +516,9 @@ where
             token_length,
             file_size_bytes: document.file_size_bytes,
             file_type,
-            quality_signals: StaticQualitySignals::analyze(
-                &document.path,
-                &document.content,
-                document.file_size_bytes,
-            ),
+            quality_signals,
+            token_offsets: (!token_offsets.is_empty()).then_some(token_offsets),
+            imports,
         }
     }
 
@@ -469,7 +566,7 @@ where
         registry: &mut impl DocumentCatalog,
         postings: &mut HashMap<Term, BTreeMap<DocId, TermDocument>>,
         document: ProcessedDocument,
-    ) {
+    ) -> DocId {
         let doc_id = registry.insert_or_update_with_features(DocumentMetadataUpdate {
             path: document.path.clone(),
             token_length: document.token_length,
@@ -479,11 +576,229 @@ where
             field_spans: document.field_spans.clone(),
             features: document.features.clone(),
             quality_signals: document.quality_signals.clone(),
+            token_offsets: document.token_offsets.clone(),
+            imports: document.imports.clone(),
         });
 
         for (term, doc_map) in Self::postings_for_document(doc_id, &document) {
             postings.entry(term).or_default().extend(doc_map);
         }
+        doc_id
+    }
+
+    /// Weights for the static quality prior added to every ranked score.
+    pub fn with_quality_priors(mut self, quality_priors: QualityPriors) -> Self {
+        self.set_quality_priors(quality_priors);
+        self
+    }
+
+    pub(crate) fn set_quality_priors(&mut self, quality_priors: QualityPriors) {
+        if let Some(sub_documents) = self.sub_documents.as_mut() {
+            sub_documents.set_quality_priors(quality_priors.clone());
+        }
+        self.quality_priors = quality_priors;
+    }
+
+    pub fn quality_priors(&self) -> &QualityPriors {
+        &self.quality_priors
+    }
+
+    /// Adds Git history signals to every document. Document paths are
+    /// joined to `base` to find them in the history.
+    pub fn with_history(mut self, history: GitHistory, base: &Path) -> Self {
+        self.set_history(Some(IndexHistory {
+            history,
+            base: base.to_path_buf(),
+        }));
+        self
+    }
+
+    /// Restores a history whose signals the documents already carry.
+    pub(crate) fn with_root(mut self, root: PathBuf) -> Self {
+        self.root = root;
+        self
+    }
+
+    pub(crate) fn with_stored_history(mut self, history: Option<IndexHistory>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn history(&self) -> Option<&IndexHistory> {
+        self.history.as_ref()
+    }
+
+    /// Replaces the history, updating every document's signals; `None`
+    /// removes them.
+    pub fn set_history(&mut self, history: Option<IndexHistory>) {
+        self.history = history;
+        let ids = self
+            .documents
+            .iter()
+            .map(|metadata| metadata.id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            self.apply_history(id);
+        }
+    }
+
+    /// Folds commits made since the history was read into it, reading from
+    /// the repository containing `dir`. Returns whether any signals may
+    /// have changed; an index without history is left alone.
+    pub fn refresh_history(&mut self, dir: &Path) -> Result<bool, HistoryError> {
+        let Some(mut history) = self.history.take() else {
+            return Ok(false);
+        };
+        let refreshed = history.history.refresh(dir);
+        let changed = matches!(refreshed, Ok(true));
+        if changed {
+            self.set_history(Some(history));
+        } else {
+            self.history = Some(history);
+        }
+        refreshed
+    }
+
+    fn apply_history(&mut self, id: DocId) {
+        let signals = match (&self.history, self.documents.get(id)) {
+            (Some(history), Some(metadata)) => {
+                history.history.signals(&history.base.join(&metadata.path))
+            }
+            _ => None,
+        };
+        if let Some(quality_signals) = self.documents.quality_signals_mut(id) {
+            quality_signals.history = signals;
+        }
+    }
+
+    /// Fits a latent semantic model to the current documents, replacing any
+    /// earlier one.
+    pub fn build_dense(&mut self, config: &DenseConfig) {
+        self.dense = Some(DenseIndex::build(self, config));
+    }
+
+    pub fn dense(&self) -> Option<&DenseIndex> {
+        self.dense.as_ref()
+    }
+
+    pub fn set_dense(&mut self, dense: Option<DenseIndex>) {
+        self.dense = dense;
+    }
+
+    fn refresh_dense(&mut self, id: DocId) {
+        if let Some(mut dense) = self.dense.take() {
+            dense.refresh_document(self, id);
+            self.dense = Some(dense);
+        }
+    }
+
+    fn remove_dense(&mut self, id: DocId) {
+        if let Some(dense) = self.dense.as_mut() {
+            dense.remove_document(id);
+        }
+    }
+
+    /// Carves every document on disk into its symbols and sections,
+    /// replacing any earlier sub-document index.
+    pub fn build_sub_documents(&mut self, config: &Config) {
+        let mut sub_documents = SubDocumentIndex::new();
+        for metadata in self.documents.iter() {
+            if let Ok(content) = std::fs::read_to_string(&metadata.path) {
+                sub_documents.insert_file(
+                    &metadata.path,
+                    &content,
+                    &metadata.quality_signals,
+                    config,
+                );
+            }
+        }
+        sub_documents.refresh_document_norms();
+        self.set_sub_documents(Some(sub_documents));
+    }
+
+    pub fn sub_documents(&self) -> Option<&SubDocumentIndex> {
+        self.sub_documents.as_deref()
+    }
+
+    /// The file a result path points into: the parent file of a
+    /// sub-document, else `path` itself. A symbol that has since gone from
+    /// the index still names its file before the `#`.
+    pub fn file_path<'a>(&'a self, path: &'a Path) -> &'a Path {
+        if let Some(unit) = self
+            .sub_documents()
+            .and_then(|sub_documents| sub_documents.sub_document(path))
+        {
+            return &unit.parent;
+        }
+        if self.doc_id(path).is_some() {
+            return path;
+        }
+        path.to_str()
+            .and_then(|text| {
+                text.match_indices('#')
+                    .map(|(position, _)| Path::new(&text[..position]))
+                    .find(|file| self.doc_id(file).is_some())
+            })
+            .unwrap_or(path)
+    }
+
+    pub fn set_sub_documents(&mut self, sub_documents: Option<SubDocumentIndex>) {
+        self.sub_documents = sub_documents.map(|mut sub_documents| {
+            sub_documents.set_quality_priors(self.quality_priors.clone());
+            Box::new(sub_documents)
+        });
+    }
+
+    fn refresh_sub_documents(&mut self, id: DocId, content: &str, config: &Config) {
+        if let (Some(sub_documents), Some(metadata)) =
+            (self.sub_documents.as_mut(), self.documents.get(id))
+        {
+            sub_documents.refresh_file(&metadata.path, content, &metadata.quality_signals, config);
+        }
+    }
+
+    fn remove_sub_documents(&mut self, path: &Path) {
+        if let Some(sub_documents) = self.sub_documents.as_mut() {
+            sub_documents.remove_file(path);
+        }
+    }
+
+    /// Indexes `content` as a document holding just `features`, as
+    /// sub-documents are. Document norms are left for the caller to
+    /// refresh once everything is inserted.
+    pub(crate) fn insert_features(
+        &mut self,
+        path: PathBuf,
+        content: &str,
+        file_type: FileType,
+        features: &[DocumentFeature],
+        quality_signals: StaticQualitySignals,
+        config: &Config,
+    ) -> DocId {
+        self.remove_features(&path);
+        let document = Self::analyze_fields(
+            &IndexCorpusDocument {
+                path,
+                content: content.to_string(),
+                file_size_bytes: content.len() as u64,
+            },
+            file_type,
+            collect_raw_fields(content, features),
+            quality_signals,
+            Vec::new(),
+            config,
+        );
+        Self::insert_processed_document(&mut self.documents, &mut self.postings, document)
+    }
+
+    pub(crate) fn remove_features(&mut self, path: &Path) {
+        if let Some(metadata) = self.documents.remove(path) {
+            self.remove_postings_for_doc(metadata.id);
+        }
+    }
+
+    pub(crate) fn refresh_document_norms(&mut self) {
+        self.document_norms = Self::compute_document_norms(&self.postings, self.documents.len());
     }
 
     pub fn get_postings(&self, term: &Term) -> Option<&BTreeMap<DocId, TermDocument>> {
@@ -594,12 +909,23 @@ where
                     },
                     transform_fn,
                 );
-                Self::insert_processed_document(&mut self.documents, &mut self.postings, document);
-                self.rebuild_document_norms();
+                let doc_id = Self::insert_processed_document(
+                    &mut self.documents,
+                    &mut self.postings,
+                    document,
+                );
+                self.apply_history(doc_id);
+                self.refresh_dense(doc_id);
+                // Symbols need the fielded analysis this update skips.
+                self.remove_sub_documents(path);
+                self.rebuild_document_statistics();
             }
             Err(_) => {
-                self.documents.remove(path);
-                self.rebuild_document_norms();
+                if let Some(metadata) = self.documents.remove(path) {
+                    self.remove_dense(metadata.id);
+                }
+                self.remove_sub_documents(path);
+                self.rebuild_document_statistics();
             }
         }
     }
@@ -611,20 +937,27 @@ where
 
         match std::fs::read_to_string(path) {
             Ok(content) => {
-                let document = Self::analyze_fielded_document(
-                    &IndexCorpusDocument {
-                        path: path.to_path_buf(),
-                        file_size_bytes: content.len() as u64,
-                        content,
-                    },
-                    config,
+                let document = IndexCorpusDocument {
+                    path: path.to_path_buf(),
+                    file_size_bytes: content.len() as u64,
+                    content,
+                };
+                let doc_id = Self::insert_processed_document(
+                    &mut self.documents,
+                    &mut self.postings,
+                    Self::analyze_fielded_document(&document, config),
                 );
-                Self::insert_processed_document(&mut self.documents, &mut self.postings, document);
-                self.rebuild_document_norms();
+                self.apply_history(doc_id);
+                self.refresh_dense(doc_id);
+                self.refresh_sub_documents(doc_id, &document.content, config);
+                self.rebuild_document_statistics();
             }
             Err(_) => {
-                self.documents.remove(path);
-                self.rebuild_document_norms();
+                if let Some(metadata) = self.documents.remove(path) {
+                    self.remove_dense(metadata.id);
+                }
+                self.remove_sub_documents(path);
+                self.rebuild_document_statistics();
             }
         }
     }
@@ -632,7 +965,9 @@ where
     pub fn remove_document(&mut self, path: &Path) {
         if let Some(metadata) = self.documents.remove(path) {
             self.remove_postings_for_doc(metadata.id);
-            self.rebuild_document_norms();
+            self.remove_dense(metadata.id);
+            self.remove_sub_documents(path);
+            self.rebuild_document_statistics();
         }
     }
 
@@ -643,8 +978,41 @@ where
         });
     }
 
-    fn rebuild_document_norms(&mut self) {
+    /// Import edges between indexed files.
+    pub fn import_graph(&self) -> ImportGraph {
+        Self::resolve_import_graph(&self.documents, &self.root)
+    }
+
+    /// Refreshes everything that depends on more than one document after
+    /// an incremental change.
+    fn rebuild_document_statistics(&mut self) {
         self.document_norms = Self::compute_document_norms(&self.postings, self.documents.len());
+        Self::apply_import_centrality(&mut self.documents, &self.root);
+    }
+
+    fn resolve_import_graph(registry: &R, root: &Path) -> ImportGraph {
+        ImportGraph::resolve(
+            root,
+            registry
+                .iter()
+                .map(|metadata| (metadata.path.as_path(), metadata.imports.as_slice())),
+        )
+    }
+
+    fn apply_import_centrality(registry: &mut R, root: &Path) {
+        let graph = Self::resolve_import_graph(registry, root);
+        let centrality = graph
+            .centrality()
+            .into_iter()
+            .filter_map(|(path, centrality)| Some((registry.doc_id(path)?, centrality)))
+            .collect::<Vec<_>>();
+
+        for (doc_id, centrality) in centrality {
+            if let Some(signals) = registry.quality_signals_mut(doc_id) {
+                signals.imported_by = centrality.imported_by;
+                signals.import_centrality = centrality.rank;
+            }
+        }
     }
 
     fn compute_document_norms(
@@ -673,16 +1041,23 @@ where
 #[derive(Debug, Default)]
 struct RawDocumentFields {
     values: HashMap<DocumentField, String>,
+    segments: HashMap<DocumentField, Vec<FieldSegment>>,
     spans: Vec<FieldSpan>,
     features: Vec<DocumentFeature>,
 }
 
 fn raw_document_fields(path: &Path, content: &str) -> RawDocumentFields {
     let file_type = FileType::detect(path);
-    let features = document_features(path, content, file_type);
+    collect_raw_fields(
+        content,
+        document_features(path, content, file_type).features(),
+    )
+}
+
+fn collect_raw_fields(content: &str, features: &[DocumentFeature]) -> RawDocumentFields {
     let mut fields = RawDocumentFields::default();
 
-    for feature in features.features() {
+    for feature in features {
         if feature.text.trim().is_empty() {
             continue;
         }
@@ -691,6 +1066,22 @@ fn raw_document_fields(path: &Path, content: &str) -> RawDocumentFields {
         if !field_value.is_empty() {
             field_value.push('\n');
         }
+        let file_start = match feature.span {
+            _ if feature.field == DocumentField::Content => Some(0),
+            Some(span) if content.get(span.start..span.end) == Some(feature.text.as_str()) => {
+                Some(span.start)
+            }
+            _ => None,
+        };
+        fields
+            .segments
+            .entry(feature.field)
+            .or_default()
+            .push(FieldSegment {
+                field_start: field_value.len(),
+                len: feature.text.len(),
+                file_start,
+            });
         field_value.push_str(&feature.text);
 
         if let Some(span) = feature.span {
@@ -727,6 +1118,7 @@ fn document_features(path: &Path, content: &str, file_type: FileType) -> Documen
             field: DocumentField::FileName,
             text: file_name.to_string(),
             span: None,
+            scope: None,
         });
     }
 
@@ -734,6 +1126,7 @@ fn document_features(path: &Path, content: &str, file_type: FileType) -> Documen
         field: DocumentField::RelativePath,
         text: path.to_string_lossy().into_owned(),
         span: None,
+        scope: None,
     });
 
     if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
@@ -741,6 +1134,7 @@ fn document_features(path: &Path, content: &str, file_type: FileType) -> Documen
             field: DocumentField::Extension,
             text: extension.to_string(),
             span: None,
+            scope: None,
         });
     }
 
@@ -748,11 +1142,13 @@ fn document_features(path: &Path, content: &str, file_type: FileType) -> Documen
         field: DocumentField::Content,
         text: content.to_string(),
         span: None,
+        scope: None,
     });
     features.push(DocumentFeature {
         field: DocumentField::Identifier,
         text: extract_identifier_lexemes(content).join(" "),
         span: None,
+        scope: None,
     });
 
     let parser_fields = crate::code_intelligence::extract(file_type, path, content)
@@ -768,6 +1164,7 @@ fn document_features(path: &Path, content: &str, file_type: FileType) -> Documen
                     field: DocumentField::Comment,
                     text,
                     span: None,
+                    scope: None,
                 }),
         );
         features.extend(
@@ -777,6 +1174,7 @@ fn document_features(path: &Path, content: &str, file_type: FileType) -> Documen
                     field: DocumentField::StringLiteral,
                     text,
                     span: None,
+                    scope: None,
                 }),
         );
     }
@@ -927,20 +1325,20 @@ fn extract_string_literals(content: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use std::{
-        collections::{BTreeMap, HashMap, HashSet},
+        collections::{BTreeMap, HashMap},
         path::{Path, PathBuf},
     };
 
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
-
     use super::InvertedIndex;
     use crate::{
-        config::Config,
         index::{
-            DocId, DocumentField, IndexSkipReason, document_registry::DocumentRegistry, term::Term,
+            DocId, DocumentField, GitHistory, IndexSkipReason,
+            document_registry::DocumentRegistry,
+            history::tests::{commit, init_repo},
+            term::Term,
         },
         ranking::idf,
+        test_support::{test_config, write_temp_file},
         tokenizer::FileType,
     };
 
@@ -1130,14 +1528,6 @@ mod tests {
         );
     }
 
-    fn write_temp_file(dir: &std::path::Path, name: &str, content: &str) {
-        let path = dir.join(name);
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
-        }
-        std::fs::write(path, content).unwrap();
-    }
-
     fn identity_transform(content: &str) -> HashMap<Term, u32> {
         content
             .split_whitespace()
@@ -1147,17 +1537,6 @@ mod tests {
             })
     }
 
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect::<HashSet<String>>(),
-        }
-    }
-
     #[test]
     fn new_with_drop_prefix_indexes_files_and_strips_paths() {
         let dir = tempfile::tempdir().unwrap();
@@ -1515,6 +1894,86 @@ impl Scorer for BM25 {
         assert_eq!(index.avg_doc_length(), 0.0);
     }
 
+    #[test]
+    fn import_centrality_follows_incremental_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "app/core.py", "def run(): pass\n");
+        write_temp_file(dir.path(), "app/a.py", "from .core import run\n");
+        write_temp_file(dir.path(), "app/b.py", "import json\n");
+        let config = test_config();
+        let mut index = InvertedIndex::new_fielded(dir.path(), &config, None::<&Path>);
+        let signals = |index: &InvertedIndex, name: &str| {
+            let path = dir.path().join(name);
+            let id = index.doc_id(&path).unwrap();
+            index.document(id).unwrap().quality_signals.clone()
+        };
+
+        assert_eq!(signals(&index, "app/core.py").imported_by, 1);
+        let before = signals(&index, "app/core.py").import_centrality;
+        assert!(before > signals(&index, "app/b.py").import_centrality);
+
+        write_temp_file(dir.path(), "app/b.py", "from . import core\n");
+        index.update_fielded(&dir.path().join("app/b.py"), &config);
+
+        assert_eq!(signals(&index, "app/core.py").imported_by, 2);
+        assert!(signals(&index, "app/core.py").import_centrality > before);
+
+        index.remove_document(&dir.path().join("app/a.py"));
+
+        assert_eq!(signals(&index, "app/core.py").imported_by, 1);
+    }
+
+    #[test]
+    fn top_level_imports_resolve_in_an_absolute_corpus() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pkg/core.py", "def run(): pass\n");
+        write_temp_file(dir.path(), "main.py", "import pkg.core\n");
+        write_temp_file(dir.path(), "web/util.ts", "export const x = 1;\n");
+        write_temp_file(dir.path(), "web/app.ts", "import { x } from './util';\n");
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), None::<&Path>);
+        let imported_by = |name: &str| {
+            let id = index.doc_id(&dir.path().join(name)).unwrap();
+            index.document(id).unwrap().quality_signals.imported_by
+        };
+
+        assert_eq!(imported_by("pkg/core.py"), 1);
+        assert_eq!(imported_by("web/util.ts"), 1);
+        assert_eq!(imported_by("main.py"), 0);
+    }
+
+    #[test]
+    fn history_signals_survive_updates_and_follow_new_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path());
+        commit(
+            dir.path(),
+            "ann",
+            1,
+            &[("a.txt", "old"), ("b.txt", "other")],
+        );
+        let history = GitHistory::read(dir.path()).unwrap();
+        let mut index = InvertedIndex::new(dir.path(), identity_transform, None::<&Path>)
+            .with_history(history, Path::new(""));
+        let path = dir.path().join("a.txt");
+        let commits = |index: &InvertedIndex| {
+            let id = index.doc_id(&path).unwrap();
+            let signals = &index.document(id).unwrap().quality_signals;
+            signals.history.as_ref().map(|history| history.commit_count)
+        };
+        assert_eq!(commits(&index), Some(1));
+
+        write_temp_file(dir.path(), "a.txt", "new");
+        index.update(&path, &identity_transform);
+        assert_eq!(commits(&index), Some(1));
+
+        commit(dir.path(), "bob", 2, &[("a.txt", "new")]);
+        assert!(index.refresh_history(dir.path()).unwrap());
+        assert_eq!(commits(&index), Some(2));
+
+        index.set_history(None);
+        assert_eq!(commits(&index), None);
+    }
+
     #[test]
     fn remove_document_removes_only_that_document() {
         let mut index = build_index(&[
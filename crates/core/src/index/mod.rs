@@ -1,31 +1,46 @@
 pub mod compression;
 pub mod corpus;
+pub mod dense;
 pub mod document_registry;
 pub mod engine;
 pub mod event_log;
 pub mod field;
+pub mod filter;
+pub mod history;
+pub mod hnsw;
+pub mod import_graph;
 pub mod inverted_file;
 pub mod inverted_index;
+pub mod offsets;
 pub mod quality;
 pub mod reader;
 pub mod skips;
 pub mod snapshot;
+pub mod sub_documents;
+pub mod symbols;
 pub mod term;
 
 pub use corpus::{
     FileSystemIndexCorpus, IndexCorpus, IndexCorpusDocument, IndexCorpusScan, IndexSkipReason,
     SkippedDocument,
 };
+pub use dense::{DenseConfig, DenseIndex, LatentSemanticModel};
 pub use document_registry::{
     DocId, DocumentCatalog, DocumentMetadata, DocumentMetadataUpdate, DocumentRegistry, FieldSpan,
 };
 pub use engine::{SearchEngine, SearchEngineError};
 pub use event_log::IndexEvent;
 pub use field::DocumentField;
+pub use filter::{FilteredIndex, SearchFilter, SearchFilterError};
+pub use history::{GitHistory, HistoryError, HistorySignals};
+pub use import_graph::{ImportCentrality, ImportGraph, ImportTarget, import_targets};
 pub use inverted_index::{
-    CorpusStats, IndexBuildReport, IndexBuildResult, InvertedIndex, PositionList, TermDocument,
-    TermFrequencySummary,
+    CorpusStats, IndexBuildReport, IndexBuildResult, IndexHistory, InvertedIndex, PositionList,
+    TermDocument, TermFrequencySummary,
 };
-pub use quality::StaticQualitySignals;
+pub use offsets::TokenOffsets;
+pub use quality::{QualityPriors, StaticQualitySignals};
 pub use reader::{OwnedPostingList, PostingList, RankedIndexReader};
+pub use sub_documents::{SubDocument, SubDocumentIndex};
+pub use symbols::{SymbolLocation, find_symbols};
 pub use term::Term;
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{code_intelligence::ByteSpan, index::DocumentField};
+
+/// Byte spans of indexed tokens, per field and token position.
+///
+/// Spans point into the original file. A position is `None` when its token
+/// came from text that does not appear verbatim in the file, such as the
+/// joined identifier list. Line starts let spans be reported as lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TokenOffsets {
+    fields: HashMap<DocumentField, Vec<Option<(usize, usize)>>>,
+    #[serde(default)]
+    line_starts: Vec<usize>,
+    #[serde(default)]
+    content_len: usize,
+}
+
+impl TokenOffsets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offsets for a file with the given content, with its line starts
+    /// recorded and no fields yet.
+    pub fn for_content(content: &str) -> Self {
+        Self {
+            fields: HashMap::new(),
+            content_len: content.len(),
+            line_starts: std::iter::once(0)
+                .chain(
+                    content
+                        .match_indices('\n')
+                        .map(|(offset, _)| offset + 1)
+                        .filter(|&start| start < content.len()),
+                )
+                .collect(),
+        }
+    }
+
+    /// Records the spans of `field`, indexed by token position. Fields in
+    /// which no token maps back to the file are not stored.
+    pub fn insert(&mut self, field: DocumentField, spans: Vec<Option<ByteSpan>>) {
+        if spans.iter().all(Option::is_none) {
+            return;
+        }
+
+        self.fields.insert(
+            field,
+            spans
+                .into_iter()
+                .map(|span| span.map(|span| (span.start, span.end)))
+                .collect(),
+        );
+    }
+
+    pub fn span(&self, field: DocumentField, position: u32) -> Option<ByteSpan> {
+        self.fields
+            .get(&field)?
+            .get(position as usize)
+            .copied()
+            .flatten()
+            .map(|(start, end)| ByteSpan { start, end })
+    }
+
+    /// The span from the token at `start` to the token at `end`, inclusive.
+    pub fn span_between(&self, field: DocumentField, start: u32, end: u32) -> Option<ByteSpan> {
+        let first = self.span(field, start)?;
+        let last = self.span(field, end)?;
+        Some(ByteSpan {
+            start: first.start.min(last.start),
+            end: first.end.max(last.end),
+        })
+    }
+
+    /// One-based line containing `offset`, or `None` when line starts were
+    /// not recorded.
+    pub fn line_of(&self, offset: usize) -> Option<usize> {
+        if self.line_starts.is_empty() {
+            return None;
+        }
+        Some(self.line_starts.partition_point(|&start| start <= offset))
+    }
+
+    /// Byte range of the one-based lines `start_line..=end_line`, including
+    /// the trailing newline of the last one.
+    pub fn line_byte_range(&self, start_line: usize, end_line: usize) -> Option<Range<usize>> {
+        if start_line == 0 || start_line > end_line {
+            return None;
+        }
+        let start = *self.line_starts.get(start_line - 1)?;
+        let end = self
+            .line_starts
+            .get(end_line)
+            .copied()
+            .unwrap_or(self.content_len);
+        Some(start..end)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    pub fn has_field(&self, field: DocumentField) -> bool {
+        self.fields.contains_key(&field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+/// Where one piece of a raw field value came from in the file.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FieldSegment {
+    /// Byte offset of the piece inside the joined field value.
+    pub(crate) field_start: usize,
+    pub(crate) len: usize,
+    /// Byte offset of the piece inside the file, when it is a verbatim slice.
+    pub(crate) file_start: Option<usize>,
+}
+
+/// Maps a span inside a joined field value back to the file.
+pub(crate) fn file_span(segments: &[FieldSegment], span: ByteSpan) -> Option<ByteSpan> {
+    let segment = segments.iter().find(|segment| {
+        segment.field_start <= span.start && span.end <= segment.field_start + segment.len
+    })?;
+    let file_start = segment.file_start?;
+
+    Some(ByteSpan {
+        start: file_start + span.start - segment.field_start,
+        end: file_start + span.end - segment.field_start,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldSegment, TokenOffsets, file_span};
+    use crate::{code_intelligence::ByteSpan, index::DocumentField};
+
+    #[test]
+    fn maps_field_spans_through_verbatim_segments_only() {
+        let segments = [
+            FieldSegment {
+                field_start: 0,
+                len: 10,
+                file_start: Some(40),
+            },
+            FieldSegment {
+                field_start: 11,
+                len: 5,
+                file_start: None,
+            },
+        ];
+
+        assert_eq!(
+            file_span(&segments, ByteSpan { start: 2, end: 6 }),
+            Some(ByteSpan { start: 42, end: 46 })
+        );
+        assert_eq!(file_span(&segments, ByteSpan { start: 12, end: 14 }), None);
+        assert_eq!(file_span(&segments, ByteSpan { start: 8, end: 12 }), None);
+    }
+
+    #[test]
+    fn stores_only_fields_with_mapped_tokens() {
+        let mut offsets = TokenOffsets::new();
+        offsets.insert(DocumentField::Identifier, vec![None, None]);
+        offsets.insert(
+            DocumentField::Content,
+            vec![
+                Some(ByteSpan { start: 0, end: 5 }),
+                None,
+                Some(ByteSpan { start: 9, end: 14 }),
+            ],
+        );
+
+        assert!(!offsets.has_field(DocumentField::Identifier));
+        assert_eq!(
+            offsets.span(DocumentField::Content, 2),
+            Some(ByteSpan { start: 9, end: 14 })
+        );
+        assert_eq!(offsets.span(DocumentField::Content, 1), None);
+        assert_eq!(
+            offsets.span_between(DocumentField::Content, 0, 2),
+            Some(ByteSpan { start: 0, end: 14 })
+        );
+        assert_eq!(offsets.line_of(3), None);
+    }
+
+    #[test]
+    fn maps_offsets_to_one_based_lines() {
+        let offsets = TokenOffsets::for_content("one\ntwo\n\nfour\n");
+
+        assert_eq!(offsets.line_count(), 4);
+        assert_eq!(offsets.line_of(0), Some(1));
+        assert_eq!(offsets.line_of(3), Some(1));
+        assert_eq!(offsets.line_of(4), Some(2));
+        assert_eq!(offsets.line_of(8), Some(3));
+        assert_eq!(offsets.line_of(12), Some(4));
+        assert_eq!(offsets.line_byte_range(2, 3), Some(4..9));
+        assert_eq!(offsets.line_byte_range(4, 9), Some(9..14));
+        assert_eq!(offsets.line_byte_range(5, 5), None);
+    }
+}
@@ -3,7 +3,9 @@ use std::{
     path::{Component, Path},
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+use crate::index::HistorySignals;
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
 pub struct StaticQualitySignals {
     pub file_depth: usize,
     pub file_size_bytes: u64,
@@ -15,6 +17,15 @@ pub struct StaticQualitySignals {
     pub config_or_manifest: bool,
     pub public_entry_point: bool,
     pub reference_count: usize,
+    /// Indexed files importing this one.
+    #[serde(default)]
+    pub imported_by: usize,
+    /// PageRank over the import graph, where 1.0 is an average file.
+    #[serde(default)]
+    pub import_centrality: f64,
+    /// Only set when the index was built with Git history.
+    #[serde(default)]
+    pub history: Option<HistorySignals>,
 }
 
 impl StaticQualitySignals {
@@ -54,52 +65,20 @@ impl StaticQualitySignals {
             config_or_manifest,
             public_entry_point,
             reference_count,
+            imported_by: 0,
+            import_centrality: 0.0,
+            history: None,
         }
     }
 
+    /// Static prior under the default [`QualityPriors`].
     pub fn prior_score(&self) -> f64 {
-        let mut score = 0.0;
-
-        if self.generated {
-            score -= 0.55;
-        }
-        if self.vendor {
-            score -= 0.45;
-        }
-        if self.test {
-            score -= 0.08;
-        }
-        if self.entry_point {
-            score += 0.28;
-        }
-        if self.readme {
-            score += 0.22;
-        }
-        if self.config_or_manifest {
-            score += 0.20;
-        }
-        if self.public_entry_point {
-            score += 0.12;
-        }
-
-        score += match self.file_depth {
-            0 | 1 => 0.10,
-            2 => 0.04,
-            3..=5 => 0.0,
-            _ => -0.08,
-        };
-
-        if self.file_size_bytes > 750_000 {
-            score -= 0.18;
-        } else if self.file_size_bytes > 250_000 {
-            score -= 0.08;
-        }
-
-        score + (self.reference_count.min(12) as f64 * 0.015)
+        QUALITY_PRIORS.score(self)
     }
 
     pub fn features(&self) -> HashMap<&'static str, f64> {
-        HashMap::from([
+        let history = self.history.as_ref();
+        let mut features = HashMap::from([
             ("file_depth", self.file_depth as f64),
             ("file_size_bytes", self.file_size_bytes as f64),
             ("generated", bool_feature(self.generated)),
@@ -110,8 +89,163 @@ impl StaticQualitySignals {
             ("config_or_manifest", bool_feature(self.config_or_manifest)),
             ("public_entry_point", bool_feature(self.public_entry_point)),
             ("reference_count", self.reference_count as f64),
+            ("imported_by", self.imported_by as f64),
+            ("import_centrality", self.import_centrality),
             ("static_prior_score", self.prior_score()),
-        ])
+            (
+                "history_recency",
+                history.map_or(0.0, |history| {
+                    history.recency(QUALITY_PRIORS.recency_half_life_days)
+                }),
+            ),
+            (
+                "history_commit_count",
+                history.map_or(0.0, |history| history.commit_count as f64),
+            ),
+            (
+                "history_author_count",
+                history.map_or(0.0, |history| history.author_count as f64),
+            ),
+            (
+                "history_co_change_count",
+                history.map_or(0.0, |history| history.co_changed.len() as f64),
+            ),
+        ]);
+        if let Some(history) = history {
+            features.insert("history_days_since_change", history.days_since_change);
+        }
+        features
+    }
+}
+
+/// Weights turning [`StaticQualitySignals`] into a score prior.
+///
+/// Every field may be set in `reaper.toml` under `[ranking.quality_priors]`;
+/// unset fields keep their defaults.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QualityPriors {
+    pub generated: f64,
+    pub vendor: f64,
+    pub test: f64,
+    pub entry_point: f64,
+    pub readme: f64,
+    pub config_or_manifest: f64,
+    pub public_entry_point: f64,
+    /// Files at the root or one directory down.
+    pub shallow: f64,
+    /// Files two directories down.
+    pub depth_two: f64,
+    /// Files more than five directories down.
+    pub deep: f64,
+    /// Files over 250 KB.
+    pub large_file: f64,
+    /// Files over 750 KB, instead of `large_file`.
+    pub huge_file: f64,
+    pub per_reference: f64,
+    /// References beyond this count add nothing.
+    pub max_references: usize,
+    /// Multiplies the log of import centrality, so only files imported more
+    /// than average gain.
+    pub import_centrality: f64,
+    /// Scales how recently a file was committed, from 1.0 for the newest
+    /// change down towards 0.0. Off unless set, and only applies to
+    /// indexes built with Git history.
+    pub recency: f64,
+    pub recency_half_life_days: f64,
+}
+
+pub(crate) const QUALITY_PRIORS: QualityPriors = QualityPriors {
+    generated: -0.55,
+    vendor: -0.45,
+    test: -0.08,
+    entry_point: 0.28,
+    readme: 0.22,
+    config_or_manifest: 0.20,
+    public_entry_point: 0.12,
+    shallow: 0.10,
+    depth_two: 0.04,
+    deep: -0.08,
+    large_file: -0.08,
+    huge_file: -0.18,
+    per_reference: 0.015,
+    max_references: 12,
+    import_centrality: 0.06,
+    recency: 0.0,
+    recency_half_life_days: 90.0,
+};
+
+impl Default for QualityPriors {
+    fn default() -> Self {
+        QUALITY_PRIORS
+    }
+}
+
+impl QualityPriors {
+    pub fn score(&self, signals: &StaticQualitySignals) -> f64 {
+        self.contributions(signals)
+            .map(|(_, _, contribution)| contribution)
+            .sum()
+    }
+
+    /// `(signal, value, contribution)` for every signal, in a fixed order.
+    pub fn contributions(
+        &self,
+        signals: &StaticQualitySignals,
+    ) -> impl Iterator<Item = (&'static str, f64, f64)> {
+        let flag = |name, value: bool, weight: f64| {
+            (name, bool_feature(value), if value { weight } else { 0.0 })
+        };
+        let depth = match signals.file_depth {
+            0 | 1 => self.shallow,
+            2 => self.depth_two,
+            3..=5 => 0.0,
+            _ => self.deep,
+        };
+        let size = if signals.file_size_bytes > 750_000 {
+            self.huge_file
+        } else if signals.file_size_bytes > 250_000 {
+            self.large_file
+        } else {
+            0.0
+        };
+
+        let recency = signals
+            .history
+            .as_ref()
+            .map_or(0.0, |history| history.recency(self.recency_half_life_days));
+
+        [
+            flag("generated", signals.generated, self.generated),
+            flag("vendor", signals.vendor, self.vendor),
+            flag("test", signals.test, self.test),
+            flag("entry_point", signals.entry_point, self.entry_point),
+            flag("readme", signals.readme, self.readme),
+            flag(
+                "config_or_manifest",
+                signals.config_or_manifest,
+                self.config_or_manifest,
+            ),
+            flag(
+                "public_entry_point",
+                signals.public_entry_point,
+                self.public_entry_point,
+            ),
+            ("file_depth", signals.file_depth as f64, depth),
+            ("file_size_bytes", signals.file_size_bytes as f64, size),
+            (
+                "reference_count",
+                signals.reference_count as f64,
+                signals.reference_count.min(self.max_references) as f64 * self.per_reference,
+            ),
+            (
+                "import_centrality",
+                signals.import_centrality,
+                signals.import_centrality.max(1.0).ln() * self.import_centrality,
+            ),
+            ("recency", recency, recency * self.recency),
+        ]
+        .into_iter()
     }
 }
 
@@ -215,7 +349,7 @@ fn approximate_reference_count(content: &str) -> usize {
 mod tests {
     use std::path::Path;
 
-    use super::StaticQualitySignals;
+    use super::{QualityPriors, StaticQualitySignals};
 
     #[test]
     fn detects_generated_vendor_and_test_markers() {
@@ -244,4 +378,40 @@ mod tests {
         assert_eq!(signals.reference_count, 2);
         assert!(signals.prior_score() > 0.0);
     }
+
+    #[test]
+    fn only_above_average_import_centrality_raises_the_prior() {
+        let signals = |import_centrality| StaticQualitySignals {
+            import_centrality,
+            ..StaticQualitySignals::default()
+        };
+
+        assert_eq!(signals(0.4).prior_score(), signals(1.0).prior_score());
+        assert!(signals(6.0).prior_score() > signals(1.0).prior_score());
+        assert_eq!(signals(6.0).features()["import_centrality"], 6.0);
+    }
+
+    #[test]
+    fn configured_priors_change_the_score() {
+        let signals = StaticQualitySignals::analyze(
+            Path::new("vendor/generated/client_test.rs"),
+            "// generated by tool\nfn test_case() {}",
+            120,
+        );
+        let lenient = QualityPriors {
+            generated: 0.0,
+            vendor: 0.0,
+            test: 0.0,
+            ..QualityPriors::default()
+        };
+
+        assert!(lenient.score(&signals) > signals.prior_score());
+        assert_eq!(
+            lenient.score(&signals),
+            lenient
+                .contributions(&signals)
+                .map(|(_, _, c)| c)
+                .sum::<f64>()
+        );
+    }
 }
@@ -1,6 +1,9 @@
 use std::{collections::BTreeMap, path::Path};
 
-use crate::index::{DocId, DocumentField, DocumentMetadata, InvertedIndex, Term, TermDocument};
+use crate::index::{
+    DenseIndex, DocId, DocumentField, DocumentMetadata, InvertedIndex, Term, TermDocument,
+    quality::{QUALITY_PRIORS, QualityPriors},
+};
 
 pub trait PostingList {
     type Iter<'a>: Iterator<Item = (DocId, &'a TermDocument)> + Send
@@ -124,6 +127,19 @@ pub trait RankedIndexReader {
     fn total_token_count(&self) -> u64;
     fn vocabulary_size(&self) -> usize;
     fn collection_frequency(&self, term: &Term) -> usize;
+    fn quality_priors(&self) -> &QualityPriors {
+        &QUALITY_PRIORS
+    }
+
+    /// Whether `id` is one of the documents this reader ranks.
+    fn contains(&self, id: DocId) -> bool {
+        self.document(id).is_some()
+    }
+
+    /// Latent semantic vectors, when the index has been given them.
+    fn dense(&self) -> Option<&DenseIndex> {
+        None
+    }
 }
 
 impl RankedIndexReader for InvertedIndex {
@@ -176,6 +192,14 @@ impl RankedIndexReader for InvertedIndex {
     fn collection_frequency(&self, term: &Term) -> usize {
         InvertedIndex::collection_frequency(self, term)
     }
+
+    fn quality_priors(&self) -> &QualityPriors {
+        InvertedIndex::quality_priors(self)
+    }
+
+    fn dense(&self) -> Option<&DenseIndex> {
+        InvertedIndex::dense(self)
+    }
 }
 
 #[cfg(test)]
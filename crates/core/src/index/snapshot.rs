@@ -10,12 +10,13 @@ use std::{
 use crate::{
     config::Config,
     index::{
-        CorpusStats, DocId, DocumentCatalog, DocumentMetadata, DocumentRegistry, InvertedIndex,
-        Term, TermDocument,
+        CorpusStats, DenseIndex, DocId, DocumentCatalog, DocumentField, DocumentMetadata,
+        DocumentRegistry, IndexHistory, InvertedIndex, SubDocumentIndex, Term, TermDocument,
     },
+    tokenizer::{AnalyzerProfile, FileType},
 };
 
-pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 2;
 const SNAPSHOT_FILE: &str = "snapshot.json";
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -32,6 +33,12 @@ struct IndexSnapshot {
     metadata: SnapshotMetadata,
     documents: Vec<DocumentMetadata>,
     postings: Vec<TermSnapshot>,
+    #[serde(default)]
+    history: Option<IndexHistory>,
+    #[serde(default)]
+    dense: Option<DenseIndex>,
+    #[serde(default)]
+    sub_documents: Option<SubDocumentIndex>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -75,11 +82,22 @@ pub fn snapshot_path(index_dir: &Path) -> PathBuf {
 pub fn config_hash(config: &Config) -> u64 {
     let mut hasher = DefaultHasher::new();
     config.n_grams.hash(&mut hasher);
+    config.language.hash(&mut hasher);
     let mut stop_words = config.stop_words.iter().collect::<Vec<_>>();
     stop_words.sort();
     for word in stop_words {
         word.hash(&mut hasher);
     }
+    for file_type in FileType::ALL {
+        let profile = AnalyzerProfile::for_file_type(file_type);
+        for field in DocumentField::ALL {
+            profile
+                .resolved_filters(field.analyzer_field(), config)
+                .hash(&mut hasher);
+        }
+    }
+    config.expansions.weight.to_bits().hash(&mut hasher);
+    config.expansions.terms.hash(&mut hasher);
     hasher.finish()
 }
 
@@ -162,6 +180,9 @@ impl IndexSnapshot {
             metadata,
             documents,
             postings,
+            history: index.history().cloned(),
+            dense: index.dense().cloned(),
+            sub_documents: index.sub_documents().cloned(),
         }
     }
 
@@ -210,7 +231,14 @@ impl IndexSnapshot {
             })
             .collect::<HashMap<_, _>>();
 
-        InvertedIndex::from_parts(postings, registry)
+        // Validated against the requested root, which a fresh build of the
+        // same tree resolves imports against.
+        let mut index = InvertedIndex::from_parts(postings, registry)
+            .with_root(self.metadata.source_root)
+            .with_stored_history(self.history);
+        index.set_dense(self.dense);
+        index.set_sub_documents(self.sub_documents);
+        index
     }
 }
 
@@ -218,32 +246,29 @@ impl IndexSnapshot {
 mod tests {
     use std::{collections::HashSet, fs, path::Path};
 
-    use rust_stemmers::{Algorithm, Stemmer};
-
-    use super::{SnapshotError, load_snapshot, snapshot_path, write_snapshot};
+    use super::{
+        SNAPSHOT_SCHEMA_VERSION, SnapshotError, config_hash, load_snapshot, snapshot_path,
+        write_snapshot,
+    };
     use crate::{
         config::Config,
-        index::{DocumentField, InvertedIndex},
+        index::{
+            DenseConfig, DocumentField, GitHistory, InvertedIndex, SearchFilter, SubDocumentIndex,
+            history::tests::{commit, init_repo},
+        },
         query::AnalyzedQuery,
         ranking::{BM25HyperParams, RankingAlgo},
-        tokenizer::n_gram_transform,
+        test_support::config_without_stop_words,
+        tokenizer::{Language, n_gram_transform},
     };
 
-    fn config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: HashSet::new(),
-        }
-    }
-
     #[test]
     fn snapshot_round_trips_ranked_search_results() {
         let source = tempfile::tempdir().unwrap();
         let index_dir = tempfile::tempdir().unwrap();
         fs::write(source.path().join("a.rs"), "rust rust search").unwrap();
         fs::write(source.path().join("b.rs"), "search").unwrap();
-        let config = config();
+        let config = config_without_stop_words();
         let index = InvertedIndex::new(
             source.path(),
             |content| n_gram_transform(content, &config),
@@ -264,7 +289,7 @@ mod tests {
     }
 
     #[test]
-    fn snapshot_round_trips_exportable_code_features() {
+    fn snapshot_round_trips_exportable_code_features_and_offsets() {
         let source = tempfile::tempdir().unwrap();
         let index_dir = tempfile::tempdir().unwrap();
         fs::write(
@@ -278,7 +303,7 @@ pub fn bm25_score() {
 "#,
         )
         .unwrap();
-        let config = config();
+        let config = config_without_stop_words();
         let index = InvertedIndex::new_fielded(source.path(), &config, Some(source.path()));
         let doc_id = index.doc_id(Path::new("a.rs")).unwrap();
         let original = index.document(doc_id).unwrap();
@@ -297,6 +322,131 @@ pub fn bm25_score() {
         let loaded_metadata = loaded.document(loaded_doc_id).unwrap();
 
         assert_eq!(loaded_metadata.features, original.features);
+        assert!(original.token_offsets.is_some());
+        assert_eq!(loaded_metadata.token_offsets, original.token_offsets);
+    }
+
+    #[test]
+    fn snapshot_round_trips_git_history() {
+        let source = tempfile::tempdir().unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        init_repo(source.path());
+        commit(source.path(), "ann", 1, &[("a.rs", "rust")]);
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new(
+            source.path(),
+            |content| n_gram_transform(content, &config),
+            Some(source.path()),
+        )
+        .with_history(GitHistory::read(source.path()).unwrap(), source.path());
+
+        write_snapshot(&index, index_dir.path(), source.path(), &config).unwrap();
+        let loaded = load_snapshot(index_dir.path(), source.path(), &config).unwrap();
+        let signals = |index: &InvertedIndex| {
+            let id = index.doc_id(Path::new("a.rs")).unwrap();
+            index.document(id).unwrap().quality_signals.history.clone()
+        };
+
+        assert!(signals(&loaded).is_some());
+        assert_eq!(signals(&loaded), signals(&index));
+        assert_eq!(loaded.history(), index.history());
+    }
+
+    #[test]
+    fn snapshot_round_trips_dense_vectors() {
+        let source = tempfile::tempdir().unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        fs::write(source.path().join("a.rs"), "pool connection database").unwrap();
+        fs::write(source.path().join("b.rs"), "connection socket handshake").unwrap();
+        fs::write(source.path().join("c.rs"), "socket database retry").unwrap();
+        let config = config_without_stop_words();
+        let mut index = InvertedIndex::new(
+            source.path(),
+            |content| n_gram_transform(content, &config),
+            Some(source.path()),
+        );
+        index.build_dense(&DenseConfig::default());
+
+        write_snapshot(&index, index_dir.path(), source.path(), &config).unwrap();
+        let loaded = load_snapshot(index_dir.path(), source.path(), &config).unwrap();
+        let query = AnalyzedQuery::new("database", &config);
+        let algo = "dense".parse::<RankingAlgo>().unwrap();
+
+        assert!(loaded.dense().is_some());
+        assert_eq!(loaded.dense(), index.dense());
+        assert_eq!(
+            algo.rank(&loaded, &query, 10),
+            algo.rank(&index, &query, 10)
+        );
+    }
+
+    #[test]
+    fn snapshot_round_trips_sub_documents() {
+        let source = tempfile::tempdir().unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            source.path().join("a.rs"),
+            "fn open_pool() {}\n\nfn close_pool() {}\n",
+        )
+        .unwrap();
+        let config = config_without_stop_words();
+        let mut index = InvertedIndex::new_fielded(source.path(), &config, None);
+        index.build_sub_documents(&config);
+
+        write_snapshot(&index, index_dir.path(), source.path(), &config).unwrap();
+        let loaded = load_snapshot(index_dir.path(), source.path(), &config).unwrap();
+        let query = AnalyzedQuery::new("close", &config);
+        let algo = "bm25f".parse::<RankingAlgo>().unwrap();
+        let filter = SearchFilter::new();
+
+        assert_eq!(
+            loaded.sub_documents().map(SubDocumentIndex::len),
+            index.sub_documents().map(SubDocumentIndex::len)
+        );
+        assert_eq!(
+            algo.rank_sub_documents(&loaded, &query, 10, &filter),
+            algo.rank_sub_documents(&index, &query, 10, &filter)
+        );
+    }
+
+    #[test]
+    fn updates_after_loading_resolve_imports_like_a_fresh_build() {
+        let source = tempfile::tempdir().unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(source.path().join("pkg")).unwrap();
+        fs::write(source.path().join("pkg/core.py"), "def run(): pass\n").unwrap();
+        fs::write(source.path().join("main.py"), "import pkg.core\n").unwrap();
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(source.path(), &config, None);
+        write_snapshot(&index, index_dir.path(), source.path(), &config).unwrap();
+
+        let mut loaded = load_snapshot(index_dir.path(), source.path(), &config).unwrap();
+        let main = source.path().join("main.py");
+        fs::write(&main, "import pkg.core\n\nrun()\n").unwrap();
+        loaded.update_fielded(&main, &config);
+        let fresh = InvertedIndex::new_fielded(source.path(), &config, None);
+
+        let signals = |index: &InvertedIndex| {
+            let mut signals = index
+                .documents_iter()
+                .map(|(_, metadata)| {
+                    let quality = &metadata.quality_signals;
+                    (
+                        metadata.path.clone(),
+                        quality.imported_by,
+                        quality.import_centrality,
+                    )
+                })
+                .collect::<Vec<_>>();
+            signals.sort_by(|a, b| a.0.cmp(&b.0));
+            signals
+        };
+        assert_eq!(signals(&loaded), signals(&fresh));
+        assert!(
+            signals(&fresh)
+                .iter()
+                .any(|(_, imported_by, _)| *imported_by == 1)
+        );
     }
 
     #[test]
@@ -304,7 +454,7 @@ pub fn bm25_score() {
         let source = tempfile::tempdir().unwrap();
         let index_dir = tempfile::tempdir().unwrap();
         fs::write(source.path().join("a.rs"), "rust").unwrap();
-        let config = config();
+        let config = config_without_stop_words();
         let index = InvertedIndex::new(
             source.path(),
             |content| n_gram_transform(content, &config),
@@ -313,7 +463,10 @@ pub fn bm25_score() {
         write_snapshot(&index, index_dir.path(), source.path(), &config).unwrap();
 
         let mut json = fs::read_to_string(snapshot_path(index_dir.path())).unwrap();
-        json = json.replace("\"schema_version\":1", "\"schema_version\":99");
+        json = json.replace(
+            &format!("\"schema_version\":{SNAPSHOT_SCHEMA_VERSION}"),
+            "\"schema_version\":99",
+        );
         fs::write(snapshot_path(index_dir.path()), json).unwrap();
 
         assert!(matches!(
@@ -321,4 +474,42 @@ pub fn bm25_score() {
             SnapshotError::Schema { .. }
         ));
     }
+
+    #[test]
+    fn snapshot_rejects_changed_analyzer_pipeline() {
+        let source = tempfile::tempdir().unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        fs::write(source.path().join("a.rs"), "rust").unwrap();
+        let original = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(source.path(), &original, Some(source.path()));
+        write_snapshot(&index, index_dir.path(), source.path(), &original).unwrap();
+
+        let mut changed = config_without_stop_words();
+        changed.analyzers =
+            serde_json::from_str(r#"{ "fields": { "Comment": ["lowercase"] } }"#).unwrap();
+
+        assert!(matches!(
+            load_snapshot(index_dir.path(), source.path(), &changed).unwrap_err(),
+            SnapshotError::ConfigHash { .. }
+        ));
+    }
+
+    #[test]
+    fn config_hash_covers_language_and_expansions() {
+        let original = config_hash(&config_without_stop_words());
+
+        let mut german = Config::for_language(Language::German).unwrap();
+        german.stop_words = HashSet::new();
+        assert_ne!(config_hash(&german), original);
+
+        let mut expanded = config_without_stop_words();
+        expanded.expansions = expanded
+            .expansions
+            .with_overrides([("k8s".to_string(), vec!["kubernetes".to_string()])].into());
+        assert_ne!(config_hash(&expanded), original);
+
+        let mut reweighted = config_without_stop_words();
+        reweighted.expansions = reweighted.expansions.with_weight(0.5);
+        assert_ne!(config_hash(&reweighted), original);
+    }
 }
@@ -0,0 +1,503 @@
+//! Symbols and sections indexed as documents of their own.
+//!
+//! Every function, type, trait, impl and Markdown section that tree-sitter
+//! finds in a file becomes a sub-document named `path#symbol`, with its
+//! own `Symbol`, `Comment` and `Content` fields and the line range it
+//! covers in its parent file. Nested symbols are named through their
+//! parents (`path#Type::method`, `README.md#Usage/Install`), and the
+//! comments and attributes directly above a declaration belong to it.
+//! Sub-documents live in an index of their own so file-level statistics
+//! are untouched; it is refreshed whenever its parent file is.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    code_intelligence::{ByteSpan, DocumentFeature},
+    config::Config,
+    index::{DocumentField, DocumentRegistry, InvertedIndex, QualityPriors, StaticQualitySignals},
+    ranking::{Passage, Score},
+    tokenizer::FileType,
+};
+
+/// Weight of each further child when aggregating sub-document scores into
+/// their file: the best child counts fully, the next half, then a quarter.
+const CHILD_DECAY: f64 = 0.5;
+
+/// One symbol or section of a file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SubDocument {
+    /// `parent#symbol`, the path the sub-document is indexed and ranked as.
+    pub path: PathBuf,
+    pub parent: PathBuf,
+    /// The symbol's name qualified by the symbols enclosing it.
+    pub symbol: String,
+    pub span: ByteSpan,
+    /// One-based, inclusive lines of the parent file the symbol covers.
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubDocumentIndex {
+    index: InvertedIndex,
+    units: BTreeMap<PathBuf, SubDocument>,
+}
+
+impl Default for SubDocumentIndex {
+    fn default() -> Self {
+        Self {
+            index: InvertedIndex::from_parts(HashMap::new(), DocumentRegistry::new()),
+            units: BTreeMap::new(),
+        }
+    }
+}
+
+impl SubDocumentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sub-documents as an index any ranking algorithm can search.
+    pub fn index(&self) -> &InvertedIndex {
+        &self.index
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    pub fn sub_document(&self, path: &Path) -> Option<&SubDocument> {
+        self.units.get(path)
+    }
+
+    pub fn sub_documents_of<'a>(
+        &'a self,
+        parent: &'a Path,
+    ) -> impl Iterator<Item = &'a SubDocument> + 'a {
+        self.units
+            .values()
+            .filter(move |unit| unit.parent == parent)
+    }
+
+    /// Replaces the sub-documents of `parent` with those carved from its
+    /// current `content`. They share the parent's quality signals.
+    pub fn refresh_file(
+        &mut self,
+        parent: &Path,
+        content: &str,
+        quality_signals: &StaticQualitySignals,
+        config: &Config,
+    ) {
+        self.insert_file(parent, content, quality_signals, config);
+        self.index.refresh_document_norms();
+    }
+
+    pub fn remove_file(&mut self, parent: &Path) {
+        if self.remove_units(parent) {
+            self.index.refresh_document_norms();
+        }
+    }
+
+    /// Folds ranked sub-documents into their files. A file scores its best
+    /// child plus a decaying share of the others, and keeps its children's
+    /// line ranges as passages, best first.
+    pub fn aggregate_by_file(&self, ranking: &[Score]) -> Vec<Score> {
+        let mut files: Vec<Score> = Vec::new();
+        let mut positions = HashMap::new();
+
+        for score in ranking {
+            let Some(unit) = self.units.get(&score.doc_path) else {
+                continue;
+            };
+            let passage = Passage {
+                start_line: unit.start_line,
+                end_line: unit.end_line,
+                score: score.score,
+            };
+            match positions.get(&unit.parent) {
+                Some(&position) => {
+                    let file: &mut Score = &mut files[position];
+                    file.score += score.score * CHILD_DECAY.powi(file.passages.len() as i32);
+                    file.passages.push(passage);
+                }
+                None => {
+                    positions.insert(unit.parent.clone(), files.len());
+                    files.push(Score {
+                        doc_path: unit.parent.clone(),
+                        score: score.score,
+                        passages: vec![passage],
+                        similar: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        files.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.doc_path.cmp(&b.doc_path))
+        });
+        files
+    }
+
+    pub(crate) fn set_quality_priors(&mut self, quality_priors: QualityPriors) {
+        self.index.set_quality_priors(quality_priors);
+    }
+
+    /// [`Self::refresh_file`] without refreshing the document norms, for
+    /// inserting many files at once.
+    pub(crate) fn insert_file(
+        &mut self,
+        parent: &Path,
+        content: &str,
+        quality_signals: &StaticQualitySignals,
+        config: &Config,
+    ) {
+        self.remove_units(parent);
+
+        let file_type = FileType::detect(parent);
+        for (unit, features) in carve_sub_documents(parent, content, file_type) {
+            self.index.insert_features(
+                unit.path.clone(),
+                &content[unit.span.start..unit.span.end],
+                file_type,
+                &features,
+                quality_signals.clone(),
+                config,
+            );
+            self.units.insert(unit.path.clone(), unit);
+        }
+    }
+
+    pub(crate) fn refresh_document_norms(&mut self) {
+        self.index.refresh_document_norms();
+    }
+
+    fn remove_units(&mut self, parent: &Path) -> bool {
+        let paths = self
+            .sub_documents_of(parent)
+            .map(|unit| unit.path.clone())
+            .collect::<Vec<_>>();
+        for path in &paths {
+            self.index.remove_features(path);
+            self.units.remove(path);
+        }
+        !paths.is_empty()
+    }
+}
+
+/// Sub-documents of `content` in source order, each with its features
+/// moved to offsets within the sub-document.
+fn carve_sub_documents(
+    parent: &Path,
+    content: &str,
+    file_type: FileType,
+) -> Vec<(SubDocument, Vec<DocumentFeature>)> {
+    let Some(features) = crate::code_intelligence::extract(file_type, parent, content)
+        .ok()
+        .flatten()
+    else {
+        return Vec::new();
+    };
+    let features = features.into_features();
+    let comments = features
+        .iter()
+        .filter(|feature| feature.field == DocumentField::Comment)
+        .filter_map(|feature| feature.span)
+        .collect::<Vec<_>>();
+    let mut symbols = features
+        .iter()
+        .filter(|feature| feature.field == DocumentField::Symbol)
+        .filter_map(|feature| Some((feature, feature.span?, feature.scope?)))
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|(_, _, scope)| (scope.start, std::cmp::Reverse(scope.end)));
+    let separator = if file_type == FileType::Markdown {
+        "/"
+    } else {
+        "::"
+    };
+
+    let mut carved = Vec::new();
+    let mut enclosing: Vec<(ByteSpan, String)> = Vec::new();
+    let mut seen = HashSet::new();
+    for (symbol, name_span, scope) in symbols {
+        let name = symbol.text.trim_start_matches('#').trim();
+        if name.is_empty() {
+            continue;
+        }
+        while enclosing
+            .last()
+            .is_some_and(|(outer, _)| outer.end < scope.end)
+        {
+            enclosing.pop();
+        }
+        let qualified = match enclosing.last() {
+            Some((_, outer)) => format!("{outer}{separator}{name}"),
+            None => name.to_string(),
+        };
+        enclosing.push((scope, qualified.clone()));
+
+        let mut unit_path = format!("{}#{qualified}", parent.display());
+        let mut duplicate = 1;
+        while !seen.insert(unit_path.clone()) {
+            duplicate += 1;
+            unit_path = format!("{}#{qualified}~{duplicate}", parent.display());
+        }
+
+        let span = ByteSpan {
+            start: leading_comments_start(content, scope.start, &comments),
+            end: scope.end,
+        };
+        let unit = SubDocument {
+            path: PathBuf::from(unit_path),
+            parent: parent.to_path_buf(),
+            symbol: qualified,
+            span,
+            start_line: line_of(content, span.start),
+            end_line: line_of(content, span.end.saturating_sub(1).max(span.start)),
+        };
+        let features = unit_features(content, span, name, name_span, &comments);
+        carved.push((unit, features));
+    }
+
+    carved
+}
+
+/// Start of the comments and attributes directly above `start`, or `start`
+/// itself when there are none.
+fn leading_comments_start(content: &str, start: usize, comments: &[ByteSpan]) -> usize {
+    let mut start = start;
+    loop {
+        let comment = comments
+            .iter()
+            .find(|comment| comment.end <= start && content[comment.end..start].trim().is_empty());
+        if let Some(comment) = comment {
+            start = comment.start;
+            continue;
+        }
+
+        let before = content[..start].trim_end();
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        if !before[line_start..].trim_start().starts_with("#[") {
+            return start;
+        }
+        start = line_start;
+    }
+}
+
+fn unit_features(
+    content: &str,
+    span: ByteSpan,
+    name: &str,
+    name_span: ByteSpan,
+    comments: &[ByteSpan],
+) -> Vec<DocumentFeature> {
+    let shift = |inner: ByteSpan| ByteSpan {
+        start: inner.start - span.start,
+        end: inner.end - span.start,
+    };
+    let mut features = vec![DocumentFeature {
+        field: DocumentField::Symbol,
+        text: name.to_string(),
+        span: (content.get(name_span.start..name_span.end) == Some(name)).then(|| shift(name_span)),
+        scope: None,
+    }];
+    features.extend(
+        comments
+            .iter()
+            .filter(|comment| span.start <= comment.start && comment.end <= span.end)
+            .map(|comment| DocumentFeature {
+                field: DocumentField::Comment,
+                text: content[comment.start..comment.end].to_string(),
+                span: Some(shift(*comment)),
+                scope: None,
+            }),
+    );
+    features.push(DocumentFeature {
+        field: DocumentField::Content,
+        text: content[span.start..span.end].to_string(),
+        span: None,
+        scope: None,
+    });
+    features
+}
+
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+#[cfg(all(test, feature = "tree-sitter"))]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        index::{InvertedIndex, SearchFilter},
+        query::AnalyzedQuery,
+        ranking::RankingAlgo,
+        test_support::config_without_stop_words,
+    };
+
+    const POOL: &str = "\
+use std::time::Duration;
+
+/// Connections kept open between requests.
+#[derive(Debug)]
+pub struct Pool {
+    size: usize,
+}
+
+impl Pool {
+    // back off before reconnecting
+    fn retry(&self, delay: Duration) {}
+
+    fn close(&self) {}
+}
+";
+
+    const README: &str = "\
+# Usage
+
+Run the server.
+
+## Install
+
+Download a release.
+";
+
+    fn index(dir: &Path) -> InvertedIndex {
+        fs::write(dir.join("pool.rs"), POOL).unwrap();
+        fs::write(dir.join("README.md"), README).unwrap();
+        let mut index = InvertedIndex::new_fielded(dir, &config_without_stop_words(), None);
+        index.build_sub_documents(&config_without_stop_words());
+        index
+    }
+
+    fn units(index: &InvertedIndex, parent: &Path) -> Vec<(String, usize, usize)> {
+        index
+            .sub_documents()
+            .unwrap()
+            .sub_documents_of(parent)
+            .map(|unit| (unit.symbol.clone(), unit.start_line, unit.end_line))
+            .collect()
+    }
+
+    fn ranked(index: &InvertedIndex, query: &str, filter: &SearchFilter) -> Vec<String> {
+        let query = AnalyzedQuery::new(query, &config_without_stop_words());
+        "bm25f"
+            .parse::<RankingAlgo>()
+            .unwrap()
+            .rank_sub_documents(index, &query, 10, filter)
+            .map(|ranking| {
+                ranking
+                    .0
+                    .iter()
+                    .map(|score| {
+                        let passage = &score.passages[0];
+                        format!(
+                            "{}:{}-{}",
+                            score.doc_path.display(),
+                            passage.start_line,
+                            passage.end_line
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn carves_symbols_and_sections_with_their_leading_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+
+        let mut pool = units(&index, &dir.path().join("pool.rs"));
+        pool.sort();
+        assert_eq!(
+            pool,
+            vec![
+                ("Pool".to_string(), 3, 7),
+                ("Pool".to_string(), 9, 14),
+                ("Pool::close".to_string(), 13, 13),
+                ("Pool::retry".to_string(), 10, 11),
+            ]
+        );
+        assert_eq!(
+            units(&index, &dir.path().join("README.md")),
+            vec![
+                ("Usage".to_string(), 1, 7),
+                ("Usage/Install".to_string(), 5, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_path_resolves_symbols_to_their_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        let pool = dir.path().join("pool.rs");
+        let readme = dir.path().join("README.md");
+        let symbol =
+            |file: &Path, symbol: &str| PathBuf::from(format!("{}#{symbol}", file.display()));
+
+        assert_eq!(index.file_path(&symbol(&pool, "Pool::retry")), pool);
+        assert_eq!(index.file_path(&symbol(&readme, "Usage/Install")), readme);
+        assert_eq!(index.file_path(&pool), pool);
+        // Symbols removed since the search still name their file.
+        assert_eq!(index.file_path(&symbol(&pool, "Pool::connect")), pool);
+        let missing = dir.path().join("missing.rs");
+        assert_eq!(index.file_path(&missing), missing);
+    }
+
+    #[test]
+    fn ranks_symbols_within_the_filter_and_aggregates_them_by_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        let pool = dir.path().join("pool.rs");
+
+        assert_eq!(
+            ranked(&index, "reconnecting", &SearchFilter::new()),
+            vec![
+                format!("{}#Pool::retry:10-11", pool.display()),
+                format!("{}#Pool~2:9-14", pool.display()),
+            ]
+        );
+        let markdown_only = SearchFilter::new().with_globs(["**/*.md"]).unwrap();
+        assert!(ranked(&index, "reconnecting", &markdown_only).is_empty());
+
+        let query = AnalyzedQuery::new("pool", &config_without_stop_words());
+        let algo = "bm25f".parse::<RankingAlgo>().unwrap();
+        let ranking = algo
+            .rank_sub_documents(&index, &query, 10, &SearchFilter::new())
+            .unwrap();
+        let files = index.sub_documents().unwrap().aggregate_by_file(&ranking.0);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].doc_path, pool);
+        assert_eq!(files[0].passages.len(), ranking.0.len());
+        assert!(files[0].score > ranking.0[0].score);
+    }
+
+    #[test]
+    fn follows_file_updates_and_removals() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = index(dir.path());
+        let pool = dir.path().join("pool.rs");
+
+        fs::write(&pool, "fn reconnect() {}\n").unwrap();
+        index.update_fielded(&pool, &config_without_stop_words());
+        assert_eq!(units(&index, &pool), vec![("reconnect".to_string(), 1, 1)]);
+
+        index.remove_document(&pool);
+        assert!(units(&index, &pool).is_empty());
+        assert_eq!(index.sub_documents().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+
+use crate::{
+    code_intelligence::ByteSpan,
+    index::{DocumentField, RankedIndexReader},
+};
+
+/// A symbol declaration recorded at index time.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SymbolLocation {
+    pub path: PathBuf,
+    pub name: String,
+    /// One-based line of the declaration, when the document has offsets.
+    pub line: Option<usize>,
+    /// Byte span of the symbol's name in the file.
+    pub span: Option<ByteSpan>,
+}
+
+/// How closely a symbol name matched the lookup, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SymbolMatch {
+    Exact,
+    Prefix,
+    Substring,
+}
+
+/// Declared symbols whose name contains `name`, ignoring case. Exact matches
+/// come first, then prefix matches, then the rest, each group ordered by
+/// path and line.
+pub fn find_symbols(
+    index: &impl RankedIndexReader,
+    name: &str,
+    limit: usize,
+) -> Vec<SymbolLocation> {
+    let needle = name.to_lowercase();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let mut found = index
+        .documents()
+        .into_iter()
+        .flat_map(|metadata| {
+            let needle = &needle;
+            metadata
+                .features
+                .iter()
+                .filter(|feature| feature.field == DocumentField::Symbol)
+                .filter_map(move |feature| {
+                    let candidate = feature.text.to_lowercase();
+                    let matched = if candidate == *needle {
+                        SymbolMatch::Exact
+                    } else if candidate.starts_with(needle.as_str()) {
+                        SymbolMatch::Prefix
+                    } else if candidate.contains(needle.as_str()) {
+                        SymbolMatch::Substring
+                    } else {
+                        return None;
+                    };
+                    let line = feature.span.and_then(|span| {
+                        metadata
+                            .token_offsets
+                            .as_ref()
+                            .and_then(|offsets| offsets.line_of(span.start))
+                    });
+
+                    Some((
+                        matched,
+                        SymbolLocation {
+                            path: metadata.path.clone(),
+                            name: feature.text.clone(),
+                            line,
+                            span: feature.span,
+                        },
+                    ))
+                })
+        })
+        .collect::<Vec<_>>();
+    found.sort_by(|(a_match, a), (b_match, b)| {
+        a_match
+            .cmp(b_match)
+            .then_with(|| a.path.cmp(&b.path))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+    found.dedup_by(|(_, a), (_, b)| a == b);
+
+    found
+        .into_iter()
+        .take(limit)
+        .map(|(_, location)| location)
+        .collect()
+}
+
+#[cfg(all(test, feature = "tree-sitter"))]
+mod tests {
+
+    use super::find_symbols;
+    use crate::{
+        index::InvertedIndex,
+        test_support::{config_without_stop_words, write_temp_file},
+    };
+
+    #[test]
+    fn finds_exact_then_prefix_then_substring_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "struct ConnectionPool;\n\nfn pool_size() {}\n\nfn pool() {}\n",
+        );
+        write_temp_file(dir.path(), "other.rs", "fn unrelated() {}\n");
+        let index =
+            InvertedIndex::new_fielded(dir.path(), &config_without_stop_words(), Some(dir.path()));
+
+        let found = find_symbols(&index, "Pool", 10);
+
+        assert_eq!(
+            found
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("pool", Some(5)),
+                ("pool_size", Some(3)),
+                ("ConnectionPool", Some(1)),
+            ]
+        );
+        assert_eq!(find_symbols(&index, "pool", 1).len(), 1);
+        assert!(find_symbols(&index, "", 10).is_empty());
+    }
+}
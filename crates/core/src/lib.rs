@@ -4,7 +4,11 @@ pub mod error;
 pub mod evaluation;
 mod fs_walk;
 pub mod index;
+pub mod pack;
 pub mod query;
 pub mod ranking;
 pub mod regex_search;
+#[cfg(test)]
+mod test_support;
 pub mod tokenizer;
+mod util;
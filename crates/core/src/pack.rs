@@ -0,0 +1,297 @@
+//! Token-budgeted context packs: the best passages of a ranking, cut to fit
+//! an LLM context window.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+pub use crate::util::estimate_tokens_from_bytes;
+use crate::{index::RankedIndexReader, ranking::Score};
+
+/// Budget used when none is given.
+pub const DEFAULT_BUDGET_TOKENS: usize = 8_000;
+
+/// Tokens charged per entry for its header line and code fences.
+const ENTRY_OVERHEAD_TOKENS: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ContextPack {
+    pub query: String,
+    pub budget_tokens: usize,
+    pub estimated_tokens: usize,
+    pub entries: Vec<PackEntry>,
+}
+
+/// A line range of one file. Lines are one-based and `end_line` is
+/// inclusive. `text` is only filled in by [`ContextPack::load_text`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PackEntry {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: f64,
+    pub estimated_tokens: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+struct Candidate<'a> {
+    path: &'a Path,
+    rank: usize,
+    start_line: usize,
+    end_line: usize,
+    value: f64,
+    bytes: usize,
+}
+
+impl ContextPack {
+    /// Picks the most valuable passages of `ranking` that fit in
+    /// `budget_tokens`.
+    ///
+    /// A passage's value is its own score scaled by its document's score
+    /// relative to the top result, so strong passages of weak files do not
+    /// crowd out the best files. Passages are taken greedily by value and
+    /// skipped when they no longer fit; touching passages of one file are
+    /// merged. Entries are grouped by file in rank order and sorted
+    /// by line within a file. Results without passages are not packed.
+    pub fn select(
+        index: &impl RankedIndexReader,
+        query: &str,
+        ranking: &[Score],
+        budget_tokens: usize,
+    ) -> Self {
+        let top_score = ranking
+            .iter()
+            .map(|score| score.score)
+            .fold(f64::MIN, f64::max);
+        let mut candidates = ranking
+            .iter()
+            .enumerate()
+            .flat_map(|(rank, score)| {
+                let offsets = index
+                    .doc_id(&score.doc_path)
+                    .and_then(|doc_id| index.document(doc_id))
+                    .and_then(|metadata| metadata.token_offsets.as_ref());
+                let document_weight = if top_score > 0.0 {
+                    (score.score / top_score).max(0.0)
+                } else {
+                    1.0
+                };
+
+                score.passages.iter().filter_map(move |passage| {
+                    let range = offsets?.line_byte_range(passage.start_line, passage.end_line)?;
+                    Some(Candidate {
+                        path: &score.doc_path,
+                        rank,
+                        start_line: passage.start_line,
+                        end_line: passage.end_line,
+                        value: passage.score * document_weight,
+                        bytes: range.len(),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            b.value
+                .total_cmp(&a.value)
+                .then_with(|| a.rank.cmp(&b.rank))
+                .then_with(|| a.start_line.cmp(&b.start_line))
+        });
+
+        let mut selected: Vec<Candidate> = Vec::new();
+        let mut used_tokens = 0;
+        for candidate in candidates {
+            let cost = entry_tokens(candidate.bytes);
+            if used_tokens + cost > budget_tokens {
+                continue;
+            }
+            used_tokens += cost;
+            selected.push(candidate);
+        }
+        selected.sort_by_key(|candidate| (candidate.rank, candidate.start_line));
+
+        let mut entries: Vec<(usize, PackEntry)> = Vec::with_capacity(selected.len());
+        for candidate in selected {
+            let cost = entry_tokens(candidate.bytes);
+            match entries.last_mut() {
+                Some((rank, last))
+                    if *rank == candidate.rank && candidate.start_line <= last.end_line + 1 =>
+                {
+                    // Passages of one document never overlap, so merging
+                    // only drops the second entry's header overhead.
+                    last.end_line = last.end_line.max(candidate.end_line);
+                    last.score = last.score.max(candidate.value);
+                    last.estimated_tokens += cost - ENTRY_OVERHEAD_TOKENS;
+                }
+                _ => entries.push((
+                    candidate.rank,
+                    PackEntry {
+                        path: candidate.path.to_path_buf(),
+                        start_line: candidate.start_line,
+                        end_line: candidate.end_line,
+                        score: candidate.value,
+                        estimated_tokens: cost,
+                        text: None,
+                    },
+                )),
+            }
+        }
+        let entries = entries
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect::<Vec<_>>();
+
+        Self {
+            query: query.to_string(),
+            budget_tokens,
+            estimated_tokens: entries.iter().map(|entry| entry.estimated_tokens).sum(),
+            entries,
+        }
+    }
+
+    /// Reads each entry's lines through `read`. Entries whose file cannot be
+    /// read keep `text` empty and are left out of the markdown.
+    pub fn load_text(&mut self, read: impl Fn(&Path) -> Option<String>) {
+        for entry in &mut self.entries {
+            entry.text = read(&entry.path).map(|content| {
+                content
+                    .lines()
+                    .skip(entry.start_line - 1)
+                    .take(entry.end_line + 1 - entry.start_line)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Context for: {}\n", self.query);
+        for entry in &self.entries {
+            let Some(text) = &entry.text else {
+                continue;
+            };
+            let language = entry
+                .path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or_default();
+            let _ = write!(
+                markdown,
+                "\n## {}:{}-{}\n\n```{language}\n{text}\n```\n",
+                entry.path.display(),
+                entry.start_line,
+                entry.end_line,
+            );
+        }
+        markdown
+    }
+}
+
+fn entry_tokens(bytes: usize) -> usize {
+    estimate_tokens_from_bytes(bytes) + ENTRY_OVERHEAD_TOKENS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{ContextPack, ENTRY_OVERHEAD_TOKENS};
+    use crate::{
+        index::InvertedIndex,
+        query::AnalyzedQuery,
+        ranking::{BM25HyperParams, PassageChunking, PassageConfig, RankingAlgo, attach_passages},
+        test_support::{config_without_stop_words, write_temp_file},
+    };
+
+    fn file(matches: &[usize]) -> String {
+        (1..=40)
+            .map(|line| {
+                if matches.contains(&line) {
+                    "fn retry_pool() {}".to_string()
+                } else {
+                    format!("fn filler_{line}() {{}}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn pack(dir: &Path, budget_tokens: usize) -> ContextPack {
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir, &config, Some(dir));
+        let query = AnalyzedQuery::new_code_search("retry pool", &config);
+        let mut ranking = RankingAlgo::BM25(BM25HyperParams { k1: 1.2, b: 0.75 })
+            .rank(&index, &query, 10)
+            .unwrap();
+        attach_passages(
+            &index,
+            &query,
+            &mut ranking,
+            &PassageConfig {
+                chunking: PassageChunking::Windows {
+                    lines: 5,
+                    stride: 5,
+                },
+                max_passages: 3,
+            },
+        );
+
+        ContextPack::select(&index, "retry pool", &ranking.0, budget_tokens)
+    }
+
+    #[test]
+    fn pack_stays_within_budget_and_orders_entries_by_file_then_line() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", &file(&[2, 3, 4, 33]));
+        write_temp_file(dir.path(), "b.rs", &file(&[20]));
+
+        let full = pack(dir.path(), 10_000);
+        assert_eq!(
+            full.entries
+                .iter()
+                .map(|entry| (
+                    entry.path.to_str().unwrap(),
+                    entry.start_line,
+                    entry.end_line
+                ))
+                .collect::<Vec<_>>(),
+            vec![("a.rs", 1, 5), ("a.rs", 31, 35), ("b.rs", 16, 20)]
+        );
+
+        let tight = pack(dir.path(), full.entries[0].estimated_tokens + 1);
+        assert_eq!(tight.entries.len(), 1);
+        assert!(tight.estimated_tokens <= tight.budget_tokens);
+        assert_eq!(tight.entries[0].start_line, 1);
+
+        assert!(pack(dir.path(), ENTRY_OVERHEAD_TOKENS).entries.is_empty());
+    }
+
+    #[test]
+    fn adjacent_passages_of_one_file_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", &file(&[4, 5, 6, 7]));
+
+        let pack = pack(dir.path(), 10_000);
+
+        assert_eq!(pack.entries.len(), 1);
+        assert_eq!(
+            (pack.entries[0].start_line, pack.entries[0].end_line),
+            (1, 10)
+        );
+    }
+
+    #[test]
+    fn markdown_has_file_and_line_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "a.rs", &file(&[2]));
+
+        let mut pack = pack(dir.path(), 10_000);
+        pack.load_text(|path| std::fs::read_to_string(dir.path().join(path)).ok());
+
+        assert_eq!(
+            pack.to_markdown(),
+            "# Context for: retry pool\n\n## a.rs:1-5\n\n```rs\nfn filler_1() {}\nfn retry_pool() {}\nfn filler_3() {}\nfn filler_4() {}\nfn filler_5() {}\n```\n"
+        );
+    }
+}
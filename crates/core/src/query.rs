@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{
     config::Config,
     index::Term,
-    tokenizer::{AnalyzerField, AnalyzerProfile, FileType, n_gram_transform},
+    tokenizer::{AnalyzerField, AnalyzerProfile, FileType, Language, n_gram_transform},
 };
 
+pub mod intent;
+
+use intent::IntentClassifier;
+
 #[derive(Clone, Debug)]
 pub struct AnalyzedQuery {
     original_text: String,
@@ -21,7 +25,9 @@ pub struct QueryTerm {
     pub provenance: QueryTermProvenance,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize, serde::Serialize,
+)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryIntent {
     Path,
@@ -44,20 +50,91 @@ pub struct QueryExpansionConfig {
     pub feedback: bool,
 }
 
+/// Abbreviations and the terms controlled expansion adds for them, at
+/// `weight` relative to the original query terms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpansionTable {
+    pub weight: f64,
+    pub terms: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for ExpansionTable {
+    fn default() -> Self {
+        let terms = [
+            ("db", &["database"][..]),
+            ("auth", &["authentication", "authorization"]),
+            ("cfg", &["configuration"]),
+            ("config", &["configuration"]),
+            ("repo", &["repository"]),
+            ("err", &["error"]),
+            ("msg", &["message"]),
+            ("req", &["request"]),
+            ("res", &["response", "result"]),
+        ];
+        Self {
+            weight: 0.35,
+            terms: terms
+                .into_iter()
+                .map(|(term, expansions)| {
+                    (
+                        term.to_string(),
+                        expansions.iter().map(|e| e.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ExpansionTable {
+    /// Replaces the entries for the terms in `overrides`; an empty list
+    /// stops a term from expanding.
+    pub fn with_overrides(mut self, overrides: BTreeMap<String, Vec<String>>) -> Self {
+        for (term, expansions) in overrides {
+            if expansions.is_empty() {
+                self.terms.remove(&term);
+            } else {
+                self.terms.insert(term, expansions);
+            }
+        }
+        self
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn expansions_for(&self, term: &str) -> &[String] {
+        self.terms.get(term).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A positional operator parsed from the query text.
+///
+/// Quoted phrases (`"connect pool"`) are ordered with zero slop, a `~N`
+/// suffix (`"connect pool"~3`) allows up to `N` intervening tokens, and
+/// `NEAR/N(a, b)` matches the terms in any order within `N` intervening
+/// tokens. A leading `+` turns the operator into a required filter; a
+/// required single term (`+"pool"`) only requires the term itself.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct QueryPhrase {
     pub raw: String,
     pub terms: Vec<Term>,
+    pub slop: u32,
+    pub ordered: bool,
+    pub required: bool,
 }
 
 impl AnalyzedQuery {
     pub fn new(query: &str, config: &Config) -> Self {
         let profile = AnalyzerProfile::for_file_type(FileType::UnknownText);
+        let operators = parse_positional_operators(query);
         Self::from_frequencies_and_phrases_with_intent(
             query.to_string(),
-            classify_query_intent(query),
-            n_gram_transform(query, config),
-            parse_quoted_phrases(query, |phrase| {
+            classify_query_intent(&operators.text),
+            n_gram_transform(&operators.text, config),
+            analyze_phrases(operators.phrases, |phrase| {
                 content_tokens_for_query(profile, phrase, config)
             }),
         )
@@ -72,26 +149,35 @@ impl AnalyzedQuery {
         config: &Config,
         expansion: QueryExpansionConfig,
     ) -> Self {
-        let intent = classify_query_intent(query);
+        let operators = parse_positional_operators(query);
+        let intent = classify_query_intent(&operators.text);
         let profile = AnalyzerProfile::for_file_type(FileType::Rust);
-        let frequencies = profile
-            .analyze(AnalyzerField::Content, query, config)
-            .into_iter()
-            .fold(HashMap::new(), |mut acc, token| {
-                *acc.entry(Term(token)).or_insert(0) += 1;
-                acc
-            });
+        let mut tokens = profile.analyze(AnalyzerField::Content, &operators.text, config);
+        if !operators.text.is_ascii() || Language::detect(&operators.text).is_some() {
+            for token in multilingual_query_tokens(&operators.text, config) {
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+        }
+        let frequencies = tokens.into_iter().fold(HashMap::new(), |mut acc, token| {
+            *acc.entry(Term(token)).or_insert(0) += 1;
+            acc
+        });
 
         let mut query = Self::from_frequencies_and_phrases_with_intent(
             query.to_string(),
             intent,
             frequencies,
-            parse_quoted_phrases(query, |phrase| {
+            analyze_phrases(operators.phrases, |phrase| {
                 content_tokens_for_query(profile, phrase, config)
             }),
         );
         if expansion.controlled && query.intent.allows_controlled_expansion() {
-            let additions = controlled_expansions(query.terms.keys().map(|term| term.0.as_str()));
+            let additions = controlled_expansions(
+                &config.expansions,
+                query.terms.keys().map(|term| term.0.as_str()),
+            );
             query.add_weighted_terms(additions, QueryTermProvenance::ControlledExpansion);
         }
 
@@ -207,6 +293,10 @@ impl AnalyzedQuery {
         &self.phrases
     }
 
+    pub fn required_phrases(&self) -> impl Iterator<Item = &QueryPhrase> {
+        self.phrases.iter().filter(|phrase| phrase.required)
+    }
+
     pub fn add_feedback_terms(&mut self, weights: HashMap<Term, f64>) {
         self.add_weighted_terms(weights, QueryTermProvenance::Feedback);
     }
@@ -238,6 +328,31 @@ impl AnalyzedQuery {
 }
 
 impl QueryIntent {
+    pub const ALL: [Self; 5] = [
+        Self::Path,
+        Self::Identifier,
+        Self::NaturalLanguage,
+        Self::ErrorMessage,
+        Self::Config,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Identifier => "identifier",
+            Self::NaturalLanguage => "natural_language",
+            Self::ErrorMessage => "error_message",
+            Self::Config => "config",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|intent| *intent == self)
+            .expect("every intent is listed")
+    }
+
     pub fn allows_controlled_expansion(self) -> bool {
         matches!(
             self,
@@ -256,7 +371,15 @@ impl QueryTermProvenance {
     }
 }
 
+/// The intent of `query` according to the shipped classifier, falling
+/// back to the keyword heuristics when it is unsure.
 pub fn classify_query_intent(query: &str) -> QueryIntent {
+    IntentClassifier::shipped()
+        .classify(query)
+        .unwrap_or_else(|| heuristic_query_intent(query))
+}
+
+pub fn heuristic_query_intent(query: &str) -> QueryIntent {
     let trimmed = query.trim();
     let lower = trimmed.to_ascii_lowercase();
 
@@ -322,55 +445,156 @@ fn looks_config_like(query: &str, lower: &str) -> bool {
         || query.contains(':') && !query.contains(char::is_whitespace)
 }
 
-fn controlled_expansions<'a>(terms: impl Iterator<Item = &'a str>) -> HashMap<Term, f64> {
+fn controlled_expansions<'a>(
+    table: &ExpansionTable,
+    terms: impl Iterator<Item = &'a str>,
+) -> HashMap<Term, f64> {
     let mut expansions = HashMap::new();
 
     for term in terms {
-        for expansion in expansions_for(term) {
-            expansions.insert(Term(expansion.to_string()), 0.35);
+        for expansion in table.expansions_for(term) {
+            expansions.insert(Term(expansion.clone()), table.weight);
         }
     }
 
     expansions
 }
 
-fn expansions_for(term: &str) -> &'static [&'static str] {
-    match term {
-        "db" => &["database"],
-        "auth" => &["authentication", "authorization"],
-        "cfg" | "config" => &["configuration"],
-        "repo" => &["repository"],
-        "err" => &["error"],
-        "msg" => &["message"],
-        "req" => &["request"],
-        "res" => &["response", "result"],
-        _ => &[],
-    }
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PositionalOperators {
+    text: String,
+    phrases: Vec<RawPhrase>,
 }
 
-fn parse_quoted_phrases(query: &str, analyzer: impl Fn(&str) -> Vec<String>) -> Vec<QueryPhrase> {
-    let mut phrases = Vec::new();
-    let mut remaining = query;
+#[derive(Debug, PartialEq, Eq)]
+struct RawPhrase {
+    raw: String,
+    slop: u32,
+    ordered: bool,
+    required: bool,
+}
 
-    while let Some(start) = remaining.find('"') {
-        let after_start = &remaining[start + 1..];
-        let Some(end) = after_start.find('"') else {
-            break;
+/// Splits positional operator syntax out of `query`.
+///
+/// The returned text keeps every operator's inner terms (quoted phrases keep
+/// their quotes so intent classification is unchanged), while `~N` suffixes,
+/// `NEAR/N(...)` wrappers and `+` markers are removed so they never become
+/// bag-of-words terms.
+fn parse_positional_operators(query: &str) -> PositionalOperators {
+    let mut operators = PositionalOperators::default();
+    let mut rest = query;
+
+    while !rest.is_empty() {
+        let at_boundary = operators
+            .text
+            .chars()
+            .last()
+            .is_none_or(char::is_whitespace);
+        let (required, candidate) = match rest.strip_prefix('+') {
+            Some(candidate) if at_boundary => (true, candidate),
+            _ => (false, rest),
         };
 
-        let raw = &after_start[..end];
-        let terms = analyzer(raw).into_iter().map(Term).collect::<Vec<_>>();
-        if terms.len() > 1 {
-            phrases.push(QueryPhrase {
-                raw: raw.to_string(),
-                terms,
+        if let Some(after_quote) = candidate.strip_prefix('"') {
+            let Some(end) = after_quote.find('"') else {
+                operators.text.push_str(rest);
+                break;
+            };
+            let inner = &after_quote[..end];
+            let (slop, remaining) = parse_slop(&after_quote[end + 1..]);
+            operators.text.push('"');
+            operators.text.push_str(inner);
+            operators.text.push('"');
+            operators.phrases.push(RawPhrase {
+                raw: inner.to_string(),
+                slop,
+                ordered: true,
+                required,
+            });
+            rest = remaining;
+            continue;
+        }
+
+        if at_boundary && let Some((window, inner, remaining)) = parse_near(candidate) {
+            operators.text.push_str(&inner.replace(',', " "));
+            operators.phrases.push(RawPhrase {
+                raw: inner.to_string(),
+                slop: window,
+                ordered: false,
+                required,
             });
+            rest = remaining;
+            continue;
         }
 
-        remaining = &after_start[end + 1..];
+        let mut characters = rest.chars();
+        if let Some(character) = characters.next() {
+            operators.text.push(character);
+        }
+        rest = characters.as_str();
+    }
+
+    operators
+}
+
+fn parse_slop(input: &str) -> (u32, &str) {
+    let Some(after_tilde) = input.strip_prefix('~') else {
+        return (0, input);
+    };
+    let digits = after_tilde
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(after_tilde.len());
+
+    match after_tilde[..digits].parse() {
+        Ok(slop) => (slop, &after_tilde[digits..]),
+        Err(_) => (0, input),
     }
+}
+
+fn parse_near(input: &str) -> Option<(u32, &str, &str)> {
+    let after_near = input.strip_prefix("NEAR/")?;
+    let digits = after_near.find(|character: char| !character.is_ascii_digit())?;
+    let window = after_near[..digits].parse().ok()?;
+    let after_open = after_near[digits..].strip_prefix('(')?;
+    let close = after_open.find(')')?;
+
+    Some((window, &after_open[..close], &after_open[close + 1..]))
+}
 
+fn analyze_phrases(
+    phrases: Vec<RawPhrase>,
+    analyzer: impl Fn(&str) -> Vec<String>,
+) -> Vec<QueryPhrase> {
     phrases
+        .into_iter()
+        .filter_map(|phrase| {
+            let mut terms = analyzer(&phrase.raw.replace(',', " "))
+                .into_iter()
+                .map(Term)
+                .collect::<Vec<_>>();
+            if !phrase.ordered {
+                terms.sort();
+                terms.dedup();
+            }
+
+            // A single term has no positions to match, but a required one
+            // still filters the results to documents containing it.
+            (terms.len() > 1 || (phrase.required && !terms.is_empty())).then_some(QueryPhrase {
+                raw: phrase.raw,
+                terms,
+                slop: phrase.slop,
+                ordered: phrase.ordered,
+                required: phrase.required,
+            })
+        })
+        .collect()
+}
+
+/// Non-ASCII queries, and queries long enough to detect a language in, are
+/// also analyzed like multilingual prose so they can match stemmed, folded
+/// or bigrammed comment and documentation text.
+fn multilingual_query_tokens(text: &str, config: &Config) -> Vec<String> {
+    AnalyzerProfile::for_file_type(FileType::Markdown).analyze(AnalyzerField::Comment, text, config)
 }
 
 fn content_tokens_for_query(
@@ -396,24 +620,9 @@ impl std::fmt::Display for AnalyzedQuery {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
-
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
 
     use super::{AnalyzedQuery, QueryExpansionConfig, QueryIntent, classify_query_intent};
-    use crate::{config::Config, index::Term};
-
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect::<HashSet<String>>(),
-        }
-    }
+    use crate::{index::Term, test_support::test_config};
 
     #[test]
     fn classifies_query_intents() {
@@ -439,6 +648,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ascii_prose_in_another_language_gets_its_stems() {
+        let query = AnalyzedQuery::new_code_search(
+            "Verbindungen zur Datenbank werden erneut aufgebaut",
+            &test_config(),
+        );
+
+        assert!(
+            query
+                .terms()
+                .any(|(term, _)| *term == Term("verbind".to_string()))
+        );
+    }
+
     #[test]
     fn controlled_expansion_is_downweighted_and_gated_by_intent() {
         let config = test_config();
@@ -487,4 +710,46 @@ mod tests {
         assert_eq!(query.phrases()[0].raw, "ranked search");
         assert_eq!(query.phrases()[0].terms.len(), 2);
     }
+
+    #[test]
+    fn parses_phrase_slop_near_and_required_operators() {
+        let query = AnalyzedQuery::new_code_search(
+            "+\"connect pool\"~3 NEAR/5(retry, timeout) database",
+            &test_config(),
+        );
+        let phrases = query.phrases();
+
+        assert_eq!(phrases.len(), 2);
+        assert_eq!(phrases[0].raw, "connect pool");
+        assert_eq!(phrases[0].slop, 3);
+        assert!(phrases[0].ordered);
+        assert!(phrases[0].required);
+        assert_eq!(phrases[1].raw, "retry, timeout");
+        assert_eq!(phrases[1].slop, 5);
+        assert!(!phrases[1].ordered);
+        assert!(!phrases[1].required);
+        assert_eq!(query.required_phrases().count(), 1);
+
+        let single = AnalyzedQuery::new_code_search("+\"pool\" connect", &test_config());
+        let required = single.required_phrases().collect::<Vec<_>>();
+        assert_eq!(required.len(), 1);
+        assert_eq!(required[0].terms, vec![Term("pool".to_string())]);
+        assert!(
+            AnalyzedQuery::new_code_search("\"pool\" connect", &test_config())
+                .phrases()
+                .is_empty()
+        );
+
+        let terms = query
+            .terms()
+            .map(|(term, _)| term.0.as_str())
+            .collect::<Vec<_>>();
+        assert!(terms.contains(&"retry"));
+        assert!(terms.contains(&"database"));
+        assert!(
+            terms
+                .iter()
+                .all(|term| !term.contains("near") && !term.contains('3'))
+        );
+    }
 }
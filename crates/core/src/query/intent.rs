@@ -0,0 +1,470 @@
+//! Query intent learned from labelled queries.
+//!
+//! A multinomial naive Bayes model over character trigrams of the query and
+//! a handful of shape features (word count, path separators, casing,
+//! punctuation). It is trained from the `query_shape` labels of the
+//! evaluation set, `data/eval/repo_reaper.json`, together with the extra
+//! labelled queries in `data/intent/queries.json`, and shipped as compact
+//! JSON in `intent_model.json`. Five-fold cross-validation over those 125
+//! queries puts its accuracy at 0.74; the confusion matrix `rr eval`
+//! reports is measured on queries the model was trained on. Predictions
+//! below the model's confidence threshold fall back to the keyword
+//! heuristics.
+
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use crate::query::{QueryIntent, heuristic_query_intent};
+
+const NGRAM_SIZE: usize = 3;
+/// Times each shape feature is counted, so a few of them can hold their
+/// own against the many n-grams of a query.
+const SHAPE_WEIGHT: u32 = 4;
+const QUESTION_WORDS: &[&str] = &["how", "what", "where", "why", "which", "when", "who"];
+
+static SHIPPED: LazyLock<IntentClassifier> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("intent_model.json")).expect("shipped intent model parses")
+});
+
+#[derive(Debug, thiserror::Error)]
+pub enum IntentModelError {
+    #[error("failed to access intent model {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("intent model JSON failed: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no labelled query to train the intent model on")]
+    NoLabelledQueries,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IntentClassifier {
+    /// Smallest posterior probability a prediction is trusted with.
+    pub min_confidence: f64,
+    /// Additive smoothing of the feature counts.
+    pub smoothing: f64,
+    classes: Vec<IntentClass>,
+    /// Per-feature counts, one per class in `classes` order.
+    features: BTreeMap<String, Vec<u32>>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct IntentClass {
+    intent: QueryIntent,
+    queries: u32,
+    feature_total: u64,
+}
+
+impl IntentClassifier {
+    /// The model shipped with the crate.
+    pub fn shipped() -> &'static IntentClassifier {
+        &SHIPPED
+    }
+
+    pub fn train<'a>(
+        examples: impl IntoIterator<Item = (&'a str, QueryIntent)>,
+    ) -> Result<Self, IntentModelError> {
+        let mut classes: Vec<IntentClass> = Vec::new();
+        let mut counts: BTreeMap<String, BTreeMap<QueryIntent, u32>> = BTreeMap::new();
+
+        for (query, intent) in examples {
+            let position = match classes.iter().position(|class| class.intent == intent) {
+                Some(position) => position,
+                None => {
+                    classes.push(IntentClass {
+                        intent,
+                        queries: 0,
+                        feature_total: 0,
+                    });
+                    classes.len() - 1
+                }
+            };
+            classes[position].queries += 1;
+            for (feature, weight) in query_features(query) {
+                *counts
+                    .entry(feature)
+                    .or_default()
+                    .entry(intent)
+                    .or_default() += weight;
+                classes[position].feature_total += u64::from(weight);
+            }
+        }
+        if classes.is_empty() {
+            return Err(IntentModelError::NoLabelledQueries);
+        }
+        classes.sort_by_key(|class| class.intent);
+
+        let features = counts
+            .into_iter()
+            .map(|(feature, by_intent)| {
+                let counts = classes
+                    .iter()
+                    .map(|class| by_intent.get(&class.intent).copied().unwrap_or(0))
+                    .collect();
+                (feature, counts)
+            })
+            .collect();
+
+        Ok(Self {
+            min_confidence: 0.6,
+            smoothing: 0.5,
+            classes,
+            features,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, IntentModelError> {
+        let content = std::fs::read_to_string(path).map_err(|source| IntentModelError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), IntentModelError> {
+        // Compact, since the shipped model is compiled into the binary.
+        std::fs::write(path, serde_json::to_string(self)?).map_err(|source| IntentModelError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// The most probable intent of `query` and its posterior probability.
+    /// Queries sharing no feature with the training data have none.
+    pub fn predict(&self, query: &str) -> Option<(QueryIntent, f64)> {
+        let total_queries = self
+            .classes
+            .iter()
+            .map(|class| f64::from(class.queries))
+            .sum::<f64>();
+        let vocabulary = self.features.len() as f64;
+        let mut log_posteriors = self
+            .classes
+            .iter()
+            .map(|class| (f64::from(class.queries) / total_queries).ln())
+            .collect::<Vec<_>>();
+
+        let mut known = false;
+        for (feature, weight) in query_features(query) {
+            let Some(counts) = self.features.get(&feature) else {
+                continue;
+            };
+            known = true;
+            for ((log_posterior, class), count) in
+                log_posteriors.iter_mut().zip(&self.classes).zip(counts)
+            {
+                let likelihood = (f64::from(*count) + self.smoothing)
+                    / (class.feature_total as f64 + self.smoothing * vocabulary);
+                *log_posterior += f64::from(weight) * likelihood.ln();
+            }
+        }
+        if !known {
+            return None;
+        }
+
+        let max = log_posteriors
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let normalizer = log_posteriors
+            .iter()
+            .map(|log_posterior| (log_posterior - max).exp())
+            .sum::<f64>();
+        self.classes
+            .iter()
+            .zip(&log_posteriors)
+            .map(|(class, log_posterior)| (class.intent, (log_posterior - max).exp() / normalizer))
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+    }
+
+    /// The predicted intent when the model is confident enough in it.
+    pub fn classify(&self, query: &str) -> Option<QueryIntent> {
+        self.predict(query)
+            .filter(|(_, probability)| *probability >= self.min_confidence)
+            .map(|(intent, _)| intent)
+    }
+}
+
+/// Trains on all but every `folds`th example in turn and classifies the
+/// held-out ones with it, heuristics falling back as in search.
+pub fn cross_validate_intents(
+    examples: &[(String, QueryIntent)],
+    folds: usize,
+) -> Result<IntentConfusion, IntentModelError> {
+    let folds = folds.max(2);
+    let mut confusion = IntentConfusion::new();
+
+    for fold in 0..folds {
+        let training = examples
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| index % folds != fold)
+            .map(|(_, (query, intent))| (query.as_str(), *intent));
+        let classifier = IntentClassifier::train(training)?;
+        for (_, (query, intent)) in examples
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| index % folds == fold)
+        {
+            let predicted = classifier
+                .classify(query)
+                .unwrap_or_else(|| heuristic_query_intent(query));
+            confusion.record(*intent, predicted);
+        }
+    }
+
+    Ok(confusion)
+}
+
+/// Character trigrams of the lowercased query, padded so word boundaries
+/// count, and its shape features, each with how often it occurs.
+fn query_features(query: &str) -> BTreeMap<String, u32> {
+    let trimmed = query.trim();
+    let padded = format!(" {} ", trimmed.to_lowercase())
+        .chars()
+        .collect::<Vec<_>>();
+    let mut features = BTreeMap::new();
+
+    for window in padded.windows(NGRAM_SIZE) {
+        *features
+            .entry(format!("c:{}", window.iter().collect::<String>()))
+            .or_default() += 1;
+    }
+    for shape in shape_features(trimmed) {
+        features.insert(format!("s:{shape}"), SHAPE_WEIGHT);
+    }
+
+    features
+}
+
+fn shape_features(query: &str) -> Vec<String> {
+    let words = query.split_whitespace().collect::<Vec<_>>();
+    let mut shapes = vec![match words.len() {
+        count @ 0..=5 => format!("words={count}"),
+        _ => "words=6+".to_string(),
+    }];
+    let mut flag = |present: bool, name: &str| {
+        if present {
+            shapes.push(name.to_string());
+        }
+    };
+
+    flag(query.contains('/') || query.contains('\\'), "slash");
+    flag(query.contains("::"), "scope");
+    flag(
+        words.iter().any(|word| {
+            word.rsplit_once('.').is_some_and(|(stem, extension)| {
+                !stem.is_empty()
+                    && (1..=4).contains(&extension.len())
+                    && extension.chars().all(|c| c.is_ascii_alphabetic())
+            })
+        }),
+        "extension",
+    );
+    flag(query.contains('_'), "underscore");
+    flag(
+        query
+            .chars()
+            .zip(query.chars().skip(1))
+            .any(|(a, b)| a.is_lowercase() && b.is_uppercase()),
+        "camel_case",
+    );
+    flag(
+        words
+            .iter()
+            .any(|word| word.len() > 1 && word.chars().all(|c| c.is_uppercase() || c == '_')),
+        "upper_case",
+    );
+    flag(query.contains('='), "equals");
+    flag(query.contains(':') && !query.contains("::"), "colon");
+    flag(query.contains(['"', '\'', '`']), "quote");
+    flag(query.contains(['(', ')']), "parenthesis");
+    flag(query.contains(|c: char| c.is_ascii_digit()), "digit");
+    flag(
+        words
+            .first()
+            .is_some_and(|word| QUESTION_WORDS.contains(&word.to_lowercase().as_str())),
+        "question",
+    );
+
+    shapes
+}
+
+/// Predicted against labelled intents, by intent.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IntentConfusion {
+    /// `counts[actual][predicted]`, both in [`QueryIntent::ALL`] order.
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl IntentConfusion {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![vec![0; QueryIntent::ALL.len()]; QueryIntent::ALL.len()],
+        }
+    }
+
+    pub fn from_predictions(
+        predictions: impl IntoIterator<Item = (QueryIntent, QueryIntent)>,
+    ) -> Self {
+        let mut confusion = Self::new();
+        for (actual, predicted) in predictions {
+            confusion.record(actual, predicted);
+        }
+        confusion
+    }
+
+    pub fn record(&mut self, actual: QueryIntent, predicted: QueryIntent) {
+        if self.counts.is_empty() {
+            *self = Self::new();
+        }
+        self.counts[actual.index()][predicted.index()] += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        let correct = (0..self.counts.len())
+            .map(|intent| self.counts[intent][intent])
+            .sum::<usize>();
+        correct as f64 / self.total().max(1) as f64
+    }
+}
+
+impl fmt::Display for IntentConfusion {
+    /// Rows are labelled intents, columns predicted ones; intents that
+    /// appear nowhere are left out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let present = QueryIntent::ALL
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                self.counts
+                    .get(*index)
+                    .is_some_and(|row| row.iter().any(|&count| count > 0))
+                    || self
+                        .counts
+                        .iter()
+                        .any(|row| row.get(*index).is_some_and(|&count| count > 0))
+            })
+            .map(|(index, intent)| (index, intent.as_str()))
+            .collect::<Vec<_>>();
+        let width = present
+            .iter()
+            .map(|(_, name)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("actual \\ predicted".len());
+
+        write!(f, "{:<width$}", "actual \\ predicted")?;
+        for (_, name) in &present {
+            write!(f, " {name:>16}")?;
+        }
+        for (actual, name) in &present {
+            write!(f, "\n{name:<width$}")?;
+            for (predicted, _) in &present {
+                write!(f, " {:>16}", self.counts[*actual][*predicted])?;
+            }
+        }
+        write!(f, "\naccuracy: {:.4} (n={})", self.accuracy(), self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IntentClassifier, IntentConfusion};
+    use crate::{
+        evaluation::dataset::LabelledQueries,
+        query::{QueryIntent, classify_query_intent},
+    };
+
+    fn classifier() -> IntentClassifier {
+        IntentClassifier::train([
+            ("src/index/snapshot.rs", QueryIntent::Path),
+            ("crates/cli/src/main.rs", QueryIntent::Path),
+            ("README.md", QueryIntent::Path),
+            ("SearchEngine", QueryIntent::Identifier),
+            ("rank_filtered", QueryIntent::Identifier),
+            ("BM25HyperParams", QueryIntent::Identifier),
+            ("how are results ranked", QueryIntent::NaturalLanguage),
+            ("where is the index written", QueryIntent::NaturalLanguage),
+            ("what removes stop words", QueryIntent::NaturalLanguage),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn learns_intents_from_labelled_queries() {
+        let classifier = classifier();
+
+        assert_eq!(
+            classifier.classify("src/ranking/bm25.rs"),
+            Some(QueryIntent::Path)
+        );
+        assert_eq!(
+            classifier.classify("IntentClassifier"),
+            Some(QueryIntent::Identifier)
+        );
+        assert_eq!(
+            classifier.classify("how is the snapshot written"),
+            Some(QueryIntent::NaturalLanguage)
+        );
+        assert!(IntentClassifier::train([]).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let classifier = classifier();
+        let encoded = serde_json::to_string(&classifier).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<IntentClassifier>(&encoded).unwrap(),
+            classifier
+        );
+    }
+
+    #[test]
+    fn shipped_model_classifies_the_evaluation_queries() {
+        let labelled: LabelledQueries =
+            serde_json::from_str(include_str!("../../../../data/eval/repo_reaper.json")).unwrap();
+        let confusion =
+            IntentConfusion::from_predictions(labelled.examples.iter().filter_map(|example| {
+                let intent = example.query_shape.intent()?;
+                Some((intent, classify_query_intent(&example.query)))
+            }));
+
+        assert!(!IntentClassifier::shipped().classes.is_empty());
+        assert!(confusion.total() > 0);
+        assert!(confusion.accuracy() >= 0.8, "{confusion}");
+    }
+
+    #[test]
+    fn confusion_counts_predictions_by_labelled_intent() {
+        let confusion = IntentConfusion::from_predictions([
+            (QueryIntent::Path, QueryIntent::Path),
+            (QueryIntent::Config, QueryIntent::NaturalLanguage),
+            (QueryIntent::Config, QueryIntent::Config),
+            (QueryIntent::NaturalLanguage, QueryIntent::NaturalLanguage),
+        ]);
+
+        assert_eq!(confusion.total(), 4);
+        assert_eq!(confusion.accuracy(), 0.75);
+        assert_eq!(
+            confusion.to_string(),
+            "actual \\ predicted             path natural_language           config\n\
+             path                              1                0                0\n\
+             natural_language                  0                1                0\n\
+             config                            0                1                1\n\
+             accuracy: 0.7500 (n=4)"
+        );
+    }
+}
@@ -19,10 +19,49 @@ impl Default for BM25HyperParams {
     }
 }
 
+/// Lower bounds on BM25's term-frequency component, so a long file that
+/// matches a term is never scored close to one that does not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BM25Variant {
+    /// BM25+: adds `delta` to every matching term's saturated frequency.
+    Plus { delta: f64 },
+    /// BM25L: shifts the length-normalised frequency by `delta` before
+    /// saturating it.
+    L { delta: f64 },
+}
+
+impl BM25Variant {
+    pub fn plus_defaults() -> Self {
+        Self::Plus { delta: 1.0 }
+    }
+
+    pub fn l_defaults() -> Self {
+        Self::L { delta: 0.5 }
+    }
+
+    pub fn delta(&self) -> f64 {
+        match self {
+            Self::Plus { delta } | Self::L { delta } => *delta,
+        }
+    }
+
+    pub fn with_delta(self, delta: f64) -> Self {
+        match self {
+            Self::Plus { .. } => Self::Plus { delta },
+            Self::L { .. } => Self::L { delta },
+        }
+    }
+}
+
 pub struct BM25 {
     pub hyper_params: BM25HyperParams,
 }
 
+pub struct LowerBoundedBM25 {
+    pub hyper_params: BM25HyperParams,
+    pub variant: BM25Variant,
+}
+
 impl Scorer for BM25 {
     fn score<I, P>(
         &self,
@@ -74,13 +113,73 @@ impl BM25 {
     }
 }
 
+impl Scorer for LowerBoundedBM25 {
+    fn score<I, P>(
+        &self,
+        index: &I,
+        _: &AnalyzedQuery,
+        _: &Term,
+        query_term: QueryTerm,
+        documents: &P,
+        scores: &DashMap<DocId, f64>,
+    ) where
+        I: RankedIndexReader + Sync,
+        P: PostingList + Sync,
+    {
+        let avgdl = index.avg_doc_length();
+        let idf = idf(index.num_docs(), documents.len());
+
+        documents
+            .iter()
+            .par_bridge()
+            .for_each(|(doc_id, term_doc)| {
+                let score = self.score_term(
+                    query_term.weight,
+                    idf,
+                    term_doc.term_freq as f64,
+                    term_doc.length as f64,
+                    avgdl,
+                );
+
+                *scores.entry(doc_id).or_insert(0.0) += score;
+            });
+    }
+}
+
+impl LowerBoundedBM25 {
+    pub fn score_term(
+        &self,
+        query_weight: f64,
+        idf: f64,
+        tf: f64,
+        doc_length: f64,
+        avgdl: f64,
+    ) -> f64 {
+        if tf == 0.0 || avgdl == 0.0 {
+            return 0.0;
+        }
+
+        let BM25HyperParams { k1, b } = self.hyper_params;
+        let length_norm = 1.0 - b + b * doc_length / avgdl;
+        let saturated = match self.variant {
+            BM25Variant::Plus { delta } => tf * (k1 + 1.0) / (tf + k1 * length_norm) + delta,
+            BM25Variant::L { delta } => {
+                let normalized_tf = tf / length_norm + delta;
+                normalized_tf * (k1 + 1.0) / (normalized_tf + k1)
+            }
+        };
+
+        query_weight * idf * saturated
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{collections::HashMap, path::PathBuf};
 
     use dashmap::DashMap;
 
-    use super::{BM25, BM25HyperParams};
+    use super::{BM25, BM25HyperParams, BM25Variant, LowerBoundedBM25};
     use crate::{
         index::{DocId, InvertedIndex, Term},
         query::AnalyzedQuery,
@@ -214,4 +313,37 @@ mod tests {
             "higher query weight should increase score: {high} vs {low}"
         );
     }
+
+    #[test]
+    fn lower_bounded_variants_narrow_the_gap_for_long_documents() {
+        let classic = bm25();
+        let short = classic.score_term(1.0, 1.0, 1.0, 10.0, 100.0);
+        let long = classic.score_term(1.0, 1.0, 1.0, 2_000.0, 100.0);
+
+        for variant in [BM25Variant::plus_defaults(), BM25Variant::l_defaults()] {
+            let bounded = LowerBoundedBM25 {
+                hyper_params: BM25HyperParams::default(),
+                variant,
+            };
+            let bounded_short = bounded.score_term(1.0, 1.0, 1.0, 10.0, 100.0);
+            let bounded_long = bounded.score_term(1.0, 1.0, 1.0, 2_000.0, 100.0);
+
+            assert!(bounded_long > long, "{variant:?}: {bounded_long} vs {long}");
+            assert!(
+                bounded_long / bounded_short > long / short,
+                "{variant:?} should penalise length less than BM25"
+            );
+        }
+    }
+
+    #[test]
+    fn bm25_plus_scores_a_matching_term_at_least_delta() {
+        let bounded = LowerBoundedBM25 {
+            hyper_params: BM25HyperParams::default(),
+            variant: BM25Variant::Plus { delta: 1.0 },
+        };
+
+        assert!(bounded.score_term(1.0, 2.0, 1.0, 1_000_000.0, 10.0) >= 2.0);
+        assert_eq!(bounded.score_term(1.0, 2.0, 0.0, 10.0, 10.0), 0.0);
+    }
 }
@@ -14,6 +14,16 @@ pub struct BM25FHyperParams {
     pub k1: f64,
     pub b: f64,
     pub field_weights: HashMap<DocumentField, f64>,
+    /// Scale applied to field-weighted phrase and `NEAR` matches.
+    #[serde(default = "default_phrase_boost")]
+    pub phrase_boost: f64,
+    /// Per-intent field multipliers replacing the built-in ones.
+    #[serde(default)]
+    pub intent_field_weights: HashMap<QueryIntent, HashMap<DocumentField, f64>>,
+}
+
+fn default_phrase_boost() -> f64 {
+    1.0
 }
 
 impl BM25FHyperParams {
@@ -33,6 +43,19 @@ impl BM25FHyperParams {
                 (DocumentField::StringLiteral, 0.8),
                 (DocumentField::Frontmatter, 1.2),
             ]),
+            phrase_boost: default_phrase_boost(),
+            intent_field_weights: HashMap::new(),
+        }
+    }
+
+    /// Code-search parameters that score `field` alone.
+    pub fn single_field(field: DocumentField) -> Self {
+        Self {
+            field_weights: DocumentField::ALL
+                .into_iter()
+                .map(|other| (other, if other == field { 1.0 } else { 0.0 }))
+                .collect(),
+            ..Self::code_search_defaults()
         }
     }
 
@@ -41,67 +64,77 @@ impl BM25FHyperParams {
     }
 
     pub fn intent_field_weight(&self, field: DocumentField, intent: QueryIntent) -> f64 {
-        let base = self.field_weight(field);
-        let multiplier = match intent {
-            QueryIntent::Path => match field {
-                DocumentField::FileName => 1.5,
-                DocumentField::RelativePath => 2.2,
-                DocumentField::Extension => 1.4,
-                DocumentField::Content => 0.45,
-                DocumentField::Identifier
-                | DocumentField::Symbol
-                | DocumentField::Import
-                | DocumentField::Frontmatter
-                | DocumentField::Comment
-                | DocumentField::StringLiteral => 0.6,
-            },
-            QueryIntent::Identifier => match field {
-                DocumentField::FileName => 1.15,
-                DocumentField::RelativePath => 1.1,
-                DocumentField::Identifier => 1.7,
-                DocumentField::Symbol => 1.8,
-                DocumentField::Import => 1.1,
-                DocumentField::Content => 0.75,
-                DocumentField::Extension
-                | DocumentField::Comment
-                | DocumentField::StringLiteral
-                | DocumentField::Frontmatter => 0.8,
-            },
-            QueryIntent::ErrorMessage => match field {
-                DocumentField::StringLiteral => 2.0,
-                DocumentField::Content => 1.25,
-                DocumentField::Symbol => 1.1,
-                DocumentField::Comment => 0.9,
-                DocumentField::FileName
-                | DocumentField::RelativePath
-                | DocumentField::Extension
-                | DocumentField::Identifier
-                | DocumentField::Import
-                | DocumentField::Frontmatter => 0.7,
-            },
-            QueryIntent::Config => match field {
-                DocumentField::FileName => 1.5,
-                DocumentField::RelativePath => 1.8,
-                DocumentField::Extension => 1.7,
-                DocumentField::Identifier => 1.25,
-                DocumentField::Frontmatter => 1.8,
-                DocumentField::Content => 0.9,
-                DocumentField::Symbol | DocumentField::Import => 1.0,
-                DocumentField::Comment | DocumentField::StringLiteral => 0.8,
-            },
-            QueryIntent::NaturalLanguage => match field {
-                DocumentField::Content => 1.1,
-                DocumentField::Comment => 1.35,
-                DocumentField::Frontmatter => 1.2,
-                DocumentField::Identifier | DocumentField::Symbol | DocumentField::Import => 0.9,
-                DocumentField::FileName
-                | DocumentField::RelativePath
-                | DocumentField::Extension
-                | DocumentField::StringLiteral => 0.8,
-            },
-        };
-
-        base * multiplier
+        let multiplier = self
+            .intent_field_weights
+            .get(&intent)
+            .and_then(|weights| weights.get(&field))
+            .copied()
+            .unwrap_or_else(|| default_intent_multiplier(field, intent));
+
+        self.field_weight(field) * multiplier
+    }
+}
+
+/// How much each field counts for a query of `intent`, relative to its
+/// field weight.
+pub(crate) fn default_intent_multiplier(field: DocumentField, intent: QueryIntent) -> f64 {
+    match intent {
+        QueryIntent::Path => match field {
+            DocumentField::FileName => 1.5,
+            DocumentField::RelativePath => 2.2,
+            DocumentField::Extension => 1.4,
+            DocumentField::Content => 0.45,
+            DocumentField::Identifier
+            | DocumentField::Symbol
+            | DocumentField::Import
+            | DocumentField::Frontmatter
+            | DocumentField::Comment
+            | DocumentField::StringLiteral => 0.6,
+        },
+        QueryIntent::Identifier => match field {
+            DocumentField::FileName => 1.15,
+            DocumentField::RelativePath => 1.1,
+            DocumentField::Identifier => 1.7,
+            DocumentField::Symbol => 1.8,
+            DocumentField::Import => 1.1,
+            DocumentField::Content => 0.75,
+            DocumentField::Extension
+            | DocumentField::Comment
+            | DocumentField::StringLiteral
+            | DocumentField::Frontmatter => 0.8,
+        },
+        QueryIntent::ErrorMessage => match field {
+            DocumentField::StringLiteral => 2.0,
+            DocumentField::Content => 1.25,
+            DocumentField::Symbol => 1.1,
+            DocumentField::Comment => 0.9,
+            DocumentField::FileName
+            | DocumentField::RelativePath
+            | DocumentField::Extension
+            | DocumentField::Identifier
+            | DocumentField::Import
+            | DocumentField::Frontmatter => 0.7,
+        },
+        QueryIntent::Config => match field {
+            DocumentField::FileName => 1.5,
+            DocumentField::RelativePath => 1.8,
+            DocumentField::Extension => 1.7,
+            DocumentField::Identifier => 1.25,
+            DocumentField::Frontmatter => 1.8,
+            DocumentField::Content => 0.9,
+            DocumentField::Symbol | DocumentField::Import => 1.0,
+            DocumentField::Comment | DocumentField::StringLiteral => 0.8,
+        },
+        QueryIntent::NaturalLanguage => match field {
+            DocumentField::Content => 1.1,
+            DocumentField::Comment => 1.35,
+            DocumentField::Frontmatter => 1.2,
+            DocumentField::Identifier | DocumentField::Symbol | DocumentField::Import => 0.9,
+            DocumentField::FileName
+            | DocumentField::RelativePath
+            | DocumentField::Extension
+            | DocumentField::StringLiteral => 0.8,
+        },
     }
 }
 
@@ -185,41 +218,16 @@ impl Scorer for BM25F {
 
 #[cfg(test)]
 mod tests {
-    use std::{
-        collections::HashSet,
-        path::{Path, PathBuf},
-    };
-
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
+    use std::path::{Path, PathBuf};
 
     use super::{BM25F, BM25FHyperParams};
     use crate::{
-        config::Config,
         index::InvertedIndex,
         query::{AnalyzedQuery, QueryIntent},
         ranking::{BM25HyperParams, RankingAlgo},
+        test_support::{test_config, write_temp_file},
     };
 
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect::<HashSet<String>>(),
-        }
-    }
-
-    fn write_temp_file(dir: &Path, name: &str, content: &str) {
-        let path = dir.join(name);
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
-        }
-        std::fs::write(path, content).unwrap();
-    }
-
     fn bm25f() -> RankingAlgo {
         RankingAlgo::BM25F(BM25FHyperParams::code_search_defaults())
     }
@@ -0,0 +1,186 @@
+use crate::{
+    index::RankedIndexReader,
+    query::AnalyzedQuery,
+    ranking::{Score, Scored},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenseParams {
+    /// Fewest nearest documents returned before truncating to the requested
+    /// count.
+    pub candidates: usize,
+    /// HNSW search beam width.
+    pub ef: usize,
+}
+
+impl DenseParams {
+    /// Widens the search to return at least `top_n` documents, with a beam
+    /// at least that wide.
+    pub fn for_top_n(&self, top_n: usize) -> Self {
+        let candidates = self.candidates.max(top_n);
+        Self {
+            candidates,
+            ef: self.ef.max(candidates),
+        }
+    }
+}
+
+impl Default for DenseParams {
+    fn default() -> Self {
+        Self {
+            candidates: 100,
+            ef: 128,
+        }
+    }
+}
+
+/// Scores documents by the cosine similarity of their latent semantic
+/// vector to the query's, so files can match without sharing its words.
+/// Indexes without dense vectors score nothing.
+pub struct DenseRetrieval {
+    pub params: DenseParams,
+}
+
+impl DenseRetrieval {
+    pub fn score<I>(&self, index: &I, query: &AnalyzedQuery) -> Scored
+    where
+        I: RankedIndexReader,
+    {
+        let Some(dense) = index.dense() else {
+            return Scored(Vec::new());
+        };
+        let Some(query_vector) = dense.model().embed(
+            query
+                .terms()
+                .map(|(term, query_term)| (term, query_term.weight)),
+        ) else {
+            return Scored(Vec::new());
+        };
+
+        let scores = dense
+            .search(
+                &query_vector,
+                self.params.candidates,
+                self.params.ef,
+                |doc_id| index.contains(doc_id),
+            )
+            .into_iter()
+            .filter(|(_, similarity)| *similarity > 0.0)
+            .filter_map(|(doc_id, similarity)| {
+                index.document(doc_id).map(|metadata| Score {
+                    doc_path: metadata.path.clone(),
+                    score: f64::from(similarity),
+                    passages: Vec::new(),
+                    similar: Vec::new(),
+                })
+            })
+            .collect();
+
+        Scored(scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use crate::{
+        index::{DenseConfig, InvertedIndex, SearchFilter, Term},
+        query::AnalyzedQuery,
+        ranking::{DenseParams, RankingAlgo},
+    };
+
+    fn index() -> InvertedIndex {
+        let mut index = InvertedIndex::from_documents(&[
+            (
+                "db/pool.rs",
+                &[("database", 3), ("connection", 2), ("pool", 2)],
+            ),
+            (
+                "db/connect.rs",
+                &[("connection", 2), ("socket", 3), ("handshake", 2)],
+            ),
+            (
+                "db/socket.rs",
+                &[("socket", 2), ("handshake", 1), ("database", 1)],
+            ),
+            (
+                "ui/button.rs",
+                &[("button", 3), ("click", 2), ("render", 2)],
+            ),
+            ("ui/list.rs", &[("render", 3), ("click", 1), ("scroll", 2)]),
+            (
+                "ui/scroll.rs",
+                &[("scroll", 2), ("button", 1), ("render", 1)],
+            ),
+        ]);
+        index.build_dense(&DenseConfig {
+            dimensions: 2,
+            ..DenseConfig::default()
+        });
+        index
+    }
+
+    fn query(term: &str) -> AnalyzedQuery {
+        AnalyzedQuery::from_frequencies(term, HashMap::from([(Term(term.to_string()), 1)]))
+    }
+
+    fn paths(index: &InvertedIndex, filter: &SearchFilter) -> Vec<PathBuf> {
+        "dense"
+            .parse::<RankingAlgo>()
+            .unwrap()
+            .rank_filtered(index, &query("handshake"), 3, filter)
+            .map(|ranking| ranking.0.into_iter().map(|score| score.doc_path).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn ranks_documents_on_the_query_topic_without_the_query_term() {
+        let index = index();
+
+        let mut ranked = paths(&index, &SearchFilter::new());
+        ranked.sort();
+
+        assert_eq!(
+            ranked,
+            vec![
+                PathBuf::from("db/connect.rs"),
+                PathBuf::from("db/pool.rs"),
+                PathBuf::from("db/socket.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn respects_the_filter_and_index_updates() {
+        let mut index = index();
+        let filter = SearchFilter::new().with_globs(["db/pool.rs"]).unwrap();
+        assert_eq!(paths(&index, &filter), vec![PathBuf::from("db/pool.rs")]);
+
+        index.remove_document(PathBuf::from("db/pool.rs").as_path());
+        assert!(paths(&index, &filter).is_empty());
+        assert_eq!(index.dense().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn returns_top_n_documents_beyond_the_candidate_count() {
+        let index = index();
+        let algo = RankingAlgo::Dense(DenseParams {
+            candidates: 1,
+            ef: 1,
+        });
+
+        assert_eq!(
+            algo.rank(&index, &query("handshake"), 3)
+                .map_or(0, |ranking| ranking.0.len()),
+            3
+        );
+    }
+
+    #[test]
+    fn scores_nothing_without_dense_vectors() {
+        let index = InvertedIndex::from_documents(&[("a.rs", &[("handshake", 1)])]);
+
+        assert!(paths(&index, &SearchFilter::new()).is_empty());
+    }
+}
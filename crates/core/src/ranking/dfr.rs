@@ -0,0 +1,238 @@
+use std::f64::consts::{LOG2_E, PI};
+
+use dashmap::DashMap;
+use rayon::iter::{ParallelBridge, ParallelIterator};
+
+use crate::{
+    index::{DocId, PostingList, RankedIndexReader, Term},
+    query::{AnalyzedQuery, QueryTerm},
+    ranking::scorer::Scorer,
+};
+
+/// Divergence-from-randomness models: a term scores by how far its
+/// frequency in a document strays from what chance would put there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DfrModel {
+    /// Poisson randomness with Laplace after-effect and length
+    /// normalisation 2, scaled by `c`.
+    PL2 { c: f64 },
+    /// Inverse document frequency randomness with Laplace after-effect and
+    /// length normalisation 2, scaled by `c`.
+    InL2 { c: f64 },
+    /// Hypergeometric randomness with Popper normalisation; has no
+    /// parameters to tune.
+    DPH,
+}
+
+impl DfrModel {
+    pub fn pl2_defaults() -> Self {
+        Self::PL2 { c: 1.0 }
+    }
+
+    pub fn inl2_defaults() -> Self {
+        Self::InL2 { c: 1.0 }
+    }
+}
+
+/// The collection statistics a DFR term score depends on besides the
+/// document's own term frequency and length.
+#[derive(Debug, Clone, Copy)]
+pub struct DfrTermStats {
+    pub num_docs: f64,
+    pub avg_doc_length: f64,
+    pub document_frequency: f64,
+    pub collection_frequency: f64,
+}
+
+impl DfrTermStats {
+    pub fn new<I>(index: &I, term: &Term, document_frequency: usize) -> Self
+    where
+        I: RankedIndexReader,
+    {
+        Self {
+            num_docs: index.num_docs() as f64,
+            avg_doc_length: index.avg_doc_length(),
+            document_frequency: document_frequency as f64,
+            collection_frequency: index.collection_frequency(term) as f64,
+        }
+    }
+}
+
+pub struct DivergenceFromRandomness {
+    pub model: DfrModel,
+}
+
+impl Scorer for DivergenceFromRandomness {
+    fn score<I, P>(
+        &self,
+        index: &I,
+        _: &AnalyzedQuery,
+        term: &Term,
+        query_term: QueryTerm,
+        documents: &P,
+        scores: &DashMap<DocId, f64>,
+    ) where
+        I: RankedIndexReader + Sync,
+        P: PostingList + Sync,
+    {
+        let stats = DfrTermStats::new(index, term, documents.len());
+
+        documents
+            .iter()
+            .par_bridge()
+            .for_each(|(doc_id, term_doc)| {
+                let score = self.score_term(
+                    query_term.weight,
+                    term_doc.term_freq as f64,
+                    term_doc.length as f64,
+                    &stats,
+                );
+
+                *scores.entry(doc_id).or_insert(0.0) += score;
+            });
+    }
+}
+
+impl DivergenceFromRandomness {
+    pub fn score_term(
+        &self,
+        query_weight: f64,
+        tf: f64,
+        doc_length: f64,
+        stats: &DfrTermStats,
+    ) -> f64 {
+        if tf == 0.0 || doc_length == 0.0 || stats.num_docs == 0.0 {
+            return 0.0;
+        }
+
+        let score = match self.model {
+            DfrModel::PL2 { c } => {
+                let tfn = normalized_tf(tf, doc_length, stats.avg_doc_length, c);
+                let lambda = stats.collection_frequency.max(tf) / stats.num_docs;
+                (tfn * (tfn / lambda).log2()
+                    + (lambda - tfn) * LOG2_E
+                    + 0.5 * (2.0 * PI * tfn).log2())
+                    / (tfn + 1.0)
+            }
+            DfrModel::InL2 { c } => {
+                let tfn = normalized_tf(tf, doc_length, stats.avg_doc_length, c);
+                tfn * ((stats.num_docs + 1.0) / (stats.document_frequency + 0.5)).log2()
+                    / (tfn + 1.0)
+            }
+            DfrModel::DPH => {
+                let relative_tf = tf / doc_length;
+                if relative_tf >= 1.0 {
+                    return 0.0;
+                }
+                let collection_frequency = stats.collection_frequency.max(tf);
+                let norm = (1.0 - relative_tf).powi(2) / (tf + 1.0);
+                norm * (tf
+                    * (tf * stats.avg_doc_length / doc_length * stats.num_docs
+                        / collection_frequency)
+                        .log2()
+                    + 0.5 * (2.0 * PI * tf * (1.0 - relative_tf)).log2())
+            }
+        };
+
+        query_weight * score
+    }
+}
+
+/// Normalisation 2: rescales `tf` to what it would be in a document of
+/// average length, with `c` setting how strongly length counts.
+fn normalized_tf(tf: f64, doc_length: f64, avg_doc_length: f64, c: f64) -> f64 {
+    tf * (1.0 + c * avg_doc_length / doc_length).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use dashmap::DashMap;
+
+    use super::{DfrModel, DfrTermStats, DivergenceFromRandomness};
+    use crate::{
+        index::{InvertedIndex, Term},
+        query::AnalyzedQuery,
+        ranking::scorer::Scorer,
+    };
+
+    const MODELS: [DfrModel; 3] = [
+        DfrModel::PL2 { c: 1.0 },
+        DfrModel::InL2 { c: 1.0 },
+        DfrModel::DPH,
+    ];
+
+    fn stats() -> DfrTermStats {
+        DfrTermStats {
+            num_docs: 1_000.0,
+            avg_doc_length: 100.0,
+            document_frequency: 10.0,
+            collection_frequency: 20.0,
+        }
+    }
+
+    #[test]
+    fn higher_tf_scores_higher_for_every_model() {
+        for model in MODELS {
+            let scorer = DivergenceFromRandomness { model };
+            let high = scorer.score_term(1.0, 8.0, 100.0, &stats());
+            let low = scorer.score_term(1.0, 1.0, 100.0, &stats());
+
+            assert!(high > low, "{model:?}: {high} vs {low}");
+            assert!(low > 0.0, "{model:?} should score a rare match positively");
+        }
+    }
+
+    #[test]
+    fn longer_documents_score_lower_for_every_model() {
+        for model in MODELS {
+            let scorer = DivergenceFromRandomness { model };
+            let short = scorer.score_term(1.0, 2.0, 50.0, &stats());
+            let long = scorer.score_term(1.0, 2.0, 500.0, &stats());
+
+            assert!(short > long, "{model:?}: {short} vs {long}");
+        }
+    }
+
+    #[test]
+    fn dph_ignores_documents_made_only_of_the_term() {
+        let scorer = DivergenceFromRandomness {
+            model: DfrModel::DPH,
+        };
+
+        assert_eq!(scorer.score_term(1.0, 4.0, 4.0, &stats()), 0.0);
+    }
+
+    #[test]
+    fn scores_every_posting_through_the_scorer_trait() {
+        let index = InvertedIndex::from_documents(&[
+            ("a.rs", &[("pool", 3), ("other", 5)]),
+            ("b.rs", &[("pool", 1), ("other", 5)]),
+            ("c.rs", &[("other", 5)]),
+        ]);
+        let term = Term("pool".to_string());
+        let query = AnalyzedQuery::from_frequencies("pool", HashMap::from([(term.clone(), 1)]));
+        let scores = DashMap::new();
+
+        DivergenceFromRandomness {
+            model: DfrModel::DPH,
+        }
+        .score(
+            &index,
+            &query,
+            &term,
+            *query.terms().next().unwrap().1,
+            index.get_postings(&term).unwrap(),
+            &scores,
+        );
+
+        let score = |path: &str| {
+            *scores
+                .get(&index.doc_id(PathBuf::from(path).as_path()).unwrap())
+                .unwrap()
+        };
+        assert_eq!(scores.len(), 2);
+        assert!(score("a.rs") > score("b.rs"));
+    }
+}
@@ -0,0 +1,409 @@
+//! Result diversification: near-duplicates fold into the result they copy,
+//! then maximal marginal relevance (MMR) reorders what is left so similar
+//! files stop crowding out everything else.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    str::FromStr,
+};
+
+use crate::{
+    index::{DocId, DocumentField, PostingList, TermDocument},
+    ranking::{Score, Scored, feedback::FeedbackTermSource, idf},
+};
+
+/// Hash functions in a MinHash signature.
+const MINHASH_PERMUTATIONS: usize = 64;
+
+/// How alike two documents are, from 0.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SimilarityMeasure {
+    /// Cosine between TF-IDF vectors of content terms.
+    #[default]
+    TermVector,
+    /// MinHash estimate of the Jaccard overlap between content term sets.
+    #[serde(alias = "min-hash")]
+    Minhash,
+}
+
+impl SimilarityMeasure {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TermVector => "term-vector",
+            Self::Minhash => "minhash",
+        }
+    }
+}
+
+impl FromStr for SimilarityMeasure {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "term-vector" | "termvector" | "cosine" => Ok(Self::TermVector),
+            "minhash" | "min-hash" => Ok(Self::Minhash),
+            _ => Err(format!(
+                "unknown similarity {value:?}; expected term-vector or minhash"
+            )),
+        }
+    }
+}
+
+/// Settings for [`diversify`].
+///
+/// Every field may be set in `reaper.toml` under `[ranking.diversity]`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiversityConfig {
+    /// Weight of relevance against novelty; 1.0 keeps the original order.
+    pub lambda: f64,
+    /// Results at least this similar to a higher one fold into it.
+    pub duplicate_threshold: f64,
+    pub similarity: SimilarityMeasure,
+    /// Candidates ranked per result returned.
+    pub depth: usize,
+}
+
+impl Default for DiversityConfig {
+    fn default() -> Self {
+        Self {
+            lambda: 0.7,
+            duplicate_threshold: 0.9,
+            similarity: SimilarityMeasure::default(),
+            depth: 3,
+        }
+    }
+}
+
+/// Pairwise similarity between a fixed set of documents, built from their
+/// content terms in one pass over the vocabulary.
+pub struct DocumentSimilarity {
+    measure: SimilarityMeasure,
+    vectors: HashMap<DocId, Vec<(usize, f64)>>,
+    signatures: HashMap<DocId, [u64; MINHASH_PERMUTATIONS]>,
+}
+
+impl DocumentSimilarity {
+    pub fn new<I>(index: &I, documents: &[DocId], measure: SimilarityMeasure) -> Self
+    where
+        I: FeedbackTermSource,
+    {
+        let num_docs = index.num_docs();
+        let mut vectors: HashMap<DocId, Vec<(usize, f64)>> = documents
+            .iter()
+            .map(|doc_id| (*doc_id, Vec::new()))
+            .collect();
+        let mut signatures = HashMap::new();
+
+        for (term_index, term) in index.feedback_terms().into_iter().enumerate() {
+            let Some(postings) = index.postings(term) else {
+                continue;
+            };
+            let term_idf = idf(num_docs, postings.len()).max(0.0);
+            let term_hash = matches!(measure, SimilarityMeasure::Minhash).then(|| {
+                let mut hasher = DefaultHasher::new();
+                term.hash(&mut hasher);
+                hasher.finish()
+            });
+
+            for (doc_id, vector) in &mut vectors {
+                let tf = postings.get(*doc_id).map_or(0, content_term_freq);
+                if tf == 0 {
+                    continue;
+                }
+                vector.push((term_index, tf as f64 * term_idf));
+                if let Some(term_hash) = term_hash {
+                    let signature = signatures
+                        .entry(*doc_id)
+                        .or_insert([u64::MAX; MINHASH_PERMUTATIONS]);
+                    for (seed, slot) in signature.iter_mut().enumerate() {
+                        *slot = (*slot).min(permute(term_hash, seed as u64));
+                    }
+                }
+            }
+        }
+
+        Self {
+            measure,
+            vectors,
+            signatures,
+        }
+    }
+
+    pub fn similarity(&self, left: DocId, right: DocId) -> f64 {
+        if left == right {
+            return 1.0;
+        }
+
+        match self.measure {
+            SimilarityMeasure::TermVector => {
+                match (self.vectors.get(&left), self.vectors.get(&right)) {
+                    (Some(left), Some(right)) => cosine(left, right),
+                    _ => 0.0,
+                }
+            }
+            SimilarityMeasure::Minhash => {
+                match (self.signatures.get(&left), self.signatures.get(&right)) {
+                    (Some(left), Some(right)) => {
+                        left.iter().zip(right).filter(|(a, b)| a == b).count() as f64
+                            / MINHASH_PERMUTATIONS as f64
+                    }
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+}
+
+/// Folds near-duplicates into the highest-ranked result they resemble,
+/// listing them in its `similar`, then picks `top_n` of the rest by MMR.
+/// `ranking` should hold `config.depth` times as many results as wanted.
+pub fn diversify<I>(index: &I, ranking: Scored, top_n: usize, config: &DiversityConfig) -> Scored
+where
+    I: FeedbackTermSource,
+{
+    let candidates = ranking
+        .0
+        .into_iter()
+        .filter_map(|score| Some((index.doc_id(&score.doc_path)?, score)))
+        .collect::<Vec<_>>();
+    let doc_ids = candidates
+        .iter()
+        .map(|(doc_id, _)| *doc_id)
+        .collect::<Vec<_>>();
+    let similarity = DocumentSimilarity::new(index, &doc_ids, config.similarity);
+
+    let mut groups: Vec<(DocId, Score)> = Vec::new();
+    for (doc_id, score) in candidates {
+        match groups.iter_mut().find(|(representative, _)| {
+            similarity.similarity(*representative, doc_id) >= config.duplicate_threshold
+        }) {
+            Some((_, representative)) => {
+                representative.similar.push(score.doc_path);
+                representative.similar.extend(score.similar);
+            }
+            None => groups.push((doc_id, score)),
+        }
+    }
+
+    let (min, max) = groups.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(min, max), (_, score)| (min.min(score.score), max.max(score.score)),
+    );
+    let relevance = |score: f64| {
+        if max > min {
+            (score - min) / (max - min)
+        } else {
+            1.0
+        }
+    };
+
+    let mut selected: Vec<(DocId, Score)> = Vec::new();
+    while selected.len() < top_n && !groups.is_empty() {
+        let (best, _) = groups
+            .iter()
+            .enumerate()
+            .map(|(position, (doc_id, score))| {
+                let redundancy = selected
+                    .iter()
+                    .map(|(chosen, _)| similarity.similarity(*chosen, *doc_id))
+                    .fold(0.0, f64::max);
+                (
+                    position,
+                    config.lambda * relevance(score.score) - (1.0 - config.lambda) * redundancy,
+                )
+            })
+            // Ties keep the original order.
+            .fold((0, f64::NEG_INFINITY), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        selected.push(groups.remove(best));
+    }
+
+    Scored(selected.into_iter().map(|(_, score)| score).collect())
+}
+
+/// Share of the first `k` results at least `config.duplicate_threshold`
+/// similar to a result ranked above them.
+pub fn redundancy_at_k<I>(index: &I, ranking: &[Score], k: usize, config: &DiversityConfig) -> f64
+where
+    I: FeedbackTermSource,
+{
+    let doc_ids = ranking
+        .iter()
+        .take(k)
+        .filter_map(|score| index.doc_id(&score.doc_path))
+        .collect::<Vec<_>>();
+    if doc_ids.len() < 2 {
+        return 0.0;
+    }
+
+    let similarity = DocumentSimilarity::new(index, &doc_ids, config.similarity);
+    let redundant = (1..doc_ids.len())
+        .filter(|position| {
+            doc_ids[..*position].iter().any(|earlier| {
+                similarity.similarity(*earlier, doc_ids[*position]) >= config.duplicate_threshold
+            })
+        })
+        .count();
+
+    redundant as f64 / doc_ids.len() as f64
+}
+
+/// Content frequency for fielded postings, where path and symbol fields
+/// would make copies in different directories look unrelated.
+fn content_term_freq(term_doc: &TermDocument) -> usize {
+    if term_doc.field_frequencies.is_empty() {
+        term_doc.term_freq
+    } else {
+        term_doc.field_term_freq(DocumentField::Content)
+    }
+}
+
+fn cosine(left: &[(usize, f64)], right: &[(usize, f64)]) -> f64 {
+    let norm = |vector: &[(usize, f64)]| {
+        vector
+            .iter()
+            .map(|(_, weight)| weight * weight)
+            .sum::<f64>()
+            .sqrt()
+    };
+    let denominator = norm(left) * norm(right);
+    if denominator == 0.0 {
+        return 0.0;
+    }
+
+    let (mut i, mut j, mut dot) = (0, 0, 0.0);
+    while i < left.len() && j < right.len() {
+        match left[i].0.cmp(&right[j].0) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                dot += left[i].1 * right[j].1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    dot / denominator
+}
+
+/// One of [`MINHASH_PERMUTATIONS`] hash functions, as a SplitMix64 mix of
+/// the term hash and the function's index.
+fn permute(hash: u64, seed: u64) -> u64 {
+    let mut z = hash ^ seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{DiversityConfig, SimilarityMeasure, diversify, redundancy_at_k};
+    use crate::{
+        index::InvertedIndex,
+        ranking::{Score, Scored},
+    };
+
+    fn index() -> InvertedIndex {
+        InvertedIndex::from_documents(&[
+            (
+                "client/en.rs",
+                &[("client", 4), ("request", 3), ("retry", 2)],
+            ),
+            (
+                "client/de.rs",
+                &[("client", 4), ("request", 3), ("retry", 2)],
+            ),
+            (
+                "client/fr.rs",
+                &[("client", 4), ("request", 3), ("retry", 2)],
+            ),
+            ("server.rs", &[("client", 1), ("listen", 4), ("socket", 3)]),
+            ("docs.md", &[("client", 1), ("guide", 5)]),
+        ])
+    }
+
+    fn ranking(paths: &[(&str, f64)]) -> Scored {
+        Scored(
+            paths
+                .iter()
+                .map(|(path, score)| Score {
+                    doc_path: PathBuf::from(path),
+                    score: *score,
+                    passages: Vec::new(),
+                    similar: Vec::new(),
+                })
+                .collect(),
+        )
+    }
+
+    fn paths(scored: &Scored) -> Vec<&str> {
+        scored
+            .0
+            .iter()
+            .map(|score| score.doc_path.to_str().unwrap())
+            .collect()
+    }
+
+    const RANKED: [(&str, f64); 5] = [
+        ("client/en.rs", 9.0),
+        ("client/de.rs", 8.9),
+        ("client/fr.rs", 8.8),
+        ("server.rs", 3.0),
+        ("docs.md", 2.0),
+    ];
+
+    #[test]
+    fn collapses_near_duplicates_into_the_top_copy() {
+        for similarity in [SimilarityMeasure::TermVector, SimilarityMeasure::Minhash] {
+            let config = DiversityConfig {
+                similarity,
+                ..DiversityConfig::default()
+            };
+
+            let diversified = diversify(&index(), ranking(&RANKED), 3, &config);
+
+            assert_eq!(
+                paths(&diversified),
+                ["client/en.rs", "server.rs", "docs.md"],
+                "{similarity:?}"
+            );
+            assert_eq!(
+                diversified.0[0].similar,
+                [PathBuf::from("client/de.rs"), PathBuf::from("client/fr.rs")]
+            );
+        }
+    }
+
+    #[test]
+    fn lambda_of_one_keeps_relevance_order() {
+        let config = DiversityConfig {
+            lambda: 1.0,
+            duplicate_threshold: 1.1,
+            ..DiversityConfig::default()
+        };
+
+        let diversified = diversify(&index(), ranking(&RANKED), 5, &config);
+
+        assert_eq!(paths(&diversified), paths(&ranking(&RANKED)));
+        assert!(diversified.0.iter().all(|score| score.similar.is_empty()));
+    }
+
+    #[test]
+    fn redundancy_counts_results_repeating_a_higher_one() {
+        let config = DiversityConfig::default();
+        let ranked = ranking(&RANKED);
+
+        assert_eq!(redundancy_at_k(&index(), &ranked.0, 5, &config), 0.4);
+        assert_eq!(redundancy_at_k(&index(), &ranked.0[2..], 3, &config), 0.0);
+    }
+}
@@ -11,15 +11,20 @@ pub struct ScoreWithExplanation {
     pub explanation: ScoreExplanation,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct ScoreExplanation {
     pub final_score: f64,
     pub query_intent: QueryIntent,
     pub terms: Vec<TermExplanation>,
+    /// Phrase and proximity bonus; zero for algorithms without one.
+    pub proximity_bonus: f64,
     pub static_quality: Vec<StaticQualityContribution>,
+    /// What each fused run added; empty for algorithms that run alone.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<RunContribution>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct TermExplanation {
     pub term: String,
     pub provenance: String,
@@ -31,14 +36,24 @@ pub struct TermExplanation {
     pub contribution: f64,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct StaticQualityContribution {
     pub signal: String,
     pub value: f64,
     pub contribution: f64,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RunContribution {
+    pub run: String,
+    /// One-based rank within the run.
+    pub rank: usize,
+    pub score: f64,
+    pub weight: f64,
+    pub contribution: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct FieldContribution {
     pub field: DocumentField,
     pub term_frequency: usize,
@@ -217,7 +217,11 @@ pub fn export_pairwise_preferences(
     rows
 }
 
-fn features_for_score<I>(index: &I, query: &AnalyzedQuery, score: &Score) -> BTreeMap<String, f64>
+pub(crate) fn features_for_score<I>(
+    index: &I,
+    query: &AnalyzedQuery,
+    score: &Score,
+) -> BTreeMap<String, f64>
 where
     I: RankedIndexReader + Sync,
 {
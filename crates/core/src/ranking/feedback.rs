@@ -16,6 +16,12 @@ impl FeedbackTermSource for crate::index::InvertedIndex {
     }
 }
 
+impl<I: FeedbackTermSource> FeedbackTermSource for crate::index::FilteredIndex<'_, I> {
+    fn feedback_terms(&self) -> Vec<&Term> {
+        self.inner().feedback_terms()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FeedbackTerm {
     pub term: Term,
@@ -157,41 +163,16 @@ fn high_signal_field(field: DocumentField) -> f64 {
 
 #[cfg(test)]
 mod tests {
-    use std::{
-        collections::HashSet,
-        path::{Path, PathBuf},
-    };
-
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
+    use std::path::PathBuf;
 
     use super::{collect_feedback_terms, expand_query_with_feedback};
     use crate::{
-        config::Config,
         index::InvertedIndex,
         query::{AnalyzedQuery, QueryTermProvenance},
         ranking::Score,
+        test_support::{test_config, write_temp_file},
     };
 
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect::<HashSet<String>>(),
-        }
-    }
-
-    fn write_temp_file(dir: &Path, name: &str, content: &str) {
-        let path = dir.join(name);
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
-        }
-        std::fs::write(path, content).unwrap();
-    }
-
     #[test]
     fn feedback_terms_use_high_signal_fields() {
         let dir = tempfile::tempdir().unwrap();
@@ -200,6 +181,8 @@ mod tests {
         let seed_results = vec![Score {
             doc_path: PathBuf::from("src/auth_repository.rs"),
             score: 1.0,
+            passages: Vec::new(),
+            similar: Vec::new(),
         }];
         let query = AnalyzedQuery::new_code_search("auth", &test_config());
 
@@ -216,6 +199,8 @@ mod tests {
         let seed_results = vec![Score {
             doc_path: PathBuf::from("src/auth_repository.rs"),
             score: 1.0,
+            passages: Vec::new(),
+            similar: Vec::new(),
         }];
         let query = AnalyzedQuery::new_code_search("auth", &test_config());
 
@@ -0,0 +1,458 @@
+//! Hybrid ranking: runs several rankings and merges them with Reciprocal
+//! Rank Fusion or a weighted sum of min-max normalised scores (CombSUM).
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    index::{DocId, PostingList, RankedIndexReader},
+    query::{AnalyzedQuery, QueryIntent},
+    ranking::{
+        BM25FHyperParams, QueryLikelihoodParams, RankingAlgo, RunContribution, Score,
+        ScoreExplanation, Scored,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FusionMethod {
+    /// `weight / (rrf_k + rank)` summed over the runs returning a document.
+    Rrf,
+    /// Weighted sum of each run's scores, min-max normalised per run.
+    CombSum,
+}
+
+#[derive(Debug, Clone)]
+pub enum FusionSource {
+    Ranked(RankingAlgo),
+    /// Files containing a quoted phrase verbatim, scored by how often.
+    Literal,
+}
+
+impl FusionSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FusionSource::Ranked(algo) => algo.name(),
+            FusionSource::Literal => "literal",
+        }
+    }
+}
+
+impl std::str::FromStr for FusionSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "literal" => Ok(FusionSource::Literal),
+            _ => s.parse().map(FusionSource::Ranked),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FusionRun {
+    pub source: FusionSource,
+    pub weight: f64,
+    /// Replaces `weight` for queries of the listed intents.
+    pub intent_weights: HashMap<QueryIntent, f64>,
+}
+
+impl FusionRun {
+    pub fn new(source: FusionSource) -> Self {
+        Self {
+            source,
+            weight: 1.0,
+            intent_weights: HashMap::new(),
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_intent_weight(mut self, intent: QueryIntent, weight: f64) -> Self {
+        self.intent_weights.insert(intent, weight);
+        self
+    }
+
+    pub fn weight_for(&self, intent: QueryIntent) -> f64 {
+        self.intent_weights
+            .get(&intent)
+            .copied()
+            .unwrap_or(self.weight)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FusionRanker {
+    pub runs: Vec<FusionRun>,
+    pub method: FusionMethod,
+    /// Rank offset damping RRF's preference for the very top results.
+    pub rrf_k: f64,
+    /// Results taken from each run before fusing.
+    pub depth: usize,
+    /// Directory the literal run resolves indexed paths against.
+    pub literal_root: PathBuf,
+}
+
+impl FusionRanker {
+    /// BM25F for identifiers and paths, query likelihood for natural
+    /// language and verbatim literals for error messages.
+    pub fn code_search_defaults(method: FusionMethod) -> Self {
+        Self {
+            runs: vec![
+                FusionRun::new(FusionSource::Ranked(RankingAlgo::BM25F(
+                    BM25FHyperParams::code_search_defaults(),
+                )))
+                .with_intent_weight(QueryIntent::Identifier, 2.0)
+                .with_intent_weight(QueryIntent::Path, 2.0),
+                FusionRun::new(FusionSource::Ranked(RankingAlgo::QueryLikelihood(
+                    QueryLikelihoodParams::dirichlet_defaults(),
+                )))
+                .with_intent_weight(QueryIntent::NaturalLanguage, 2.0),
+                FusionRun::new(FusionSource::Literal)
+                    .with_intent_weight(QueryIntent::ErrorMessage, 3.0),
+            ],
+            method,
+            rrf_k: 60.0,
+            depth: 100,
+            literal_root: PathBuf::new(),
+        }
+    }
+
+    pub fn with_literal_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.literal_root = root.into();
+        self
+    }
+
+    pub fn needs_fielded_index(&self) -> bool {
+        self.runs.iter().any(|run| match &run.source {
+            FusionSource::Ranked(algo) => algo.needs_fielded_index(),
+            FusionSource::Literal => false,
+        })
+    }
+
+    pub fn needs_dense_index(&self) -> bool {
+        self.runs.iter().any(|run| match &run.source {
+            FusionSource::Ranked(algo) => algo.needs_dense_index(),
+            FusionSource::Literal => false,
+        })
+    }
+
+    pub(crate) fn fuse<I>(&self, index: &I, query: &AnalyzedQuery) -> Scored
+    where
+        I: RankedIndexReader + Sync,
+    {
+        Scored(
+            self.contributions(index, query)
+                .into_iter()
+                .map(|(doc_path, runs)| Score {
+                    doc_path,
+                    score: runs.iter().map(|run| run.contribution).sum(),
+                    passages: Vec::new(),
+                    similar: Vec::new(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Lists what each run added to `doc_id`; the fused score has no
+    /// per-term breakdown of its own.
+    pub(crate) fn explain<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        doc_id: DocId,
+        final_score: f64,
+    ) -> ScoreExplanation
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let runs = index
+            .document(doc_id)
+            .and_then(|metadata| {
+                self.contributions(index, query)
+                    .remove(metadata.path.as_path())
+            })
+            .unwrap_or_default();
+
+        explanation(query, runs, final_score)
+    }
+
+    /// Explains each of `results` in order, running every component ranker
+    /// once for the whole list. Results no longer in `index` get `None`.
+    pub(crate) fn explain_ranking<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        results: &[Score],
+    ) -> Vec<Option<ScoreExplanation>>
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let contributions = self.contributions(index, query);
+        results
+            .iter()
+            .map(|score| {
+                index.doc_id(&score.doc_path)?;
+                let runs = contributions
+                    .get(&score.doc_path)
+                    .cloned()
+                    .unwrap_or_default();
+                Some(explanation(query, runs, score.score))
+            })
+            .collect()
+    }
+
+    fn contributions<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+    ) -> BTreeMap<PathBuf, Vec<RunContribution>>
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let results = self
+            .runs
+            .par_iter()
+            .map(|run| match &run.source {
+                FusionSource::Ranked(algo) => algo
+                    .rank(index, query, self.depth)
+                    .map(|scored| scored.0)
+                    .unwrap_or_default(),
+                FusionSource::Literal => {
+                    literal_matches(index, query, &self.literal_root, self.depth)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut contributions: BTreeMap<PathBuf, Vec<RunContribution>> = BTreeMap::new();
+        for (run, scores) in self.runs.iter().zip(results) {
+            let weight = run.weight_for(query.intent());
+            let (min, max) = scores
+                .iter()
+                .fold((f64::MAX, f64::MIN), |(min, max), score| {
+                    (min.min(score.score), max.max(score.score))
+                });
+
+            for (rank, score) in scores.into_iter().enumerate() {
+                let contribution = match self.method {
+                    FusionMethod::Rrf => weight / (self.rrf_k + (rank + 1) as f64),
+                    FusionMethod::CombSum if max > min => {
+                        weight * (score.score - min) / (max - min)
+                    }
+                    FusionMethod::CombSum => weight,
+                };
+                contributions
+                    .entry(score.doc_path)
+                    .or_default()
+                    .push(RunContribution {
+                        run: run.source.name().to_string(),
+                        rank: rank + 1,
+                        score: score.score,
+                        weight,
+                        contribution,
+                    });
+            }
+        }
+        contributions
+    }
+}
+
+fn explanation(
+    query: &AnalyzedQuery,
+    runs: Vec<RunContribution>,
+    final_score: f64,
+) -> ScoreExplanation {
+    ScoreExplanation {
+        final_score,
+        query_intent: query.intent(),
+        terms: Vec::new(),
+        proximity_bonus: 0.0,
+        static_quality: Vec::new(),
+        runs,
+    }
+}
+
+/// Verifies each quoted phrase against the file text, the ground truth for
+/// error messages and other exact strings. Only documents holding every
+/// term of a phrase are read.
+fn literal_matches<I>(index: &I, query: &AnalyzedQuery, root: &Path, depth: usize) -> Vec<Score>
+where
+    I: RankedIndexReader + Sync,
+{
+    let literals = query
+        .phrases()
+        .iter()
+        .filter(|phrase| phrase.ordered && phrase.slop == 0 && !phrase.raw.trim().is_empty())
+        .collect::<Vec<_>>();
+
+    let candidates = literals
+        .iter()
+        .flat_map(|phrase| {
+            phrase
+                .terms
+                .iter()
+                .map(|term| {
+                    index
+                        .postings(term)
+                        .map(|postings| {
+                            postings
+                                .iter()
+                                .map(|(doc_id, _)| doc_id)
+                                .collect::<BTreeSet<_>>()
+                        })
+                        .unwrap_or_default()
+                })
+                .reduce(|a, b| a.intersection(&b).copied().collect())
+                .unwrap_or_default()
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut matches = candidates
+        .into_iter()
+        .filter_map(|doc_id| {
+            let path = &index.document(doc_id)?.path;
+            let content = std::fs::read_to_string(root.join(path)).ok()?;
+            let count = literals
+                .iter()
+                .map(|phrase| content.matches(phrase.raw.as_str()).count())
+                .sum::<usize>();
+            (count > 0).then(|| Score {
+                doc_path: path.clone(),
+                score: count as f64,
+                passages: Vec::new(),
+                similar: Vec::new(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.doc_path.cmp(&b.doc_path))
+    });
+    matches.truncate(depth);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{FusionMethod, FusionRanker, FusionRun, FusionSource};
+    use crate::{
+        index::InvertedIndex,
+        query::{AnalyzedQuery, QueryIntent},
+        ranking::RankingAlgo,
+        test_support::{test_config, write_temp_file},
+    };
+
+    fn corpus() -> (tempfile::TempDir, InvertedIndex) {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "src/error.rs",
+            "fn fail() { panic!(\"connection refused by pool\"); }\n",
+        );
+        write_temp_file(
+            dir.path(),
+            "src/pool.rs",
+            "// connection pool refused connection refused pool connection\nfn pool() {}\n",
+        );
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        (dir, index)
+    }
+
+    #[test]
+    fn literal_run_finds_the_verbatim_error_message() {
+        let (dir, index) = corpus();
+        let query =
+            AnalyzedQuery::new_code_search("\"connection refused by pool\"", &test_config());
+        let fusion = RankingAlgo::Fusion(
+            FusionRanker::code_search_defaults(FusionMethod::Rrf).with_literal_root(dir.path()),
+        );
+
+        let ranked = fusion.rank(&index, &query, 2).unwrap();
+
+        assert_eq!(ranked.0[0].doc_path, Path::new("src/error.rs"));
+    }
+
+    #[test]
+    fn intent_weights_replace_the_default_weight() {
+        let run = FusionRun::new(FusionSource::Literal)
+            .with_weight(0.5)
+            .with_intent_weight(QueryIntent::ErrorMessage, 3.0);
+
+        assert_eq!(run.weight_for(QueryIntent::ErrorMessage), 3.0);
+        assert_eq!(run.weight_for(QueryIntent::Identifier), 0.5);
+    }
+
+    #[test]
+    fn combsum_normalises_each_run_before_weighting() {
+        let (dir, index) = corpus();
+        let query = AnalyzedQuery::new_code_search("connection pool", &test_config());
+        let fusion = FusionRanker {
+            runs: vec![
+                FusionRun::new(FusionSource::Ranked(RankingAlgo::TFIDF)),
+                FusionRun::new(FusionSource::Ranked("bm25".parse().unwrap())).with_weight(2.0),
+            ],
+            ..FusionRanker::code_search_defaults(FusionMethod::CombSum)
+        }
+        .with_literal_root(dir.path());
+
+        let ranked = RankingAlgo::Fusion(fusion).rank(&index, &query, 2).unwrap();
+
+        assert_eq!(ranked.0[0].doc_path, Path::new("src/pool.rs"));
+        assert!((ranked.0[0].score - 3.0).abs() < 1e-9);
+        assert!(ranked.0[1].score.abs() < 1e-9);
+    }
+
+    #[test]
+    fn explanations_list_every_contributing_run() {
+        let (dir, index) = corpus();
+        let query =
+            AnalyzedQuery::new_code_search("\"connection refused by pool\"", &test_config());
+        let fusion = RankingAlgo::Fusion(
+            FusionRanker::code_search_defaults(FusionMethod::Rrf).with_literal_root(dir.path()),
+        );
+
+        let explained = fusion.rank_with_explanations(&index, &query, 1).unwrap();
+        let explanation = &explained.results[0].explanation;
+        let runs = explanation
+            .runs
+            .iter()
+            .map(|run| run.run.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(runs, ["bm25f", "ql", "literal"]);
+        let ranked = fusion.rank(&index, &query, 2).unwrap();
+        let per_result = ranked
+            .0
+            .iter()
+            .map(|score| {
+                index
+                    .doc_id(&score.doc_path)
+                    .map(|doc_id| fusion.explain(&index, &query, doc_id, score.score))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fusion.explain_ranking(&index, &query, &ranked.0),
+            per_result
+        );
+        assert!(
+            (explanation
+                .runs
+                .iter()
+                .map(|run| run.contribution)
+                .sum::<f64>()
+                - explanation.final_score)
+                .abs()
+                < 1e-12
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::ops::Range;
+
+use crate::{
+    code_intelligence::ByteSpan,
+    index::{DocId, PostingList, RankedIndexReader, Term, TokenOffsets},
+    query::{AnalyzedQuery, QueryPhrase, QueryTermProvenance},
+    ranking::proximity::phrase_matches,
+};
+
+/// Byte spans in the document's file that match the query's own terms and
+/// phrases, sorted and merged. Expansion and feedback terms are not
+/// highlighted. Returns nothing for documents indexed without offsets.
+pub fn highlight_spans(
+    index: &impl RankedIndexReader,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+) -> Vec<ByteSpan> {
+    let mut spans = term_matches(index, query, doc_id)
+        .into_iter()
+        .map(|(_, span)| span)
+        .collect::<Vec<_>>();
+    spans.extend(
+        phrase_spans(index, query, doc_id)
+            .into_iter()
+            .map(|(_, span)| span),
+    );
+
+    merge_spans(spans)
+}
+
+/// Every file span where an original query term occurs in `doc_id`.
+pub(crate) fn term_matches<'q>(
+    index: &impl RankedIndexReader,
+    query: &'q AnalyzedQuery,
+    doc_id: DocId,
+) -> Vec<(&'q Term, ByteSpan)> {
+    let Some(offsets) = token_offsets(index, doc_id) else {
+        return Vec::new();
+    };
+
+    let mut matches = Vec::new();
+    for (term, query_term) in query.terms() {
+        if query_term.provenance != QueryTermProvenance::Original {
+            continue;
+        }
+        let Some(term_doc) = index
+            .postings(term)
+            .and_then(|postings| postings.get(doc_id).cloned())
+        else {
+            continue;
+        };
+        for (field, positions) in &term_doc.field_positions {
+            matches.extend(
+                positions
+                    .positions()
+                    .into_iter()
+                    .filter_map(|position| offsets.span(*field, position))
+                    .map(|span| (term, span)),
+            );
+        }
+    }
+    matches
+}
+
+/// Every file span covered by a phrase match in `doc_id`.
+pub(crate) fn phrase_spans<'q>(
+    index: &impl RankedIndexReader,
+    query: &'q AnalyzedQuery,
+    doc_id: DocId,
+) -> Vec<(&'q QueryPhrase, ByteSpan)> {
+    let Some(offsets) = token_offsets(index, doc_id) else {
+        return Vec::new();
+    };
+
+    query
+        .phrases()
+        .iter()
+        .flat_map(|phrase| {
+            phrase_matches(index, doc_id, phrase)
+                .into_iter()
+                .filter_map(move |matched| {
+                    offsets
+                        .span_between(matched.field, matched.start, matched.end)
+                        .map(|span| (phrase, span))
+                })
+        })
+        .collect()
+}
+
+fn token_offsets(index: &impl RankedIndexReader, doc_id: DocId) -> Option<&TokenOffsets> {
+    index
+        .document(doc_id)
+        .and_then(|metadata| metadata.token_offsets.as_ref())
+}
+
+fn merge_spans(mut spans: Vec<ByteSpan>) -> Vec<ByteSpan> {
+    spans.sort_by_key(|span| (span.start, span.end));
+
+    let mut merged: Vec<ByteSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// A few consecutive lines of a file with the matched ranges marked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// One-based number of the first line.
+    pub line_number: usize,
+    pub text: String,
+    /// Byte ranges into `text`.
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// Picks the window of at most `max_lines` lines containing the most
+/// highlight spans, preferring the earliest. Spans that do not fall on
+/// character boundaries of `content` (e.g. the file changed since it was
+/// indexed) are ignored.
+pub fn snippet(content: &str, spans: &[ByteSpan], max_lines: usize) -> Option<Snippet> {
+    let spans = spans
+        .iter()
+        .filter(|span| span.start < span.end && content.get(span.start..span.end).is_some())
+        .collect::<Vec<_>>();
+    if spans.is_empty() || max_lines == 0 {
+        return None;
+    }
+
+    let line_starts = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(offset, _)| offset + 1))
+        .collect::<Vec<_>>();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+    let (first_line, _) = spans
+        .iter()
+        .map(|span| {
+            let line = line_of(span.start);
+            let count = spans
+                .iter()
+                .filter(|other| (line..line + max_lines).contains(&line_of(other.start)))
+                .count();
+            (line, count)
+        })
+        .fold((0, 0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+
+    let start = line_starts[first_line];
+    let end = line_starts
+        .get(first_line + max_lines)
+        .map_or(content.len(), |next| next - 1);
+    let text = content[start..end].trim_end_matches(['\n', '\r']);
+    let highlights = spans
+        .iter()
+        .filter(|span| span.start >= start && span.start < start + text.len())
+        .map(|span| span.start - start..(span.end - start).min(text.len()))
+        .collect();
+
+    Some(Snippet {
+        line_number: first_line + 1,
+        text: text.to_string(),
+        highlights,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Snippet, highlight_spans, snippet};
+    use crate::{
+        code_intelligence::ByteSpan,
+        index::InvertedIndex,
+        query::AnalyzedQuery,
+        test_support::{config_without_stop_words, write_temp_file},
+    };
+
+    fn highlighted<'a>(content: &'a str, spans: &[ByteSpan]) -> Vec<&'a str> {
+        spans
+            .iter()
+            .map(|span| &content[span.start..span.end])
+            .collect()
+    }
+
+    #[test]
+    fn highlights_matched_terms_and_phrases_at_file_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let content =
+            "fn open() {}\n\n// Reconnect the pool after a timeout.\nfn retry_pool() {}\n";
+        write_temp_file(dir.path(), "pool.rs", content);
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+        let doc_id = index.doc_id(Path::new("pool.rs")).unwrap();
+
+        let query = AnalyzedQuery::new_code_search("\"the pool\" timeout", &config);
+        let spans = highlight_spans(&index, &query, doc_id);
+
+        assert_eq!(
+            highlighted(content, &spans),
+            vec!["the pool", "timeout", "retry_pool"]
+        );
+    }
+
+    #[test]
+    fn offsets_survive_unicode_and_are_absent_for_unfielded_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = "// Café résumé: naïve encoding\nfn encode() {}\n";
+        write_temp_file(dir.path(), "enc.rs", content);
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+        let doc_id = index.doc_id(Path::new("enc.rs")).unwrap();
+
+        let query = AnalyzedQuery::new_code_search("naïve", &config);
+        assert_eq!(
+            highlighted(content, &highlight_spans(&index, &query, doc_id)),
+            vec!["naïve"]
+        );
+
+        let transform = |text: &str| crate::tokenizer::n_gram_transform(text, &config);
+        let unfielded = InvertedIndex::new(dir.path(), transform, Some(dir.path()));
+        let doc_id = unfielded.doc_id(Path::new("enc.rs")).unwrap();
+        assert!(highlight_spans(&unfielded, &query, doc_id).is_empty());
+    }
+
+    #[test]
+    fn snippet_picks_the_densest_window() {
+        let content = "alpha\nbeta pool\ngamma\npool delta pool\nepsilon\n";
+        let spans = content
+            .match_indices("pool")
+            .map(|(start, text)| ByteSpan {
+                start,
+                end: start + text.len(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            snippet(content, &spans, 1),
+            Some(Snippet {
+                line_number: 4,
+                text: "pool delta pool".to_string(),
+                highlights: vec![0..4, 11..15],
+            })
+        );
+        assert_eq!(
+            snippet(content, &spans, 3).map(|snippet| snippet.line_number),
+            Some(2)
+        );
+        assert_eq!(snippet(content, &[ByteSpan { start: 2, end: 99 }], 2), None);
+    }
+}
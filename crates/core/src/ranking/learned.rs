@@ -0,0 +1,707 @@
+//! Learning to rank: a linear model fit with coordinate ascent on exported
+//! [`RankingFeatureRow`]s, served as a reranker over a first-stage ranking.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    evaluation::metrics::{Evaluation, average_evaluations, evaluate_query_at_k},
+    index::{DocId, RankedIndexReader},
+    query::AnalyzedQuery,
+    ranking::{
+        RankingAlgo, Score, ScoreExplanation, Scored, StaticQualityContribution,
+        features::{RankingFeatureRow, features_for_score},
+    },
+};
+
+/// Weight changes tried for each feature on every coordinate ascent pass.
+const STEPS: [f64; 8] = [-1.0, -0.5, -0.1, -0.01, 0.01, 0.1, 0.5, 1.0];
+
+#[derive(Debug, thiserror::Error)]
+pub enum LearnedModelError {
+    #[error("failed to access ranking model {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("ranking model JSON failed: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("ranking model has an invalid first stage: {0}")]
+    FirstStage(String),
+    #[error("no query has a judged relevant document to train on")]
+    NoJudgedQueries,
+}
+
+/// One model input. Values are standardised with the training `mean` and
+/// `scale` so weights are comparable across features.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LinearFeature {
+    pub name: String,
+    pub mean: f64,
+    pub scale: f64,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LearnedModel {
+    /// Canonical name of the algorithm whose results are reranked.
+    pub first_stage: String,
+    /// How many first-stage results are reranked: the depth the training
+    /// rows were exported at.
+    pub candidates: usize,
+    pub features: Vec<LinearFeature>,
+}
+
+impl LearnedModel {
+    pub fn load(path: &Path) -> Result<Self, LearnedModelError> {
+        let content = std::fs::read_to_string(path).map_err(|source| LearnedModelError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), LearnedModelError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?).map_err(|source| {
+            LearnedModelError::Io {
+                path: path.to_path_buf(),
+                source,
+            }
+        })
+    }
+
+    pub fn score(&self, features: &BTreeMap<String, f64>) -> f64 {
+        self.contributions(features)
+            .map(|(_, _, contribution)| contribution)
+            .sum()
+    }
+
+    /// `(feature, raw value, contribution)` for every weighted feature;
+    /// features missing from `features` count as zero.
+    pub fn contributions<'a>(
+        &'a self,
+        features: &'a BTreeMap<String, f64>,
+    ) -> impl Iterator<Item = (&'a str, f64, f64)> + 'a {
+        self.features
+            .iter()
+            .filter(|feature| feature.weight != 0.0)
+            .map(|feature| {
+                let value = features.get(&feature.name).copied().unwrap_or(0.0);
+                (
+                    feature.name.as_str(),
+                    value,
+                    feature.weight * (value - feature.mean) / feature.scale,
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrainingOptions {
+    /// Maximum coordinate ascent passes over all features.
+    pub passes: usize,
+    /// Cutoff of the NDCG the model is fit to.
+    pub k: usize,
+}
+
+impl Default for TrainingOptions {
+    fn default() -> Self {
+        Self { passes: 10, k: 10 }
+    }
+}
+
+/// First-stage and reranked metrics on the queries held out of one fold.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FoldEvaluation {
+    pub fold: usize,
+    pub queries: usize,
+    pub first_stage: Evaluation,
+    pub learned: Evaluation,
+}
+
+/// Fits a linear model to `rows` by coordinate ascent on graded NDCG@k,
+/// starting from the first-stage score alone. A judged document's gain is
+/// the reciprocal of its relevance rank; unjudged documents gain nothing.
+pub fn train(
+    rows: &[RankingFeatureRow],
+    first_stage: &str,
+    options: &TrainingOptions,
+) -> Result<LearnedModel, LearnedModelError> {
+    let names = rows
+        .iter()
+        .flat_map(|row| row.features.keys().cloned())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut features = names
+        .iter()
+        .map(|name| {
+            let values = rows
+                .iter()
+                .map(|row| row.features.get(name).copied().unwrap_or(0.0))
+                .collect::<Vec<_>>();
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            let variance = values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / values.len() as f64;
+            LinearFeature {
+                name: name.clone(),
+                mean,
+                scale: if variance > 1e-12 {
+                    variance.sqrt()
+                } else {
+                    1.0
+                },
+                weight: if name == "ranking_score" { 1.0 } else { 0.0 },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let queries = training_queries(rows, &features, options.k);
+    if queries.is_empty() {
+        return Err(LearnedModelError::NoJudgedQueries);
+    }
+
+    let mut weights = features
+        .iter()
+        .map(|feature| feature.weight)
+        .collect::<Vec<_>>();
+    let mut best = mean_ndcg(&queries, &weights, options.k);
+    for _ in 0..options.passes {
+        let mut improved = false;
+        for feature in 0..weights.len() {
+            for step in STEPS {
+                let mut candidate = weights.clone();
+                candidate[feature] += step;
+                let value = mean_ndcg(&queries, &candidate, options.k);
+                if value > best + 1e-12 {
+                    best = value;
+                    weights = candidate;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    for (feature, weight) in features.iter_mut().zip(weights) {
+        feature.weight = weight;
+    }
+
+    Ok(LearnedModel {
+        first_stage: first_stage.to_string(),
+        candidates: rows.iter().map(|row| row.rank).max().unwrap_or(0),
+        features,
+    })
+}
+
+/// Splits the judged queries into `folds` (at least two), trains on all but one and
+/// scores the held-out fold before and after reranking with the
+/// evaluation harness's metrics.
+pub fn cross_validate(
+    rows: &[RankingFeatureRow],
+    first_stage: &str,
+    folds: usize,
+    options: &TrainingOptions,
+) -> Result<Vec<FoldEvaluation>, LearnedModelError> {
+    let by_query = group_by_query(rows);
+    let judged = by_query
+        .iter()
+        .filter(|(_, rows)| rows.iter().any(|row| row.relevance.is_some()))
+        .map(|(query_id, _)| *query_id)
+        .collect::<Vec<_>>();
+    if judged.is_empty() {
+        return Err(LearnedModelError::NoJudgedQueries);
+    }
+    let fold_of = |query_id: &str| {
+        judged
+            .iter()
+            .position(|judged| *judged == query_id)
+            .map(|position| position % folds.max(2))
+    };
+
+    (0..folds.max(2))
+        .filter_map(|fold| {
+            let held_out = judged
+                .iter()
+                .filter(|query_id| fold_of(query_id) == Some(fold))
+                .collect::<Vec<_>>();
+            if held_out.is_empty() {
+                return None;
+            }
+            let training = rows
+                .iter()
+                .filter(|row| fold_of(&row.query_id) != Some(fold))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            Some(train(&training, first_stage, options).map(|model| {
+                let (first_stage, learned): (Vec<_>, Vec<_>) = held_out
+                    .iter()
+                    .map(|query_id| evaluate_held_out(&model, &by_query[*query_id], options.k))
+                    .unzip();
+                FoldEvaluation {
+                    fold: fold + 1,
+                    queries: held_out.len(),
+                    first_stage: average_evaluations(&first_stage),
+                    learned: average_evaluations(&learned),
+                }
+            }))
+        })
+        .collect()
+}
+
+fn evaluate_held_out(
+    model: &LearnedModel,
+    rows: &[&RankingFeatureRow],
+    k: usize,
+) -> (Evaluation, Evaluation) {
+    let mut judged = rows
+        .iter()
+        .filter_map(|row| {
+            row.relevance
+                .map(|relevance| (relevance, row.doc_path.clone()))
+        })
+        .collect::<Vec<_>>();
+    judged.sort();
+    let relevant_docs = judged.into_iter().map(|(_, path)| path).collect::<Vec<_>>();
+
+    let mut first_stage = rows.to_vec();
+    first_stage.sort_by_key(|row| row.rank);
+    let mut learned = first_stage
+        .iter()
+        .map(|row| (model.score(&row.features), *row))
+        .collect::<Vec<_>>();
+    learned.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.rank.cmp(&b.1.rank)));
+
+    let first_stage = first_stage
+        .iter()
+        .map(|row| score_of(row, row.features.get("ranking_score").copied()))
+        .collect::<Vec<_>>();
+    let learned = learned
+        .into_iter()
+        .map(|(score, row)| score_of(row, Some(score)))
+        .collect::<Vec<_>>();
+
+    (
+        evaluate_query_at_k(&first_stage, &relevant_docs, k),
+        evaluate_query_at_k(&learned, &relevant_docs, k),
+    )
+}
+
+fn score_of(row: &RankingFeatureRow, score: Option<f64>) -> Score {
+    Score {
+        doc_path: row.doc_path.clone(),
+        score: score.unwrap_or(0.0),
+        passages: Vec::new(),
+        similar: Vec::new(),
+    }
+}
+
+fn group_by_query(rows: &[RankingFeatureRow]) -> BTreeMap<&str, Vec<&RankingFeatureRow>> {
+    let mut by_query: BTreeMap<&str, Vec<&RankingFeatureRow>> = BTreeMap::new();
+    for row in rows {
+        by_query.entry(&row.query_id).or_default().push(row);
+    }
+    by_query
+}
+
+/// A judged query's candidates as standardised feature vectors, gains and
+/// first-stage ranks, which break ties between equal model scores, plus the
+/// DCG@k of a perfect ordering.
+struct TrainingQuery {
+    candidates: Vec<(Vec<f64>, f64, usize)>,
+    ideal_dcg: f64,
+}
+
+fn training_queries(
+    rows: &[RankingFeatureRow],
+    features: &[LinearFeature],
+    k: usize,
+) -> Vec<TrainingQuery> {
+    group_by_query(rows)
+        .into_values()
+        .filter_map(|rows| {
+            let candidates = rows
+                .iter()
+                .map(|row| {
+                    let values = features
+                        .iter()
+                        .map(|feature| {
+                            let value = row.features.get(&feature.name).copied().unwrap_or(0.0);
+                            (value - feature.mean) / feature.scale
+                        })
+                        .collect();
+                    let gain = row.relevance.map_or(0.0, |rank| 1.0 / rank.max(1) as f64);
+                    (values, gain, row.rank)
+                })
+                .collect::<Vec<_>>();
+            let mut gains = candidates
+                .iter()
+                .map(|(_, gain, _)| *gain)
+                .collect::<Vec<_>>();
+            gains.sort_by(|a, b| b.total_cmp(a));
+            gains.truncate(k);
+            let ideal_dcg = discounted_gain(gains);
+
+            (ideal_dcg > 0.0).then_some(TrainingQuery {
+                candidates,
+                ideal_dcg,
+            })
+        })
+        .collect()
+}
+
+fn mean_ndcg(queries: &[TrainingQuery], weights: &[f64], k: usize) -> f64 {
+    queries
+        .iter()
+        .map(|query| {
+            let mut scored = query
+                .candidates
+                .iter()
+                .map(|(values, gain, rank)| {
+                    let score = values
+                        .iter()
+                        .zip(weights)
+                        .map(|(value, weight)| value * weight)
+                        .sum::<f64>();
+                    (score, *gain, *rank)
+                })
+                .collect::<Vec<_>>();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.2.cmp(&b.2)));
+            discounted_gain(
+                scored
+                    .into_iter()
+                    .take(k)
+                    .map(|(_, gain, _)| gain)
+                    .collect(),
+            ) / query.ideal_dcg
+        })
+        .sum::<f64>()
+        / queries.len() as f64
+}
+
+fn discounted_gain(gains: Vec<f64>) -> f64 {
+    gains
+        .into_iter()
+        .enumerate()
+        .map(|(rank, gain)| gain / ((rank + 2) as f64).log2())
+        .sum()
+}
+
+/// Serves a [`LearnedModel`]: ranks with the first stage, then reorders its
+/// top candidates by the model's score over the exported features.
+#[derive(Debug, Clone)]
+pub struct LearnedRanker {
+    model: Arc<LearnedModel>,
+    first_stage: Box<RankingAlgo>,
+}
+
+impl LearnedRanker {
+    pub fn new(model: LearnedModel) -> Result<Self, LearnedModelError> {
+        let first_stage = model
+            .first_stage
+            .parse::<RankingAlgo>()
+            .map_err(LearnedModelError::FirstStage)?;
+        if matches!(first_stage, RankingAlgo::Learned(_)) {
+            return Err(LearnedModelError::FirstStage(model.first_stage));
+        }
+
+        Ok(Self {
+            model: Arc::new(model),
+            first_stage: Box::new(first_stage),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LearnedModelError> {
+        Self::new(LearnedModel::load(path)?)
+    }
+
+    pub fn model(&self) -> &LearnedModel {
+        &self.model
+    }
+
+    pub fn first_stage(&self) -> &RankingAlgo {
+        &self.first_stage
+    }
+
+    pub fn with_first_stage(mut self, first_stage: RankingAlgo) -> Self {
+        self.first_stage = Box::new(first_stage);
+        self
+    }
+
+    pub(crate) fn rerank<I>(&self, index: &I, query: &AnalyzedQuery) -> Scored
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let candidates = self
+            .first_stage
+            .rank(index, query, self.model.candidates)
+            .map(|scored| scored.0)
+            .unwrap_or_default();
+
+        Scored(
+            candidates
+                .into_iter()
+                .map(|score| Score {
+                    score: self.model.score(&features_for_score(index, query, &score)),
+                    ..score
+                })
+                .collect(),
+        )
+    }
+
+    /// Lists each weighted feature's contribution as a static signal, since
+    /// the model has no per-term scores.
+    pub(crate) fn explain<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        doc_id: DocId,
+        final_score: f64,
+    ) -> ScoreExplanation
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let doc_path = index
+            .document(doc_id)
+            .map(|metadata| metadata.path.clone())
+            .unwrap_or_default();
+        let candidates = self.candidates(index, query);
+
+        self.explanation(index, query, &candidates, doc_path, final_score)
+    }
+
+    /// Explains each of `results` in order from one first-stage ranking.
+    /// Results no longer in `index` get `None`.
+    pub(crate) fn explain_ranking<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        results: &[Score],
+    ) -> Vec<Option<ScoreExplanation>>
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let candidates = self.candidates(index, query);
+        results
+            .iter()
+            .map(|score| {
+                index.doc_id(&score.doc_path)?;
+                Some(self.explanation(
+                    index,
+                    query,
+                    &candidates,
+                    score.doc_path.clone(),
+                    score.score,
+                ))
+            })
+            .collect()
+    }
+
+    /// First-stage scores by path.
+    fn candidates<I>(&self, index: &I, query: &AnalyzedQuery) -> HashMap<PathBuf, Score>
+    where
+        I: RankedIndexReader + Sync,
+    {
+        self.first_stage
+            .rank(index, query, self.model.candidates)
+            .map(|scored| {
+                scored
+                    .0
+                    .into_iter()
+                    .map(|score| (score.doc_path.clone(), score))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn explanation<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        candidates: &HashMap<PathBuf, Score>,
+        doc_path: PathBuf,
+        final_score: f64,
+    ) -> ScoreExplanation
+    where
+        I: RankedIndexReader + Sync,
+    {
+        let first_stage = candidates.get(&doc_path).cloned().unwrap_or(Score {
+            doc_path,
+            score: 0.0,
+            passages: Vec::new(),
+            similar: Vec::new(),
+        });
+        let features = features_for_score(index, query, &first_stage);
+
+        ScoreExplanation {
+            final_score,
+            query_intent: query.intent(),
+            terms: Vec::new(),
+            proximity_bonus: 0.0,
+            static_quality: self
+                .model
+                .contributions(&features)
+                .map(|(signal, value, contribution)| StaticQualityContribution {
+                    signal: signal.to_string(),
+                    value,
+                    contribution,
+                })
+                .collect(),
+            runs: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    use super::{LearnedModel, LearnedRanker, TrainingOptions, cross_validate, train};
+    use crate::{
+        index::InvertedIndex,
+        query::AnalyzedQuery,
+        ranking::{RankingAlgo, features::RankingFeatureRow},
+    };
+
+    fn row(
+        query_id: &str,
+        doc: &str,
+        rank: usize,
+        relevance: Option<usize>,
+        signal: f64,
+    ) -> RankingFeatureRow {
+        RankingFeatureRow {
+            query_id: query_id.to_string(),
+            query: query_id.to_string(),
+            query_intent: "Mixed".to_string(),
+            doc_path: PathBuf::from(doc),
+            rank,
+            relevance,
+            features: BTreeMap::from([
+                ("ranking_score".to_string(), 10.0 - rank as f64),
+                ("signal".to_string(), signal),
+            ]),
+            expansion_provenance: BTreeMap::new(),
+        }
+    }
+
+    /// The first stage ranks the relevant document last; only `signal`
+    /// tells it apart.
+    fn rows() -> Vec<RankingFeatureRow> {
+        (1..=4)
+            .flat_map(|query| {
+                let query_id = format!("q{query}");
+                [
+                    row(&query_id, "a.rs", 1, None, 0.0),
+                    row(&query_id, "b.rs", 2, None, 0.0),
+                    row(&query_id, "c.rs", 3, Some(1), 1.0),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn training_learns_the_feature_that_separates_relevant_documents() {
+        let model = train(&rows(), "bm25", &TrainingOptions::default()).unwrap();
+        let score = |signal: f64| {
+            model.score(&BTreeMap::from([
+                ("ranking_score".to_string(), 9.0),
+                ("signal".to_string(), signal),
+            ]))
+        };
+
+        assert_eq!(model.candidates, 3);
+        assert!(score(1.0) > score(0.0));
+    }
+
+    #[test]
+    fn training_without_judgements_fails() {
+        let rows = vec![row("q1", "a.rs", 1, None, 0.0)];
+
+        assert!(train(&rows, "bm25", &TrainingOptions::default()).is_err());
+    }
+
+    #[test]
+    fn cross_validation_improves_held_out_queries() {
+        let folds = cross_validate(&rows(), "bm25", 2, &TrainingOptions::default()).unwrap();
+
+        assert_eq!(folds.len(), 2);
+        for fold in folds {
+            assert_eq!(fold.queries, 2);
+            assert_eq!(fold.learned.mean_reciprocal_rank, 1.0);
+            assert!(fold.first_stage.mean_reciprocal_rank < 1.0);
+        }
+    }
+
+    #[test]
+    fn learned_ranker_round_trips_and_reranks_first_stage_candidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.json");
+        let mut model = train(&rows(), "tfidf", &TrainingOptions::default()).unwrap();
+        // Prefer the document the first stage scores lowest.
+        model.features.iter_mut().for_each(|feature| {
+            feature.weight = if feature.name == "ranking_score" {
+                -1.0
+            } else {
+                0.0
+            };
+        });
+        model.save(&path).unwrap();
+        assert_eq!(LearnedModel::load(&path).unwrap(), model);
+
+        let algo = format!("learned:{}", path.display())
+            .parse::<RankingAlgo>()
+            .unwrap();
+        let index = InvertedIndex::from_documents(&[
+            ("src/many.rs", &[("rust", 5)]),
+            ("src/few.rs", &[("rust", 1), ("other", 4)]),
+        ]);
+        let query = AnalyzedQuery::from_frequencies(
+            "rust",
+            [(crate::index::Term("rust".to_string()), 1)].into(),
+        );
+
+        let first = RankingAlgo::TFIDF.rank(&index, &query, 2).unwrap();
+        let learned = algo.rank(&index, &query, 2).unwrap();
+        assert_eq!(algo.name(), "learned");
+        assert_eq!(learned.0[0].doc_path, first.0[1].doc_path);
+
+        let explanations = algo.rank_with_explanations(&index, &query, 2).unwrap();
+        for result in explanations.results {
+            let total = result
+                .explanation
+                .static_quality
+                .iter()
+                .map(|signal| signal.contribution)
+                .sum::<f64>();
+            assert!((total - result.explanation.final_score).abs() < 1e-9);
+            let doc_id = index.doc_id(&result.score.doc_path).unwrap();
+            assert_eq!(
+                result.explanation,
+                algo.explain(&index, &query, doc_id, result.score.score)
+            );
+        }
+    }
+
+    #[test]
+    fn learned_models_cannot_rerank_themselves() {
+        let model = LearnedModel {
+            first_stage: "learned".to_string(),
+            candidates: 10,
+            features: Vec::new(),
+        };
+
+        assert!(LearnedRanker::new(model).is_err());
+    }
+}
@@ -1,24 +1,42 @@
 pub mod bm25;
 pub mod bm25f;
 pub mod cosine_similarity;
+pub mod dense;
+pub mod dfr;
+pub mod diversity;
 pub mod explanation;
 pub mod features;
 pub mod feedback;
+pub mod fusion;
+pub mod highlight;
+pub mod learned;
+pub mod passage;
 pub mod proximity;
 pub mod query_likelihood;
 pub mod scorer;
+pub mod symbol_search;
 pub mod tf_idf;
 mod utils;
 
-pub use bm25::{BM25, BM25HyperParams};
+pub use bm25::{BM25, BM25HyperParams, BM25Variant, LowerBoundedBM25};
 pub use bm25f::{BM25F, BM25FHyperParams};
 pub use cosine_similarity::CosineSimilarity;
+pub use dense::{DenseParams, DenseRetrieval};
+pub use dfr::{DfrModel, DfrTermStats, DivergenceFromRandomness};
+pub use diversity::{
+    DiversityConfig, DocumentSimilarity, SimilarityMeasure, diversify, redundancy_at_k,
+};
 pub use explanation::{
-    FieldContribution, ScoreExplanation, ScoreWithExplanation, ScoredWithExplanations,
-    StaticQualityContribution, TermExplanation,
+    FieldContribution, RunContribution, ScoreExplanation, ScoreWithExplanation,
+    ScoredWithExplanations, StaticQualityContribution, TermExplanation,
 };
+pub use fusion::{FusionMethod, FusionRanker, FusionRun, FusionSource};
+pub use highlight::{Snippet, highlight_spans, snippet};
+pub use learned::{LearnedModel, LearnedModelError, LearnedRanker};
+pub use passage::{Passage, PassageChunking, PassageConfig, attach_passages, best_passages};
 pub use proximity::ProximityConfig;
 pub use query_likelihood::{QueryLikelihood, QueryLikelihoodParams, QueryLikelihoodSmoothing};
 pub use scorer::{RankingAlgo, RankingAlgorithm, Score, Scored, Scorer};
+pub use symbol_search::{SymbolHit, search_symbols};
 pub use tf_idf::TFIDF;
 pub(crate) use utils::idf;
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::{
+    index::{DocId, DocumentField, RankedIndexReader, TokenOffsets},
+    query::AnalyzedQuery,
+    ranking::{
+        Scored,
+        highlight::{phrase_spans, term_matches},
+        idf,
+    },
+};
+
+/// Term-frequency saturation inside a passage, as in BM25.
+const PASSAGE_K1: f64 = 1.2;
+
+/// How a document is cut into candidate passages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassageChunking {
+    /// Windows of `lines` lines, one starting every `stride` lines.
+    Windows { lines: usize, stride: usize },
+    /// One chunk per symbol, running from the line the symbol is declared on
+    /// to the line before the next symbol. Chunks longer than `max_lines`
+    /// and files without symbols fall back to windows.
+    Symbols { max_lines: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassageConfig {
+    pub chunking: PassageChunking,
+    /// Passages kept per document, best first; they never overlap.
+    pub max_passages: usize,
+}
+
+impl Default for PassageConfig {
+    fn default() -> Self {
+        Self {
+            chunking: PassageChunking::Windows {
+                lines: 20,
+                stride: 10,
+            },
+            max_passages: 3,
+        }
+    }
+}
+
+/// A line range inside a ranked document. Lines are one-based and
+/// `end_line` is inclusive.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Passage {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: f64,
+}
+
+impl Passage {
+    pub fn overlaps_lines(&self, start_line: usize, end_line: usize) -> bool {
+        self.start_line <= end_line && start_line <= self.end_line
+    }
+}
+
+/// Fills in the best passages of every ranked document. Documents indexed
+/// without token offsets keep an empty list.
+pub fn attach_passages(
+    index: &impl RankedIndexReader,
+    query: &AnalyzedQuery,
+    ranking: &mut Scored,
+    config: &PassageConfig,
+) {
+    for score in &mut ranking.0 {
+        if let Some(doc_id) = index.doc_id(&score.doc_path) {
+            score.passages = best_passages(index, query, doc_id, config);
+        }
+    }
+}
+
+/// Scores every chunk of `doc_id` by the query terms and phrases it
+/// contains and returns the best non-overlapping ones.
+pub fn best_passages(
+    index: &impl RankedIndexReader,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+    config: &PassageConfig,
+) -> Vec<Passage> {
+    let Some(metadata) = index.document(doc_id) else {
+        return Vec::new();
+    };
+    let Some(offsets) = metadata.token_offsets.as_ref() else {
+        return Vec::new();
+    };
+    if config.max_passages == 0 || offsets.line_count() == 0 {
+        return Vec::new();
+    }
+
+    let term_weight = |term| {
+        let weight = query
+            .terms()
+            .find(|(query_term, _)| *query_term == term)
+            .map_or(1.0, |(_, query_term)| query_term.weight);
+        weight * idf(index.num_docs(), index.doc_freq(term))
+    };
+    let term_hits = term_matches(index, query, doc_id)
+        .into_iter()
+        .filter_map(|(term, span)| Some((term, offsets.line_of(span.start)?)))
+        .collect::<Vec<_>>();
+    let phrase_hits = phrase_spans(index, query, doc_id)
+        .into_iter()
+        .filter_map(|(phrase, span)| {
+            let weight = phrase.terms.iter().map(term_weight).sum::<f64>();
+            Some((
+                weight,
+                offsets.line_of(span.start)?,
+                offsets.line_of(span.end.saturating_sub(1))?,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut candidates = chunks(metadata, offsets, config.chunking)
+        .into_iter()
+        .filter_map(|(start_line, end_line)| {
+            let mut frequencies = HashMap::new();
+            for (term, line) in &term_hits {
+                if (start_line..=end_line).contains(line) {
+                    *frequencies.entry(*term).or_insert(0usize) += 1;
+                }
+            }
+            let term_score = frequencies
+                .into_iter()
+                .map(|(term, frequency)| {
+                    let frequency = frequency as f64;
+                    term_weight(term) * frequency * (PASSAGE_K1 + 1.0) / (frequency + PASSAGE_K1)
+                })
+                .sum::<f64>();
+            let phrase_score = phrase_hits
+                .iter()
+                .filter(|(_, start, end)| start_line <= *start && *end <= end_line)
+                .map(|(weight, _, _)| weight)
+                .sum::<f64>();
+
+            let score = term_score + phrase_score;
+            (score > 0.0).then_some(Passage {
+                start_line,
+                end_line,
+                score,
+            })
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.start_line.cmp(&b.start_line))
+    });
+
+    let mut passages: Vec<Passage> = Vec::with_capacity(config.max_passages);
+    for candidate in candidates {
+        if passages.len() == config.max_passages {
+            break;
+        }
+        if !passages
+            .iter()
+            .any(|passage| passage.overlaps_lines(candidate.start_line, candidate.end_line))
+        {
+            passages.push(candidate);
+        }
+    }
+    passages
+}
+
+fn chunks(
+    metadata: &crate::index::DocumentMetadata,
+    offsets: &TokenOffsets,
+    chunking: PassageChunking,
+) -> Vec<(usize, usize)> {
+    let line_count = offsets.line_count();
+    match chunking {
+        PassageChunking::Windows { lines, stride } => windows(1, line_count, lines, stride),
+        PassageChunking::Symbols { max_lines } => {
+            let mut starts = metadata
+                .features
+                .iter()
+                .filter(|feature| feature.field == DocumentField::Symbol)
+                .filter_map(|feature| offsets.line_of(feature.span?.start))
+                .collect::<Vec<_>>();
+            starts.sort_unstable();
+            starts.dedup();
+            if starts.is_empty() {
+                return windows(1, line_count, max_lines, max_lines.div_ceil(2));
+            }
+            if starts[0] > 1 {
+                starts.insert(0, 1);
+            }
+
+            starts
+                .iter()
+                .enumerate()
+                .flat_map(|(i, &start)| {
+                    let end = starts.get(i + 1).map_or(line_count, |next| next - 1);
+                    windows(start, end, max_lines, max_lines)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Windows of `lines` lines covering `first..=last`, the final one clipped.
+fn windows(first: usize, last: usize, lines: usize, stride: usize) -> Vec<(usize, usize)> {
+    let lines = lines.max(1);
+    let stride = stride.max(1);
+
+    let mut windows = Vec::new();
+    let mut start = first;
+    while start <= last {
+        let end = (start + lines - 1).min(last);
+        windows.push((start, end));
+        if end == last {
+            break;
+        }
+        start += stride;
+    }
+    windows
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Passage, PassageChunking, PassageConfig, attach_passages, best_passages, windows};
+    use crate::{
+        index::InvertedIndex,
+        query::AnalyzedQuery,
+        ranking::{BM25HyperParams, RankingAlgo},
+        test_support::{config_without_stop_words, write_temp_file},
+    };
+
+    fn source() -> String {
+        let mut lines = vec!["fn setup() {}".to_string(); 30];
+        lines[11] = "fn reconnect_pool() {".to_string();
+        lines[12] = "    // retry the pool after a timeout".to_string();
+        lines[13] = "}".to_string();
+        lines[25] = "// pool".to_string();
+        lines.join("\n")
+    }
+
+    #[test]
+    fn windows_cover_the_range_and_clip_the_last_one() {
+        assert_eq!(windows(1, 7, 4, 2), vec![(1, 4), (3, 6), (5, 7)]);
+        assert_eq!(windows(5, 5, 4, 4), vec![(5, 5)]);
+        assert!(windows(3, 2, 4, 4).is_empty());
+    }
+
+    #[test]
+    fn best_window_holds_the_densest_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", &source());
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+        let doc_id = index.doc_id(Path::new("pool.rs")).unwrap();
+        let query = AnalyzedQuery::new_code_search("retry pool timeout", &config);
+
+        let passages = best_passages(
+            &index,
+            &query,
+            doc_id,
+            &PassageConfig {
+                chunking: PassageChunking::Windows {
+                    lines: 5,
+                    stride: 5,
+                },
+                max_passages: 2,
+            },
+        );
+
+        assert_eq!(passages.len(), 2);
+        assert_eq!((passages[0].start_line, passages[0].end_line), (11, 15));
+        assert_eq!((passages[1].start_line, passages[1].end_line), (26, 30));
+        assert!(passages[0].score > passages[1].score);
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn symbol_chunks_follow_declarations() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "use std::io;\n\nfn open() {\n    io::stdin();\n}\n\nfn retry_pool() {\n    // retry after timeout\n    open();\n}\n",
+        );
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+        let doc_id = index.doc_id(Path::new("pool.rs")).unwrap();
+        let query = AnalyzedQuery::new_code_search("retry timeout", &config);
+
+        let passages = best_passages(
+            &index,
+            &query,
+            doc_id,
+            &PassageConfig {
+                chunking: PassageChunking::Symbols { max_lines: 40 },
+                max_passages: 1,
+            },
+        );
+
+        assert_eq!(
+            passages
+                .iter()
+                .map(|passage| (passage.start_line, passage.end_line))
+                .collect::<Vec<_>>(),
+            vec![(7, 10)]
+        );
+    }
+
+    #[test]
+    fn ranked_results_carry_passages() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "pool.rs", &source());
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("timeout", &config);
+
+        let mut ranking = RankingAlgo::BM25(BM25HyperParams { k1: 1.2, b: 0.75 })
+            .rank(&index, &query, 1)
+            .unwrap();
+        assert!(ranking.0[0].passages.is_empty());
+        attach_passages(&index, &query, &mut ranking, &PassageConfig::default());
+
+        assert!(matches!(
+            ranking.0[0].passages.as_slice(),
+            [Passage {
+                start_line: 1,
+                end_line: 20,
+                ..
+            }]
+        ));
+    }
+}
@@ -12,7 +12,8 @@ const POSITIONAL_FIELDS: [DocumentField; 4] = [
     DocumentField::StringLiteral,
 ];
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ProximityConfig {
     pub window: u32,
     pub phrase_boost: f64,
@@ -29,6 +30,28 @@ impl Default for ProximityConfig {
     }
 }
 
+/// A span of token positions within one field that satisfies a
+/// [`QueryPhrase`]. `end` is inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PhraseMatch {
+    pub field: DocumentField,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl PhraseMatch {
+    /// Number of intervening tokens beyond what an exact phrase would need.
+    pub fn slop(&self, term_count: usize) -> u32 {
+        (self.end - self.start).saturating_sub(term_count.saturating_sub(1) as u32)
+    }
+
+    /// Sloppy frequency contribution: exact matches count fully and looser
+    /// matches decay with the number of intervening tokens.
+    pub fn weight(&self, term_count: usize) -> f64 {
+        1.0 / (1.0 + self.slop(term_count) as f64)
+    }
+}
+
 pub fn positional_bonus(
     index: &impl RankedIndexReader,
     query: &AnalyzedQuery,
@@ -38,68 +61,171 @@ pub fn positional_bonus(
     let phrase_bonus = query
         .phrases()
         .iter()
-        .map(|phrase| phrase_match_count(index, doc_id, phrase) as f64 * config.phrase_boost)
+        .map(|phrase| phrase_match_score(index, doc_id, phrase) * config.phrase_boost)
         .sum::<f64>();
 
     phrase_bonus + proximity_bonus(index, query, doc_id, config)
 }
 
+/// Phrase bonus where each match is scaled by the weight of the field it
+/// occurred in, so BM25F can reuse its per-intent field weights.
+pub fn field_weighted_phrase_bonus(
+    index: &impl RankedIndexReader,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+    field_weight: impl Fn(DocumentField) -> f64,
+) -> f64 {
+    query
+        .phrases()
+        .iter()
+        .flat_map(|phrase| {
+            phrase_matches(index, doc_id, phrase)
+                .into_iter()
+                .map(|matched| field_weight(matched.field) * matched.weight(phrase.terms.len()))
+        })
+        .sum()
+}
+
+pub fn satisfies_required_phrases(
+    index: &impl RankedIndexReader,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+) -> bool {
+    query
+        .required_phrases()
+        .all(|phrase| match phrase.terms.as_slice() {
+            [term] => term_doc(index, term, doc_id).is_some(),
+            _ => !phrase_matches(index, doc_id, phrase).is_empty(),
+        })
+}
+
 pub fn phrase_match_count(
     index: &impl RankedIndexReader,
     doc_id: DocId,
     phrase: &QueryPhrase,
 ) -> usize {
-    if phrase.terms.len() < 2 {
-        return 0;
-    }
+    phrase_matches(index, doc_id, phrase).len()
+}
 
-    POSITIONAL_FIELDS
-        .into_iter()
-        .map(|field| phrase_match_count_in_field(index, doc_id, phrase, field))
+pub fn phrase_match_score(
+    index: &impl RankedIndexReader,
+    doc_id: DocId,
+    phrase: &QueryPhrase,
+) -> f64 {
+    phrase_matches(index, doc_id, phrase)
+        .iter()
+        .map(|matched| matched.weight(phrase.terms.len()))
         .sum()
 }
 
-fn phrase_match_count_in_field(
+pub fn phrase_matches(
     index: &impl RankedIndexReader,
     doc_id: DocId,
     phrase: &QueryPhrase,
-    field: DocumentField,
-) -> usize {
-    let Some(first_doc) = term_doc(index, &phrase.terms[0], doc_id) else {
-        return 0;
+) -> Vec<PhraseMatch> {
+    if phrase.terms.len() < 2 {
+        return Vec::new();
+    }
+
+    let term_docs = phrase
+        .terms
+        .iter()
+        .map(|term| term_doc(index, term, doc_id))
+        .collect::<Option<Vec<_>>>();
+    let Some(term_docs) = term_docs else {
+        return Vec::new();
     };
-    let Some(first_positions) = first_doc.field_positions(field) else {
-        return 0;
+
+    POSITIONAL_FIELDS
+        .into_iter()
+        .flat_map(|field| {
+            let positions = term_docs
+                .iter()
+                .map(|term_doc| {
+                    term_doc
+                        .field_positions(field)
+                        .map(|positions| positions.positions())
+                })
+                .collect::<Option<Vec<_>>>();
+            let spans = match positions {
+                Some(positions) if phrase.ordered => ordered_spans(&positions, phrase.slop),
+                Some(positions) => unordered_spans(&positions, phrase.slop),
+                None => Vec::new(),
+            };
+
+            spans
+                .into_iter()
+                .map(move |(start, end)| PhraseMatch { field, start, end })
+        })
+        .collect()
+}
+
+/// Ordered matches: for each position of the first term, greedily take the
+/// next position of every following term, which yields the tightest span
+/// starting there.
+fn ordered_spans(positions: &[Vec<u32>], slop: u32) -> Vec<(u32, u32)> {
+    let Some((first, rest)) = positions.split_first() else {
+        return Vec::new();
     };
+    let max_span = rest.len() as u32 + slop;
 
-    let rest_positions = phrase.terms[1..]
+    first
         .iter()
-        .filter_map(|term| {
-            let term_doc = term_doc(index, term, doc_id)?;
-            term_doc
-                .field_positions(field)
-                .map(|positions| positions.positions())
+        .filter_map(|&start| {
+            let mut previous = start;
+            for term_positions in rest {
+                let next = term_positions.partition_point(|&position| position <= previous);
+                previous = *term_positions.get(next)?;
+            }
+
+            (previous - start <= max_span).then_some((start, previous))
+        })
+        .collect()
+}
+
+/// Unordered matches: minimal windows covering every term at least once,
+/// reported once per distinct window start.
+fn unordered_spans(positions: &[Vec<u32>], slop: u32) -> Vec<(u32, u32)> {
+    let mut occurrences = positions
+        .iter()
+        .enumerate()
+        .flat_map(|(term_idx, term_positions)| {
+            term_positions
+                .iter()
+                .map(move |&position| (position, term_idx))
         })
         .collect::<Vec<_>>();
+    occurrences.sort_unstable();
 
-    if rest_positions.len() != phrase.terms.len() - 1 {
-        return 0;
+    let max_span = positions.len().saturating_sub(1) as u32 + slop;
+    let mut counts = vec![0usize; positions.len()];
+    let mut covered_terms = 0;
+    let mut left = 0;
+    let mut spans: Vec<(u32, u32)> = Vec::new();
+
+    for right in 0..occurrences.len() {
+        let right_term = occurrences[right].1;
+        if counts[right_term] == 0 {
+            covered_terms += 1;
+        }
+        counts[right_term] += 1;
+
+        if covered_terms < positions.len() {
+            continue;
+        }
+
+        while counts[occurrences[left].1] > 1 {
+            counts[occurrences[left].1] -= 1;
+            left += 1;
+        }
+
+        let span = (occurrences[left].0, occurrences[right].0);
+        if span.1 - span.0 <= max_span && spans.last().is_none_or(|previous| previous.0 != span.0) {
+            spans.push(span);
+        }
     }
 
-    first_positions
-        .positions()
-        .into_iter()
-        .filter(|start| {
-            rest_positions
-                .iter()
-                .enumerate()
-                .all(|(offset, positions)| {
-                    positions
-                        .binary_search(&(*start + offset as u32 + 1))
-                        .is_ok()
-                })
-        })
-        .count()
+    spans
 }
 
 fn proximity_bonus(
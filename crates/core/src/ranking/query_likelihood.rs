@@ -44,6 +44,8 @@ impl QueryLikelihood {
             .map(|metadata| Score {
                 doc_path: metadata.path.clone(),
                 score: self.score_doc(index, query, metadata.id),
+                passages: Vec::new(),
+                similar: Vec::new(),
             })
             .collect();
 
@@ -4,13 +4,18 @@ use dashmap::DashMap;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 
 use crate::{
-    index::{DocId, DocumentField, PostingList, RankedIndexReader, Term, TermDocument},
+    index::{
+        DocId, DocumentField, FilteredIndex, InvertedIndex, PostingList, RankedIndexReader,
+        SearchFilter, Term, TermDocument,
+    },
     query::{AnalyzedQuery, QueryTerm},
     ranking::{
-        BM25, BM25F, BM25FHyperParams, BM25HyperParams, CosineSimilarity, FieldContribution,
-        ProximityConfig, QueryLikelihood, QueryLikelihoodParams, ScoreExplanation,
-        ScoreWithExplanation, ScoredWithExplanations, StaticQualityContribution, TFIDF,
-        TermExplanation, idf,
+        BM25, BM25F, BM25FHyperParams, BM25HyperParams, BM25Variant, CosineSimilarity, DenseParams,
+        DenseRetrieval, DfrModel, DfrTermStats, DivergenceFromRandomness, DiversityConfig,
+        FieldContribution, FusionMethod, FusionRanker, LearnedRanker, LowerBoundedBM25, Passage,
+        ProximityConfig, QueryLikelihood, QueryLikelihoodParams, QueryLikelihoodSmoothing,
+        ScoreExplanation, ScoreWithExplanation, ScoredWithExplanations, StaticQualityContribution,
+        TFIDF, TermExplanation, diversify, idf,
     },
 };
 
@@ -33,6 +38,12 @@ impl std::fmt::Display for Scored {
 pub struct Score {
     pub doc_path: PathBuf,
     pub score: f64,
+    /// Best line ranges inside the document, filled in by
+    /// [`attach_passages`](crate::ranking::attach_passages).
+    pub passages: Vec<Passage>,
+    /// Near-duplicates folded into this result by
+    /// [`diversify`](crate::ranking::diversify).
+    pub similar: Vec<PathBuf>,
 }
 
 impl std::fmt::Display for Score {
@@ -47,8 +58,17 @@ pub enum RankingAlgo {
     BM25(BM25HyperParams),
     BM25F(BM25FHyperParams),
     BM25Proximity(BM25HyperParams, ProximityConfig),
+    /// BM25+ or BM25L, which penalise long documents less than BM25.
+    BM25Variant(BM25HyperParams, BM25Variant),
     QueryLikelihood(QueryLikelihoodParams),
+    DivergenceFromRandomness(DfrModel),
     TFIDF,
+    /// Cosine similarity in the index's latent semantic space.
+    Dense(DenseParams),
+    /// Reranks another algorithm's top results with a trained model.
+    Learned(LearnedRanker),
+    /// Merges several rankings, weighted by query intent.
+    Fusion(FusionRanker),
 }
 
 pub trait RankingAlgorithm {
@@ -85,35 +105,63 @@ impl RankingAlgorithm for RankingAlgo {
                 index,
                 query,
             ),
-            RankingAlgo::BM25F(hyper_params) => score_with(
-                BM25F {
-                    hyper_params: hyper_params.clone(),
-                },
-                index,
-                query,
-            ),
-            RankingAlgo::BM25Proximity(hyper_params, config) => {
+            RankingAlgo::BM25F(hyper_params) => {
                 let mut scored = score_with(
-                    BM25 {
+                    BM25F {
                         hyper_params: hyper_params.clone(),
                     },
                     index,
                     query,
                 );
-                for score in &mut scored.0 {
-                    if let Some(doc_id) = index.doc_id(&score.doc_path) {
-                        score.score += crate::ranking::proximity::positional_bonus(
-                            index, query, doc_id, config,
-                        );
-                    }
+                if !query.phrases().is_empty() {
+                    add_proximity_bonus(self, index, query, &mut scored);
                 }
                 scored
             }
+            RankingAlgo::BM25Proximity(hyper_params, _) => {
+                let mut scored = score_with(
+                    BM25 {
+                        hyper_params: hyper_params.clone(),
+                    },
+                    index,
+                    query,
+                );
+                add_proximity_bonus(self, index, query, &mut scored);
+                scored
+            }
+            RankingAlgo::BM25Variant(hyper_params, variant) => score_with(
+                LowerBoundedBM25 {
+                    hyper_params: hyper_params.clone(),
+                    variant: *variant,
+                },
+                index,
+                query,
+            ),
             RankingAlgo::QueryLikelihood(params) => QueryLikelihood {
                 params: params.clone(),
             }
             .score(index, query),
+            RankingAlgo::DivergenceFromRandomness(model) => {
+                score_with(DivergenceFromRandomness { model: *model }, index, query)
+            }
             RankingAlgo::TFIDF => score_with(TFIDF, index, query),
+            RankingAlgo::Dense(params) => DenseRetrieval {
+                params: params.clone(),
+            }
+            .score(index, query),
+            RankingAlgo::Learned(ranker) => ranker.rerank(index, query),
+            RankingAlgo::Fusion(ranker) => ranker.fuse(index, query),
+        }
+    }
+}
+
+fn add_proximity_bonus<I>(algo: &RankingAlgo, index: &I, query: &AnalyzedQuery, scored: &mut Scored)
+where
+    I: RankedIndexReader + Sync,
+{
+    for score in &mut scored.0 {
+        if let Some(doc_id) = index.doc_id(&score.doc_path) {
+            score.score += algo.proximity_bonus(index, query, doc_id);
         }
     }
 }
@@ -139,6 +187,8 @@ where
                 index.document(*score.key()).map(|metadata| Score {
                     doc_path: metadata.path.clone(),
                     score: *score.value(),
+                    passages: Vec::new(),
+                    similar: Vec::new(),
                 })
             })
             .collect(),
@@ -154,9 +204,32 @@ impl RankingAlgo {
             return None;
         }
 
-        let mut ranking = self.score(index, query).0;
+        let mut ranking = match self {
+            // Nearest-neighbour search stops at a fixed count, so ask for at
+            // least `top_n`.
+            RankingAlgo::Dense(params) => {
+                DenseRetrieval {
+                    params: params.for_top_n(top_n),
+                }
+                .score(index, query)
+                .0
+            }
+            _ => self.score(index, query).0,
+        };
+
+        if query.required_phrases().next().is_some() {
+            ranking.retain(|score| {
+                index.doc_id(&score.doc_path).is_some_and(|doc_id| {
+                    crate::ranking::proximity::satisfies_required_phrases(index, query, doc_id)
+                })
+            });
+        }
 
-        apply_static_quality_priors(index, &mut ranking);
+        // Learned and fused scores already include the priors through the
+        // rankings they build on.
+        if !matches!(self, RankingAlgo::Learned(_) | RankingAlgo::Fusion(_)) {
+            apply_static_quality_priors(index, &mut ranking);
+        }
 
         ranking.sort_by(|a, b| {
             b.score
@@ -172,6 +245,66 @@ impl RankingAlgo {
         }
     }
 
+    /// Ranks only the documents admitted by `filter`. Excluded documents are
+    /// hidden from the scorers, so they cost nothing beyond the filter check.
+    pub fn rank_filtered<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        top_n: usize,
+        filter: &SearchFilter,
+    ) -> Option<Scored>
+    where
+        I: RankedIndexReader + Sync,
+    {
+        if filter.is_empty() {
+            self.rank(index, query, top_n)
+        } else {
+            self.rank(&FilteredIndex::new(index, filter), query, top_n)
+        }
+    }
+
+    /// Ranks the symbols and sections of `index` instead of its files,
+    /// each reported as `path#symbol` with its line range as its passage.
+    /// `filter` is checked against the file a sub-document comes from.
+    pub fn rank_sub_documents(
+        &self,
+        index: &InvertedIndex,
+        query: &AnalyzedQuery,
+        top_n: usize,
+        filter: &SearchFilter,
+    ) -> Option<Scored> {
+        let sub_documents = index.sub_documents()?;
+        let allowed = sub_documents
+            .index()
+            .documents()
+            .filter(|metadata| {
+                sub_documents
+                    .sub_document(&metadata.path)
+                    .and_then(|unit| index.doc_id(&unit.parent))
+                    .and_then(|doc_id| index.document(doc_id))
+                    .is_some_and(|parent| filter.matches(parent))
+            })
+            .map(|metadata| metadata.id)
+            .collect();
+        let mut ranking = self.rank(
+            &FilteredIndex::with_allowed(sub_documents.index(), allowed),
+            query,
+            top_n,
+        )?;
+
+        for score in &mut ranking.0 {
+            if let Some(unit) = sub_documents.sub_document(&score.doc_path) {
+                score.passages = vec![Passage {
+                    start_line: unit.start_line,
+                    end_line: unit.end_line,
+                    score: score.score,
+                }];
+            }
+        }
+        Some(ranking)
+    }
+
     pub fn rank_with_explanations<I>(
         &self,
         index: &I,
@@ -182,13 +315,14 @@ impl RankingAlgo {
         I: RankedIndexReader + Sync,
     {
         let ranked = self.rank(index, query, top_n)?;
+        let explanations = self.explain_ranking(index, query, &ranked.0);
         let results = ranked
             .0
             .into_iter()
-            .filter_map(|score| {
-                let doc_id = index.doc_id(&score.doc_path)?;
+            .zip(explanations)
+            .filter_map(|(score, explanation)| {
                 Some(ScoreWithExplanation {
-                    explanation: self.explain_doc(index, query, doc_id, score.score),
+                    explanation: explanation?,
                     score,
                 })
             })
@@ -205,21 +339,57 @@ impl RankingAlgo {
         feedback_docs: usize,
         feedback_terms: usize,
     ) -> Option<Scored>
+    where
+        I: crate::ranking::feedback::FeedbackTermSource + Sync,
+    {
+        let expanded = self.expand_with_feedback(index, query, feedback_docs, feedback_terms)?;
+
+        self.rank(index, &expanded, top_n)
+    }
+
+    /// Adds the best `feedback_terms` terms of the top `feedback_docs`
+    /// results to `query`. Diversified feedback searches rank the expanded
+    /// query with [`RankingAlgo::rank_diversified`].
+    pub fn expand_with_feedback<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        feedback_docs: usize,
+        feedback_terms: usize,
+    ) -> Option<AnalyzedQuery>
     where
         I: crate::ranking::feedback::FeedbackTermSource + Sync,
     {
         let seed = self.rank(index, query, feedback_docs)?;
-        let expanded = crate::ranking::feedback::expand_query_with_feedback(
+
+        Some(crate::ranking::feedback::expand_query_with_feedback(
             index,
             query,
             &seed.0,
             feedback_terms,
-        );
+        ))
+    }
 
-        self.rank(index, &expanded, top_n)
+    /// Ranks `config.depth` times deeper than `top_n`, then folds
+    /// near-duplicates together and reorders for variety.
+    pub fn rank_diversified<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        top_n: usize,
+        config: &DiversityConfig,
+    ) -> Option<Scored>
+    where
+        I: crate::ranking::feedback::FeedbackTermSource + Sync,
+    {
+        let ranking = self.rank(index, query, top_n.saturating_mul(config.depth.max(1)))?;
+        Some(diversify(index, ranking, top_n, config))
     }
 
-    fn explain_doc<I>(
+    /// Breaks `final_score`, the score `doc_id` ranked with for `query`,
+    /// into per-term contributions, the proximity bonus and the static
+    /// quality prior.
+    pub fn explain<I>(
         &self,
         index: &I,
         query: &AnalyzedQuery,
@@ -230,26 +400,137 @@ impl RankingAlgo {
         I: RankedIndexReader + Sync,
     {
         let terms = match self {
+            RankingAlgo::Learned(ranker) => {
+                return ranker.explain(index, query, doc_id, final_score);
+            }
+            RankingAlgo::Fusion(ranker) => {
+                return ranker.explain(index, query, doc_id, final_score);
+            }
             RankingAlgo::BM25(hyper_params) => explain_bm25(index, query, doc_id, hyper_params),
             RankingAlgo::BM25F(hyper_params) => explain_bm25f(index, query, doc_id, hyper_params),
             RankingAlgo::BM25Proximity(hyper_params, _) => {
                 explain_bm25(index, query, doc_id, hyper_params)
             }
-            RankingAlgo::CosineSimilarity
-            | RankingAlgo::QueryLikelihood(_)
-            | RankingAlgo::TFIDF => explain_matched_terms(index, query, doc_id),
+            RankingAlgo::BM25Variant(hyper_params, variant) => {
+                explain_lower_bounded_bm25(index, query, doc_id, hyper_params, *variant)
+            }
+            RankingAlgo::CosineSimilarity => explain_cosine(index, query, doc_id),
+            RankingAlgo::QueryLikelihood(params) => {
+                explain_query_likelihood(index, query, doc_id, params)
+            }
+            RankingAlgo::DivergenceFromRandomness(model) => {
+                explain_dfr(index, query, doc_id, *model)
+            }
+            RankingAlgo::TFIDF => explain_tfidf(index, query, doc_id),
+            // The similarity has no per-term breakdown.
+            RankingAlgo::Dense(_) => Vec::new(),
         };
 
         ScoreExplanation {
             final_score,
             query_intent: query.intent(),
             terms,
+            proximity_bonus: self.proximity_bonus(index, query, doc_id),
             static_quality: static_quality_explanation(index, doc_id),
+            runs: Vec::new(),
+        }
+    }
+
+    /// Explains each of `results` in order, or gives `None` for results no
+    /// longer in `index`. Rankers built on other rankings run those once for
+    /// the whole list rather than once per result.
+    pub fn explain_ranking<I>(
+        &self,
+        index: &I,
+        query: &AnalyzedQuery,
+        results: &[Score],
+    ) -> Vec<Option<ScoreExplanation>>
+    where
+        I: RankedIndexReader + Sync,
+    {
+        match self {
+            RankingAlgo::Learned(ranker) => ranker.explain_ranking(index, query, results),
+            RankingAlgo::Fusion(ranker) => ranker.explain_ranking(index, query, results),
+            _ => results
+                .iter()
+                .map(|score| {
+                    index
+                        .doc_id(&score.doc_path)
+                        .map(|doc_id| self.explain(index, query, doc_id, score.score))
+                })
+                .collect(),
+        }
+    }
+
+    /// Phrase and term-proximity bonus added on top of the term scores.
+    fn proximity_bonus<I>(&self, index: &I, query: &AnalyzedQuery, doc_id: DocId) -> f64
+    where
+        I: RankedIndexReader + Sync,
+    {
+        match self {
+            RankingAlgo::BM25F(hyper_params) if !query.phrases().is_empty() => {
+                hyper_params.phrase_boost
+                    * crate::ranking::proximity::field_weighted_phrase_bonus(
+                        index,
+                        query,
+                        doc_id,
+                        |field| hyper_params.intent_field_weight(field, query.intent()),
+                    )
+            }
+            RankingAlgo::BM25Proximity(_, config) => {
+                crate::ranking::proximity::positional_bonus(index, query, doc_id, config)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The canonical name [`FromStr`] parses back into this algorithm with
+    /// default parameters. Learned models parse from `learned:PATH` instead.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RankingAlgo::CosineSimilarity => "cosim",
+            RankingAlgo::BM25(_) => "bm25",
+            RankingAlgo::BM25F(_) => "bm25f",
+            RankingAlgo::BM25Proximity(_, _) => "bm25-proximity",
+            RankingAlgo::BM25Variant(_, variant) => match variant {
+                BM25Variant::Plus { .. } => "bm25+",
+                BM25Variant::L { .. } => "bm25l",
+            },
+            RankingAlgo::QueryLikelihood(params) => match params.smoothing {
+                QueryLikelihoodSmoothing::Dirichlet { .. } => "ql",
+                QueryLikelihoodSmoothing::JelinekMercer { .. } => "ql-jm",
+            },
+            RankingAlgo::DivergenceFromRandomness(model) => match model {
+                DfrModel::PL2 { .. } => "pl2",
+                DfrModel::InL2 { .. } => "inl2",
+                DfrModel::DPH => "dph",
+            },
+            RankingAlgo::TFIDF => "tfidf",
+            RankingAlgo::Dense(_) => "dense",
+            RankingAlgo::Learned(_) => "learned",
+            RankingAlgo::Fusion(ranker) => match ranker.method {
+                FusionMethod::Rrf => "fusion",
+                FusionMethod::CombSum => "fusion-combsum",
+            },
         }
     }
 
     pub fn needs_fielded_index(&self) -> bool {
-        matches!(self, Self::BM25F(_) | Self::BM25Proximity(_, _))
+        match self {
+            Self::Learned(ranker) => ranker.first_stage().needs_fielded_index(),
+            Self::Fusion(ranker) => ranker.needs_fielded_index(),
+            algo => matches!(algo, Self::BM25F(_) | Self::BM25Proximity(_, _)),
+        }
+    }
+
+    /// Whether ranking needs the index's latent semantic vectors, built
+    /// with [`InvertedIndex::build_dense`](crate::index::InvertedIndex::build_dense).
+    pub fn needs_dense_index(&self) -> bool {
+        match self {
+            Self::Learned(ranker) => ranker.first_stage().needs_dense_index(),
+            Self::Fusion(ranker) => ranker.needs_dense_index(),
+            algo => matches!(algo, Self::Dense(_)),
+        }
     }
 }
 
@@ -296,15 +577,57 @@ where
         .collect()
 }
 
-fn explain_matched_terms<I>(index: &I, query: &AnalyzedQuery, doc_id: DocId) -> Vec<TermExplanation>
+fn explain_lower_bounded_bm25<I>(
+    index: &I,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+    hyper_params: &BM25HyperParams,
+    variant: BM25Variant,
+) -> Vec<TermExplanation>
+where
+    I: RankedIndexReader + Sync,
+{
+    let scorer = LowerBoundedBM25 {
+        hyper_params: hyper_params.clone(),
+        variant,
+    };
+    let avgdl = index.avg_doc_length();
+
+    explain_matched_terms(index, query, doc_id, |query_term, term_doc, term_idf| {
+        scorer.score_term(
+            query_term.weight,
+            term_idf,
+            term_doc.term_freq as f64,
+            term_doc.length as f64,
+            avgdl,
+        )
+    })
+}
+
+fn explain_dfr<I>(
+    index: &I,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+    model: DfrModel,
+) -> Vec<TermExplanation>
 where
     I: RankedIndexReader + Sync,
 {
+    let scorer = DivergenceFromRandomness { model };
+
     query
         .terms()
         .filter_map(|(term, query_term)| {
             let documents = index.postings(term)?;
             let term_doc = documents.get(doc_id)?;
+            let stats = DfrTermStats::new(index, term, documents.len());
+            let contribution = scorer.score_term(
+                query_term.weight,
+                term_doc.term_freq as f64,
+                term_doc.length as f64,
+                &stats,
+            );
+
             Some(TermExplanation {
                 term: term.0.clone(),
                 provenance: query_term.provenance.as_str().to_string(),
@@ -312,13 +635,120 @@ where
                 term_frequency: term_doc.term_freq,
                 document_frequency: documents.len(),
                 idf: idf(index.num_docs(), documents.len()),
-                matched_fields: field_contributions(term_doc, 0.0),
-                contribution: 0.0,
+                matched_fields: field_contributions(term_doc, contribution),
+                contribution,
             })
         })
         .collect()
 }
 
+fn explain_tfidf<I>(index: &I, query: &AnalyzedQuery, doc_id: DocId) -> Vec<TermExplanation>
+where
+    I: RankedIndexReader + Sync,
+{
+    explain_matched_terms(index, query, doc_id, |query_term, term_doc, term_idf| {
+        query_term.weight * term_doc.term_freq as f64 / term_doc.length as f64 * term_idf
+    })
+}
+
+fn explain_cosine<I>(index: &I, query: &AnalyzedQuery, doc_id: DocId) -> Vec<TermExplanation>
+where
+    I: RankedIndexReader + Sync,
+{
+    let num_docs = index.num_docs();
+    let query_magnitude = query
+        .terms()
+        .map(|(term, query_term)| {
+            let weight = query_term.weight * idf(num_docs, index.doc_freq(term));
+            weight * weight
+        })
+        .sum::<f64>()
+        .sqrt();
+    let doc_magnitude = index.document_norm(doc_id).unwrap_or(0.0);
+
+    explain_matched_terms(index, query, doc_id, |query_term, term_doc, term_idf| {
+        if query_magnitude == 0.0 || doc_magnitude == 0.0 {
+            return 0.0;
+        }
+        query_term.weight * term_doc.term_freq as f64 * term_idf * term_idf
+            / (query_magnitude * doc_magnitude)
+    })
+}
+
+/// Explains the query terms `doc_id` contains, scoring each with
+/// `contribution(query_term, term_doc, idf)`.
+fn explain_matched_terms<I>(
+    index: &I,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+    contribution: impl Fn(&QueryTerm, &TermDocument, f64) -> f64,
+) -> Vec<TermExplanation>
+where
+    I: RankedIndexReader + Sync,
+{
+    query
+        .terms()
+        .filter_map(|(term, query_term)| {
+            let documents = index.postings(term)?;
+            let term_doc = documents.get(doc_id)?;
+            let term_idf = idf(index.num_docs(), documents.len());
+            let contribution = contribution(query_term, term_doc, term_idf);
+            Some(TermExplanation {
+                term: term.0.clone(),
+                provenance: query_term.provenance.as_str().to_string(),
+                query_weight: query_term.weight,
+                term_frequency: term_doc.term_freq,
+                document_frequency: documents.len(),
+                idf: term_idf,
+                matched_fields: field_contributions(term_doc, contribution),
+                contribution,
+            })
+        })
+        .collect()
+}
+
+/// Query likelihood scores every query term, including the ones `doc_id`
+/// lacks, whose smoothed log probability is still part of the score.
+fn explain_query_likelihood<I>(
+    index: &I,
+    query: &AnalyzedQuery,
+    doc_id: DocId,
+    params: &QueryLikelihoodParams,
+) -> Vec<TermExplanation>
+where
+    I: RankedIndexReader + Sync,
+{
+    let scorer = QueryLikelihood {
+        params: params.clone(),
+    };
+
+    query
+        .terms()
+        .map(|(term, query_term)| {
+            let contribution =
+                query_term.weight * scorer.smoothed_term_probability(index, doc_id, term).ln();
+            let documents = index.postings(term);
+            let term_doc = documents
+                .as_ref()
+                .and_then(|documents| documents.get(doc_id));
+            let document_frequency = documents.as_ref().map_or(0, |documents| documents.len());
+
+            TermExplanation {
+                term: term.0.clone(),
+                provenance: query_term.provenance.as_str().to_string(),
+                query_weight: query_term.weight,
+                term_frequency: term_doc.map_or(0, |term_doc| term_doc.term_freq),
+                document_frequency,
+                idf: idf(index.num_docs(), document_frequency),
+                matched_fields: term_doc
+                    .map(|term_doc| field_contributions(term_doc, contribution))
+                    .unwrap_or_default(),
+                contribution,
+            }
+        })
+        .collect()
+}
+
 fn explain_bm25f<I>(
     index: &I,
     query: &AnalyzedQuery,
@@ -428,11 +858,12 @@ fn apply_static_quality_priors<I>(index: &I, ranking: &mut [Score])
 where
     I: RankedIndexReader + Sync,
 {
+    let priors = index.quality_priors();
     for score in ranking {
         if let Some(doc_id) = index.doc_id(&score.doc_path)
             && let Some(metadata) = index.document(doc_id)
         {
-            score.score += metadata.quality_signals.prior_score();
+            score.score += priors.score(&metadata.quality_signals);
         }
     }
 }
@@ -444,78 +875,28 @@ where
     let Some(metadata) = index.document(doc_id) else {
         return Vec::new();
     };
-    let signals = &metadata.quality_signals;
-
-    let mut contributions = Vec::new();
-    push_quality(&mut contributions, "generated", signals.generated, -0.55);
-    push_quality(&mut contributions, "vendor", signals.vendor, -0.45);
-    push_quality(&mut contributions, "test", signals.test, -0.08);
-    push_quality(&mut contributions, "entry_point", signals.entry_point, 0.28);
-    push_quality(&mut contributions, "readme", signals.readme, 0.22);
-    push_quality(
-        &mut contributions,
-        "config_or_manifest",
-        signals.config_or_manifest,
-        0.20,
-    );
-    push_quality(
-        &mut contributions,
-        "public_entry_point",
-        signals.public_entry_point,
-        0.12,
-    );
-
-    let depth_contribution = match signals.file_depth {
-        0 | 1 => 0.10,
-        2 => 0.04,
-        3..=5 => 0.0,
-        _ => -0.08,
-    };
-    contributions.push(StaticQualityContribution {
-        signal: "file_depth".to_string(),
-        value: signals.file_depth as f64,
-        contribution: depth_contribution,
-    });
-
-    let size_contribution = if signals.file_size_bytes > 750_000 {
-        -0.18
-    } else if signals.file_size_bytes > 250_000 {
-        -0.08
-    } else {
-        0.0
-    };
-    contributions.push(StaticQualityContribution {
-        signal: "file_size_bytes".to_string(),
-        value: signals.file_size_bytes as f64,
-        contribution: size_contribution,
-    });
 
-    contributions.push(StaticQualityContribution {
-        signal: "reference_count".to_string(),
-        value: signals.reference_count as f64,
-        contribution: signals.reference_count.min(12) as f64 * 0.015,
-    });
-
-    contributions
-}
-
-fn push_quality(
-    contributions: &mut Vec<StaticQualityContribution>,
-    signal: &str,
-    value: bool,
-    contribution: f64,
-) {
-    contributions.push(StaticQualityContribution {
-        signal: signal.to_string(),
-        value: if value { 1.0 } else { 0.0 },
-        contribution: if value { contribution } else { 0.0 },
-    });
+    index
+        .quality_priors()
+        .contributions(&metadata.quality_signals)
+        .map(|(signal, value, contribution)| StaticQualityContribution {
+            signal: signal.to_string(),
+            value,
+            contribution,
+        })
+        .collect()
 }
 
 impl FromStr for RankingAlgo {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("learned:") {
+            return LearnedRanker::load(std::path::Path::new(path))
+                .map(RankingAlgo::Learned)
+                .map_err(|error| error.to_string());
+        }
+
         match s {
             "cosim" => Ok(RankingAlgo::CosineSimilarity),
             "bm25" => Ok(RankingAlgo::BM25(BM25HyperParams::default())),
@@ -524,13 +905,35 @@ impl FromStr for RankingAlgo {
                 BM25HyperParams::default(),
                 ProximityConfig::default(),
             )),
+            "bm25+" | "bm25plus" | "bm25-plus" => Ok(RankingAlgo::BM25Variant(
+                BM25HyperParams::default(),
+                BM25Variant::plus_defaults(),
+            )),
+            "bm25l" => Ok(RankingAlgo::BM25Variant(
+                BM25HyperParams::default(),
+                BM25Variant::l_defaults(),
+            )),
             "ql" | "ql-dirichlet" | "query-likelihood" => Ok(RankingAlgo::QueryLikelihood(
                 QueryLikelihoodParams::dirichlet_defaults(),
             )),
             "ql-jm" | "jelinek-mercer" => Ok(RankingAlgo::QueryLikelihood(
                 QueryLikelihoodParams::jelinek_mercer_defaults(),
             )),
+            "pl2" => Ok(RankingAlgo::DivergenceFromRandomness(
+                DfrModel::pl2_defaults(),
+            )),
+            "inl2" => Ok(RankingAlgo::DivergenceFromRandomness(
+                DfrModel::inl2_defaults(),
+            )),
+            "dph" => Ok(RankingAlgo::DivergenceFromRandomness(DfrModel::DPH)),
             "tfidf" => Ok(RankingAlgo::TFIDF),
+            "dense" | "lsi" => Ok(RankingAlgo::Dense(DenseParams::default())),
+            "fusion" | "rrf" => Ok(RankingAlgo::Fusion(FusionRanker::code_search_defaults(
+                FusionMethod::Rrf,
+            ))),
+            "fusion-combsum" | "combsum" => Ok(RankingAlgo::Fusion(
+                FusionRanker::code_search_defaults(FusionMethod::CombSum),
+            )),
             _ => Err(format!("{} is not a valid ranking algorithm", s)),
         }
     }
@@ -539,21 +942,18 @@ impl FromStr for RankingAlgo {
 #[cfg(test)]
 mod tests {
     use std::{
-        collections::{HashMap, HashSet},
+        collections::HashMap,
         path::{Path, PathBuf},
     };
 
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
-
     use super::{BM25FHyperParams, BM25HyperParams, ProximityConfig, RankingAlgo};
     use crate::{
-        config::Config,
         index::{
             DocId, DocumentField, DocumentMetadata, InvertedIndex, OwnedPostingList, PostingList,
             RankedIndexReader, Term, TermDocument,
         },
         query::AnalyzedQuery,
+        test_support::{test_config, write_temp_file},
         tokenizer::FileType,
     };
 
@@ -650,25 +1050,6 @@ mod tests {
         RankingAlgo::BM25(BM25HyperParams { k1: 1.2, b: 0.75 })
     }
 
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect::<HashSet<String>>(),
-        }
-    }
-
-    fn write_temp_file(dir: &Path, name: &str, content: &str) {
-        let path = dir.join(name);
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
-        }
-        std::fs::write(path, content).unwrap();
-    }
-
     #[test]
     fn ranking_uses_reader_trait_with_owned_posting_lists() {
         let rust = Term("rust".to_string());
@@ -695,6 +1076,8 @@ mod tests {
                         field_spans: Vec::new(),
                         features: Vec::new(),
                         quality_signals: Default::default(),
+                        token_offsets: None,
+                        imports: Vec::new(),
                     },
                 ),
                 (
@@ -709,6 +1092,8 @@ mod tests {
                         field_spans: Vec::new(),
                         features: Vec::new(),
                         quality_signals: Default::default(),
+                        token_offsets: None,
+                        imports: Vec::new(),
                     },
                 ),
             ]),
@@ -839,6 +1224,101 @@ mod tests {
         );
     }
 
+    #[test]
+    fn algorithm_names_parse_back_to_the_same_algorithm() {
+        for name in [
+            "cosim",
+            "bm25",
+            "bm25f",
+            "bm25-proximity",
+            "bm25+",
+            "bm25l",
+            "ql",
+            "ql-jm",
+            "pl2",
+            "inl2",
+            "dph",
+            "tfidf",
+            "fusion",
+            "fusion-combsum",
+        ] {
+            assert_eq!(name.parse::<RankingAlgo>().unwrap().name(), name);
+        }
+        assert_eq!(
+            "proximity".parse::<RankingAlgo>().unwrap().name(),
+            "bm25-proximity"
+        );
+    }
+
+    #[test]
+    fn explanations_add_up_to_the_final_score_for_every_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "src/pool.rs",
+            "// retry pool connection\nfn retry_pool() { connection(); }\n",
+        );
+        write_temp_file(
+            dir.path(),
+            "src/other.rs",
+            "// pool of workers\nfn retry() {}\n",
+        );
+        write_temp_file(dir.path(), "README.md", "connection notes\n");
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("\"retry pool\" connection", &test_config());
+
+        for name in [
+            "cosim",
+            "bm25",
+            "bm25f",
+            "bm25-proximity",
+            "bm25+",
+            "bm25l",
+            "ql",
+            "ql-jm",
+            "pl2",
+            "inl2",
+            "dph",
+            "tfidf",
+            "fusion",
+            "fusion-combsum",
+        ] {
+            let algo = name.parse::<RankingAlgo>().unwrap();
+            let explanations = algo.rank_with_explanations(&index, &query, 3).unwrap();
+
+            for result in &explanations.results {
+                let explanation = &result.explanation;
+                let total = explanation
+                    .terms
+                    .iter()
+                    .map(|term| term.contribution)
+                    .sum::<f64>()
+                    + explanation.proximity_bonus
+                    + explanation
+                        .static_quality
+                        .iter()
+                        .map(|signal| signal.contribution)
+                        .sum::<f64>()
+                    + explanation
+                        .runs
+                        .iter()
+                        .map(|run| run.contribution)
+                        .sum::<f64>();
+
+                assert!(
+                    (total - explanation.final_score).abs() < 1e-9,
+                    "{name}: {} explains {total}, ranked {}",
+                    result.score.doc_path.display(),
+                    explanation.final_score
+                );
+            }
+            if name == "bm25-proximity" {
+                assert!(explanations.results[0].explanation.proximity_bonus > 0.0);
+            }
+        }
+    }
+
     #[test]
     fn quoted_phrase_requires_adjacent_terms_in_one_field() {
         let dir = tempfile::tempdir().unwrap();
@@ -906,4 +1386,202 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn sloppy_phrase_matches_within_slop_only() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "near.rs", "// connect the pool");
+        write_temp_file(
+            dir.path(),
+            "far.rs",
+            "// connect filler filler filler filler pool",
+        );
+        write_temp_file(dir.path(), "reversed.rs", "// pool connect");
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("\"connect pool\"~2", &test_config());
+        let phrase = query.phrases().first().unwrap();
+        let count = |path: &str| {
+            crate::ranking::proximity::phrase_match_count(
+                &index,
+                index.doc_id(Path::new(path)).unwrap(),
+                phrase,
+            )
+        };
+
+        assert!(count("near.rs") > 0);
+        assert_eq!(count("far.rs"), 0);
+        assert_eq!(count("reversed.rs"), 0);
+
+        let matches = crate::ranking::proximity::phrase_matches(
+            &index,
+            index.doc_id(Path::new("near.rs")).unwrap(),
+            phrase,
+        );
+        let content_match = matches
+            .iter()
+            .find(|matched| matched.field == crate::index::DocumentField::Content)
+            .unwrap();
+        assert_eq!((content_match.start, content_match.end), (0, 2));
+        assert_eq!(content_match.slop(phrase.terms.len()), 1);
+    }
+
+    #[test]
+    fn near_operator_matches_terms_in_either_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "reversed.rs", "// timeout after retry");
+        write_temp_file(
+            dir.path(),
+            "far.rs",
+            "// timeout filler filler filler filler filler filler retry",
+        );
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("NEAR/3(retry, timeout)", &test_config());
+        let phrase = query.phrases().first().unwrap();
+
+        let reversed = index.doc_id(Path::new("reversed.rs")).unwrap();
+        let far = index.doc_id(Path::new("far.rs")).unwrap();
+
+        assert_eq!(
+            crate::ranking::proximity::phrase_match_count(&index, reversed, phrase),
+            1
+        );
+        assert_eq!(
+            crate::ranking::proximity::phrase_match_count(&index, far, phrase),
+            0
+        );
+    }
+
+    #[test]
+    fn required_phrase_filters_rankings() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "phrase.rs", "// connect pool");
+        write_temp_file(dir.path(), "bag.rs", "// pool connect connect pool pool");
+        write_temp_file(dir.path(), "split.rs", "// connect\nfn pool() {}");
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("+\"connect pool\"", &test_config());
+
+        for algo in [
+            bm25(),
+            RankingAlgo::BM25F(BM25FHyperParams::code_search_defaults()),
+            RankingAlgo::TFIDF,
+        ] {
+            let paths = algo
+                .rank(&index, &query, 10)
+                .unwrap()
+                .0
+                .into_iter()
+                .map(|score| score.doc_path)
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                paths,
+                vec![PathBuf::from("bag.rs"), PathBuf::from("phrase.rs")]
+            );
+        }
+    }
+
+    #[test]
+    fn required_single_term_filters_rankings() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "retry.rs", "// connect retry");
+        write_temp_file(dir.path(), "connect.rs", "// connect connect");
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("+\"retry\" connect", &test_config());
+
+        let paths = bm25()
+            .rank(&index, &query, 10)
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|score| score.doc_path)
+            .collect::<Vec<_>>();
+
+        assert_eq!(paths, vec![PathBuf::from("retry.rs")]);
+    }
+
+    #[test]
+    fn bm25f_rewards_phrase_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "phrase.rs", "// alpha beta gamma");
+        write_temp_file(dir.path(), "scattered.rs", "// beta gamma alpha");
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("\"alpha beta\"", &test_config());
+        let ranking = RankingAlgo::BM25F(BM25FHyperParams::code_search_defaults())
+            .rank(&index, &query, 2)
+            .unwrap()
+            .0;
+
+        assert_eq!(ranking[0].doc_path, PathBuf::from("phrase.rs"));
+        assert!(ranking[0].score > ranking[1].score);
+    }
+
+    #[test]
+    fn filtered_ranking_skips_excluded_documents_without_changing_scores() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(dir.path(), "lib.rs", "fn search() {}");
+        write_temp_file(dir.path(), "guide.md", "search search guide");
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("search", &test_config());
+        let filter =
+            crate::index::SearchFilter::new().with_file_types([crate::tokenizer::FileType::Rust]);
+
+        for algo in [
+            bm25(),
+            RankingAlgo::BM25F(BM25FHyperParams::code_search_defaults()),
+            RankingAlgo::BM25Proximity(BM25HyperParams::default(), ProximityConfig::default()),
+            RankingAlgo::CosineSimilarity,
+            RankingAlgo::TFIDF,
+            "ql".parse().unwrap(),
+        ] {
+            let unfiltered = algo.rank(&index, &query, 10).unwrap().0;
+            let filtered = algo.rank_filtered(&index, &query, 10, &filter).unwrap().0;
+
+            assert_eq!(filtered.len(), 1);
+            assert_eq!(filtered[0].doc_path, PathBuf::from("lib.rs"));
+            let unfiltered_score = unfiltered
+                .iter()
+                .find(|score| score.doc_path == filtered[0].doc_path)
+                .unwrap()
+                .score;
+            assert!((filtered[0].score - unfiltered_score).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn multilingual_comments_match_queries_in_the_same_language() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "// 接続プールはタイムアウトの後に再接続されます。\nfn pool() {}",
+        );
+        write_temp_file(
+            dir.path(),
+            "db.rs",
+            "// Die Verbindungen zur Datenbank werden nach dem Timeout erneut aufgebaut.\nfn db() {}",
+        );
+        write_temp_file(
+            dir.path(),
+            "other.rs",
+            "// unrelated english comment\nfn other() {}",
+        );
+
+        let index = InvertedIndex::new_fielded(dir.path(), &test_config(), Some(dir.path()));
+        let algo = RankingAlgo::BM25F(BM25FHyperParams::code_search_defaults());
+
+        let japanese = AnalyzedQuery::new_code_search("接続プール", &test_config());
+        let ranking = algo.rank(&index, &japanese, 3).unwrap().0;
+        assert_eq!(ranking[0].doc_path, PathBuf::from("pool.rs"));
+
+        let german =
+            AnalyzedQuery::new_code_search("Verbindung zur Datenbank über", &test_config());
+        let ranking = algo.rank(&index, &german, 3).unwrap().0;
+        assert_eq!(ranking[0].doc_path, PathBuf::from("db.rs"));
+    }
 }
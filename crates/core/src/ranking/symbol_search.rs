@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    index::{DocumentField, PostingList, RankedIndexReader, SymbolLocation},
+    query::AnalyzedQuery,
+    ranking::{BM25FHyperParams, RankingAlgo, idf},
+};
+
+/// A declared symbol matched by [`search_symbols`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SymbolHit {
+    pub symbol: SymbolLocation,
+    pub score: f64,
+}
+
+/// Ranks declared symbols for `query`.
+///
+/// Documents are ranked by BM25F over the `Symbol` field alone. Each symbol
+/// of a ranked document then scores its document's score times the share of
+/// the query's idf weight that its own name matches, so a symbol matching
+/// every term beats a sibling matching one. Documents indexed without token
+/// offsets yield no symbols.
+pub fn search_symbols(
+    index: &(impl RankedIndexReader + Sync),
+    query: &AnalyzedQuery,
+    limit: usize,
+) -> Vec<SymbolHit> {
+    let algo = RankingAlgo::BM25F(BM25FHyperParams::single_field(DocumentField::Symbol));
+    let Some(ranking) = algo.rank(index, query, limit) else {
+        return Vec::new();
+    };
+
+    let term_weights = query
+        .terms()
+        .map(|(term, query_term)| {
+            (
+                term,
+                query_term.weight * idf(index.num_docs(), index.doc_freq(term)),
+            )
+        })
+        .collect::<Vec<_>>();
+    let total_weight = term_weights.iter().map(|(_, weight)| weight).sum::<f64>();
+    if total_weight <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut hits = Vec::new();
+    for score in ranking.0.iter().filter(|score| score.score > 0.0) {
+        let Some(metadata) = index
+            .doc_id(&score.doc_path)
+            .and_then(|doc_id| index.document(doc_id))
+        else {
+            continue;
+        };
+        let Some(offsets) = metadata.token_offsets.as_ref() else {
+            continue;
+        };
+        let symbols = metadata
+            .features
+            .iter()
+            .filter(|feature| feature.field == DocumentField::Symbol)
+            .filter_map(|feature| Some((feature, feature.span?)))
+            .collect::<Vec<_>>();
+
+        let mut matched = HashMap::<usize, f64>::new();
+        let mut seen = HashSet::new();
+        for (term, weight) in &term_weights {
+            let Some(positions) = index
+                .postings(term)
+                .and_then(|postings| postings.get(metadata.id).cloned())
+                .and_then(|term_doc| term_doc.field_positions(DocumentField::Symbol).cloned())
+            else {
+                continue;
+            };
+            for position in positions.positions() {
+                let Some(span) = offsets.span(DocumentField::Symbol, position) else {
+                    continue;
+                };
+                let Some(symbol) = symbols.iter().position(|(_, symbol_span)| {
+                    symbol_span.start <= span.start && span.end <= symbol_span.end
+                }) else {
+                    continue;
+                };
+                if seen.insert((symbol, *term)) {
+                    *matched.entry(symbol).or_default() += weight;
+                }
+            }
+        }
+
+        hits.extend(matched.into_iter().map(|(symbol, weight)| {
+            let (feature, span) = symbols[symbol];
+            SymbolHit {
+                symbol: SymbolLocation {
+                    path: metadata.path.clone(),
+                    name: feature.text.clone(),
+                    line: offsets.line_of(span.start),
+                    span: Some(span),
+                },
+                score: score.score * weight / total_weight,
+            }
+        }));
+    }
+
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.symbol.path.cmp(&b.symbol.path))
+            .then_with(|| a.symbol.line.cmp(&b.symbol.line))
+    });
+    hits.truncate(limit);
+    hits
+}
+
+#[cfg(all(test, feature = "tree-sitter"))]
+mod tests {
+
+    use super::search_symbols;
+    use crate::{
+        index::InvertedIndex,
+        query::AnalyzedQuery,
+        test_support::{config_without_stop_words, write_temp_file},
+    };
+
+    #[test]
+    fn ranks_symbols_matching_more_of_the_query_first() {
+        let dir = tempfile::tempdir().unwrap();
+        write_temp_file(
+            dir.path(),
+            "pool.rs",
+            "fn open_pool() {}\n\nfn retry_pool_connection() {}\n",
+        );
+        // Mentions the terms only in comments, so it has no matching symbols.
+        write_temp_file(dir.path(), "notes.rs", "// retry the pool connection\n");
+        let config = config_without_stop_words();
+        let index = InvertedIndex::new_fielded(dir.path(), &config, Some(dir.path()));
+        let query = AnalyzedQuery::new_code_search("retryPoolConnection", &config);
+
+        let hits = search_symbols(&index, &query, 10);
+
+        assert_eq!(
+            hits.iter()
+                .map(|hit| (hit.symbol.name.as_str(), hit.symbol.line))
+                .collect::<Vec<_>>(),
+            vec![("retry_pool_connection", Some(3)), ("open_pool", Some(1))]
+        );
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(search_symbols(&index, &query, 1).len(), 1);
+    }
+}
@@ -0,0 +1,38 @@
+//! Fixtures shared by the unit tests.
+
+use std::path::Path;
+
+use rust_stemmers::{Algorithm, Stemmer};
+
+use crate::config::Config;
+
+/// Unigrams with English stemming and stop words.
+pub(crate) fn test_config() -> Config {
+    Config {
+        stop_words: stop_words::get(stop_words::LANGUAGE::English)
+            .iter()
+            .map(|word| word.to_string())
+            .collect(),
+        ..config_without_stop_words()
+    }
+}
+
+/// Unigrams with English stemming that keep every word.
+pub(crate) fn config_without_stop_words() -> Config {
+    Config {
+        n_grams: 1,
+        stemmer: Stemmer::create(Algorithm::English),
+        stop_words: Default::default(),
+        analyzers: Default::default(),
+        ..Default::default()
+    }
+}
+
+/// Writes `content` to `name` under `dir`, creating its parent directories.
+pub(crate) fn write_temp_file(dir: &Path, name: &str, content: &str) {
+    let path = dir.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    std::fs::write(path, content).unwrap();
+}
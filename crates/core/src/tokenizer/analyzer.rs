@@ -1,8 +1,10 @@
-use std::path::Path;
+use std::{path::Path, str::FromStr};
 
 use crate::{
+    code_intelligence::ByteSpan,
     config::Config,
-    tokenizer::{content_tokens, tokenize_identifier},
+    index::DocumentField,
+    tokenizer::chain::{PreserveIdentifier, TokenFilter, apply_filters, apply_filters_with_spans},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
@@ -21,6 +23,20 @@ pub enum FileType {
 }
 
 impl FileType {
+    pub const ALL: [Self; 11] = [
+        Self::Rust,
+        Self::Python,
+        Self::JavaScript,
+        Self::TypeScript,
+        Self::Go,
+        Self::Markdown,
+        Self::Toml,
+        Self::Yaml,
+        Self::Json,
+        Self::Shell,
+        Self::UnknownText,
+    ];
+
     pub fn detect(path: &Path) -> Self {
         if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
             match file_name {
@@ -58,6 +74,27 @@ impl FileType {
     }
 }
 
+impl FromStr for FileType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Ok(Self::Rust),
+            "python" | "py" => Ok(Self::Python),
+            "javascript" | "js" => Ok(Self::JavaScript),
+            "typescript" | "ts" => Ok(Self::TypeScript),
+            "go" => Ok(Self::Go),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "shell" | "sh" => Ok(Self::Shell),
+            "text" | "unknown" => Ok(Self::UnknownText),
+            _ => Err(format!("{} is not a known file type", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AnalyzerField {
     FileName,
@@ -91,22 +128,20 @@ impl AnalyzerProfile {
     }
 
     pub fn for_file_type(file_type: FileType) -> Self {
-        let exact_fields = FieldAnalyzer::exact_identifier();
+        let exact_fields = FieldAnalyzer::identifier();
         let identifier = FieldAnalyzer::identifier();
         let symbol = FieldAnalyzer::identifier();
         let import = FieldAnalyzer::identifier();
         let content = if file_type.is_code() {
-            FieldAnalyzer::code_content()
+            FieldAnalyzer::identifier()
+        } else if file_type == FileType::Markdown {
+            FieldAnalyzer::multilingual_prose()
         } else {
             FieldAnalyzer::prose()
         };
-        let comment = if file_type == FileType::Markdown {
-            FieldAnalyzer::prose()
-        } else {
-            FieldAnalyzer::comment()
-        };
-        let string_literal = FieldAnalyzer::string_literal();
-        let frontmatter = FieldAnalyzer::prose();
+        let comment = FieldAnalyzer::multilingual_prose();
+        let string_literal = FieldAnalyzer::identifier();
+        let frontmatter = FieldAnalyzer::multilingual_prose();
 
         Self {
             file_type,
@@ -140,146 +175,102 @@ impl AnalyzerProfile {
         }
     }
 
+    /// The filters that run for `field`, after applying any overrides from
+    /// `config.analyzers`.
+    pub fn resolved_filters(self, field: AnalyzerField, config: &Config) -> &[TokenFilter] {
+        match config
+            .analyzers
+            .chain(self.file_type, DocumentField::from_analyzer_field(field))
+        {
+            Some(chain) => chain.filters(),
+            None => self.analyzer_for(field).filters(),
+        }
+    }
+
     pub fn analyze(self, field: AnalyzerField, text: &str, config: &Config) -> Vec<String> {
-        self.analyzer_for(field).analyze(text, config)
+        apply_filters(self.resolved_filters(field, config), text, config)
+    }
+
+    /// Analyzes `text` and pairs every token with the byte span it came from.
+    pub fn analyze_with_spans(
+        self,
+        field: AnalyzerField,
+        text: &str,
+        config: &Config,
+    ) -> Vec<(String, ByteSpan)> {
+        apply_filters_with_spans(self.resolved_filters(field, config), text, config)
     }
 }
 
+/// A built-in token-filter chain.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FieldAnalyzer {
-    split_identifiers: bool,
-    preserve_exact: bool,
-    remove_stop_words: bool,
-    stem: bool,
+    filters: &'static [TokenFilter],
 }
 
 impl FieldAnalyzer {
-    fn exact_identifier() -> Self {
-        Self {
-            split_identifiers: true,
-            preserve_exact: true,
-            remove_stop_words: false,
-            stem: false,
-        }
-    }
-
+    /// Identifiers keep their exact and compound forms next to unstemmed parts.
     fn identifier() -> Self {
         Self {
-            split_identifiers: true,
-            preserve_exact: true,
-            remove_stop_words: false,
-            stem: false,
-        }
-    }
-
-    fn code_content() -> Self {
-        Self {
-            split_identifiers: true,
-            preserve_exact: true,
-            remove_stop_words: false,
-            stem: false,
+            filters: &[TokenFilter::IdentifierSplit {
+                preserve: PreserveIdentifier::All,
+            }],
         }
     }
 
+    /// Prose drops stop words and stems, keeping whole forms only for
+    /// identifier-shaped words.
     fn prose() -> Self {
         Self {
-            split_identifiers: true,
-            preserve_exact: false,
-            remove_stop_words: true,
-            stem: true,
+            filters: &[
+                TokenFilter::IdentifierSplit {
+                    preserve: PreserveIdentifier::Identifiers,
+                },
+                TokenFilter::StopWords,
+                TokenFilter::Stem,
+            ],
         }
     }
 
-    fn comment() -> Self {
+    /// Prose that may not be English: CJK text becomes bigrams, the detected
+    /// language picks the stemmer and stop words, and diacritics are folded.
+    fn multilingual_prose() -> Self {
         Self {
-            split_identifiers: true,
-            preserve_exact: false,
-            remove_stop_words: true,
-            stem: true,
+            filters: &[
+                TokenFilter::DetectLanguage,
+                TokenFilter::CjkBigram,
+                TokenFilter::IdentifierSplit {
+                    preserve: PreserveIdentifier::Identifiers,
+                },
+                TokenFilter::StopWords,
+                TokenFilter::Stem,
+                TokenFilter::AsciiFolding,
+            ],
         }
     }
 
-    fn string_literal() -> Self {
-        Self {
-            split_identifiers: true,
-            preserve_exact: true,
-            remove_stop_words: false,
-            stem: false,
-        }
+    pub fn filters(self) -> &'static [TokenFilter] {
+        self.filters
     }
 
     pub fn analyze(self, text: &str, config: &Config) -> Vec<String> {
-        if self == Self::prose() {
-            return content_tokens(text, config);
-        }
-
-        text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
-            .filter(|lexeme| !lexeme.is_empty())
-            .flat_map(|lexeme| self.analyze_lexeme(lexeme, config))
-            .collect()
-    }
-
-    fn analyze_lexeme(self, lexeme: &str, config: &Config) -> Vec<String> {
-        let Some(identifier) = tokenize_identifier(lexeme) else {
-            return Vec::new();
-        };
-
-        let mut tokens = Vec::new();
-
-        if self.preserve_exact {
-            push_unique(&mut tokens, identifier.exact);
-            push_unique(&mut tokens, identifier.compound);
-        }
-
-        if self.split_identifiers {
-            for part in identifier.parts {
-                if self.remove_stop_words && config.stop_words.contains(&part) {
-                    continue;
-                }
-
-                let token = if self.stem {
-                    config.stemmer.stem(&part).to_string()
-                } else {
-                    part
-                };
-                push_unique(&mut tokens, token);
-            }
-        }
-
-        tokens
-    }
-}
-
-fn push_unique(tokens: &mut Vec<String>, token: String) {
-    if !tokens.iter().any(|existing| existing == &token) {
-        tokens.push(token);
+        apply_filters(self.filters, text, config)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashSet, path::Path};
-
-    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-    use rust_stemmers::{Algorithm, Stemmer};
+    use std::path::Path;
 
     use super::{AnalyzerField, AnalyzerProfile, FileType};
-    use crate::config::Config;
-
-    fn test_config() -> Config {
-        Config {
-            n_grams: 1,
-            stemmer: Stemmer::create(Algorithm::English),
-            stop_words: stop_words::get(stop_words::LANGUAGE::English)
-                .par_iter()
-                .map(|word| word.to_string())
-                .collect::<HashSet<String>>(),
-        }
-    }
+    use crate::test_support::test_config;
 
     #[test]
     fn detects_file_types_from_extensions_and_special_names() {
         assert_eq!(FileType::detect(Path::new("src/lib.rs")), FileType::Rust);
+        assert_eq!("rust".parse::<FileType>(), Ok(FileType::Rust));
+        assert_eq!("TS".parse::<FileType>(), Ok(FileType::TypeScript));
+        assert!("cobol".parse::<FileType>().is_err());
         assert_eq!(FileType::detect(Path::new("tool.py")), FileType::Python);
         assert_eq!(FileType::detect(Path::new("app.jsx")), FileType::JavaScript);
         assert_eq!(FileType::detect(Path::new("app.tsx")), FileType::TypeScript);
@@ -0,0 +1,576 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use rust_stemmers::Stemmer;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use crate::{
+    code_intelligence::ByteSpan,
+    config::Config,
+    index::DocumentField,
+    tokenizer::{FileType, language::Language, tokenize_identifier},
+};
+
+/// One step of a token-filter chain.
+///
+/// Text is first split into lexemes: runs of Unicode letters, digits,
+/// combining marks, `_` and `-`, with CJK runs split from any surrounding
+/// Latin text. Every lexeme then flows through the chain in order.
+/// `detect_language` is a switch rather than a step: when present, the
+/// language of the whole text picks the stemmer and stop words. `n_gram`
+/// works across lexemes and must be the last step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenFilter {
+    Lowercase,
+    AsciiFolding,
+    CjkBigram,
+    DetectLanguage,
+    IdentifierSplit { preserve: PreserveIdentifier },
+    StopWords,
+    Stem,
+    Length { min: usize, max: usize },
+    NGram { size: usize },
+}
+
+/// Which unsplit forms an `identifier_split` step keeps next to the parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreserveIdentifier {
+    /// Keep only the split parts.
+    None,
+    /// Keep the exact and compound forms of lexemes that look like
+    /// identifiers (several parts, or `_`/`-` separators).
+    Identifiers,
+    /// Keep the exact and compound forms of every lexeme.
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(transparent)]
+pub struct TokenFilterChain(Vec<TokenFilter>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyzerConfigError {
+    #[error("failed to read analyzer config {path}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to parse analyzer config {path}")]
+    Parse {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("n_gram must be the last filter in a chain")]
+    NGramNotLast,
+    #[error("n_gram size must be at least 1")]
+    EmptyNGram,
+}
+
+impl TokenFilterChain {
+    pub fn new(filters: Vec<TokenFilter>) -> Result<Self, AnalyzerConfigError> {
+        for (idx, filter) in filters.iter().enumerate() {
+            if let TokenFilter::NGram { size } = filter {
+                if *size == 0 {
+                    return Err(AnalyzerConfigError::EmptyNGram);
+                }
+                if idx + 1 != filters.len() {
+                    return Err(AnalyzerConfigError::NGramNotLast);
+                }
+            }
+        }
+
+        Ok(Self(filters))
+    }
+
+    pub fn filters(&self) -> &[TokenFilter] {
+        &self.0
+    }
+
+    pub fn analyze(&self, text: &str, config: &Config) -> Vec<String> {
+        apply_filters(&self.0, text, config)
+    }
+}
+
+impl TryFrom<Vec<TokenFilter>> for TokenFilterChain {
+    type Error = AnalyzerConfigError;
+
+    fn try_from(filters: Vec<TokenFilter>) -> Result<Self, Self::Error> {
+        Self::new(filters)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TokenFilterChain {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let filters = Vec::<TokenFilter>::deserialize(deserializer)?;
+        Self::new(filters).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Per-field analyzer overrides, optionally narrowed to a file type.
+///
+/// A chain declared under `file_types` wins over one declared under
+/// `fields`, which wins over the built-in analyzer for that field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AnalyzerConfig {
+    #[serde(default)]
+    pub fields: HashMap<DocumentField, TokenFilterChain>,
+    #[serde(default)]
+    pub file_types: HashMap<FileType, HashMap<DocumentField, TokenFilterChain>>,
+}
+
+impl AnalyzerConfig {
+    pub fn from_json_file(path: &Path) -> Result<Self, AnalyzerConfigError> {
+        let display = path.display().to_string();
+        let content = std::fs::read_to_string(path).map_err(|source| AnalyzerConfigError::Io {
+            path: display.clone(),
+            source,
+        })?;
+
+        serde_json::from_str(&content).map_err(|source| AnalyzerConfigError::Parse {
+            path: display,
+            source,
+        })
+    }
+
+    pub fn chain(&self, file_type: FileType, field: DocumentField) -> Option<&TokenFilterChain> {
+        self.file_types
+            .get(&file_type)
+            .and_then(|fields| fields.get(&field))
+            .or_else(|| self.fields.get(&field))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    /// Preserved identifier forms skip stop-word removal and stemming.
+    preserved: bool,
+}
+
+pub(crate) fn apply_filters(filters: &[TokenFilter], text: &str, config: &Config) -> Vec<String> {
+    apply_filters_with_spans(filters, text, config)
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect()
+}
+
+/// Like [`apply_filters`], but also returns the byte span in `text` that
+/// produced each token: the whole lexeme for tokens split out of it, and
+/// the first to last lexeme for n-grams.
+pub(crate) fn apply_filters_with_spans(
+    filters: &[TokenFilter],
+    text: &str,
+    config: &Config,
+) -> Vec<(String, ByteSpan)> {
+    let detected = filters
+        .contains(&TokenFilter::DetectLanguage)
+        .then(|| Language::detect(text))
+        .flatten()
+        .and_then(Language::resources);
+    let (stemmer, stop_words) = match detected {
+        Some(resources) => (&resources.stemmer, &resources.stop_words),
+        None => (&config.stemmer, &config.stop_words),
+    };
+
+    let tokens = lexemes(text)
+        .into_iter()
+        .flat_map(|(lexeme, span)| {
+            analyze_lexeme(filters, lexeme, stemmer, stop_words)
+                .into_iter()
+                .map(move |token| (token, span))
+        })
+        .collect::<Vec<_>>();
+
+    match filters.last() {
+        Some(TokenFilter::NGram { size }) if *size > 1 => tokens
+            .windows(*size)
+            .map(|window| {
+                let text = window
+                    .iter()
+                    .map(|(token, _)| token.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let span = ByteSpan {
+                    start: window[0].1.start,
+                    end: window[window.len() - 1].1.end,
+                };
+                (text, span)
+            })
+            .collect(),
+        _ => tokens,
+    }
+}
+
+/// Splits text into runs of word characters, keeping CJK and non-CJK runs
+/// apart, and returns each run NFC-normalized together with its byte span
+/// in the original text. Pure ASCII text splits exactly as it always has.
+fn lexemes(text: &str) -> Vec<(String, ByteSpan)> {
+    let mut lexemes = Vec::new();
+    let mut start = None;
+    let mut current_is_cjk = false;
+    for (offset, character) in text.char_indices() {
+        if !is_word_char(character) {
+            if let Some(start) = start.take() {
+                lexemes.push(lexeme(text, start, offset));
+            }
+            continue;
+        }
+
+        let character_is_cjk = is_cjk(character);
+        if let Some(run_start) = start
+            && character_is_cjk != current_is_cjk
+        {
+            lexemes.push(lexeme(text, run_start, offset));
+            start = None;
+        }
+        current_is_cjk = character_is_cjk;
+        start.get_or_insert(offset);
+    }
+    if let Some(start) = start {
+        lexemes.push(lexeme(text, start, text.len()));
+    }
+
+    lexemes
+}
+
+fn lexeme(text: &str, start: usize, end: usize) -> (String, ByteSpan) {
+    let raw = &text[start..end];
+    let normalized = if raw.is_ascii() {
+        raw.to_string()
+    } else {
+        raw.nfc().collect()
+    };
+    (normalized, ByteSpan { start, end })
+}
+
+fn is_word_char(character: char) -> bool {
+    character.is_alphanumeric()
+        || character == '_'
+        || character == '-'
+        || is_combining_mark(character)
+}
+
+pub(crate) fn is_cjk(character: char) -> bool {
+    matches!(
+        character,
+        '\u{1100}'..='\u{11FF}'
+            | '\u{3040}'..='\u{30FF}'
+            | '\u{3130}'..='\u{318F}'
+            | '\u{31F0}'..='\u{31FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF66}'..='\u{FF9F}'
+            | '\u{20000}'..='\u{2FFFF}'
+    )
+}
+
+fn analyze_lexeme(
+    filters: &[TokenFilter],
+    lexeme: String,
+    stemmer: &Stemmer,
+    stop_words: &HashSet<String>,
+) -> Vec<String> {
+    let mut tokens = vec![Token {
+        text: lexeme,
+        preserved: false,
+    }];
+
+    for filter in filters {
+        match *filter {
+            TokenFilter::Lowercase => {
+                for token in &mut tokens {
+                    token.text = token.text.to_lowercase();
+                }
+            }
+            TokenFilter::AsciiFolding => {
+                for token in &mut tokens {
+                    if !token.text.is_ascii() {
+                        token.text = fold_to_ascii(&token.text);
+                    }
+                }
+            }
+            TokenFilter::CjkBigram => {
+                tokens = tokens.into_iter().flat_map(cjk_bigrams).collect();
+            }
+            TokenFilter::IdentifierSplit { preserve } => {
+                tokens = tokens
+                    .into_iter()
+                    .flat_map(|token| split_identifier(token, preserve))
+                    .collect();
+            }
+            TokenFilter::StopWords => {
+                tokens.retain(|token| token.preserved || !stop_words.contains(&token.text));
+            }
+            TokenFilter::Stem => {
+                for token in tokens.iter_mut().filter(|token| !token.preserved) {
+                    token.text = stemmer.stem(&token.text).to_string();
+                }
+            }
+            TokenFilter::Length { min, max } => {
+                tokens.retain(|token| (min..=max).contains(&token.text.chars().count()));
+            }
+            TokenFilter::DetectLanguage | TokenFilter::NGram { .. } => {}
+        }
+    }
+
+    let mut unique: Vec<String> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if !unique.contains(&token.text) {
+            unique.push(token.text);
+        }
+    }
+    unique
+}
+
+/// Overlapping character bigrams for CJK tokens; a lone character is kept.
+fn cjk_bigrams(token: Token) -> Vec<Token> {
+    let characters = token.text.chars().collect::<Vec<_>>();
+    if characters.len() < 2 || !characters.iter().copied().all(is_cjk) {
+        return vec![token];
+    }
+
+    characters
+        .windows(2)
+        .map(|pair| Token {
+            text: pair.iter().collect(),
+            preserved: token.preserved,
+        })
+        .collect()
+}
+
+fn split_identifier(token: Token, preserve: PreserveIdentifier) -> Vec<Token> {
+    if token.preserved {
+        return vec![token];
+    }
+    let Some(identifier) = tokenize_identifier(&token.text) else {
+        return Vec::new();
+    };
+
+    let keep_original = match preserve {
+        PreserveIdentifier::None => false,
+        PreserveIdentifier::Identifiers => {
+            identifier.parts.len() > 1 || token.text.contains(['_', '-'])
+        }
+        PreserveIdentifier::All => true,
+    };
+
+    let mut tokens = Vec::with_capacity(identifier.parts.len() + 2);
+    if keep_original {
+        for text in [identifier.exact, identifier.compound] {
+            tokens.push(Token {
+                text,
+                preserved: true,
+            });
+        }
+    }
+    tokens.extend(identifier.parts.into_iter().map(|text| Token {
+        text,
+        preserved: false,
+    }));
+    tokens
+}
+
+/// Strips diacritics by decomposing and dropping combining marks, then
+/// spells out letters that have no decomposition (`ß`, `æ`, `ø`, ...).
+pub(crate) fn fold_to_ascii(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for character in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        match fold_char(character) {
+            Some(replacement) => folded.push_str(replacement),
+            None => folded.push(character),
+        }
+    }
+    folded
+}
+
+fn fold_char(character: char) -> Option<&'static str> {
+    let replacement = match character {
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Đ' | 'Ð' => "D",
+        'đ' | 'ð' => "d",
+        'Ħ' => "H",
+        'ħ' => "h",
+        'ı' => "i",
+        'Ł' => "L",
+        'ł' => "l",
+        'Ø' => "O",
+        'ø' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'ß' => "ss",
+        'Þ' => "TH",
+        'þ' => "th",
+        _ => return None,
+    };
+
+    Some(replacement)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rust_stemmers::{Algorithm, Stemmer};
+
+    use super::{AnalyzerConfig, PreserveIdentifier, TokenFilter, TokenFilterChain};
+    use crate::{config::Config, index::DocumentField, tokenizer::FileType};
+
+    fn test_config() -> Config {
+        Config {
+            n_grams: 1,
+            stemmer: Stemmer::create(Algorithm::English),
+            stop_words: HashSet::from(["the".to_string()]),
+            analyzers: AnalyzerConfig::default(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn chain_applies_filters_in_order() {
+        let chain = TokenFilterChain::new(vec![
+            TokenFilter::AsciiFolding,
+            TokenFilter::IdentifierSplit {
+                preserve: PreserveIdentifier::None,
+            },
+            TokenFilter::StopWords,
+            TokenFilter::Stem,
+            TokenFilter::Length { min: 3, max: 20 },
+        ])
+        .unwrap();
+
+        assert_eq!(
+            chain.analyze("the Café connectionPools at ok", &test_config()),
+            vec!["cafe", "connect", "pool"]
+        );
+    }
+
+    #[test]
+    fn n_gram_joins_tokens_across_lexemes_and_must_be_last() {
+        let chain =
+            TokenFilterChain::new(vec![TokenFilter::Lowercase, TokenFilter::NGram { size: 2 }])
+                .unwrap();
+
+        assert_eq!(
+            chain.analyze("Alpha beta Gamma", &test_config()),
+            vec!["alpha beta", "beta gamma"]
+        );
+        assert!(
+            TokenFilterChain::new(vec![TokenFilter::NGram { size: 2 }, TokenFilter::Stem]).is_err()
+        );
+    }
+
+    #[test]
+    fn file_type_overrides_win_over_field_overrides() {
+        let config: AnalyzerConfig = serde_json::from_str(
+            r#"{
+                "fields": { "Comment": ["lowercase"] },
+                "file_types": { "Markdown": { "Comment": ["lowercase", "stem"] } }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .chain(FileType::Rust, DocumentField::Comment)
+                .unwrap()
+                .filters(),
+            &[TokenFilter::Lowercase]
+        );
+        assert_eq!(
+            config
+                .chain(FileType::Markdown, DocumentField::Comment)
+                .unwrap()
+                .filters(),
+            &[TokenFilter::Lowercase, TokenFilter::Stem]
+        );
+        assert!(
+            config
+                .chain(FileType::Rust, DocumentField::Content)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn invalid_chains_are_rejected_when_parsing() {
+        let error = serde_json::from_str::<AnalyzerConfig>(
+            r#"{ "fields": { "Content": [{ "n_gram": { "size": 2 } }, "stem"] } }"#,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("n_gram must be the last filter"));
+    }
+
+    #[test]
+    fn unicode_lexemes_keep_accented_identifiers_and_split_cjk_runs() {
+        let chain = TokenFilterChain::new(vec![
+            TokenFilter::CjkBigram,
+            TokenFilter::IdentifierSplit {
+                preserve: PreserveIdentifier::None,
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(
+            chain.analyze("größeBerechnen 東京タワーtower", &test_config()),
+            vec![
+                "größe",
+                "berechnen",
+                "東京",
+                "京タ",
+                "タワ",
+                "ワー",
+                "tower"
+            ]
+        );
+    }
+
+    #[test]
+    fn detected_language_picks_stemmer_and_stop_words() {
+        let chain = TokenFilterChain::new(vec![
+            TokenFilter::DetectLanguage,
+            TokenFilter::IdentifierSplit {
+                preserve: PreserveIdentifier::None,
+            },
+            TokenFilter::StopWords,
+            TokenFilter::Stem,
+            TokenFilter::AsciiFolding,
+        ])
+        .unwrap();
+
+        let tokens = chain.analyze(
+            "Die Verbindungen zur Datenbank werden nach dem Timeout erneut aufgebaut.",
+            &test_config(),
+        );
+
+        assert!(tokens.contains(&"verbind".to_string()));
+        assert!(!tokens.contains(&"die".to_string()));
+        assert!(!tokens.contains(&"dem".to_string()));
+
+        // English text is analyzed as English even when the configured
+        // default language is not.
+        let german_default = Config {
+            stemmer: Stemmer::create(Algorithm::German),
+            stop_words: HashSet::from(["die".to_string()]),
+            ..test_config()
+        };
+        let tokens = chain.analyze(
+            "The connections to the database are opened again when the previous request has timed out.",
+            &german_default,
+        );
+
+        assert!(tokens.contains(&"connect".to_string()));
+        assert!(!tokens.contains(&"the".to_string()));
+    }
+}
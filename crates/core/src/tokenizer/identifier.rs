@@ -12,7 +12,7 @@ pub fn tokenize_identifier(identifier: &str) -> Option<IdentifierTokens> {
     }
 
     let parts = identifier
-        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
         .flat_map(split_identifier_segment)
         .filter(|part| !part.is_empty())
         .map(str::to_lowercase)
@@ -83,21 +83,21 @@ fn split_identifier_segment(segment: &str) -> Vec<&str> {
 }
 
 fn is_identifier_boundary(previous: char, current: char, next: Option<char>) -> bool {
-    if previous.is_ascii_lowercase() && current.is_ascii_uppercase() {
+    if previous.is_lowercase() && current.is_uppercase() {
         return true;
     }
 
-    if previous.is_ascii_alphabetic() && current.is_ascii_digit() {
+    if previous.is_alphabetic() && current.is_ascii_digit() {
         return true;
     }
 
-    if previous.is_ascii_digit() && current.is_ascii_alphabetic() {
+    if previous.is_ascii_digit() && current.is_alphabetic() {
         return true;
     }
 
-    previous.is_ascii_uppercase()
-        && current.is_ascii_uppercase()
-        && next.is_some_and(|next| next.is_ascii_lowercase())
+    previous.is_uppercase()
+        && current.is_uppercase()
+        && next.is_some_and(|next| next.is_lowercase())
 }
 
 fn push_unique(tokens: &mut Vec<String>, token: String) {
@@ -0,0 +1,225 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use stop_words::LANGUAGE;
+
+/// Languages with their own stemmer and stop words, plus the CJK languages
+/// that are handled by bigrams instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Portuguese,
+    Dutch,
+    Swedish,
+    Danish,
+    Finnish,
+    Russian,
+    Hungarian,
+    Romanian,
+    Turkish,
+    Japanese,
+    Chinese,
+    Korean,
+}
+
+/// Texts shorter than this many characters are too small to detect reliably.
+const MIN_DETECTION_CHARS: usize = 24;
+/// Only the start of a long field is inspected.
+const MAX_DETECTION_CHARS: usize = 2_048;
+
+impl Language {
+    const STEMMED: [Self; 14] = [
+        Self::English,
+        Self::German,
+        Self::French,
+        Self::Spanish,
+        Self::Italian,
+        Self::Portuguese,
+        Self::Dutch,
+        Self::Swedish,
+        Self::Danish,
+        Self::Finnish,
+        Self::Russian,
+        Self::Hungarian,
+        Self::Romanian,
+        Self::Turkish,
+    ];
+
+    /// Detects the dominant language of `text`, returning `None` when the
+    /// text is short or the detector is not confident.
+    pub fn detect(text: &str) -> Option<Self> {
+        let sample = match text.char_indices().nth(MAX_DETECTION_CHARS) {
+            Some((end, _)) => &text[..end],
+            None => text,
+        };
+        if sample.chars().filter(|c| c.is_alphabetic()).count() < MIN_DETECTION_CHARS {
+            return None;
+        }
+
+        let info = whatlang::detect(sample)?;
+        if !info.is_reliable() {
+            return None;
+        }
+
+        Self::from_whatlang(info.lang())
+    }
+
+    fn from_whatlang(lang: whatlang::Lang) -> Option<Self> {
+        use whatlang::Lang;
+
+        Some(match lang {
+            Lang::Eng => Self::English,
+            Lang::Deu => Self::German,
+            Lang::Fra => Self::French,
+            Lang::Spa => Self::Spanish,
+            Lang::Ita => Self::Italian,
+            Lang::Por => Self::Portuguese,
+            Lang::Nld => Self::Dutch,
+            Lang::Swe => Self::Swedish,
+            Lang::Dan => Self::Danish,
+            Lang::Fin => Self::Finnish,
+            Lang::Rus => Self::Russian,
+            Lang::Hun => Self::Hungarian,
+            Lang::Ron => Self::Romanian,
+            Lang::Tur => Self::Turkish,
+            Lang::Jpn => Self::Japanese,
+            Lang::Cmn => Self::Chinese,
+            Lang::Kor => Self::Korean,
+            _ => return None,
+        })
+    }
+
+    pub fn stemmer_algorithm(self) -> Option<Algorithm> {
+        Some(match self {
+            Self::English => Algorithm::English,
+            Self::German => Algorithm::German,
+            Self::French => Algorithm::French,
+            Self::Spanish => Algorithm::Spanish,
+            Self::Italian => Algorithm::Italian,
+            Self::Portuguese => Algorithm::Portuguese,
+            Self::Dutch => Algorithm::Dutch,
+            Self::Swedish => Algorithm::Swedish,
+            Self::Danish => Algorithm::Danish,
+            Self::Finnish => Algorithm::Finnish,
+            Self::Russian => Algorithm::Russian,
+            Self::Hungarian => Algorithm::Hungarian,
+            Self::Romanian => Algorithm::Romanian,
+            Self::Turkish => Algorithm::Turkish,
+            Self::Japanese | Self::Chinese | Self::Korean => return None,
+        })
+    }
+
+    fn stop_word_list(self) -> Option<LANGUAGE> {
+        Some(match self {
+            Self::English => LANGUAGE::English,
+            Self::German => LANGUAGE::German,
+            Self::French => LANGUAGE::French,
+            Self::Spanish => LANGUAGE::Spanish,
+            Self::Italian => LANGUAGE::Italian,
+            Self::Portuguese => LANGUAGE::Portuguese,
+            Self::Dutch => LANGUAGE::Dutch,
+            Self::Swedish => LANGUAGE::Swedish,
+            Self::Danish => LANGUAGE::Danish,
+            Self::Finnish => LANGUAGE::Finnish,
+            Self::Russian => LANGUAGE::Russian,
+            Self::Hungarian => LANGUAGE::Hungarian,
+            Self::Romanian => LANGUAGE::Romanian,
+            Self::Turkish => LANGUAGE::Turkish,
+            Self::Japanese | Self::Chinese | Self::Korean => return None,
+        })
+    }
+
+    /// Stemmer and stop words for this language, or `None` for languages
+    /// that are not stemmed.
+    pub fn resources(self) -> Option<&'static LanguageResources> {
+        static RESOURCES: OnceLock<HashMap<Language, LanguageResources>> = OnceLock::new();
+
+        RESOURCES
+            .get_or_init(|| {
+                Self::STEMMED
+                    .into_iter()
+                    .filter_map(|language| {
+                        Some((
+                            language,
+                            LanguageResources {
+                                stemmer: Stemmer::create(language.stemmer_algorithm()?),
+                                stop_words: stop_words::get(language.stop_word_list()?)
+                                    .iter()
+                                    .map(|word| word.to_string())
+                                    .collect(),
+                            },
+                        ))
+                    })
+                    .collect()
+            })
+            .get(&self)
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "english" | "en" => Ok(Self::English),
+            "german" | "de" => Ok(Self::German),
+            "french" | "fr" => Ok(Self::French),
+            "spanish" | "es" => Ok(Self::Spanish),
+            "italian" | "it" => Ok(Self::Italian),
+            "portuguese" | "pt" => Ok(Self::Portuguese),
+            "dutch" | "nl" => Ok(Self::Dutch),
+            "swedish" | "sv" => Ok(Self::Swedish),
+            "danish" | "da" => Ok(Self::Danish),
+            "finnish" | "fi" => Ok(Self::Finnish),
+            "russian" | "ru" => Ok(Self::Russian),
+            "hungarian" | "hu" => Ok(Self::Hungarian),
+            "romanian" | "ro" => Ok(Self::Romanian),
+            "turkish" | "tr" => Ok(Self::Turkish),
+            "japanese" | "ja" => Ok(Self::Japanese),
+            "chinese" | "zh" => Ok(Self::Chinese),
+            "korean" | "ko" => Ok(Self::Korean),
+            _ => Err(format!("{} is not a supported language", s)),
+        }
+    }
+}
+
+pub struct LanguageResources {
+    pub stemmer: Stemmer,
+    pub stop_words: HashSet<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Language;
+
+    #[test]
+    fn detects_languages_with_enough_text() {
+        assert_eq!(
+            Language::detect(
+                "Die Verbindung zur Datenbank wird nach dem Timeout erneut aufgebaut."
+            ),
+            Some(Language::German)
+        );
+        assert_eq!(
+            Language::detect(
+                "接続プールはタイムアウトの後に再接続されます。設定ファイルを確認してください。"
+            ),
+            Some(Language::Japanese)
+        );
+        assert_eq!(Language::detect("retry pool"), None);
+    }
+
+    #[test]
+    fn cjk_languages_have_no_stemmer() {
+        assert!(Language::German.resources().is_some());
+        assert!(Language::Japanese.resources().is_none());
+    }
+}
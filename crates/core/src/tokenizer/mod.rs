@@ -1,7 +1,13 @@
 pub mod analyzer;
+pub mod chain;
 pub mod identifier;
+pub mod language;
 pub mod pipeline;
 
 pub use analyzer::{AnalyzerField, AnalyzerProfile, FieldAnalyzer, FileType};
+pub use chain::{
+    AnalyzerConfig, AnalyzerConfigError, PreserveIdentifier, TokenFilter, TokenFilterChain,
+};
 pub use identifier::{IdentifierTokens, identifier_token_stream, tokenize_identifier};
+pub use language::Language;
 pub use pipeline::{content_tokens, n_gram_transform};
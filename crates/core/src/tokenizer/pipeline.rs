@@ -76,6 +76,8 @@ mod tests {
                 .par_iter()
                 .map(|word| word.to_string())
                 .collect(),
+            analyzers: Default::default(),
+            ..Default::default()
         }
     }
 
@@ -0,0 +1,4 @@
+/// Rough token count of `bytes` of source text, at four bytes per token.
+pub fn estimate_tokens_from_bytes(bytes: usize) -> usize {
+    bytes.div_ceil(4)
+}